@@ -0,0 +1,59 @@
+use std::io::{self, Read};
+
+use super::{Beatmap, BeatmapDocument};
+
+impl Beatmap {
+    /// The MD5 checksum of the given .osu file contents, as a lowercase hex string.
+    ///
+    /// This is the hash the game uses to identify a beatmap, for example in
+    /// [`Replay::beatmap_hash`][crate::replay::Replay::beatmap_hash] and
+    /// [`DbBeatmap::hash`][crate::db::DbBeatmap::hash].
+    ///
+    /// ```
+    /// # use libosu::prelude::*;
+    /// assert_eq!(
+    ///     Beatmap::checksum_of("osu file format v14\n"),
+    ///     "36f70c48e16e2b13c3c93609eafdaff9",
+    /// );
+    /// ```
+    pub fn checksum_of(data: impl AsRef<[u8]>) -> String {
+        format!("{:x}", md5::compute(data))
+    }
+
+    /// The MD5 checksum of everything read from the reader. See [`Beatmap::checksum_of`].
+    pub fn checksum_reader(mut reader: impl Read) -> io::Result<String> {
+        let mut context = md5::Context::new();
+        let mut buf = [0; 8192];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => context.consume(&buf[..n]),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(format!("{:x}", context.compute()))
+    }
+
+    /// Whether the .osu file contents have the given checksum (in either case).
+    pub fn verify_checksum(data: impl AsRef<[u8]>, checksum: &str) -> bool {
+        Beatmap::checksum_of(data).eq_ignore_ascii_case(checksum.trim())
+    }
+
+    /// The checksum of this beatmap as it would be written out with [`Beatmap::write`].
+    ///
+    /// This only matches the checksum of the file it was parsed from if that file was written
+    /// by this crate; use [`BeatmapDocument::checksum`] to keep the original layout.
+    pub fn checksum(&self) -> String {
+        Beatmap::checksum_of(self.to_string())
+    }
+}
+
+impl BeatmapDocument {
+    /// The checksum of this beatmap as it would be written out with [`BeatmapDocument::write`].
+    ///
+    /// If nothing was changed, this is the checksum of the original file.
+    pub fn checksum(&self) -> String {
+        Beatmap::checksum_of(self.to_string())
+    }
+}
@@ -0,0 +1,142 @@
+use crate::color::Color;
+
+use super::Beatmap;
+
+/// The combo colours of the default skin, used when a map doesn't have its own.
+pub const DEFAULT_COMBO_COLORS: [Color; 4] = [
+    Color {
+        red: 255,
+        green: 192,
+        blue: 0,
+    },
+    Color {
+        red: 0,
+        green: 202,
+        blue: 0,
+    },
+    Color {
+        red: 18,
+        green: 124,
+        blue: 255,
+    },
+    Color {
+        red: 242,
+        green: 24,
+        blue: 57,
+    },
+];
+
+/// The last format version where every spinner makes the object after it start a new combo. In
+/// later versions only spinners marked as a new combo do.
+const LAST_SPINNER_COMBO_VERSION: u32 = 8;
+
+/// Which combo a hit object is in and which colour it's drawn with, see [`Beatmap::combos`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ComboInfo {
+    /// Which combo the object is in, counting from 1. Skipped colours don't count.
+    pub combo_number: u32,
+
+    /// The number drawn on the object, which is its place in the combo counting from 1
+    pub index_in_combo: u32,
+
+    /// Which of the [combo colours][Beatmap::combo_colors] the object is drawn with
+    pub color_index: usize,
+
+    /// The colour the object is drawn with
+    pub color: Color,
+
+    /// Whether this is the last object in its combo
+    pub last_in_combo: bool,
+}
+
+impl Beatmap {
+    /// The combo colours objects are drawn with: the map's own [`colors`][Beatmap::colors], or
+    /// the [default skin's][DEFAULT_COMBO_COLORS] if it doesn't have any.
+    pub fn combo_colors(&self) -> &[Color] {
+        if self.colors.is_empty() {
+            &DEFAULT_COMBO_COLORS
+        } else {
+            &self.colors
+        }
+    }
+
+    /// Works out the combo number, the number drawn on it and the combo colour of each hit object
+    /// (in the same order as [`hit_objects`][Beatmap::hit_objects]).
+    ///
+    /// The first object always starts a new combo, and so do objects marked with
+    /// [`new_combo`][crate::hitobject::HitObject::new_combo], which also skip ahead
+    /// [`skip_color`][crate::hitobject::HitObject::skip_color] colours. Spinners don't start a
+    /// combo themselves, but the object after a spinner always starts a new one (from v9, only if
+    /// the spinner is marked as a new combo), skipping the spinner's colours as well.
+    ///
+    /// Like the game, the colour moves on at the start of every combo including the first, so the
+    /// first combo is drawn with the second colour.
+    ///
+    /// ```
+    /// # use libosu::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = "osu file format v14\n\n[HitObjects]\n0,0,1000,1,0,0:0:0:0:\n0,0,1100,1,0,0:0:0:0:\n0,0,1200,37,0,0:0:0:0:\n";
+    /// let beatmap = Beatmap::from_str(input).unwrap();
+    /// let combos = beatmap.combos();
+    /// assert_eq!(combos[1].combo_number, 1);
+    /// assert_eq!(combos[1].index_in_combo, 2);
+    /// assert_eq!(combos[1].color_index, 1);
+    /// // a new combo that skips two colours
+    /// assert_eq!(combos[2].combo_number, 2);
+    /// assert_eq!(combos[2].index_in_combo, 1);
+    /// assert_eq!(combos[2].color_index, 0);
+    /// ```
+    pub fn combos(&self) -> Vec<ComboInfo> {
+        let colors = self.combo_colors();
+        let mut combos: Vec<ComboInfo> = Vec::with_capacity(self.hit_objects.len());
+
+        let mut combo_number = 0;
+        let mut index_in_combo = 0;
+        let mut color_count = 0;
+
+        // set by spinners for the object after them
+        let mut force_new_combo = false;
+        let mut extra_skip = 0;
+
+        for (i, obj) in self.hit_objects.iter().enumerate() {
+            let skip = obj.skip_color.max(0) as usize;
+            let (new_combo, skip) = if obj.kind.is_spinner() {
+                force_new_combo |= self.version <= LAST_SPINNER_COMBO_VERSION || obj.new_combo;
+                extra_skip += skip;
+                (i == 0, 0)
+            } else {
+                let new_combo = i == 0 || obj.new_combo || force_new_combo;
+                let skip = skip + extra_skip;
+                force_new_combo = false;
+                extra_skip = 0;
+                (new_combo, skip)
+            };
+
+            if new_combo {
+                combo_number += 1;
+                index_in_combo = 1;
+                color_count += skip + 1;
+                if let Some(last) = combos.last_mut() {
+                    last.last_in_combo = true;
+                }
+            } else {
+                index_in_combo += 1;
+            }
+
+            let color_index = color_count % colors.len();
+            combos.push(ComboInfo {
+                combo_number,
+                index_in_combo,
+                color_index,
+                color: colors[color_index],
+                last_in_combo: false,
+            });
+        }
+
+        if let Some(last) = combos.last_mut() {
+            last.last_in_combo = true;
+        }
+        combos
+    }
+}
@@ -5,7 +5,7 @@ use std::ops::{Index, IndexMut};
 
 use ordered_float::NotNan;
 
-use crate::beatmap::Beatmap;
+use crate::beatmap::{Beatmap, StackingAlgorithm};
 use crate::data::{Mode, Mods};
 use crate::hitobject::{HitObject, HitObjectKind};
 use crate::math::Point;
@@ -23,6 +23,9 @@ pub struct DiffCalc<'a> {
 
     /// Strains
     strains: Vec<f64>,
+
+    /// How to stack objects before calculating, if at all
+    stacking: Option<StackingAlgorithm>,
 }
 
 /// Wrapper around hit object used for difficulty calculation
@@ -54,13 +57,13 @@ impl<'a> CalcHitObject<'a> {
     #[inline]
     /// Convenience function for getting start time as an i32
     pub fn time(&self) -> i32 {
-        self.inner.start_time.0
+        self.inner.start_time.millis().0
     }
 
     #[inline]
     /// Convenience function for getting start time as an f64
     pub fn timef(&self) -> f64 {
-        self.time() as f64
+        self.inner.start_time.0
     }
 }
 
@@ -141,9 +144,17 @@ impl<'a> DiffCalc<'a> {
             beatmap,
             hit_objects,
             strains: Vec::new(),
+            stacking: None,
         }
     }
 
+    /// Calculate with objects where the game actually draws them, after stacking (see
+    /// [`Beatmap::stacking`]) instead of at the positions in the file.
+    pub fn with_stacking(mut self, algorithm: StackingAlgorithm) -> Self {
+        self.stacking = Some(algorithm);
+        self
+    }
+
     ///  calculates difficulty and stores results in self.total, self.aim, self.speed,
     ///  self.nsingles, self.nsingles_threshold.
     ///
@@ -195,6 +206,21 @@ impl<'a> DiffCalc<'a> {
 
         let playfield_center = PLAYFIELD_CENTER * scaling_factor;
 
+        // stacking uses the approach rate and circle size with mods, but not the rate change
+        let stacked = self.stacking.map(|algorithm| {
+            let mut difficulty = self.beatmap.difficulty.clone();
+            let ModsApply { ar, cs, .. } = mods_apply(
+                mods & (Mods::HardRock | Mods::Easy),
+                difficulty.approach_rate as f64,
+                0.0,
+                difficulty.circle_size as f64,
+                0.0,
+            );
+            difficulty.approach_rate = ar as f32;
+            difficulty.circle_size = cs as f32;
+            self.beatmap.stacking_with(algorithm, &difficulty)
+        });
+
         // calculate normalized positions
         for curr_idx in 0..self.hit_objects.len() {
             let (a, b) = self.hit_objects.split_at_mut(curr_idx);
@@ -203,7 +229,10 @@ impl<'a> DiffCalc<'a> {
             if let HitObjectKind::Spinner(_) = obj.inner.kind {
                 obj.normpos = playfield_center.clone();
             } else {
-                let pos = Point::new(obj.inner.pos.x as f64, obj.inner.pos.y as f64);
+                let pos = match &stacked {
+                    Some(stacked) => stacked[curr_idx].pos,
+                    None => obj.inner.pos,
+                };
                 obj.normpos = pos * scaling_factor;
             }
 
@@ -0,0 +1,723 @@
+use std::fmt;
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use regex::Regex;
+
+use crate::color::Color;
+use crate::events::{parse_event_line, Event};
+use crate::hitobject::HitObject;
+use crate::hitsounds::SampleSet;
+use crate::storyboard::{expand_variables, parse_variable};
+use crate::timing::TimingPoint;
+
+use super::{Beatmap, BeatmapParseError, SECTION_HEADER_RGX};
+
+lazy_static! {
+    static ref KEY_LINE_RGX: Regex =
+        Regex::new(r"^(?P<prefix>(?P<key>[A-Za-z0-9]+)\s*:\s*)(?P<value>.*)$").expect("compile");
+}
+
+/// The keys we know how to write, by section, along with the separator to use when a key has to
+/// be added to a file that didn't have it.
+const KNOWN_KEYS: &[(&str, &str, &[&str])] = &[
+    (
+        "General",
+        ": ",
+        &[
+            "AudioFilename",
+            "AudioLeadIn",
+            "AudioHash",
+            "PreviewTime",
+            "Countdown",
+            "SampleSet",
+            "SampleVolume",
+            "StackLeniency",
+            "Mode",
+            "LetterboxInBreaks",
+            "StoryFireInFront",
+            "UseSkinSprites",
+            "AlwaysShowPlayfield",
+            "OverlayPosition",
+            "SkinPreference",
+            "EpilepsyWarning",
+            "CountdownOffset",
+            "SpecialStyle",
+            "WidescreenStoryboard",
+            "SamplesMatchPlaybackRate",
+        ],
+    ),
+    (
+        "Editor",
+        ": ",
+        &[
+            "Bookmarks",
+            "DistanceSpacing",
+            "BeatDivisor",
+            "GridSize",
+            "TimelineZoom",
+        ],
+    ),
+    (
+        "Metadata",
+        ":",
+        &[
+            "Title",
+            "TitleUnicode",
+            "Artist",
+            "ArtistUnicode",
+            "Creator",
+            "Version",
+            "Source",
+            "Tags",
+            "BeatmapID",
+            "BeatmapSetID",
+        ],
+    ),
+    (
+        "Difficulty",
+        ":",
+        &[
+            "HPDrainRate",
+            "CircleSize",
+            "OverallDifficulty",
+            "ApproachRate",
+            "SliderMultiplier",
+            "SliderTickRate",
+        ],
+    ),
+    ("Colours", " : ", &["SliderTrackOverride", "SliderBorder"]),
+];
+
+/// The sections that hold lists of objects, in the order they're added to files that don't
+/// have them.
+const LIST_SECTIONS: &[&str] = &["Events", "TimingPoints", "Colours", "HitObjects"];
+
+/// Renders the value of a key the same way the game writes it, or `None` if we don't model
+/// the key. Optional values that aren't set are rendered as an empty string.
+fn key_value(beatmap: &Beatmap, key: &str) -> Option<String> {
+    let flag = |b: bool| String::from(if b { "1" } else { "0" });
+    let color = |c: Option<Color>| c.map(|c| c.to_string()).unwrap_or_default();
+    Some(match key {
+        "AudioFilename" => beatmap.audio_filename.clone(),
+        "AudioLeadIn" => beatmap.audio_leadin.0.to_string(),
+        "AudioHash" => beatmap.audio_hash.clone(),
+        "PreviewTime" => {
+            let version = beatmap.format_version();
+            version
+                .encode_preview_time(beatmap.preview_time)
+                .0
+                .to_string()
+        }
+        "Countdown" => (beatmap.countdown as u8).to_string(),
+        "SampleSet" => String::from(match beatmap.sample_set {
+            SampleSet::None => "None",
+            SampleSet::Normal => "Normal",
+            SampleSet::Soft => "Soft",
+            SampleSet::Drum => "Drum",
+        }),
+        "SampleVolume" => beatmap.sample_volume.to_string(),
+        "StackLeniency" => beatmap.stack_leniency.to_string(),
+        "Mode" => (beatmap.mode as u32).to_string(),
+        "LetterboxInBreaks" | "LetterBoxInBreaks" => flag(beatmap.letterbox_in_breaks),
+        "StoryFireInFront" => flag(beatmap.story_fire_in_front),
+        "UseSkinSprites" => flag(beatmap.use_skin_sprites),
+        "AlwaysShowPlayfield" => flag(beatmap.always_show_playfield),
+        "OverlayPosition" => String::from(beatmap.overlay_position.as_str()),
+        "SkinPreference" => beatmap.skin_preference.clone(),
+        "EpilepsyWarning" => flag(beatmap.epilepsy_warning),
+        "CountdownOffset" => beatmap.countdown_offset.to_string(),
+        "SpecialStyle" => flag(beatmap.special_style),
+        "WidescreenStoryboard" => flag(beatmap.widescreen_storyboard),
+        "SamplesMatchPlaybackRate" => flag(beatmap.samples_match_playback_rate),
+
+        "Bookmarks" => beatmap
+            .bookmarks
+            .iter()
+            .map(|b| b.to_string())
+            .collect::<Vec<_>>()
+            .join(","),
+        "DistanceSpacing" => beatmap.distance_spacing.to_string(),
+        "BeatDivisor" => beatmap.beat_divisor.to_string(),
+        "GridSize" => beatmap.grid_size.to_string(),
+        "TimelineZoom" => beatmap.timeline_zoom.to_string(),
+
+        "Title" => beatmap.title.clone(),
+        "TitleUnicode" => beatmap.title_unicode.clone(),
+        "Artist" => beatmap.artist.clone(),
+        "ArtistUnicode" => beatmap.artist_unicode.clone(),
+        "Creator" => beatmap.creator.clone(),
+        "Version" => beatmap.difficulty_name.clone(),
+        "Source" => beatmap.source.clone(),
+        "Tags" => beatmap.tags.join(" "),
+        "BeatmapID" => beatmap.beatmap_id.to_string(),
+        "BeatmapSetID" => beatmap.beatmap_set_id.to_string(),
+
+        "HPDrainRate" => beatmap.difficulty.hp_drain_rate.to_string(),
+        "CircleSize" => beatmap.difficulty.circle_size.to_string(),
+        "OverallDifficulty" => beatmap.difficulty.overall_difficulty.to_string(),
+        "ApproachRate" => beatmap.difficulty.approach_rate.to_string(),
+        "SliderMultiplier" => beatmap.difficulty.slider_multiplier.to_string(),
+        "SliderTickRate" => beatmap.difficulty.slider_tick_rate.to_string(),
+
+        "SliderTrackOverride" => color(beatmap.slider_track_override),
+        "SliderBorder" => color(beatmap.slider_border),
+
+        _ => return None,
+    })
+}
+
+#[derive(Clone, Debug)]
+enum Line {
+    /// Written back exactly as it was: blank lines, comments, unknown keys and sections
+    Verbatim(String),
+
+    /// The `osu file format vN` line
+    Version(String),
+
+    /// A `Key: value` line
+    Key {
+        key: String,
+        prefix: String,
+        raw: String,
+    },
+
+    /// A (possibly multi-line) object in a list section, by its index in the original list
+    Element { index: usize, raw: Vec<String> },
+
+    /// A combo colour line
+    Color(String),
+}
+
+#[derive(Clone, Debug)]
+struct Section {
+    /// `None` for the lines before the first section header
+    name: Option<String>,
+    header: String,
+    lines: Vec<Line>,
+}
+
+impl Section {
+    /// Index of the line after which new entries should be inserted, so they go before any
+    /// trailing blank lines.
+    fn insertion_point(&self) -> usize {
+        self.lines
+            .iter()
+            .rposition(|line| match line {
+                Line::Verbatim(s) => !s.trim().is_empty(),
+                _ => true,
+            })
+            .map_or(0, |i| i + 1)
+    }
+}
+
+/// A beatmap together with the layout of the file it was parsed from, so that it can be
+/// written back out with only the lines that actually changed being different.
+///
+/// Comments, blank lines, unknown keys and sections that aren't modeled are kept as they are.
+/// Objects that are added are inserted next to the objects around them, and objects that
+/// didn't change are written back with their original text (including any storyboard
+/// variables they used).
+///
+/// ```
+/// # use std::str::FromStr;
+/// # use libosu::prelude::*;
+/// let input = "osu file format v14\n\n[Metadata]\n// comment\nTitle: hello\nSomeUnknownKey:42\n";
+/// let mut doc = BeatmapDocument::from_str(input).unwrap();
+/// doc.beatmap.title = String::from("world");
+/// assert_eq!(
+///     doc.to_string(),
+///     "osu file format v14\n\n[Metadata]\n// comment\nTitle: world\nSomeUnknownKey:42\n",
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct BeatmapDocument {
+    /// The beatmap, which can be freely modified
+    pub beatmap: Beatmap,
+
+    original: Beatmap,
+    events: Vec<Event>,
+    timing_points: Vec<TimingPoint>,
+    hit_objects: Vec<HitObject>,
+    sections: Vec<Section>,
+    line_ending: &'static str,
+    trailing_newline: bool,
+}
+
+impl FromStr for BeatmapDocument {
+    type Err = BeatmapParseError;
+
+    fn from_str(input: &str) -> Result<BeatmapDocument, Self::Err> {
+        let beatmap = Beatmap::from_str(input)?;
+        let err = |line: usize| move |inner| BeatmapParseError::in_line(line, None, inner);
+
+        let mut doc = BeatmapDocument {
+            original: beatmap.clone(),
+            beatmap,
+            events: Vec::new(),
+            timing_points: Vec::new(),
+            hit_objects: Vec::new(),
+            sections: vec![Section {
+                name: None,
+                header: String::new(),
+                lines: Vec::new(),
+            }],
+            line_ending: if input.contains("\r\n") { "\r\n" } else { "\n" },
+            trailing_newline: input.ends_with('\n'),
+        };
+
+        let version = doc.original.format_version();
+        let mut variables = Vec::new();
+        // comments and blank lines in the events, which belong to the last event if more of its
+        // commands come after them
+        let mut pending = Vec::new();
+        for (i, line) in input.lines().enumerate() {
+            let line_no = i + 1;
+            if SECTION_HEADER_RGX.is_match(line) {
+                let section = doc.sections.last_mut().unwrap();
+                section.lines.extend(pending.drain(..).map(Line::Verbatim));
+                let captures = SECTION_HEADER_RGX.captures(line).unwrap();
+                doc.sections.push(Section {
+                    name: Some(String::from(&captures["name"])),
+                    header: line.to_owned(),
+                    lines: Vec::new(),
+                });
+                continue;
+            }
+
+            let section = doc.sections.last_mut().unwrap();
+            let verbatim = Line::Verbatim(line.to_owned());
+            if line.trim().is_empty() || line.starts_with("//") {
+                if section.name.as_deref() == Some("Events") {
+                    pending.push(line.to_owned());
+                } else {
+                    section.lines.push(verbatim);
+                }
+                continue;
+            }
+
+            let parsed = match section.name.as_deref() {
+                None if line.starts_with("osu file format v") => Line::Version(line.to_owned()),
+                Some("Variables") => {
+                    if let Some(variable) = parse_variable(line) {
+                        variables.push(variable);
+                    }
+                    verbatim
+                }
+                Some("Events") => {
+                    let expanded = expand_variables(line, &variables);
+                    let count = doc.events.len();
+                    parse_event_line(&mut doc.events, &expanded).map_err(err(line_no))?;
+                    if doc.events.len() > count {
+                        Line::Element {
+                            index: count,
+                            raw: vec![line.to_owned()],
+                        }
+                    } else {
+                        // a command belonging to the last event
+                        let last = section.lines.iter_mut().rev().find_map(|line| match line {
+                            Line::Element { raw, .. } => Some(raw),
+                            _ => None,
+                        });
+                        match last {
+                            Some(raw) => {
+                                raw.append(&mut pending);
+                                raw.push(line.to_owned());
+                                continue;
+                            }
+                            None => verbatim,
+                        }
+                    }
+                }
+                Some("TimingPoints") => {
+                    let mut tp = TimingPoint::parse_with_defaults(
+                        line,
+                        doc.original.sample_set,
+                        doc.original.sample_volume as u16,
+                    )
+                    .map_err(err(line_no))?;
+                    version.decode_timing_point(&mut tp);
+                    doc.timing_points.push(tp);
+                    Line::Element {
+                        index: doc.timing_points.len() - 1,
+                        raw: vec![line.to_owned()],
+                    }
+                }
+                Some("HitObjects") => {
+                    let mut obj = HitObject::from_str(line).map_err(err(line_no))?;
+                    version.decode_hit_object(&mut obj);
+                    doc.hit_objects.push(obj);
+                    Line::Element {
+                        index: doc.hit_objects.len() - 1,
+                        raw: vec![line.to_owned()],
+                    }
+                }
+                Some("Colours") => match KEY_LINE_RGX.captures(line) {
+                    Some(captures) if key_value(&doc.beatmap, &captures["key"]).is_some() => {
+                        Line::Key {
+                            key: String::from(&captures["key"]),
+                            prefix: String::from(&captures["prefix"]),
+                            raw: line.to_owned(),
+                        }
+                    }
+                    _ => Line::Color(line.to_owned()),
+                },
+                Some(name) if KNOWN_KEYS.iter().any(|(section, _, _)| *section == name) => {
+                    match KEY_LINE_RGX.captures(line) {
+                        Some(captures) => Line::Key {
+                            key: String::from(&captures["key"]),
+                            prefix: String::from(&captures["prefix"]),
+                            raw: line.to_owned(),
+                        },
+                        None => verbatim,
+                    }
+                }
+                _ => verbatim,
+            };
+            section.lines.extend(pending.drain(..).map(Line::Verbatim));
+            section.lines.push(parsed);
+        }
+        let section = doc.sections.last_mut().unwrap();
+        section
+            .lines
+            .extend(pending.into_iter().map(Line::Verbatim));
+
+        for event in doc.events.iter_mut() {
+            version.decode_event(event);
+        }
+        Ok(doc)
+    }
+}
+
+impl BeatmapDocument {
+    /// Parse a beatmap from any `Read`er, remembering the layout of the file
+    pub fn parse(mut reader: impl Read) -> Result<BeatmapDocument, BeatmapParseError> {
+        let mut input = String::new();
+        reader
+            .read_to_string(&mut input)
+            .map_err(|err| BeatmapParseError {
+                line: 0,
+                column: None,
+                inner: err.into(),
+            })?;
+        BeatmapDocument::from_str(&input)
+    }
+
+    /// Write the beatmap to any `Write`r, keeping the original layout
+    pub fn write(&self, mut w: impl Write) -> Result<(), std::io::Error> {
+        let beatmap = format!("{}", self);
+        w.write_all(beatmap.as_bytes())?;
+        Ok(())
+    }
+
+    /// Discards the original layout, returning just the beatmap.
+    pub fn into_beatmap(self) -> Beatmap {
+        self.beatmap
+    }
+
+    fn write_section(&self, section: &Section, out: &mut Vec<String>) {
+        let name = section.name.as_deref().unwrap_or("");
+        if section.name.is_some() {
+            out.push(section.header.clone());
+        }
+        let insert_at = section.insertion_point();
+
+        match name {
+            "Events" | "TimingPoints" | "HitObjects" => {
+                let (original, current) = self.render_list(name);
+                self.write_list(section, insert_at, &original, &current, out)
+            }
+            _ => {
+                let colors_changed = self.beatmap.colors != self.original.colors;
+                let mut wrote_colors = false;
+                for (i, line) in section.lines.iter().enumerate() {
+                    if i == insert_at {
+                        self.write_missing(name, section, colors_changed && !wrote_colors, out);
+                    }
+                    match line {
+                        Line::Verbatim(raw) => out.push(raw.clone()),
+                        Line::Version(raw) => {
+                            if self.beatmap.version == self.original.version {
+                                out.push(raw.clone());
+                            } else {
+                                out.push(format!("osu file format v{}", self.beatmap.version));
+                            }
+                        }
+                        Line::Key { key, prefix, raw } => {
+                            let current = key_value(&self.beatmap, key);
+                            if current == key_value(&self.original, key) {
+                                out.push(raw.clone());
+                            } else if let Some(current) = current.filter(|v| !v.is_empty()) {
+                                out.push(format!("{}{}", prefix, current));
+                            } else if name != "Colours" {
+                                out.push(prefix.clone());
+                            }
+                        }
+                        Line::Color(raw) => {
+                            if !colors_changed {
+                                out.push(raw.clone());
+                            } else if !wrote_colors {
+                                self.write_colors(out);
+                                wrote_colors = true;
+                            }
+                        }
+                        Line::Element { raw, .. } => out.extend(raw.iter().cloned()),
+                    }
+                }
+                if insert_at == section.lines.len() {
+                    self.write_missing(name, section, colors_changed && !wrote_colors, out);
+                }
+            }
+        }
+    }
+
+    /// Writes keys that weren't in the original file but have since been changed, and the
+    /// combo colours if the section didn't have any.
+    fn write_missing(&self, name: &str, section: &Section, colors: bool, out: &mut Vec<String>) {
+        if name == "Colours" && colors {
+            self.write_colors(out);
+        }
+
+        let (_, separator, keys) = match KNOWN_KEYS.iter().find(|(s, _, _)| *s == name) {
+            Some(known) => known,
+            None => return,
+        };
+        for key in keys.iter() {
+            let present = section.lines.iter().any(|line| match line {
+                Line::Key { key: k, .. } => {
+                    key_value(&self.beatmap, k).is_some()
+                        && (k == key || (k == "LetterBoxInBreaks" && *key == "LetterboxInBreaks"))
+                }
+                _ => false,
+            });
+            let current = key_value(&self.beatmap, key).unwrap();
+            let has_key = self.beatmap.format_version().has_key(key);
+            if !present && has_key && Some(&current) != key_value(&self.original, key).as_ref() {
+                out.push(format!("{}{}{}", key, separator, current));
+            }
+        }
+    }
+
+    fn write_colors(&self, out: &mut Vec<String>) {
+        for (i, color) in self.beatmap.colors.iter().enumerate() {
+            out.push(format!("Combo{} : {}", i + 1, color));
+        }
+    }
+
+    /// Writes a list section, keeping the original text of the objects that are unchanged and
+    /// putting new or changed objects where they belong relative to them.
+    ///
+    /// Objects are compared by how they're written out, since that's what decides whether
+    /// their lines need to change.
+    fn write_list(
+        &self,
+        section: &Section,
+        insert_at: usize,
+        original: &[String],
+        current: &[String],
+        out: &mut Vec<String>,
+    ) {
+        let matches = match_lists(original, current);
+        let mut next = 0;
+        let write_new = |until: usize, next: &mut usize, out: &mut Vec<String>| {
+            for obj in current[*next..until].iter() {
+                out.extend(obj.lines().map(String::from));
+            }
+            *next = until.max(*next);
+        };
+
+        for (i, line) in section.lines.iter().enumerate() {
+            if i == insert_at {
+                write_new(current.len(), &mut next, out);
+            }
+            match line {
+                Line::Element { index, raw } => {
+                    if let Some(j) = matches[*index] {
+                        write_new(j, &mut next, out);
+                        out.extend(raw.iter().cloned());
+                        next = j + 1;
+                    }
+                }
+                Line::Verbatim(raw)
+                | Line::Version(raw)
+                | Line::Key { raw, .. }
+                | Line::Color(raw) => out.push(raw.clone()),
+            }
+        }
+        write_new(current.len(), &mut next, out);
+    }
+
+    /// Renders the original and current objects of a list section the way they're written in
+    /// the original and current format versions
+    fn render_list(&self, name: &str) -> (Vec<String>, Vec<String>) {
+        let render = |beatmap: &Beatmap,
+                      events: &[Event],
+                      tps: &[TimingPoint],
+                      objs: &[HitObject]| {
+            let version = beatmap.format_version();
+            match name {
+                "Events" => render_all(events.iter().map(|e| version.encode_event(e))),
+                "TimingPoints" => render_all(tps.iter().map(|tp| version.encode_timing_point(tp))),
+                _ => render_all(objs.iter().map(|obj| version.encode_hit_object(obj))),
+            }
+        };
+        (
+            render(
+                &self.original,
+                &self.events,
+                &self.timing_points,
+                &self.hit_objects,
+            ),
+            render(
+                &self.beatmap,
+                &self.beatmap.events,
+                &self.beatmap.timing_points,
+                &self.beatmap.hit_objects,
+            ),
+        )
+    }
+}
+
+fn render_all<T: fmt::Display>(objects: impl Iterator<Item = T>) -> Vec<String> {
+    objects.map(|obj| obj.to_string()).collect()
+}
+
+/// The most entries the table for finding the longest common subsequence of two lists can have.
+/// Past this, objects between the unchanged start and end of the lists are all rewritten.
+const MAX_LCS_CELLS: usize = 1 << 22;
+
+/// Matches up the objects in two lists using their longest common subsequence, returning
+/// which object in `current` (if any) each object in `original` became.
+fn match_lists<T: PartialEq>(original: &[T], current: &[T]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; original.len()];
+
+    let prefix = original
+        .iter()
+        .zip(current.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = original[prefix..]
+        .iter()
+        .rev()
+        .zip(current[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    for (i, m) in matches.iter_mut().enumerate().take(prefix) {
+        *m = Some(i);
+    }
+    for i in 0..suffix {
+        matches[original.len() - 1 - i] = Some(current.len() - 1 - i);
+    }
+
+    let a = &original[prefix..original.len() - suffix];
+    let b = &current[prefix..current.len() - suffix];
+    let cells = (a.len() + 1).saturating_mul(b.len() + 1);
+    if a.is_empty() || b.is_empty() || cells > MAX_LCS_CELLS {
+        return matches;
+    }
+
+    // lengths[i][j] is the length of the LCS of a[i..] and b[j..]
+    let width = b.len() + 1;
+    let mut lengths = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i * width + j] = if a[i] == b[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            matches[prefix + i] = Some(prefix + j);
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    matches
+}
+
+impl fmt::Display for BeatmapDocument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = Vec::new();
+        for section in self.sections.iter() {
+            self.write_section(section, &mut out);
+        }
+
+        // sections that the beatmap has data for, but the original file didn't have
+        for (name, _, _) in KNOWN_KEYS.iter() {
+            if LIST_SECTIONS.contains(name)
+                || self
+                    .sections
+                    .iter()
+                    .any(|s| s.name.as_deref() == Some(name))
+            {
+                continue;
+            }
+            if self.keys_changed(name) {
+                self.write_new_section(name, &mut out);
+            }
+        }
+        for name in LIST_SECTIONS.iter() {
+            if self
+                .sections
+                .iter()
+                .any(|s| s.name.as_deref() == Some(name))
+            {
+                continue;
+            }
+            let changed = match *name {
+                "Colours" => self.beatmap.colors != self.original.colors || self.keys_changed(name),
+                _ => {
+                    let (original, current) = self.render_list(name);
+                    original != current
+                }
+            };
+            if changed {
+                self.write_new_section(name, &mut out);
+            }
+        }
+
+        for (i, line) in out.iter().enumerate() {
+            if i > 0 {
+                write!(f, "{}", self.line_ending)?;
+            }
+            write!(f, "{}", line)?;
+        }
+        if self.trailing_newline && !out.is_empty() {
+            write!(f, "{}", self.line_ending)?;
+        }
+        Ok(())
+    }
+}
+
+impl BeatmapDocument {
+    /// Whether any of the keys we know about in a section have changed
+    fn keys_changed(&self, name: &str) -> bool {
+        KNOWN_KEYS
+            .iter()
+            .filter(|(section, _, _)| *section == name)
+            .flat_map(|(_, _, keys)| keys.iter())
+            .any(|key| key_value(&self.beatmap, key) != key_value(&self.original, key))
+    }
+
+    fn write_new_section(&self, name: &str, out: &mut Vec<String>) {
+        if out.last().is_some_and(|line| !line.trim().is_empty()) {
+            out.push(String::new());
+        }
+        let section = Section {
+            name: Some(name.to_owned()),
+            header: format!("[{}]", name),
+            lines: Vec::new(),
+        };
+        self.write_section(&section, out);
+    }
+}
@@ -1,50 +1,13 @@
 use crate::beatmap::Beatmap;
 use crate::hitobject::{HitObject, HitObjectKind, HoldInfo, SpinnerInfo};
-use crate::timing::{
-    InheritedTimingInfo, Millis, TimingPoint, TimingPointKind, UninheritedTimingInfo,
-};
+use crate::timing::{Time, TimingIndex, TimingPoint};
 
 impl Beatmap {
-    /// Get the maximum combo in this map
+    /// Get the maximum combo in this map, in the mode it's made for.
+    ///
+    /// See [`Beatmap::statistics`] for the other counts, and converted maps.
     pub fn max_combo(&self) -> u32 {
-        let mut res = 0;
-
-        let mut mpb = 0.0;
-        let mut sv = 0.0;
-        for (i, (obj, tp)) in self.double_iter().enumerate() {
-            let sl = match &obj.kind {
-                // trivial case of circle or spinner
-                HitObjectKind::Circle | HitObjectKind::Spinner(_) => {
-                    res += 1;
-                    continue;
-                }
-                // XXX
-                // TODO
-                HitObjectKind::Hold(_) => {
-                    res += 1;
-                    continue;
-                }
-                HitObjectKind::Slider(v) => v,
-            };
-
-            match &tp.kind {
-                TimingPointKind::Inherited(tp) => sv = tp.slider_velocity,
-                TimingPointKind::Uninherited(tp) => mpb = tp.mpb,
-            };
-            let slider_multiplier = self.difficulty.slider_multiplier;
-            let pixels_per_beat = slider_multiplier * 100.0 * sv;
-
-            let num_beats = (sl.pixel_length * sl.num_repeats as f64) / pixels_per_beat;
-            let mut ticks = ((num_beats - 0.1) / sl.num_repeats as f64
-                * self.difficulty.slider_tick_rate)
-                .ceil() as i32;
-            ticks -= 1;
-            ticks *= sl.num_repeats as i32;
-            ticks += sl.num_repeats as i32 + 1;
-            res += ticks.max(0) as u32;
-        }
-
-        res
+        self.statistics().max_combo
     }
 
     /// Iterate both hit objects and timing points
@@ -54,10 +17,20 @@ impl Beatmap {
 
     /// Computes the end time of the given hitobject
     pub fn get_hitobject_end_time(&self, ho: &HitObject) -> Option<f64> {
+        self.hitobject_end_time_in(ho, &self.timing_lookup())
+    }
+
+    /// Like [`Beatmap::get_hitobject_end_time`], but with an index that's reused for many
+    /// objects.
+    pub(crate) fn hitobject_end_time_in(
+        &self,
+        ho: &HitObject,
+        timing: &TimingIndex,
+    ) -> Option<f64> {
         match ho.kind {
             HitObjectKind::Circle => Some(ho.start_time.as_seconds()),
             HitObjectKind::Slider(_) => {
-                let duration = self.get_slider_duration(ho)?;
+                let duration = self.slider_duration_in(ho, timing)?;
                 Some(ho.start_time.as_seconds() + duration)
             }
             HitObjectKind::Spinner(SpinnerInfo { end_time }) => Some(end_time.as_seconds()),
@@ -67,82 +40,66 @@ impl Beatmap {
 
     /// Returns the slider duration (in seconds) for a given slider
     pub fn get_slider_duration(&self, ho: &HitObject) -> Option<f64> {
+        self.slider_duration_in(ho, &self.timing_lookup())
+    }
+
+    /// Like [`Beatmap::get_slider_duration`], but with an index that's reused for many objects.
+    pub(crate) fn slider_duration_in(&self, ho: &HitObject, timing: &TimingIndex) -> Option<f64> {
         let info = match &ho.kind {
             HitObjectKind::Slider(info) => info,
             _ => return None,
         };
 
-        let slider_velocity = self.get_slider_velocity_at_time(ho.start_time);
+        let slider_velocity = timing.slider_velocity_at(ho.start_time);
         let slider_multiplier = self.difficulty.slider_multiplier;
         let pixels_per_beat = slider_multiplier * 100.0 * slider_velocity;
         let beats_number = info.pixel_length * info.num_repeats as f64 / pixels_per_beat;
 
-        let bpm = self.get_bpm_at_time(ho.start_time)?;
+        let bpm = timing.bpm_at(ho.start_time)?;
         let beat_duration = 60.0 / bpm;
         let duration = beats_number * beat_duration;
 
         Some(duration)
     }
 
-    /// Returns the slider velocity at the given time
-    pub fn get_slider_velocity_at_time(&self, time: Millis) -> f64 {
-        // TODO: replace this with binary search
-        let mut current = 1.0;
-
-        // assume this is sorted
-        for tp in self.timing_points.iter() {
-            if tp.time > time {
-                break;
-            }
-
-            match &tp.kind {
-                TimingPointKind::Uninherited(_) => {
-                    current = 1.0;
-                }
-                TimingPointKind::Inherited(InheritedTimingInfo {
-                    slider_velocity, ..
-                }) => {
-                    current = *slider_velocity;
-                }
-            }
-        }
-
-        current
+    /// Builds an index for looking up the timing at any time in this map. Building it goes
+    /// through all of the timing points, so build it once when looking up many times. It borrows
+    /// the timing points, so build a new one after changing them.
+    pub fn timing_index(&self) -> TimingIndex<'_> {
+        TimingIndex::new(&self.timing_points)
     }
 
-    /// Returns the BPM at the given time
-    pub fn get_bpm_at_time(&self, time: Millis) -> Option<f64> {
-        // TODO: replace this with binary search
-        let mut current = None;
-
-        // assume this is sorted
-        for tp in self.timing_points.iter() {
-            if tp.time > time {
-                break;
-            }
+    /// A lookup for a single time, which doesn't go through all of the timing points like
+    /// [`Beatmap::timing_index`] does.
+    pub(crate) fn timing_lookup(&self) -> TimingIndex<'_> {
+        TimingIndex::unindexed(&self.timing_points)
+    }
 
-            if let TimingPointKind::Uninherited(UninheritedTimingInfo { mpb, .. }) = tp.kind {
-                current = Some(60_000.0 / mpb as f64);
-            }
-        }
+    /// Returns the slider velocity at the given time
+    pub fn get_slider_velocity_at_time(&self, time: impl Into<Time>) -> f64 {
+        self.timing_lookup().slider_velocity_at(time)
+    }
 
-        current
+    /// Returns the BPM at the given time, or `None` if it's before the first uninherited timing
+    /// point
+    pub fn get_bpm_at_time(&self, time: impl Into<Time>) -> Option<f64> {
+        self.timing_lookup().bpm_at(time)
     }
 }
 
 /// An iterator over both hit objects and their corresponding timing points
 pub struct DoubleIter<'a> {
     beatmap: &'a Beatmap,
+    timing: TimingIndex<'a>,
     ho_index: usize,
-    tp_index: usize,
 }
 
 impl<'a> DoubleIter<'a> {
     pub fn new(beatmap: &'a Beatmap) -> Self {
         DoubleIter {
             beatmap,
+            timing: beatmap.timing_lookup(),
             ho_index: 0,
-            tp_index: 0,
         }
     }
 }
@@ -151,28 +108,13 @@ impl<'a> Iterator for DoubleIter<'a> {
     type Item = (&'a HitObject, &'a TimingPoint);
 
     fn next(&mut self) -> Option<Self::Item> {
-        // try to get the next hit object
-        let ho = match self.beatmap.hit_objects.get(self.ho_index) {
-            Some(v) => v,
-            None => return None,
-        };
+        let ho = self.beatmap.hit_objects.get(self.ho_index)?;
 
-        let tp = loop {
-            // get the currently tracked tp
-            let this_tp = match self.beatmap.timing_points.get(self.tp_index) {
-                Some(v) => v,
-                None => return None,
-            };
-
-            if let Some(v) = self.beatmap.timing_points.get(self.tp_index + 1) {
-                if v.time <= ho.start_time {
-                    self.tp_index += 1;
-                    continue;
-                }
-            }
-
-            break this_tp;
-        };
+        // objects before the first timing point go with the first one
+        let tp = self
+            .timing
+            .point_at(ho.start_time)
+            .or_else(|| self.beatmap.timing_points.first())?;
 
         self.ho_index += 1;
         Some((ho, tp))
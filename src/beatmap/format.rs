@@ -1,50 +1,49 @@
 use std::fmt;
-use std::io::{BufRead, BufReader, Cursor, Read, Write};
+use std::io::{Cursor, Read, Write};
 use std::str::FromStr;
 
 use num::FromPrimitive;
 use regex::Regex;
 
+use crate::color::Color;
 use crate::data::Mode;
 use crate::errors::ParseError;
-use crate::events::Event;
-use crate::hitobject::HitObject;
 use crate::hitsounds::SampleSet;
-use crate::timing::TimingPoint;
-use crate::{color::Color, timing::Millis};
+use crate::timing::Millis;
+use crate::utils::write_fmt_io;
 
-use super::Beatmap;
+use super::{Beatmap, BeatmapItem, BeatmapReader, Countdown, OverlayPosition, OBJECT_SECTIONS};
 
 lazy_static! {
-    static ref OSU_FORMAT_VERSION_RGX: Regex =
+    pub(crate) static ref OSU_FORMAT_VERSION_RGX: Regex =
         Regex::new(r"^osu file format v(?P<version>\d+)$").expect("compile");
-    static ref SECTION_HEADER_RGX: Regex =
+    pub(crate) static ref SECTION_HEADER_RGX: Regex =
         Regex::new(r"^\[(?P<name>[A-Za-z]+)\]$").expect("compile");
-    static ref KEY_VALUE_RGX: Regex =
+    pub(crate) static ref KEY_VALUE_RGX: Regex =
         Regex::new(r"^(?P<key>[A-Za-z0-9]+)\s*:\s*(?P<value>.+)$").expect("compile");
 }
 
 /// Macro for matching beatmap keys easier.
 macro_rules! kvalue {
-    ($line:expr, $captures:ident[$name:expr]: str) => {
-        $name = String::from(&$captures["value"]);
+    ($line:expr, $value:ident[$name:expr]: str) => {
+        $name = String::from($value);
     };
-    ($line:expr, $captures:ident[$name:expr] => str) => {
-        String::from(&$captures["value"])
+    ($line:expr, $value:ident[$name:expr] => str) => {
+        String::from($value)
     };
-    ($line:expr, $captures:ident[$name:expr]: parse(bool)) => {
+    ($line:expr, $value:ident[$name:expr]: parse(bool)) => {
         $name = {
-            let val = kvalue!($line, $captures[$name] => parse(u8));
+            let val = kvalue!($line, $value[$name] => parse(u8));
             !(val == 0)
         };
     };
-    ($line:expr, $captures:ident[$name:expr] => parse($type:ident)) => {
-        $captures["value"].parse::<$type>()
-            .map_err(|err| BeatmapParseError { line: $line, inner: err.into() })?;
+    ($line:expr, $value:ident[$name:expr] => parse($type:ident)) => {
+        $value.parse::<$type>()
+            .map_err(|err| BeatmapParseError { line: $line, column: None, inner: err.into() })?;
     };
-    ($line:expr, $captures:ident[$name:expr]: parse($type:ident)) => {
-        $name = $captures["value"].parse::<$type>()
-            .map_err(|err| BeatmapParseError { line: $line, inner: err.into() })?;
+    ($line:expr, $value:ident[$name:expr]: parse($type:ident)) => {
+        $name = $value.parse::<$type>()
+            .map_err(|err| BeatmapParseError { line: $line, column: None, inner: err.into() })?;
     };
 }
 
@@ -54,13 +53,50 @@ pub struct BeatmapParseError {
     /// The line number where the error occurred
     pub line: usize,
 
+    /// The (1-based) column the error starts at, if it's known
+    pub column: Option<usize>,
+
     /// The kind of error that occurred
     pub inner: ParseError,
 }
 
+impl BeatmapParseError {
+    /// Wraps an error from parsing `text`, turning the field it points at into a column.
+    pub(crate) fn in_line(line: usize, text: Option<&str>, inner: ParseError) -> Self {
+        match inner {
+            ParseError::Field { index, inner } => BeatmapParseError {
+                line,
+                column: text.map(|text| field_column(text, index)),
+                inner: *inner,
+            },
+            inner => BeatmapParseError {
+                line,
+                column: None,
+                inner,
+            },
+        }
+    }
+}
+
+/// The (1-based) column the comma-separated field at `index` starts at, or the end of the line if
+/// it has fewer fields.
+fn field_column(text: &str, index: usize) -> usize {
+    let text = text.trim_start_matches('\u{feff}');
+    let start = text
+        .split(',')
+        .take(index)
+        .map(|part| part.len() + 1)
+        .sum::<usize>();
+    start.min(text.len()) + 1
+}
+
 impl fmt::Display for BeatmapParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "error on line {}: {}", self.line, self.inner)
+        write!(f, "error on line {}", self.line)?;
+        if let Some(column) = self.column {
+            write!(f, ":{}", column)?;
+        }
+        write!(f, ": {}", self.inner)
     }
 }
 
@@ -79,273 +115,314 @@ impl FromStr for Beatmap {
 impl Beatmap {
     /// Parse a beatmap from any `Read`er
     pub fn parse(reader: impl Read) -> Result<Beatmap, BeatmapParseError> {
-        let reader = BufReader::new(reader);
-
         // TODO: actually, replace all the required "default" values with Option<T>s.
-        let mut section = "Version".to_owned();
         let mut beatmap = Beatmap::default();
+        for item in BeatmapReader::new(reader) {
+            beatmap.apply_item(item?)?;
+        }
 
-        for (i, line) in reader.lines().enumerate() {
-            let line_no = i + 1;
-            let line = line.map_err(|err| BeatmapParseError {
-                line: line_no,
-                inner: err.into(),
-            })?;
-            let line = line.as_ref();
+        // sort timing points and hit objects
+        beatmap.timing_points.sort_by_key(|tp| tp.time);
+        beatmap.hit_objects.sort_by_key(|ho| ho.start_time);
+        Ok(beatmap)
+    }
 
-            if let Some(captures) = SECTION_HEADER_RGX.captures(&line) {
-                section = String::from(&captures["name"]);
-                continue;
+    /// Parse only the settings at the top of a beatmap (everything up to `[Difficulty]`),
+    /// stopping as soon as the events, timing points or hit objects start.
+    ///
+    /// This is much faster than [`Beatmap::parse`] for indexing large numbers of beatmaps. The
+    /// returned beatmap has no events, timing points, colours or hit objects.
+    pub fn parse_header(reader: impl Read) -> Result<Beatmap, BeatmapParseError> {
+        let mut beatmap = Beatmap::default();
+        for item in BeatmapReader::new(reader) {
+            match item? {
+                BeatmapItem::Section(name) if OBJECT_SECTIONS.contains(&name.as_str()) => break,
+                item => beatmap.apply_item(item)?,
             }
+        }
+        Ok(beatmap)
+    }
 
-            // skip empty lines
-            if line.trim().is_empty() {
-                continue;
-            }
+    /// Adds a single item read by a [`BeatmapReader`] to this beatmap.
+    ///
+    /// Objects are appended in the order they're given, without sorting them.
+    pub fn apply_item(&mut self, item: BeatmapItem) -> Result<(), BeatmapParseError> {
+        match item {
+            BeatmapItem::Version(version) => self.version = version,
+            BeatmapItem::Section(_) => (),
+            BeatmapItem::KeyValue {
+                line, key, value, ..
+            } => self.apply_key_value(line, &key, &value)?,
+            BeatmapItem::Event(event) => self.events.push(event),
+            BeatmapItem::TimingPoint(tp) => self.timing_points.push(tp),
+            BeatmapItem::Color(color) => self.colors.push(color),
+            BeatmapItem::HitObject(obj) => self.hit_objects.push(obj),
+        }
+        Ok(())
+    }
 
-            match section.as_ref() {
-                "Events" => {
-                    if line.starts_with("//") {
-                        continue;
+    fn apply_key_value(
+        &mut self,
+        line_no: usize,
+        key: &str,
+        value: &str,
+    ) -> Result<(), BeatmapParseError> {
+        let beatmap = self;
+        match key {
+            "AudioFilename" => {
+                kvalue!(line_no, value[beatmap.audio_filename]: str)
+            }
+            "AudioHash" => kvalue!(line_no, value[beatmap.audio_hash]: str),
+            "AudioLeadIn" => {
+                let ms = kvalue!(line_no, value[beatmap.audio_leadin] => parse(i32));
+                beatmap.audio_leadin = Millis(ms);
+            }
+            "PreviewTime" => {
+                let ms = kvalue!(line_no, value[beatmap.preview_time] => parse(i32));
+                beatmap.preview_time = beatmap.format_version().decode_preview_time(Millis(ms));
+            }
+            "Countdown" => {
+                let countdown = kvalue!(line_no, value[beatmap.countdown] => parse(u8));
+                beatmap.countdown = Countdown::from_u8(countdown).ok_or(BeatmapParseError {
+                    line: line_no,
+                    column: None,
+                    inner: ParseError::InvalidCountdown(countdown),
+                })?;
+            }
+            "CountdownOffset" => {
+                kvalue!(line_no, value[beatmap.countdown_offset]: parse(u32))
+            }
+            "SampleVolume" => {
+                kvalue!(line_no, value[beatmap.sample_volume]: parse(u32))
+            }
+            "SampleSet" => {
+                beatmap.sample_set = {
+                    let sample_set = kvalue!(line_no, value[beatmap.sample_set] => str);
+                    match sample_set.as_ref() {
+                        "None" => SampleSet::None,
+                        "Normal" => SampleSet::Normal,
+                        "Soft" => SampleSet::Soft,
+                        "Drum" => SampleSet::Drum,
+                        s => {
+                            return Err(BeatmapParseError {
+                                line: line_no,
+                                column: None,
+                                inner: ParseError::InvalidSampleSetString(s.to_owned()),
+                            })
+                        }
                     }
-                    let evt = Event::from_str(line).map_err(|err| BeatmapParseError {
-                        line: line_no,
-                        inner: err,
-                    })?;
-                    beatmap.events.push(evt);
-                }
-                "HitObjects" => {
-                    let obj = HitObject::from_str(line).map_err(|err| BeatmapParseError {
-                        line: line_no,
-                        inner: err,
-                    })?;
-                    beatmap.hit_objects.push(obj);
                 }
-                "TimingPoints" => {
-                    let tp = TimingPoint::from_str(line).map_err(|err| BeatmapParseError {
-                        line: line_no,
-                        inner: err,
-                    })?;
-                    beatmap.timing_points.push(tp);
-                }
-                "Version" => {
-                    if let Some(capture) = OSU_FORMAT_VERSION_RGX.captures(&line) {
-                        beatmap.version =
-                            capture["version"]
-                                .parse::<u32>()
-                                .map_err(|err| BeatmapParseError {
-                                    line: line_no,
-                                    inner: err.into(),
-                                })?;
+            }
+            "StackLeniency" => {
+                kvalue!(line_no, value[beatmap.stack_leniency]: parse(f64))
+            }
+            "Mode" => {
+                beatmap.mode = {
+                    let mode = kvalue!(line_no, value[beatmap.mode]=> parse(u8));
+                    match mode {
+                        0 => Mode::Osu,
+                        1 => Mode::Taiko,
+                        2 => Mode::Catch,
+                        3 => Mode::Mania,
+                        _ => {
+                            return Err(BeatmapParseError {
+                                line: line_no,
+                                column: None,
+                                inner: ParseError::InvalidGameMode(mode),
+                            })
+                        }
                     }
                 }
-                "Colours" => {
-                    let color = Color::from_str(line).map_err(|err| BeatmapParseError {
-                        line: line_no,
-                        inner: err,
-                    })?;
-                    beatmap.colors.push(color);
+            }
+            "LetterboxInBreaks" | "LetterBoxInBreaks" => {
+                kvalue!(line_no, value[beatmap.letterbox_in_breaks]: parse(bool))
+            }
+            "StoryFireInFront" => {
+                kvalue!(line_no, value[beatmap.story_fire_in_front]: parse(bool))
+            }
+            "UseSkinSprites" => {
+                kvalue!(line_no, value[beatmap.use_skin_sprites]: parse(bool))
+            }
+            "AlwaysShowPlayfield" => {
+                kvalue!(line_no, value[beatmap.always_show_playfield]: parse(bool))
+            }
+            "OverlayPosition" => {
+                beatmap.overlay_position = match value {
+                    "NoChange" => OverlayPosition::NoChange,
+                    "Below" => OverlayPosition::Below,
+                    "Above" => OverlayPosition::Above,
+                    s => {
+                        return Err(BeatmapParseError {
+                            line: line_no,
+                            column: None,
+                            inner: ParseError::InvalidOverlayPosition(s.to_owned()),
+                        })
+                    }
                 }
-                _ => {
-                    if let Some(captures) = KEY_VALUE_RGX.captures(line) {
-                        match &captures["key"] {
-                            "AudioFilename" => {
-                                kvalue!(line_no, captures[beatmap.audio_filename]: str)
-                            }
-                            "AudioLeadIn" => {
-                                let ms =
-                                    kvalue!(line_no, captures[beatmap.audio_leadin] => parse(i32));
-                                beatmap.audio_leadin = Millis(ms);
-                            }
-                            "PreviewTime" => {
-                                let ms =
-                                    kvalue!(line_no, captures[beatmap.preview_time] => parse(i32));
-                                beatmap.preview_time = Millis(ms);
-                            }
-                            "Countdown" => {
-                                kvalue!(line_no, captures[beatmap.countdown]: parse(bool))
-                            }
-                            "SampleSet" => {
-                                beatmap.sample_set = {
-                                    let sample_set =
-                                        kvalue!(line_no, captures[beatmap.sample_set] => str);
-                                    match sample_set.as_ref() {
-                                        "None" => SampleSet::None,
-                                        "Normal" => SampleSet::Normal,
-                                        "Soft" => SampleSet::Soft,
-                                        "Drum" => SampleSet::Drum,
-                                        s => {
-                                            return Err(BeatmapParseError {
-                                                line: line_no,
-                                                inner: ParseError::InvalidSampleSetString(
-                                                    s.to_owned(),
-                                                ),
-                                            })
-                                        }
-                                    }
-                                }
-                            }
-                            "StackLeniency" => {
-                                kvalue!(line_no, captures[beatmap.stack_leniency]: parse(f64))
-                            }
-                            "Mode" => {
-                                beatmap.mode = {
-                                    let mode = kvalue!(line_no, captures[beatmap.mode]=> parse(u8));
-                                    match mode {
-                                        0 => Mode::Osu,
-                                        1 => Mode::Taiko,
-                                        2 => Mode::Catch,
-                                        3 => Mode::Mania,
-                                        _ => {
-                                            return Err(BeatmapParseError {
-                                                line: line_no,
-                                                inner: ParseError::InvalidGameMode(mode),
-                                            })
-                                        }
-                                    }
-                                }
-                            }
-                            "LetterBoxInBreaks" => {
-                                kvalue!(line_no, captures[beatmap.letterbox_in_breaks]: parse(bool))
-                            }
-                            "EpilepsyWarning" => {
-                                kvalue!(line_no, captures[beatmap.epilepsy_warning]: parse(bool))
-                            }
-                            "WidescreenStoryboard" => {
-                                kvalue!(
-                                    line_no,
-                                    captures[beatmap.widescreen_storyboard]: parse(bool)
-                                )
-                            }
-
-                            "Bookmarks" => {
-                                beatmap.bookmarks = captures["value"]
-                                    .trim()
-                                    .split(',')
-                                    .filter_map(|s| {
-                                        let s = s.trim();
-                                        if s.is_empty() {
-                                            None
-                                        } else {
-                                            Some(s)
-                                        }
-                                    })
-                                    .map(|n| {
-                                        n.parse::<i32>().map_err(|err| BeatmapParseError {
-                                            line: line_no,
-                                            inner: err.into(),
-                                        })
-                                    })
-                                    .collect::<Result<Vec<_>, BeatmapParseError>>()?
-                            }
-                            "DistanceSpacing" => {
-                                kvalue!(line_no, captures[beatmap.distance_spacing]: parse(f64))
-                            }
-                            "BeatDivisor" => {
-                                kvalue!(line_no, captures[beatmap.beat_divisor]: parse(u8))
-                            }
-                            // "GridSize" => kvalue!(captures[beatmap.grid_size]: parse(u8)),
-                            "GridSize" => {
-                                kvalue!(line_no, captures[beatmap.grid_size]: parse(u32));
-                            }
-                            "TimelineZoom" => {
-                                kvalue!(line_no, captures[beatmap.timeline_zoom]: parse(f64))
-                            }
-
-                            "Title" => kvalue!(line_no, captures[beatmap.title]: str),
-                            "TitleUnicode" => {
-                                kvalue!(line_no, captures[beatmap.title_unicode]: str)
-                            }
-                            "Artist" => kvalue!(line_no, captures[beatmap.artist]: str),
-                            "ArtistUnicode" => {
-                                kvalue!(line_no, captures[beatmap.artist_unicode]: str)
-                            }
-                            "Creator" => kvalue!(line_no, captures[beatmap.creator]: str),
-                            "Version" => kvalue!(line_no, captures[beatmap.difficulty_name]: str),
-                            "Source" => kvalue!(line_no, captures[beatmap.source]: str),
-                            "Tags" => {
-                                beatmap.tags =
-                                    captures["value"].split(' ').map(|s| s.to_owned()).collect()
-                            }
-                            "BeatmapID" => {
-                                kvalue!(line_no, captures[beatmap.beatmap_id]: parse(i32))
-                            }
-                            "BeatmapSetID" => {
-                                kvalue!(line_no, captures[beatmap.beatmap_set_id]: parse(i32))
-                            }
-
-                            "HPDrainRate" => {
-                                kvalue!(
-                                    line_no,
-                                    captures[beatmap.difficulty.hp_drain_rate]: parse(f32)
-                                )
-                            }
-                            "CircleSize" => {
-                                kvalue!(
-                                    line_no,
-                                    captures[beatmap.difficulty.circle_size]: parse(f32)
-                                )
-                            }
-                            "OverallDifficulty" => {
-                                kvalue!(
-                                    line_no,
-                                    captures[beatmap.difficulty.overall_difficulty]: parse(f32)
-                                )
-                            }
-                            "ApproachRate" => {
-                                kvalue!(
-                                    line_no,
-                                    captures[beatmap.difficulty.approach_rate]: parse(f32)
-                                )
-                            }
-                            "SliderMultiplier" => {
-                                kvalue!(
-                                    line_no,
-                                    captures[beatmap.difficulty.slider_multiplier]: parse(f64)
-                                )
-                            }
-                            "SliderTickRate" => {
-                                kvalue!(
-                                    line_no,
-                                    captures[beatmap.difficulty.slider_tick_rate]: parse(f64)
-                                )
-                            }
-
-                            _ => (),
+            }
+            "SkinPreference" => {
+                kvalue!(line_no, value[beatmap.skin_preference]: str)
+            }
+            "EpilepsyWarning" => {
+                kvalue!(line_no, value[beatmap.epilepsy_warning]: parse(bool))
+            }
+            "WidescreenStoryboard" => {
+                kvalue!(
+                    line_no,
+                    value[beatmap.widescreen_storyboard]: parse(bool)
+                )
+            }
+            "SpecialStyle" => {
+                kvalue!(line_no, value[beatmap.special_style]: parse(bool))
+            }
+            "SamplesMatchPlaybackRate" => {
+                kvalue!(
+                    line_no,
+                    value[beatmap.samples_match_playback_rate]: parse(bool)
+                )
+            }
+
+            "Bookmarks" => {
+                beatmap.bookmarks = value
+                    .trim()
+                    .split(',')
+                    .filter_map(|s| {
+                        let s = s.trim();
+                        if s.is_empty() {
+                            None
+                        } else {
+                            Some(s)
                         }
-                    }
+                    })
+                    .map(|n| {
+                        n.parse::<i32>().map_err(|err| BeatmapParseError {
+                            line: line_no,
+                            column: None,
+                            inner: err.into(),
+                        })
+                    })
+                    .collect::<Result<Vec<_>, BeatmapParseError>>()?
+            }
+            "DistanceSpacing" => {
+                kvalue!(line_no, value[beatmap.distance_spacing]: parse(f64))
+            }
+            "BeatDivisor" => {
+                kvalue!(line_no, value[beatmap.beat_divisor]: parse(u8))
+            }
+            // "GridSize" => kvalue!(value[beatmap.grid_size]: parse(u8)),
+            "GridSize" => {
+                kvalue!(line_no, value[beatmap.grid_size]: parse(u32));
+            }
+            "TimelineZoom" => {
+                kvalue!(line_no, value[beatmap.timeline_zoom]: parse(f64))
+            }
+
+            "Title" => kvalue!(line_no, value[beatmap.title]: str),
+            "TitleUnicode" => {
+                kvalue!(line_no, value[beatmap.title_unicode]: str)
+            }
+            "Artist" => kvalue!(line_no, value[beatmap.artist]: str),
+            "ArtistUnicode" => {
+                kvalue!(line_no, value[beatmap.artist_unicode]: str)
+            }
+            "Creator" => kvalue!(line_no, value[beatmap.creator]: str),
+            "Version" => kvalue!(line_no, value[beatmap.difficulty_name]: str),
+            "Source" => kvalue!(line_no, value[beatmap.source]: str),
+            "Tags" => beatmap.tags = value.split(' ').map(|s| s.to_owned()).collect(),
+            "BeatmapID" => {
+                kvalue!(line_no, value[beatmap.beatmap_id]: parse(i32))
+            }
+            "BeatmapSetID" => {
+                kvalue!(line_no, value[beatmap.beatmap_set_id]: parse(i32))
+            }
+
+            "HPDrainRate" => {
+                kvalue!(
+                    line_no,
+                    value[beatmap.difficulty.hp_drain_rate]: parse(f32)
+                )
+            }
+            "CircleSize" => {
+                kvalue!(
+                    line_no,
+                    value[beatmap.difficulty.circle_size]: parse(f32)
+                )
+            }
+            "OverallDifficulty" => {
+                kvalue!(
+                    line_no,
+                    value[beatmap.difficulty.overall_difficulty]: parse(f32)
+                )
+            }
+            "ApproachRate" => {
+                kvalue!(
+                    line_no,
+                    value[beatmap.difficulty.approach_rate]: parse(f32)
+                )
+            }
+            "SliderMultiplier" => {
+                kvalue!(
+                    line_no,
+                    value[beatmap.difficulty.slider_multiplier]: parse(f64)
+                )
+            }
+            "SliderTickRate" => {
+                kvalue!(
+                    line_no,
+                    value[beatmap.difficulty.slider_tick_rate]: parse(f64)
+                )
+            }
+
+            "SliderTrackOverride" | "SliderBorder" => {
+                let color = Color::from_str(value).map_err(|err| BeatmapParseError {
+                    line: line_no,
+                    column: None,
+                    inner: err,
+                })?;
+                if key == "SliderBorder" {
+                    beatmap.slider_border = Some(color);
+                } else {
+                    beatmap.slider_track_override = Some(color);
                 }
             }
-        }
 
-        // sort timing points and hit objects
-        beatmap.timing_points.sort_by_key(|tp| tp.time);
-        beatmap.hit_objects.sort_by_key(|ho| ho.start_time);
-        Ok(beatmap)
+            _ => (),
+        }
+        Ok(())
     }
 
     /// Write this beatmap to any `Write`r
-    pub fn write(&self, mut w: impl Write) -> Result<(), std::io::Error> {
-        // TODO: write line-by-line
-        let beatmap = format!("{}", self);
-        w.write_all(beatmap.as_bytes())?;
-        Ok(())
+    ///
+    /// The beatmap is written line by line as it's formatted, so wrap the writer in a
+    /// `BufWriter` if it's unbuffered.
+    pub fn write(&self, w: impl Write) -> Result<(), std::io::Error> {
+        write_fmt_io(w, |f| self.write_fmt_to(f))
     }
-}
 
-impl fmt::Display for Beatmap {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    fn write_fmt_to(&self, f: &mut impl fmt::Write) -> fmt::Result {
         // version
-        // TODO: should probably use a fixed version
-        writeln!(f, "osu file format v{}", self.version)?;
+        let version = self.format_version();
+        writeln!(f, "osu file format v{}", version.0)?;
         writeln!(f)?;
 
-        // general
+        // general, leaving out keys this version doesn't have yet
+        let has = |key: &str| version.has_key(key);
         writeln!(f, "[General]")?;
         writeln!(f, "AudioFilename: {}", self.audio_filename)?;
-        writeln!(f, "AudioLeadIn: {}", self.audio_leadin.0)?;
-        writeln!(f, "PreviewTime: {}", self.preview_time.0)?;
-        writeln!(f, "Countdown: {}", if self.countdown { 1 } else { 0 })?;
+        if has("AudioLeadIn") {
+            writeln!(f, "AudioLeadIn: {}", self.audio_leadin.0)?;
+        }
+        if !self.audio_hash.is_empty() {
+            writeln!(f, "AudioHash: {}", self.audio_hash)?;
+        }
+        writeln!(
+            f,
+            "PreviewTime: {}",
+            version.encode_preview_time(self.preview_time).0
+        )?;
+        if has("Countdown") {
+            writeln!(f, "Countdown: {}", self.countdown as u8)?;
+        }
         writeln!(
             f,
             "SampleSet: {}",
@@ -356,48 +433,103 @@ impl fmt::Display for Beatmap {
                 SampleSet::Drum => "Drum",
             }
         )?;
-        writeln!(f, "StackLeniency: {}", self.stack_leniency)?;
-        writeln!(f, "Mode: {}", self.mode as u32)?;
-        writeln!(
-            f,
-            "LetterboxInBreaks: {}",
-            if self.letterbox_in_breaks { 1 } else { 0 }
-        )?;
-        writeln!(
-            f,
-            "WidescreenStoryboard: {}",
-            if self.widescreen_storyboard { 1 } else { 0 }
-        )?;
+        if self.sample_volume != 100 && has("SampleVolume") {
+            writeln!(f, "SampleVolume: {}", self.sample_volume)?;
+        }
+        if has("StackLeniency") {
+            writeln!(f, "StackLeniency: {}", self.stack_leniency)?;
+        }
+        if has("Mode") {
+            writeln!(f, "Mode: {}", self.mode as u32)?;
+        }
+        if has("LetterboxInBreaks") {
+            writeln!(
+                f,
+                "LetterboxInBreaks: {}",
+                if self.letterbox_in_breaks { 1 } else { 0 }
+            )?;
+        }
+        // the rest are only written when they're not the default, like the game does
+        if !self.story_fire_in_front && has("StoryFireInFront") {
+            writeln!(f, "StoryFireInFront: 0")?;
+        }
+        if self.use_skin_sprites && has("UseSkinSprites") {
+            writeln!(f, "UseSkinSprites: 1")?;
+        }
+        if self.always_show_playfield && has("AlwaysShowPlayfield") {
+            writeln!(f, "AlwaysShowPlayfield: 1")?;
+        }
+        if self.overlay_position != OverlayPosition::NoChange && has("OverlayPosition") {
+            writeln!(f, "OverlayPosition: {}", self.overlay_position.as_str())?;
+        }
+        if !self.skin_preference.is_empty() && has("SkinPreference") {
+            writeln!(f, "SkinPreference: {}", self.skin_preference)?;
+        }
+        if self.epilepsy_warning && has("EpilepsyWarning") {
+            writeln!(f, "EpilepsyWarning: 1")?;
+        }
+        if self.countdown_offset > 0 && has("CountdownOffset") {
+            writeln!(f, "CountdownOffset: {}", self.countdown_offset)?;
+        }
+        if self.special_style && has("SpecialStyle") {
+            writeln!(f, "SpecialStyle: 1")?;
+        }
+        if has("WidescreenStoryboard") {
+            writeln!(
+                f,
+                "WidescreenStoryboard: {}",
+                if self.widescreen_storyboard { 1 } else { 0 }
+            )?;
+        }
+        if self.samples_match_playback_rate && has("SamplesMatchPlaybackRate") {
+            writeln!(f, "SamplesMatchPlaybackRate: 1")?;
+        }
         writeln!(f)?;
 
         // editor
-        writeln!(f, "[Editor]")?;
-        write!(f, "Bookmarks: ")?;
-        for (i, bookmark) in self.bookmarks.iter().enumerate() {
-            if i > 0 {
-                write!(f, ",")?;
+        if version.has_section("Editor") {
+            writeln!(f, "[Editor]")?;
+            write!(f, "Bookmarks: ")?;
+            for (i, bookmark) in self.bookmarks.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, "{}", bookmark)?;
             }
-            write!(f, "{}", bookmark)?;
+            writeln!(f)?;
+            writeln!(f, "DistanceSpacing: {}", self.distance_spacing)?;
+            writeln!(f, "BeatDivisor: {}", self.beat_divisor)?;
+            writeln!(f, "GridSize: {}", self.grid_size as u32)?;
+            if has("TimelineZoom") {
+                writeln!(f, "TimelineZoom: {}", self.timeline_zoom)?;
+            }
+            writeln!(f)?;
         }
-        writeln!(f)?;
-        writeln!(f, "DistanceSpacing: {}", self.distance_spacing)?;
-        writeln!(f, "BeatDivisor: {}", self.beat_divisor)?;
-        writeln!(f, "GridSize: {}", self.grid_size as u32)?;
-        writeln!(f, "TimelineZoom: {}", self.timeline_zoom)?;
-        writeln!(f)?;
 
         // metadata
         writeln!(f, "[Metadata]")?;
         writeln!(f, "Title:{}", self.title)?;
-        writeln!(f, "TitleUnicode:{}", self.title_unicode)?;
+        if has("TitleUnicode") {
+            writeln!(f, "TitleUnicode:{}", self.title_unicode)?;
+        }
         writeln!(f, "Artist:{}", self.artist)?;
-        writeln!(f, "ArtistUnicode:{}", self.artist_unicode)?;
+        if has("ArtistUnicode") {
+            writeln!(f, "ArtistUnicode:{}", self.artist_unicode)?;
+        }
         writeln!(f, "Creator:{}", self.creator)?;
         writeln!(f, "Version:{}", self.difficulty_name)?;
-        writeln!(f, "Source:{}", self.source)?;
-        writeln!(f, "Tags:{}", self.tags.join(" "))?;
-        writeln!(f, "BeatmapID:{}", self.beatmap_id)?;
-        writeln!(f, "BeatmapSetID:{}", self.beatmap_set_id)?;
+        if has("Source") {
+            writeln!(f, "Source:{}", self.source)?;
+        }
+        if has("Tags") {
+            writeln!(f, "Tags:{}", self.tags.join(" "))?;
+        }
+        if has("BeatmapID") {
+            writeln!(f, "BeatmapID:{}", self.beatmap_id)?;
+        }
+        if has("BeatmapSetID") {
+            writeln!(f, "BeatmapSetID:{}", self.beatmap_set_id)?;
+        }
         writeln!(f)?;
 
         // difficulty
@@ -409,38 +541,57 @@ impl fmt::Display for Beatmap {
             "OverallDifficulty:{}",
             self.difficulty.overall_difficulty
         )?;
-        writeln!(f, "ApproachRate:{}", self.difficulty.approach_rate)?;
+        if has("ApproachRate") {
+            writeln!(f, "ApproachRate:{}", self.difficulty.approach_rate)?;
+        }
         writeln!(f, "SliderMultiplier:{}", self.difficulty.slider_multiplier)?;
         writeln!(f, "SliderTickRate:{}", self.difficulty.slider_tick_rate)?;
 
         // events
         writeln!(f, "[Events]")?;
         for event in self.events.iter() {
-            writeln!(f, "{}", event)?;
+            writeln!(f, "{}", version.encode_event(event))?;
         }
         writeln!(f)?;
 
         // timing points
         writeln!(f, "[TimingPoints]")?;
         for timing_point in self.timing_points.iter() {
-            writeln!(f, "{}", timing_point)?;
+            writeln!(f, "{}", version.encode_timing_point(timing_point))?;
         }
         writeln!(f)?;
 
         // colors
-        writeln!(f, "[Colours]")?;
-        for (i, color) in self.colors.iter().enumerate() {
-            writeln!(f, "Combo{} : {}", i + 1, color)?;
+        if version.has_section("Colours") {
+            writeln!(f, "[Colours]")?;
+            for (i, color) in self.colors.iter().enumerate() {
+                writeln!(f, "Combo{} : {}", i + 1, color)?;
+            }
+            if let Some(color) = self
+                .slider_track_override
+                .filter(|_| has("SliderTrackOverride"))
+            {
+                writeln!(f, "SliderTrackOverride : {}", color)?;
+            }
+            if let Some(color) = self.slider_border.filter(|_| has("SliderBorder")) {
+                writeln!(f, "SliderBorder : {}", color)?;
+            }
+            writeln!(f)?;
         }
-        writeln!(f)?;
 
         // hit objects
         writeln!(f, "[HitObjects]")?;
         for hit_object in self.hit_objects.iter() {
-            writeln!(f, "{}", hit_object)?;
+            writeln!(f, "{}", version.encode_hit_object(hit_object))?;
         }
         writeln!(f)?;
 
         Ok(())
     }
 }
+
+impl fmt::Display for Beatmap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_fmt_to(f)
+    }
+}
@@ -0,0 +1,174 @@
+use std::fmt;
+use std::io::{Cursor, Read};
+
+use crate::hitobject::HitObject;
+use crate::timing::{Time, TimingPoint};
+
+use super::{Beatmap, BeatmapItem, BeatmapReader};
+
+/// The sections that osu! itself knows about.
+const KNOWN_SECTIONS: &[&str] = &[
+    "General",
+    "Editor",
+    "Metadata",
+    "Difficulty",
+    "Variables",
+    "Events",
+    "TimingPoints",
+    "Colours",
+    "HitObjects",
+];
+
+/// How bad a problem found by [`Beatmap::parse_lenient`] is.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Severity {
+    /// Something odd that was repaired or ignored without losing anything
+    Warning,
+
+    /// A line that couldn't be understood and was skipped
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found while leniently parsing a beatmap.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParseDiagnostic {
+    /// The (1-based) line the problem is on
+    pub line: usize,
+
+    /// The (1-based) column the problem starts at, if it's known
+    pub column: Option<usize>,
+
+    /// The section the line is in, or `Version` for lines before the first section
+    pub section: String,
+
+    /// How bad the problem is
+    pub severity: Severity,
+
+    /// A description of the problem
+    pub message: String,
+}
+
+impl fmt::Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} on line {}", self.severity, self.line)?;
+        if let Some(column) = self.column {
+            write!(f, ":{}", column)?;
+        }
+        write!(f, " [{}]: {}", self.section, self.message)
+    }
+}
+
+impl Beatmap {
+    /// Parse a beatmap from any `Read`er without giving up on the first problem.
+    ///
+    /// Lines that can't be parsed are skipped (settings keep their default value), and
+    /// recoverable oddities like unsorted objects are fixed up. Everything that was skipped or
+    /// repaired is reported in the returned diagnostics, in the order it was found.
+    ///
+    /// ```
+    /// # use libosu::prelude::*;
+    /// let input = "osu file format v14\n\n[Difficulty]\nCircleSize:big\n\n[HitObjects]\n256,192\n256,192,1000,1,0,0:0:0:0:\n";
+    /// let (beatmap, diagnostics) = Beatmap::parse_lenient(input.as_bytes());
+    /// assert_eq!(beatmap.hit_objects.len(), 1);
+    /// assert_eq!(diagnostics.len(), 2);
+    /// assert_eq!(diagnostics[0].line, 4);
+    /// assert_eq!(diagnostics[1].line, 7);
+    /// ```
+    pub fn parse_lenient(reader: impl Read) -> (Beatmap, Vec<ParseDiagnostic>) {
+        let mut beatmap = Beatmap::default();
+        let mut diagnostics = Vec::new();
+        let mut reader = BeatmapReader::new(reader);
+        reader.set_lenient(true);
+
+        let mut seen_version = false;
+        let mut last_time = None::<Time>;
+
+        while let Some(item) = reader.next() {
+            let mut diagnostic = |line, column, severity, message| {
+                diagnostics.push(ParseDiagnostic {
+                    line,
+                    column,
+                    section: String::from(reader.section()),
+                    severity,
+                    message,
+                })
+            };
+
+            let item = match item {
+                Ok(item) => item,
+                Err(err) => {
+                    diagnostic(err.line, err.column, Severity::Error, err.inner.to_string());
+                    continue;
+                }
+            };
+
+            match &item {
+                BeatmapItem::Version(_) => seen_version = true,
+                BeatmapItem::Section(name) => {
+                    if !seen_version {
+                        seen_version = true;
+                        diagnostic(
+                            reader.line_no(),
+                            None,
+                            Severity::Warning,
+                            String::from("missing `osu file format` version line"),
+                        );
+                    }
+                    if !KNOWN_SECTIONS.contains(&name.as_str()) {
+                        diagnostic(
+                            reader.line_no(),
+                            Some(1),
+                            Severity::Warning,
+                            format!("unknown section [{}] ignored", name),
+                        );
+                    }
+                    last_time = None;
+                }
+                BeatmapItem::TimingPoint(TimingPoint { time, .. })
+                | BeatmapItem::HitObject(HitObject {
+                    start_time: time, ..
+                }) => {
+                    if matches!(last_time, Some(last) if *time < last) {
+                        diagnostic(
+                            reader.line_no(),
+                            None,
+                            Severity::Warning,
+                            String::from("out of order, moved to its place by time"),
+                        );
+                    }
+                    last_time = Some(*time);
+                }
+                _ => (),
+            }
+
+            let column = match &item {
+                BeatmapItem::KeyValue { column, .. } => Some(*column),
+                _ => None,
+            };
+            if let Err(err) = beatmap.apply_item(item) {
+                let column = err.column.or(column);
+                diagnostic(err.line, column, Severity::Error, err.inner.to_string());
+            }
+        }
+
+        beatmap.timing_points.sort_by_key(|tp| tp.time);
+        beatmap.hit_objects.sort_by_key(|ho| ho.start_time);
+        (beatmap, diagnostics)
+    }
+
+    /// Leniently parse a beatmap from a string. See [`Beatmap::parse_lenient`].
+    pub fn from_str_lenient(input: &str) -> (Beatmap, Vec<ParseDiagnostic>) {
+        Beatmap::parse_lenient(Cursor::new(input))
+    }
+}
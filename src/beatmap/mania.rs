@@ -0,0 +1,1016 @@
+use std::collections::VecDeque;
+
+use crate::events::Event;
+use crate::hitobject::{HitObject, HitObjectKind, SliderInfo};
+use crate::hitsounds::Additions;
+use crate::math::Point;
+use crate::timing::{Time, TimingIndex, TimingPointKind};
+
+use super::Beatmap;
+
+/// The beat length (in milliseconds) used before the first uninherited timing point, if the map
+/// doesn't have any.
+const DEFAULT_BEAT_LENGTH: f64 = 1000.0;
+
+/// How many of the most recent notes the note density is worked out from.
+const MAX_NOTES_FOR_DENSITY: usize = 7;
+
+/// The x position of the playfield's centre, where the ends of spinners count as being.
+const SPINNER_POSITION: Point<f32> = Point { x: 256.0, y: 192.0 };
+
+bitflags! {
+    /// What kind of pattern the converter goes for, depending on the timing and hitsounds of a
+    /// hit object.
+    struct PatternType: u32 {
+        /// Use the same columns as the previous pattern
+        const FORCE_STACK = 1;
+        /// Stay away from the columns of the previous pattern
+        const FORCE_NOT_STACK = 1 << 1;
+        /// Only place a single note
+        const KEEP_SINGLE = 1 << 2;
+        /// Place fewer notes at once
+        const LOW_PROBABILITY = 1 << 3;
+        /// Place notes in neighbouring columns
+        const GATHERED = 1 << 7;
+        /// Mirror notes across the middle of the stage
+        const MIRROR = 1 << 8;
+        /// Use the columns of the previous pattern, mirrored
+        const REVERSE = 1 << 9;
+        /// Alternate between a column and its mirror
+        const CYCLE = 1 << 10;
+        /// Place the next note one column to the right
+        const STAIR = 1 << 11;
+        /// Place the next note one column to the left
+        const REVERSE_STAIR = 1 << 12;
+    }
+}
+
+/// A note placed by converting a map to osu!mania.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct ManiaNote {
+    /// The column the note is in, counting from 0 on the left
+    pub column: i32,
+
+    /// When the note is hit
+    pub start_time: f64,
+
+    /// When the note is released, which is the same as `start_time` unless it's a hold note
+    pub end_time: f64,
+}
+
+impl ManiaNote {
+    /// Whether this is a hold note
+    pub fn is_hold(&self) -> bool {
+        self.end_time != self.start_time
+    }
+}
+
+/// The notes placed for one hit object, or one part of it.
+#[derive(Clone, Debug, Default)]
+struct Pattern {
+    notes: Vec<ManiaNote>,
+}
+
+impl Pattern {
+    fn has_column(&self, column: i32) -> bool {
+        self.notes.iter().any(|note| note.column == column)
+    }
+
+    /// How many different columns there are notes in
+    fn column_count(&self) -> i32 {
+        let mut columns = self
+            .notes
+            .iter()
+            .map(|note| note.column)
+            .collect::<Vec<_>>();
+        columns.sort_unstable();
+        columns.dedup();
+        columns.len() as i32
+    }
+}
+
+/// The random number generator the game uses to place notes, seeded from the map's difficulty
+/// settings so that every conversion of a map comes out the same.
+struct LegacyRandom {
+    x: u32,
+    y: u32,
+    z: u32,
+    w: u32,
+}
+
+impl LegacyRandom {
+    fn new(seed: i32) -> Self {
+        LegacyRandom {
+            x: seed as u32,
+            y: 842_502_087,
+            z: 3_579_807_591,
+            w: 273_326_509,
+        }
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let t = self.x ^ (self.x << 11);
+        self.x = self.y;
+        self.y = self.z;
+        self.z = self.w;
+        self.w = self.w ^ (self.w >> 19) ^ t ^ (t >> 8);
+        self.w
+    }
+
+    /// A number between 0 (inclusive) and 1 (exclusive)
+    fn next_double(&mut self) -> f64 {
+        (self.next_u32() & 0x7fff_ffff) as f64 / (i32::MAX as f64 + 1.0)
+    }
+
+    /// A whole number between `lower` (inclusive) and `upper` (exclusive)
+    fn next_range(&mut self, lower: i32, upper: i32) -> i32 {
+        (lower as f64 + self.next_double() * (upper - lower) as f64) as i32
+    }
+}
+
+/// The timing of a slider as the converter sees it, in whole milliseconds.
+struct SliderTiming {
+    start_time: i32,
+    end_time: i32,
+    segment_duration: i32,
+    spans: i32,
+}
+
+/// Converts an osu!standard map into osu!mania notes the way the game's legacy converter does.
+struct Converter<'a> {
+    beatmap: &'a Beatmap,
+    timing: TimingIndex<'a>,
+    random: LegacyRandom,
+    columns: i32,
+    conversion_difficulty: f64,
+
+    prev_note_times: VecDeque<f64>,
+    density: f64,
+    last_time: f64,
+    last_position: Point<f32>,
+    last_stair: PatternType,
+    last_pattern: Pattern,
+}
+
+/// Converts an osu!standard map into the notes it has in osu!mania, the way the game does.
+///
+/// Notes are placed in patterns that depend on the objects before them and on a random number
+/// generator seeded from the map's difficulty settings, so the whole map has to be converted at
+/// once.
+pub(crate) fn convert(beatmap: &Beatmap) -> Vec<ManiaNote> {
+    let mut converter = Converter::new(beatmap);
+    let mut notes = Vec::new();
+    for obj in beatmap.hit_objects.iter() {
+        for pattern in converter.convert_object(obj) {
+            notes.extend(pattern.notes);
+        }
+    }
+    notes
+}
+
+/// The number of columns an osu!standard map gets when it's converted, which goes by how many of
+/// its objects last for a while and by its circle size and overall difficulty. Converted maps
+/// have between 4 and 7 columns.
+fn column_count(beatmap: &Beatmap) -> i32 {
+    let objects = &beatmap.hit_objects;
+    let long_objects = objects
+        .iter()
+        .filter(|obj| !matches!(obj.kind, HitObjectKind::Circle))
+        .count();
+    let long_ratio = long_objects as f32 / objects.len() as f32;
+    let circle_size = (beatmap.difficulty.circle_size as f64).round_ties_even();
+    let overall_difficulty = (beatmap.difficulty.overall_difficulty as f64).round_ties_even();
+
+    if long_ratio < 0.2 {
+        7
+    } else if long_ratio < 0.3 || circle_size >= 5.0 {
+        if overall_difficulty > 5.0 {
+            7
+        } else {
+            6
+        }
+    } else if long_ratio > 0.6 {
+        if overall_difficulty > 4.0 {
+            5
+        } else {
+            4
+        }
+    } else {
+        (overall_difficulty as i32 + 1).clamp(4, 7)
+    }
+}
+
+/// How hard the converted map should be, which decides how many notes are placed at once. It
+/// goes up with the HP drain, approach rate and how many objects there are for the map's length.
+fn conversion_difficulty(beatmap: &Beatmap) -> f64 {
+    let objects = &beatmap.hit_objects;
+    let (first, last) = match (objects.first(), objects.last()) {
+        (Some(first), Some(last)) => (first.start_time.0, last.start_time.0),
+        _ => (0.0, 0.0),
+    };
+    let break_time = beatmap
+        .events
+        .iter()
+        .map(|evt| match evt {
+            Event::Break(info) => (info.end_time.0 - info.start_time.0) as f64,
+            _ => 0.0,
+        })
+        .sum::<f64>();
+    let drain_time = match ((last - first - break_time) / 1000.0) as i32 {
+        0 => 10_000,
+        drain_time => drain_time,
+    };
+
+    let difficulty = &beatmap.difficulty;
+    let settings = (difficulty.hp_drain_rate + difficulty.approach_rate.clamp(4.0, 7.0)) as f64;
+    let density = objects.len() as f64 / drain_time as f64 * 9.0;
+    ((settings / 1.5 + density) / 38.0 * 5.0 / 1.15).min(12.0)
+}
+
+/// The seed for the random number generator, which comes from the difficulty settings.
+fn seed(beatmap: &Beatmap) -> i32 {
+    let difficulty = &beatmap.difficulty;
+    let hp_and_cs = (difficulty.hp_drain_rate + difficulty.circle_size).round_ties_even() as i32;
+    let od = (difficulty.overall_difficulty as f64 * 41.2) as i32;
+    let ar = difficulty.approach_rate.round_ties_even() as i32;
+    hp_and_cs * 20 + od + ar
+}
+
+/// The additions on one of the nodes (the head, repeats and tail) of a slider.
+fn node_additions(obj: &HitObject, info: &SliderInfo, node: i32) -> Additions {
+    // sliders without edge additions get a single empty one when they're read, and then every
+    // node uses the slider's own additions
+    if info.edge_additions == [Additions::empty()] {
+        return obj.additions;
+    }
+    info.edge_additions
+        .get(node as usize)
+        .copied()
+        .unwrap_or(obj.additions)
+}
+
+impl<'a> Converter<'a> {
+    fn new(beatmap: &'a Beatmap) -> Self {
+        Converter {
+            beatmap,
+            timing: beatmap.timing_index(),
+            random: LegacyRandom::new(seed(beatmap)),
+            columns: column_count(beatmap),
+            conversion_difficulty: conversion_difficulty(beatmap),
+            prev_note_times: VecDeque::with_capacity(MAX_NOTES_FOR_DENSITY),
+            density: i32::MAX as f64,
+            last_time: 0.0,
+            last_position: Point::new(0.0, 0.0),
+            last_stair: PatternType::STAIR,
+            last_pattern: Pattern::default(),
+        }
+    }
+
+    fn convert_object(&mut self, obj: &HitObject) -> Vec<Pattern> {
+        let position = Point::new(obj.pos.x as f32, obj.pos.y as f32);
+        match &obj.kind {
+            HitObjectKind::Slider(info) => {
+                let slider = self.slider_timing(obj, info);
+                for i in 0..=slider.spans {
+                    let time = obj.start_time.0 + (slider.segment_duration * i) as f64;
+                    self.record_note(time, position);
+                    self.compute_density(time);
+                }
+                let patterns = self.slider_patterns(obj, info, &slider);
+                if let Some(last) = patterns.last() {
+                    self.last_pattern = last.clone();
+                }
+                patterns
+            }
+            HitObjectKind::Spinner(spinner) => {
+                self.record_note(spinner.end_time.0, SPINNER_POSITION);
+                self.compute_density(spinner.end_time.0);
+                vec![self.end_time_pattern(obj, spinner.end_time)]
+            }
+            HitObjectKind::Hold(hold) => {
+                self.record_note(hold.end_time.0, SPINNER_POSITION);
+                self.compute_density(hold.end_time.0);
+                vec![self.end_time_pattern(obj, hold.end_time)]
+            }
+            HitObjectKind::Circle => {
+                self.compute_density(obj.start_time.0);
+                let pattern = self.circle_pattern(obj, position);
+                self.record_note(obj.start_time.0, position);
+                self.last_pattern = pattern.clone();
+                vec![pattern]
+            }
+        }
+    }
+
+    fn record_note(&mut self, time: f64, position: Point<f32>) {
+        self.last_time = time;
+        self.last_position = position;
+    }
+
+    /// Works out the average time between the last few notes.
+    fn compute_density(&mut self, time: f64) {
+        if self.prev_note_times.len() == MAX_NOTES_FOR_DENSITY {
+            self.prev_note_times.pop_front();
+        }
+        self.prev_note_times.push_back(time);
+        if let (Some(first), Some(last)) =
+            (self.prev_note_times.front(), self.prev_note_times.back())
+        {
+            if self.prev_note_times.len() >= 2 {
+                self.density = (last - first) / self.prev_note_times.len() as f64;
+            }
+        }
+    }
+
+    /// The column under the given x position.
+    fn column_at(&self, x: f64) -> i32 {
+        let column_width = 512.0 / self.columns as f32;
+        ((x as f32 / column_width).floor() as i32).clamp(0, self.columns - 1)
+    }
+
+    fn random_column(&mut self) -> i32 {
+        self.random.next_range(0, self.columns)
+    }
+
+    /// How many notes to place at once, with the given chances of placing at least 2, 3, 4, 5
+    /// and 6.
+    fn random_note_count(&mut self, p2: f64, p3: f64, p4: f64, p5: f64, p6: f64) -> i32 {
+        let value = self.random.next_double();
+        if value >= 1.0 - p6 {
+            6
+        } else if value >= 1.0 - p5 {
+            5
+        } else if value >= 1.0 - p4 {
+            4
+        } else if value >= 1.0 - p3 {
+            3
+        } else if value >= 1.0 - p2 {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Finds a column that `valid` accepts, starting with `column` and then trying random
+    /// columns below `upper` (or the next column over, for gathered patterns).
+    fn find_available_column(
+        &mut self,
+        mut column: i32,
+        upper: i32,
+        gathered: bool,
+        valid: impl Fn(i32) -> bool,
+    ) -> i32 {
+        if valid(column) {
+            return column;
+        }
+        // the game gives up if every column is taken, which never happens with the number of
+        // notes it places in converted maps
+        if !(0..upper).any(&valid) {
+            return column;
+        }
+        loop {
+            column = if gathered {
+                (column + 1) % self.columns
+            } else {
+                self.random.next_range(0, upper)
+            };
+            if valid(column) {
+                return column;
+            }
+        }
+    }
+
+    fn beat_length_at(&self, time: Time) -> f64 {
+        // the first timing section also covers anything before it
+        self.timing
+            .timing_at(time)
+            .map(|info| info.mpb)
+            .or_else(|| {
+                self.timing.points().iter().find_map(|tp| match &tp.kind {
+                    TimingPointKind::Uninherited(info) => Some(info.mpb),
+                    TimingPointKind::Inherited(_) => None,
+                })
+            })
+            .unwrap_or(DEFAULT_BEAT_LENGTH)
+    }
+
+    fn circle_pattern(&mut self, obj: &HitObject, position: Point<f32>) -> Pattern {
+        let beat_length = self.beat_length_at(obj.start_time);
+        let kiai = self.timing.kiai_at(obj.start_time);
+        let dx = position.x - self.last_position.x;
+        let dy = position.y - self.last_position.y;
+        let position_separation = (dx * dx + dy * dy).sqrt();
+        let time_separation = obj.start_time.0 - self.last_time;
+
+        let mut convert = if time_separation <= 80.0 {
+            // more than 187 BPM
+            PatternType::FORCE_NOT_STACK | PatternType::KEEP_SINGLE
+        } else if time_separation <= 95.0 {
+            // more than 157 BPM
+            PatternType::FORCE_NOT_STACK | PatternType::KEEP_SINGLE | self.last_stair
+        } else if time_separation <= 105.0 {
+            // more than 140 BPM
+            PatternType::FORCE_NOT_STACK | PatternType::LOW_PROBABILITY
+        } else if time_separation <= 125.0 {
+            // more than 120 BPM
+            PatternType::FORCE_NOT_STACK
+        } else if time_separation <= 135.0 && position_separation < 20.0 {
+            // a stream at more than 111 BPM
+            PatternType::CYCLE | PatternType::KEEP_SINGLE
+        } else if time_separation <= 150.0 && position_separation < 20.0 {
+            // a stream at more than 100 BPM
+            PatternType::FORCE_STACK | PatternType::LOW_PROBABILITY
+        } else if position_separation < 20.0 && self.density >= beat_length / 2.5 {
+            // a low density stream
+            PatternType::REVERSE | PatternType::LOW_PROBABILITY
+        } else if self.density < beat_length / 2.5 || kiai {
+            // high density
+            PatternType::empty()
+        } else {
+            PatternType::LOW_PROBABILITY
+        };
+        if !convert.contains(PatternType::KEEP_SINGLE) {
+            if obj.additions.contains(Additions::FINISH) {
+                convert |= PatternType::MIRROR;
+            } else if obj.additions.contains(Additions::CLAP) {
+                convert |= PatternType::GATHERED;
+            }
+        }
+
+        let pattern = self.circle_pattern_core(obj, convert);
+        for note in pattern.notes.iter() {
+            if convert.contains(PatternType::STAIR) && note.column == self.columns - 1 {
+                self.last_stair = PatternType::REVERSE_STAIR;
+            }
+            if convert.contains(PatternType::REVERSE_STAIR) && note.column == 0 {
+                self.last_stair = PatternType::STAIR;
+            }
+        }
+        pattern
+    }
+
+    fn circle_pattern_core(&mut self, obj: &HitObject, convert: PatternType) -> Pattern {
+        let columns = self.columns;
+        let prev = self.last_pattern.clone();
+        let last_column = prev.notes.first().map_or(0, |note| note.column);
+        let mut pattern = Pattern::default();
+        let add = |pattern: &mut Pattern, column: i32| {
+            pattern.notes.push(ManiaNote {
+                column,
+                start_time: obj.start_time.0,
+                end_time: obj.start_time.0,
+            })
+        };
+
+        if convert.contains(PatternType::REVERSE) && !prev.notes.is_empty() {
+            // the previous pattern's columns, mirrored
+            for column in (0..columns).filter(|column| prev.has_column(*column)) {
+                add(&mut pattern, columns - column - 1);
+            }
+            return pattern;
+        }
+
+        if convert.contains(PatternType::CYCLE)
+            && prev.notes.len() == 1
+            && (columns % 2 == 0 || last_column != columns / 2)
+        {
+            // the mirror of the previous note's column, unless that's the middle one
+            add(&mut pattern, columns - last_column - 1);
+            return pattern;
+        }
+
+        if convert.contains(PatternType::FORCE_STACK) && !prev.notes.is_empty() {
+            // the same columns as the previous pattern
+            for column in (0..columns).filter(|column| prev.has_column(*column)) {
+                add(&mut pattern, column);
+            }
+            return pattern;
+        }
+
+        if prev.notes.len() == 1 {
+            if convert.contains(PatternType::STAIR) {
+                let column = if last_column + 1 == columns {
+                    0
+                } else {
+                    last_column + 1
+                };
+                add(&mut pattern, column);
+                return pattern;
+            }
+            if convert.contains(PatternType::REVERSE_STAIR) {
+                let column = if last_column == 0 {
+                    columns - 1
+                } else {
+                    last_column - 1
+                };
+                add(&mut pattern, column);
+                return pattern;
+            }
+        }
+
+        if convert.contains(PatternType::KEEP_SINGLE) {
+            return self.random_circle_notes(obj, convert, &prev, 1);
+        }
+
+        let difficulty = self.conversion_difficulty;
+        let low = convert.contains(PatternType::LOW_PROBABILITY);
+        if convert.contains(PatternType::MIRROR) {
+            return if difficulty > 6.5 {
+                self.mirrored_circle_pattern(obj, convert, &prev, 0.12, 0.38, 0.12)
+            } else if difficulty > 4.0 {
+                self.mirrored_circle_pattern(obj, convert, &prev, 0.12, 0.17, 0.0)
+            } else {
+                self.mirrored_circle_pattern(obj, convert, &prev, 0.12, 0.0, 0.0)
+            };
+        }
+
+        let (p2, p3) = match (difficulty, low) {
+            (d, true) if d > 6.5 => (0.78, 0.42),
+            (d, false) if d > 6.5 => (1.0, 0.62),
+            (d, true) if d > 4.0 => (0.35, 0.08),
+            (d, false) if d > 4.0 => (0.52, 0.15),
+            (d, true) if d > 2.0 => (0.18, 0.0),
+            (d, false) if d > 2.0 => (0.45, 0.0),
+            _ => (0.0, 0.0),
+        };
+        self.random_circle_pattern(obj, convert, &prev, p2, p3, 0.0, 0.0)
+    }
+
+    /// Places the given number of notes for a circle, starting from the column under it.
+    fn random_circle_notes(
+        &mut self,
+        obj: &HitObject,
+        convert: PatternType,
+        prev: &Pattern,
+        mut note_count: i32,
+    ) -> Pattern {
+        let allow_stacking = !convert.contains(PatternType::FORCE_NOT_STACK);
+        if !allow_stacking {
+            note_count = note_count.min(self.columns - prev.column_count());
+        }
+        let gathered = convert.contains(PatternType::GATHERED);
+
+        let mut pattern = Pattern::default();
+        let mut column = self.column_at(obj.pos.x);
+        for _ in 0..note_count {
+            column = self.find_available_column(column, self.columns, gathered, |c| {
+                !pattern.has_column(c) && (allow_stacking || !prev.has_column(c))
+            });
+            pattern.notes.push(ManiaNote {
+                column,
+                start_time: obj.start_time.0,
+                end_time: obj.start_time.0,
+            });
+        }
+        pattern
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn random_circle_pattern(
+        &mut self,
+        obj: &HitObject,
+        convert: PatternType,
+        prev: &Pattern,
+        mut p2: f64,
+        mut p3: f64,
+        mut p4: f64,
+        mut p5: f64,
+    ) -> Pattern {
+        match self.columns {
+            4 => {
+                p2 = p2.min(0.23);
+                p3 = p3.min(0.04);
+                p4 = 0.0;
+                p5 = 0.0;
+            }
+            5 => {
+                p3 = p3.min(0.15);
+                p4 = p4.min(0.03);
+                p5 = 0.0;
+            }
+            _ => (),
+        }
+        if obj.additions.contains(Additions::CLAP) {
+            p2 = 1.0;
+        }
+        let note_count = self.random_note_count(p2, p3, p4, p5, 0.0);
+        self.random_circle_notes(obj, convert, prev, note_count)
+    }
+
+    /// Places notes in pairs mirrored across the middle of the stage, and sometimes one in the
+    /// middle column.
+    fn mirrored_circle_pattern(
+        &mut self,
+        obj: &HitObject,
+        convert: PatternType,
+        prev: &Pattern,
+        mut centre_probability: f64,
+        mut p2: f64,
+        mut p3: f64,
+    ) -> Pattern {
+        if convert.contains(PatternType::FORCE_NOT_STACK) {
+            let (p4, p5) = ((p2 + p3) / 2.0, p3);
+            return self.random_circle_pattern(obj, convert, prev, 0.5 + p2 / 2.0, p2, p4, p5);
+        }
+
+        let columns = self.columns;
+        match columns {
+            4 => {
+                centre_probability = 0.0;
+                p2 = 1.0 - ((1.0 - p2) * 2.0).max(0.8);
+                p3 = 0.0;
+            }
+            5 => {
+                centre_probability = centre_probability.min(0.03);
+                p3 = 0.0;
+            }
+            6 => {
+                centre_probability = 0.0;
+                p2 = 1.0 - ((1.0 - p2) * 2.0).max(0.5);
+                p3 = 1.0 - ((1.0 - p3) * 2.0).max(0.85);
+            }
+            _ => (),
+        }
+        let (p2, p3) = (p2.clamp(0.0, 1.0), p3.clamp(0.0, 1.0));
+        let centre_value = self.random.next_double();
+        let note_count = self.random_note_count(p2, p3, 0.0, 0.0, 0.0);
+        let add_to_centre =
+            columns % 2 != 0 && note_count != 3 && centre_value > 1.0 - centre_probability;
+
+        let mut pattern = Pattern::default();
+        let add = |pattern: &mut Pattern, column: i32| {
+            pattern.notes.push(ManiaNote {
+                column,
+                start_time: obj.start_time.0,
+                end_time: obj.start_time.0,
+            })
+        };
+        let column_limit = (if columns % 2 == 0 {
+            columns
+        } else {
+            columns - 1
+        }) / 2;
+        let mut column = self.random.next_range(0, column_limit);
+        for _ in 0..note_count {
+            column =
+                self.find_available_column(column, column_limit, false, |c| !pattern.has_column(c));
+            add(&mut pattern, column);
+            add(&mut pattern, columns - column - 1);
+        }
+        if add_to_centre {
+            add(&mut pattern, columns / 2);
+        }
+        pattern
+    }
+
+    /// Spinners become a hold note in a random column, or a note if they're too short.
+    fn end_time_pattern(&mut self, obj: &HitObject, end_time: Time) -> Pattern {
+        let end_time = end_time.0 as i32 as f64;
+        let hold = end_time - obj.start_time.0 >= 100.0;
+        let column = self.random_column();
+        Pattern {
+            notes: vec![ManiaNote {
+                column,
+                start_time: obj.start_time.0,
+                end_time: if hold { end_time } else { obj.start_time.0 },
+            }],
+        }
+    }
+
+    fn slider_timing(&self, obj: &HitObject, info: &SliderInfo) -> SliderTiming {
+        let beat_length =
+            self.beat_length_at(obj.start_time) / self.timing.slider_velocity_at(obj.start_time);
+        let spans = info.num_repeats.max(1) as i32;
+        let start_time = obj.start_time.0.round_ties_even() as i32;
+        let end_time = (start_time as f64
+            + info.pixel_length * beat_length * spans as f64 * 0.01
+                / self.beatmap.difficulty.slider_multiplier)
+            .floor() as i32;
+        SliderTiming {
+            start_time,
+            end_time,
+            segment_duration: (end_time - start_time) / spans,
+            spans,
+        }
+    }
+
+    /// Converts a slider, splitting the notes into those that end with the slider and those
+    /// that don't. The ones that end with it are what the next object's pattern goes by.
+    fn slider_patterns(
+        &mut self,
+        obj: &HitObject,
+        info: &SliderInfo,
+        slider: &SliderTiming,
+    ) -> Vec<Pattern> {
+        let prev = self.last_pattern.clone();
+        let mut generator = SliderGenerator {
+            converter: self,
+            obj,
+            info,
+            slider,
+            prev,
+            convert: PatternType::empty(),
+        };
+        let pattern = generator.generate();
+        if pattern.notes.len() == 1 {
+            return vec![pattern];
+        }
+
+        let (ending, intermediate) = pattern
+            .notes
+            .into_iter()
+            .partition(|note| note.end_time as i32 == slider.end_time);
+        vec![
+            Pattern {
+                notes: intermediate,
+            },
+            Pattern { notes: ending },
+        ]
+    }
+}
+
+/// Places the notes for a slider.
+struct SliderGenerator<'c, 'a, 'o> {
+    converter: &'c mut Converter<'a>,
+    obj: &'o HitObject,
+    info: &'o SliderInfo,
+    slider: &'o SliderTiming,
+    prev: Pattern,
+    convert: PatternType,
+}
+
+impl SliderGenerator<'_, '_, '_> {
+    fn generate(&mut self) -> Pattern {
+        if !self.converter.timing.kiai_at(self.obj.start_time) {
+            self.convert = PatternType::LOW_PROBABILITY;
+        }
+        let columns = self.converter.columns;
+        let difficulty = self.converter.conversion_difficulty;
+        let SliderTiming {
+            start_time,
+            end_time,
+            segment_duration,
+            spans,
+        } = *self.slider;
+
+        if spans > 1 {
+            if segment_duration <= 90 {
+                return self.random_holds(start_time, 1);
+            }
+            if segment_duration <= 120 {
+                self.convert |= PatternType::FORCE_NOT_STACK;
+                return self.random_notes(start_time, spans + 1);
+            }
+            if segment_duration <= 160 {
+                return self.stair(start_time);
+            }
+            if segment_duration <= 200 && difficulty > 3.0 {
+                return self.random_multiple_notes(start_time);
+            }
+            if end_time - start_time >= 4000 {
+                return self.n_random_holds(start_time, 0.23, 0.0, 0.0);
+            }
+            if segment_duration > 400 && spans < columns - 1 {
+                return self.tiled_holds(start_time);
+            }
+            return self.hold_and_notes(start_time);
+        }
+
+        if segment_duration <= 110 {
+            if self.prev.column_count() < columns {
+                self.convert |= PatternType::FORCE_NOT_STACK;
+            } else {
+                self.convert -= PatternType::FORCE_NOT_STACK;
+            }
+            let note_count = if segment_duration < 80 { 1 } else { 2 };
+            return self.random_notes(start_time, note_count);
+        }
+
+        let low = self.convert.contains(PatternType::LOW_PROBABILITY);
+        let (p2, p3, p4) = match (difficulty, low) {
+            (d, true) if d > 6.5 => (0.78, 0.3, 0.0),
+            (d, false) if d > 6.5 => (0.85, 0.36, 0.03),
+            (d, true) if d > 4.0 => (0.43, 0.08, 0.0),
+            (d, false) if d > 4.0 => (0.56, 0.18, 0.0),
+            (d, true) if d > 2.5 => (0.3, 0.0, 0.0),
+            (d, false) if d > 2.5 => (0.37, 0.08, 0.0),
+            (_, true) => (0.17, 0.0, 0.0),
+            (_, false) => (0.27, 0.0, 0.0),
+        };
+        self.n_random_holds(start_time, p2, p3, p4)
+    }
+
+    fn add(&self, pattern: &mut Pattern, column: i32, start_time: i32, end_time: i32) {
+        pattern.notes.push(ManiaNote {
+            column,
+            start_time: start_time as f64,
+            end_time: end_time as f64,
+        });
+    }
+
+    /// The additions on the node at or after the given time.
+    fn additions_at(&self, time: i32) -> Additions {
+        let node = match self.slider.segment_duration {
+            0 => 0,
+            duration => (time - self.slider.start_time) / duration,
+        };
+        node_additions(self.obj, self.info, node)
+    }
+
+    fn column_under(&self) -> i32 {
+        self.converter.column_at(self.obj.pos.x)
+    }
+
+    /// Starting from the column under the slider, or the first one clear of the previous pattern
+    fn start_column(&mut self) -> i32 {
+        let column = self.column_under();
+        let columns = self.converter.columns;
+        if self.convert.contains(PatternType::FORCE_NOT_STACK) && self.prev.column_count() < columns
+        {
+            let prev = &self.prev;
+            self.converter
+                .find_available_column(column, columns, false, |c| !prev.has_column(c))
+        } else {
+            column
+        }
+    }
+
+    /// Hold notes in random columns that last as long as the slider.
+    fn random_holds(&mut self, start_time: i32, note_count: i32) -> Pattern {
+        let columns = self.converter.columns;
+        let usable_columns = columns - self.prev.column_count();
+        let mut pattern = Pattern::default();
+        let mut column = self.converter.random_column();
+        for _ in 0..usable_columns.min(note_count) {
+            let prev = &self.prev;
+            column = self
+                .converter
+                .find_available_column(column, columns, false, |c| {
+                    !pattern.has_column(c) && !prev.has_column(c)
+                });
+            self.add(&mut pattern, column, start_time, self.slider.end_time);
+        }
+        for _ in 0..note_count - usable_columns {
+            column = self
+                .converter
+                .find_available_column(column, columns, false, |c| !pattern.has_column(c));
+            self.add(&mut pattern, column, start_time, self.slider.end_time);
+        }
+        pattern
+    }
+
+    /// A note on each node of the slider, never in the same column twice in a row.
+    fn random_notes(&mut self, mut start_time: i32, note_count: i32) -> Pattern {
+        let columns = self.converter.columns;
+        let mut pattern = Pattern::default();
+        let mut column = self.start_column();
+        let mut last_column = column;
+        for _ in 0..note_count {
+            self.add(&mut pattern, column, start_time, start_time);
+            column = self
+                .converter
+                .find_available_column(column, columns, false, |c| c != last_column);
+            last_column = column;
+            start_time += self.slider.segment_duration;
+        }
+        pattern
+    }
+
+    /// A note on each node of the slider, going across the stage and back.
+    fn stair(&mut self, mut start_time: i32) -> Pattern {
+        let columns = self.converter.columns;
+        let mut pattern = Pattern::default();
+        let mut column = self.column_under();
+        let mut increasing = self.converter.random.next_double() > 0.5;
+        for _ in 0..=self.slider.spans {
+            self.add(&mut pattern, column, start_time, start_time);
+            start_time += self.slider.segment_duration;
+            if increasing {
+                if column >= columns - 1 {
+                    increasing = false;
+                    column -= 1;
+                } else {
+                    column += 1;
+                }
+            } else if column <= 0 {
+                increasing = true;
+                column += 1;
+            } else {
+                column -= 1;
+            }
+        }
+        pattern
+    }
+
+    /// Two notes on each node of the slider.
+    fn random_multiple_notes(&mut self, mut start_time: i32) -> Pattern {
+        let columns = self.converter.columns;
+        let mut pattern = Pattern::default();
+        let interval = self.converter.random.next_range(1, columns - 1);
+        let mut column = self.column_under();
+        for _ in 0..=self.slider.spans {
+            self.add(&mut pattern, column, start_time, start_time);
+            column += interval;
+            if column >= columns {
+                column = column - columns + 1;
+            }
+            self.add(&mut pattern, column, start_time, start_time);
+            column = self.converter.random_column();
+            start_time += self.slider.segment_duration;
+        }
+        pattern
+    }
+
+    /// Hold notes that last as long as the slider, with the given chances of placing at least
+    /// 2, 3 and 4 of them.
+    fn n_random_holds(
+        &mut self,
+        start_time: i32,
+        mut p2: f64,
+        mut p3: f64,
+        mut p4: f64,
+    ) -> Pattern {
+        match self.converter.columns {
+            4 => {
+                p2 = p2.min(0.3);
+                p3 = p3.min(0.04);
+                p4 = 0.0;
+            }
+            5 => {
+                p2 = p2.min(0.34);
+                p3 = p3.min(0.1);
+                p4 = p4.min(0.03);
+            }
+            _ => (),
+        }
+        let double_sample =
+            |additions: Additions| additions.intersects(Additions::CLAP | Additions::FINISH);
+        if !self.convert.contains(PatternType::LOW_PROBABILITY)
+            && (double_sample(self.obj.additions)
+                || double_sample(self.additions_at(self.slider.start_time)))
+        {
+            p2 = 1.0;
+        }
+        let note_count = self.converter.random_note_count(p2, p3, p4, 0.0, 0.0);
+        self.random_holds(start_time, note_count)
+    }
+
+    /// Hold notes that start on each node and all end at the end of the slider.
+    fn tiled_holds(&mut self, mut start_time: i32) -> Pattern {
+        let columns = self.converter.columns;
+        let spans = self.slider.spans;
+        let end_time = start_time + self.slider.segment_duration * spans;
+        let mut pattern = Pattern::default();
+        let mut column = self.start_column();
+        for _ in 0..spans.min(columns) {
+            column = self
+                .converter
+                .find_available_column(column, columns, false, |c| !pattern.has_column(c));
+            self.add(&mut pattern, column, start_time, end_time);
+            start_time += self.slider.segment_duration;
+        }
+        pattern
+    }
+
+    /// A hold note for the whole slider, with notes next to it on the nodes.
+    fn hold_and_notes(&mut self, mut start_time: i32) -> Pattern {
+        let columns = self.converter.columns;
+        let difficulty = self.converter.conversion_difficulty;
+        let mut pattern = Pattern::default();
+        let hold_column = self.start_column();
+        self.add(&mut pattern, hold_column, start_time, self.slider.end_time);
+
+        let mut column = self.converter.random_column();
+        let note_count = if difficulty > 6.5 {
+            self.converter.random_note_count(0.63, 0.0, 0.0, 0.0, 0.0)
+        } else if difficulty > 4.0 {
+            let p2 = if columns < 6 { 0.12 } else { 0.45 };
+            self.converter.random_note_count(p2, 0.0, 0.0, 0.0, 0.0)
+        } else if difficulty > 2.5 {
+            let p2 = if columns < 6 { 0.0 } else { 0.24 };
+            self.converter.random_note_count(p2, 0.0, 0.0, 0.0, 0.0)
+        } else {
+            0
+        }
+        .min(columns - 1);
+
+        // the head only gets notes next to the hold if it has a hitsound
+        let ignore_head = self.additions_at(start_time).is_empty();
+        for _ in 0..=self.slider.spans {
+            let mut row = Pattern::default();
+            if !(ignore_head && start_time == self.slider.start_time) {
+                for _ in 0..note_count {
+                    column = self
+                        .converter
+                        .find_available_column(column, columns, false, |c| {
+                            c != hold_column && !row.has_column(c)
+                        });
+                    self.add(&mut row, column, start_time, start_time);
+                }
+            }
+            pattern.notes.extend(row.notes);
+            start_time += self.slider.segment_duration;
+        }
+        pattern
+    }
+}
@@ -1,16 +1,37 @@
+mod checksum;
+mod combo;
 pub mod diff_calc;
+mod document;
 mod ext;
 mod format;
+mod lenient;
+mod mania;
+mod mods;
 pub mod pp_calc;
+mod reader;
+mod slider_events;
+mod stacking;
+mod stats;
+mod variant;
+mod version;
 
 use crate::color::Color;
 use crate::data::Mode;
 use crate::events::Event;
 use crate::hitobject::{HitObject, HitObjectKind};
 use crate::hitsounds::SampleSet;
-use crate::timing::{Millis, TimingPoint};
+use crate::timing::{Millis, Time, TimingPoint};
 
+pub use self::combo::*;
+pub use self::document::*;
 pub use self::format::*;
+pub use self::lenient::*;
+pub use self::reader::*;
+pub use self::slider_events::*;
+pub use self::stacking::*;
+pub use self::stats::*;
+pub use self::variant::*;
+pub use self::version::*;
 
 /// Difficulty settings defined by the map.
 #[derive(Clone, Debug, Default, PartialEq)]
@@ -93,6 +114,48 @@ impl Difficulty {
     }
 }
 
+/// The speed of the countdown before the first hit object.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Countdown {
+    /// No countdown
+    None = 0,
+
+    /// Countdown at normal speed
+    Normal = 1,
+
+    /// Countdown at half speed
+    Half = 2,
+
+    /// Countdown at double speed
+    Double = 3,
+}
+
+/// Where hit circle overlays are drawn relative to the numbers on them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OverlayPosition {
+    /// Use the skin's setting
+    NoChange,
+
+    /// Draw overlays under the numbers
+    Below,
+
+    /// Draw overlays on top of the numbers
+    Above,
+}
+
+impl OverlayPosition {
+    /// The name used for this position in .osu files
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OverlayPosition::NoChange => "NoChange",
+            OverlayPosition::Below => "Below",
+            OverlayPosition::Above => "Above",
+        }
+    }
+}
+
 /// Represents a single beatmap.
 #[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -106,15 +169,24 @@ pub struct Beatmap {
     /// The amount of time (in milliseconds) added before the audio file begins playing. Useful for audio files that begin immediately.
     pub audio_leadin: Millis,
 
+    /// MD5 hash of the audio file (deprecated, empty if not set)
+    pub audio_hash: String,
+
     /// When (in milliseconds) the audio file should begin playing when selected in the song selection menu.
     pub preview_time: Millis,
 
-    /// Whether or not to show the countdown
-    pub countdown: bool,
+    /// The speed of the countdown before the first hit object
+    pub countdown: Countdown,
+
+    /// How many beats earlier than usual the countdown starts.
+    pub countdown_offset: u32,
 
     /// The default sample set for hit objects which don't have a custom override.
     pub sample_set: SampleSet,
 
+    /// The default volume of hitsounds (deprecated, replaced by timing point volumes).
+    pub sample_volume: u32,
+
     /// Leniency for stacked objects.
     pub stack_leniency: f64,
 
@@ -124,12 +196,37 @@ pub struct Beatmap {
     /// Whether or not to show black borders during breaks.
     pub letterbox_in_breaks: bool,
 
+    /// Whether the storyboard is drawn in front of combo fire (deprecated).
+    pub story_fire_in_front: bool,
+
+    /// Whether the storyboard may use the player's skin images.
+    pub use_skin_sprites: bool,
+
+    /// Whether the playfield is shown instead of the background when there's a storyboard
+    /// (deprecated).
+    pub always_show_playfield: bool,
+
+    /// Where hit circle overlays are drawn relative to the numbers on them.
+    pub overlay_position: OverlayPosition,
+
+    /// The name of the skin to use while playing, if it's installed (empty if not set).
+    pub skin_preference: String,
+
     /// Whether the beatmap has an epilepsy warning
     pub epilepsy_warning: bool,
 
-    /// TODO: unknown field
+    /// Whether the storyboard is made for a 16:9 screen.
+    ///
+    /// Storyboards without this are drawn in a 4:3 area in the middle of the screen, so
+    /// anything outside of x = 0..640 is cut off.
     pub widescreen_storyboard: bool,
 
+    /// Whether osu!mania uses the "N+1" style key layout, with an extra column on the left.
+    pub special_style: bool,
+
+    /// Whether hitsounds are sped up or slowed down along with rate-changing mods.
+    pub samples_match_playback_rate: bool,
+
     /// An instance of the difficulty settings.
     pub difficulty: Difficulty,
 
@@ -184,6 +281,12 @@ pub struct Beatmap {
     /// Overridden combo colors.
     pub colors: Vec<Color>,
 
+    /// Overridden color of the inside of slider bodies.
+    pub slider_track_override: Option<Color>,
+
+    /// Overridden color of the border of slider bodies.
+    pub slider_border: Option<Color>,
+
     /// The set of hit objects.
     pub hit_objects: Vec<HitObject>,
 
@@ -198,14 +301,24 @@ impl Default for Beatmap {
 
             audio_filename: String::new(),
             audio_leadin: Millis(0),
+            audio_hash: String::new(),
             preview_time: Millis(0),
-            countdown: false,
+            countdown: Countdown::Normal,
+            countdown_offset: 0,
             sample_set: SampleSet::None,
+            sample_volume: 100,
             stack_leniency: 0.7,
             mode: Mode::Osu,
             epilepsy_warning: false,
             letterbox_in_breaks: false,
+            story_fire_in_front: true,
+            use_skin_sprites: false,
+            always_show_playfield: false,
+            overlay_position: OverlayPosition::NoChange,
+            skin_preference: String::new(),
             widescreen_storyboard: false,
+            special_style: false,
+            samples_match_playback_rate: false,
 
             difficulty: Difficulty::default(),
 
@@ -228,6 +341,8 @@ impl Default for Beatmap {
 
             events: Vec::new(),
             colors: Vec::new(),
+            slider_track_override: None,
+            slider_border: None,
             hit_objects: Vec::new(),
             timing_points: Vec::new(),
         }
@@ -236,20 +351,15 @@ impl Default for Beatmap {
 
 impl Beatmap {
     /// Returns the timing point associated with the timing section to which the given time belongs.
-    pub fn locate_timing_point(&self, time: impl Into<Millis>) -> Option<TimingPoint> {
-        // TODO: make this efficient
-        let mut tp = None;
-        let time = time.into();
-        for timing_point in self.timing_points.iter() {
-            if timing_point.time < time {
-                tp = Some(timing_point.clone());
-            }
-        }
-        tp
+    ///
+    /// This is the last timing point (inherited or not) at or before the given time. See
+    /// [`Beatmap::timing_index`] for looking up many times.
+    pub fn locate_timing_point(&self, time: impl Into<Time>) -> Option<TimingPoint> {
+        self.timing_lookup().point_at(time).cloned()
     }
 
     /// Returns the hitobject located at the given time.
-    pub fn locate_hitobject(&self, time: impl Into<Millis>) -> Option<HitObject> {
+    pub fn locate_hitobject(&self, time: impl Into<Time>) -> Option<HitObject> {
         let time = time.into();
         for hit_object in self.hit_objects.iter() {
             if hit_object.start_time == time {
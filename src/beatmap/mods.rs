@@ -0,0 +1,251 @@
+use crate::data::{Mode, Mods};
+use crate::events::Event;
+use crate::hitobject::{HitObjectKind, HoldInfo, SpinnerInfo};
+use crate::math::Point;
+use crate::storyboard::{command_bounds, Command, Origin, Parameter, Tween};
+use crate::timing::{Millis, Time, TimingPointKind};
+
+use super::pp_calc::{mods_apply, ModsApply};
+use super::Beatmap;
+
+/// The height of the playfield in osu!pixels, which HardRock flips hit objects across.
+const PLAYFIELD_HEIGHT: f64 = 384.0;
+
+/// The height of the storyboard area, which HardRock flips storyboard sprites across.
+const STORYBOARD_HEIGHT: f64 = 480.0;
+
+impl Beatmap {
+    /// Returns a copy of this beatmap as it actually plays with the given mods applied.
+    ///
+    /// See [`Beatmap::apply_mods`].
+    pub fn with_mods(&self, mods: Mods) -> Beatmap {
+        let mut beatmap = self.clone();
+        beatmap.apply_mods(mods);
+        beatmap
+    }
+
+    /// Changes this beatmap into the way it actually plays with the given mods:
+    ///
+    /// * HardRock flips hit objects, slider paths and the storyboard vertically (in osu!
+    ///   standard only). Storyboard sprites get a mirrored origin and a flipped image too
+    /// * HardRock and Easy change the [`Difficulty`][super::Difficulty] settings, as
+    ///   [`mods_apply`] does
+    /// * DoubleTime, Nightcore and HalfTime rescale every time in the map (hit objects, timing
+    ///   points, events, storyboard commands, the preview time and bookmarks) so the map plays at
+    ///   normal speed. AR and OD are converted to what they are at the new speed, so they may go
+    ///   past 10.
+    ///
+    /// The audio file can't be rescaled, so it won't line up with the changed map anymore.
+    ///
+    /// ```
+    /// # use libosu::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = "osu file format v14\n\n[Difficulty]\nApproachRate:9\n\n[HitObjects]\n100,100,3000,1,0,0:0:0:0:\n";
+    /// let beatmap = Beatmap::from_str(input).unwrap().with_mods(Mods::DoubleTime | Mods::HardRock);
+    /// assert_eq!(beatmap.difficulty.approach_rate, 11.0);
+    /// assert_eq!(beatmap.hit_objects[0].pos, Point::new(100.0, 284.0));
+    /// assert_eq!(beatmap.hit_objects[0].start_time, Time(2000.0));
+    /// ```
+    pub fn apply_mods(&mut self, mods: Mods) {
+        let difficulty = &mut self.difficulty;
+        let ModsApply {
+            speed_mul,
+            ar,
+            od,
+            cs,
+            hp,
+        } = mods_apply(
+            mods,
+            difficulty.approach_rate as f64,
+            difficulty.overall_difficulty as f64,
+            difficulty.circle_size as f64,
+            difficulty.hp_drain_rate as f64,
+        );
+        difficulty.approach_rate = ar as f32;
+        difficulty.overall_difficulty = od as f32;
+        difficulty.hp_drain_rate = hp as f32;
+        // in mania the circle size is the number of keys
+        if self.mode != Mode::Mania {
+            difficulty.circle_size = cs as f32;
+        }
+
+        if mods.contains(Mods::HardRock) && self.mode == Mode::Osu {
+            self.flip_vertical();
+        }
+        if speed_mul != 1.0 {
+            self.change_rate(speed_mul);
+        }
+    }
+
+    fn flip_vertical(&mut self) {
+        let flip = |p: &mut Point<f64>| p.y = PLAYFIELD_HEIGHT - p.y;
+        for obj in self.hit_objects.iter_mut() {
+            flip(&mut obj.pos);
+            if let HitObjectKind::Slider(info) = &mut obj.kind {
+                info.control_points.iter_mut().for_each(flip);
+            }
+        }
+
+        for evt in self.events.iter_mut() {
+            let (origin, pos, commands) = match evt {
+                Event::Sprite(sprite) => {
+                    (&mut sprite.origin, &mut sprite.pos, &mut sprite.commands)
+                }
+                Event::Animation(anim) => (&mut anim.origin, &mut anim.pos, &mut anim.commands),
+                _ => continue,
+            };
+            *origin = flip_origin(*origin);
+            pos.y = STORYBOARD_HEIGHT - pos.y;
+            commands.iter_mut().for_each(flip_command);
+            flip_image(commands);
+        }
+    }
+
+    /// Retimes the whole map so that it plays `rate` times as fast, the way DoubleTime (1.5) and
+    /// HalfTime (0.75) do. Every time in the map is divided by the rate: hit objects, timing
+    /// points (including the beat length, so slider durations follow), breaks, video and sample
+    /// events, storyboard commands, the audio lead-in, the preview time and editor bookmarks.
+    ///
+    /// Nothing else is changed, so AR and OD keep their values and the approach and hit windows
+    /// stay the same length in real time. Event, storyboard and bookmark times are stored in whole
+    /// milliseconds and get rounded right away. Hit object and timing point times are divided
+    /// exactly, but hit objects are still rounded to whole milliseconds when the map is written
+    /// with a version before [`FIRST_LAZER_VERSION`][super::FIRST_LAZER_VERSION].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` isn't a positive, finite number.
+    pub fn change_rate(&mut self, rate: f64) {
+        assert!(
+            rate > 0.0 && rate.is_finite(),
+            "rate must be positive, got {}",
+            rate
+        );
+        let scale = |t: Time| Time(t.0 / rate);
+        for obj in self.hit_objects.iter_mut() {
+            obj.start_time = scale(obj.start_time);
+            match &mut obj.kind {
+                HitObjectKind::Spinner(SpinnerInfo { end_time })
+                | HitObjectKind::Hold(HoldInfo { end_time }) => *end_time = scale(*end_time),
+                HitObjectKind::Circle | HitObjectKind::Slider(_) => (),
+            }
+        }
+        for tp in self.timing_points.iter_mut() {
+            tp.time = scale(tp.time);
+            if let TimingPointKind::Uninherited(info) = &mut tp.kind {
+                info.mpb /= rate;
+            }
+        }
+
+        for evt in self.events.iter_mut() {
+            match evt {
+                Event::Break(info) => {
+                    info.start_time = scale_millis(info.start_time, rate);
+                    info.end_time = scale_millis(info.end_time, rate);
+                }
+                Event::Video(info) => info.start_time = scale_millis(info.start_time, rate),
+                Event::Sample(info) => info.time = scale_millis(info.time, rate),
+                Event::Sprite(sprite) => {
+                    for cmd in sprite.commands.iter_mut() {
+                        scale_command(cmd, rate);
+                    }
+                }
+                Event::Animation(anim) => {
+                    anim.frame_delay /= rate;
+                    for cmd in anim.commands.iter_mut() {
+                        scale_command(cmd, rate);
+                    }
+                }
+                Event::Background(_) | Event::Unknown(_) => (),
+            }
+        }
+
+        self.audio_leadin = scale_millis(self.audio_leadin, rate);
+        self.preview_time = scale_millis(self.preview_time, rate);
+        for bookmark in self.bookmarks.iter_mut() {
+            *bookmark = scale_millis(Millis(*bookmark), rate).0;
+        }
+    }
+}
+
+fn scale_millis(time: Millis, rate: f64) -> Millis {
+    Millis((time.0 as f64 / rate).round() as i32)
+}
+
+/// Mirrors a storyboard command vertically: positions are flipped and rotations reversed.
+fn flip_command(cmd: &mut Command) {
+    let flip = |y: &mut f64| *y = STORYBOARD_HEIGHT - *y;
+    match cmd {
+        Command::Move(t) => t.values.iter_mut().for_each(|p| flip(&mut p.y)),
+        Command::MoveY(t) => t.values.iter_mut().for_each(flip),
+        Command::Rotate(t) => t.values.iter_mut().for_each(|r| *r = -*r),
+        _ => (),
+    }
+    if let Some(children) = cmd.children_mut() {
+        children.iter_mut().for_each(flip_command);
+    }
+}
+
+/// Mirrors a sprite origin vertically, so the image stays on the same side of its position once
+/// it's flipped. `Custom` behaves like `TopLeft`, so it becomes `BottomLeft`.
+fn flip_origin(origin: Origin) -> Origin {
+    match origin {
+        Origin::TopLeft | Origin::Custom => Origin::BottomLeft,
+        Origin::TopCentre => Origin::BottomCentre,
+        Origin::TopRight => Origin::BottomRight,
+        Origin::BottomLeft => Origin::TopLeft,
+        Origin::BottomCentre => Origin::TopCentre,
+        Origin::BottomRight => Origin::TopRight,
+        origin => origin,
+    }
+}
+
+/// Turns the vertical flip of a sprite's image on or off for its whole lifetime, with a `P,V`
+/// command that starts and ends at the same time. Flips that only apply for part of the
+/// lifetime are left as they are.
+fn flip_image(commands: &mut Vec<Command>) {
+    let is_flip = |cmd: &Command| match cmd {
+        Command::Parameter(t) => {
+            t.start_time == t.end_time && t.values == [Parameter::FlipVertical]
+        }
+        _ => false,
+    };
+    if let Some(idx) = commands.iter().position(is_flip) {
+        commands.remove(idx);
+    } else if let Some((start, _)) = command_bounds(commands) {
+        commands.insert(
+            0,
+            Command::Parameter(Tween::constant(start, start, Parameter::FlipVertical)),
+        );
+    }
+}
+
+/// Rescales the times of a storyboard command. Nested commands are relative to their parent,
+/// but their offsets and durations scale the same way.
+fn scale_command(cmd: &mut Command, rate: f64) {
+    fn scale_tween<T>(t: &mut Tween<T>, rate: f64) {
+        t.start_time = scale_millis(t.start_time, rate);
+        t.end_time = scale_millis(t.end_time, rate);
+    }
+
+    match cmd {
+        Command::Fade(t)
+        | Command::MoveX(t)
+        | Command::MoveY(t)
+        | Command::Scale(t)
+        | Command::Rotate(t) => scale_tween(t, rate),
+        Command::Move(t) | Command::VectorScale(t) => scale_tween(t, rate),
+        Command::Color(t) => scale_tween(t, rate),
+        Command::Parameter(t) => scale_tween(t, rate),
+        Command::Loop(info) => info.start_time = scale_millis(info.start_time, rate),
+        Command::Trigger(info) => {
+            info.start_time = scale_millis(info.start_time, rate);
+            info.end_time = scale_millis(info.end_time, rate);
+        }
+    }
+    if let Some(children) = cmd.children_mut() {
+        for child in children.iter_mut() {
+            scale_command(child, rate);
+        }
+    }
+}
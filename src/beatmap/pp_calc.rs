@@ -239,12 +239,13 @@ pub struct ModsApply {
     pub hp: f64,
 }
 
-/// Apply mods to difficulty scores
+/// Apply mods to difficulty scores. HardRock and Easy change them whether or not a speed
+/// changing mod is used as well
 pub fn mods_apply(mods: Mods, ar: f64, od: f64, cs: f64, hp: f64) -> ModsApply {
     let mods_speed_changing = Mods::DoubleTime | Mods::HalfTime | Mods::Nightcore;
     let mods_map_changing = Mods::HardRock | Mods::Easy | mods_speed_changing;
 
-    if !mods.intersects(mods_speed_changing) {
+    if !mods.intersects(mods_map_changing) {
         return ModsApply {
             speed_mul: 1.0,
             ar,
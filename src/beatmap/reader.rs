@@ -0,0 +1,316 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, ErrorKind, Lines, Read};
+use std::str::FromStr;
+
+use crate::color::Color;
+use crate::errors::ParseError;
+use crate::events::{parse_event_line, Event};
+use crate::hitobject::HitObject;
+use crate::hitsounds::SampleSet;
+use crate::storyboard::{expand_variables, parse_variable};
+use crate::timing::TimingPoint;
+
+use super::{
+    BeatmapParseError, FormatVersion, KEY_VALUE_RGX, OSU_FORMAT_VERSION_RGX, SECTION_HEADER_RGX,
+};
+
+/// The sections that hold lists of objects rather than `Key: value` pairs.
+pub(crate) const OBJECT_SECTIONS: &[&str] = &["Events", "TimingPoints", "Colours", "HitObjects"];
+
+/// A single item read from a .osu file by a [`BeatmapReader`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum BeatmapItem {
+    /// The `osu file format vN` line
+    Version(u32),
+
+    /// The start of a new section, such as `[Metadata]`
+    Section(String),
+
+    /// A `Key: value` line from one of the settings sections
+    KeyValue {
+        /// The line number, for reporting errors in the value
+        line: usize,
+
+        /// The (1-based) column where the value starts
+        column: usize,
+
+        /// The name of the setting
+        key: String,
+
+        /// The (unparsed) value of the setting
+        value: String,
+    },
+
+    /// An event, along with any storyboard commands attached to it
+    Event(Event),
+
+    /// A timing point
+    TimingPoint(TimingPoint),
+
+    /// A combo colour (the other colours in `[Colours]` are read as key-values)
+    Color(Color),
+
+    /// A hit object
+    HitObject(HitObject),
+}
+
+/// Reads a .osu file one item at a time.
+///
+/// Objects come out the way the game sees them: the quirks of the file's [`FormatVersion`] are
+/// applied, and timing points that leave out their sample set or volume get the ones from
+/// `[General]`.
+///
+/// Nothing is parsed until it's asked for, so a caller that only needs the metadata can stop
+/// reading once it sees the section after `[Difficulty]`, without paying for the hit objects.
+/// [`Beatmap::parse_header`][crate::beatmap::Beatmap::parse_header] does exactly that.
+///
+/// ```
+/// # use libosu::prelude::*;
+/// let input = "osu file format v14\n\n[Metadata]\nTitle:hello\n\n[HitObjects]\n256,192,1000,1,0,0:0:0:0:\n";
+/// let mut reader = BeatmapReader::new(input.as_bytes());
+/// assert_eq!(reader.next().unwrap().unwrap(), BeatmapItem::Version(14));
+/// assert_eq!(reader.next().unwrap().unwrap(), BeatmapItem::Section(String::from("Metadata")));
+/// match reader.next().unwrap().unwrap() {
+///     BeatmapItem::KeyValue { key, value, .. } => assert_eq!((key.as_str(), value.as_str()), ("Title", "hello")),
+///     item => panic!("unexpected {:?}", item),
+/// }
+/// ```
+///
+/// By default the reader stops at the first line it can't parse. In lenient mode (see
+/// [`BeatmapReader::set_lenient`]) it yields the error and carries on with the next line.
+pub struct BeatmapReader<R> {
+    lines: Lines<BufReader<R>>,
+    line_no: usize,
+    section: String,
+    variables: Vec<(String, String)>,
+
+    version: FormatVersion,
+    default_sample_set: SampleSet,
+    default_volume: u16,
+
+    /// The event currently being read, which might still get more commands
+    pending_event: Vec<Event>,
+    queue: VecDeque<BeatmapItem>,
+    error: Option<BeatmapParseError>,
+    done: bool,
+
+    lenient: bool,
+
+    /// After an event line fails to parse, anything indented deeper than it gets skipped, since
+    /// it would otherwise be attached to the wrong event
+    skip_depth: Option<usize>,
+}
+
+impl<R: Read> BeatmapReader<R> {
+    /// Creates a reader over any `Read`er
+    pub fn new(reader: R) -> Self {
+        BeatmapReader {
+            lines: BufReader::new(reader).lines(),
+            line_no: 0,
+            section: String::from("Version"),
+            variables: Vec::new(),
+            version: FormatVersion::LATEST,
+            default_sample_set: SampleSet::Normal,
+            default_volume: 100,
+            pending_event: Vec::new(),
+            queue: VecDeque::new(),
+            error: None,
+            done: false,
+            lenient: false,
+            skip_depth: None,
+        }
+    }
+
+    /// Sets whether the reader keeps going after a line fails to parse.
+    ///
+    /// In lenient mode a bad line is yielded as an error and then skipped, along with any
+    /// storyboard commands nested under it. Reading only stops at the end of the input (or an
+    /// io error that isn't just invalid UTF-8).
+    pub fn set_lenient(&mut self, lenient: bool) {
+        self.lenient = lenient;
+    }
+
+    /// The name of the section currently being read
+    pub fn section(&self) -> &str {
+        &self.section
+    }
+
+    /// The number of the last line that was read
+    pub fn line_no(&self) -> usize {
+        self.line_no
+    }
+
+    fn flush_event(&mut self) {
+        if let Some(mut event) = self.pending_event.pop() {
+            self.version.decode_event(&mut event);
+            self.queue.push_back(BeatmapItem::Event(event));
+        }
+    }
+
+    /// Remembers the settings that timing points fall back on
+    fn read_default(&mut self, key: &str, value: &str) {
+        match key {
+            "SampleSet" => {
+                self.default_sample_set = match value {
+                    "None" => SampleSet::None,
+                    "Soft" => SampleSet::Soft,
+                    "Drum" => SampleSet::Drum,
+                    _ => SampleSet::Normal,
+                }
+            }
+            "SampleVolume" => self.default_volume = value.parse().unwrap_or(100),
+            _ => (),
+        }
+    }
+
+    fn read_line(&mut self, line: &str) -> Result<(), ParseError> {
+        // some editors save files with a byte order mark
+        let line = match self.line_no {
+            1 => line.trim_start_matches('\u{feff}'),
+            _ => line,
+        };
+
+        if let Some(captures) = SECTION_HEADER_RGX.captures(line) {
+            self.flush_event();
+            self.section = String::from(&captures["name"]);
+            self.queue
+                .push_back(BeatmapItem::Section(self.section.clone()));
+            return Ok(());
+        }
+
+        // skip empty lines and comments
+        if line.trim().is_empty() || line.starts_with("//") {
+            return Ok(());
+        }
+
+        let item = match self.section.as_ref() {
+            "Events" => {
+                let line = expand_variables(line, &self.variables);
+                let depth = line.chars().take_while(|c| *c == ' ' || *c == '_').count();
+                if let Some(skip_depth) = self.skip_depth {
+                    if depth > skip_depth {
+                        return Ok(());
+                    }
+                    self.skip_depth = None;
+                }
+                if depth == 0 {
+                    self.flush_event();
+                }
+                let result =
+                    parse_event_line(&mut self.pending_event, &line).map_err(|err| {
+                        match (&line, err) {
+                            // the field's column in the expanded line doesn't match the file
+                            (Cow::Owned(_), ParseError::Field { inner, .. }) => *inner,
+                            (_, err) => err,
+                        }
+                    });
+                if result.is_err() {
+                    self.skip_depth = Some(depth);
+                }
+                return result;
+            }
+            "Variables" => {
+                if let Some(variable) = parse_variable(line) {
+                    self.variables.push(variable);
+                }
+                return Ok(());
+            }
+            "HitObjects" => {
+                let mut obj = HitObject::from_str(line)?;
+                self.version.decode_hit_object(&mut obj);
+                BeatmapItem::HitObject(obj)
+            }
+            "TimingPoints" => {
+                let mut tp = TimingPoint::parse_with_defaults(
+                    line,
+                    self.default_sample_set,
+                    self.default_volume,
+                )?;
+                self.version.decode_timing_point(&mut tp);
+                BeatmapItem::TimingPoint(tp)
+            }
+            "Colours" => match KEY_VALUE_RGX.captures(line) {
+                Some(captures) if !captures["key"].starts_with("Combo") => BeatmapItem::KeyValue {
+                    line: self.line_no,
+                    column: captures.name("value").map_or(1, |m| m.start() + 1),
+                    key: String::from(&captures["key"]),
+                    value: String::from(&captures["value"]),
+                },
+                _ => BeatmapItem::Color(Color::from_str(line)?),
+            },
+            "Version" => match OSU_FORMAT_VERSION_RGX.captures(line) {
+                Some(captures) => {
+                    let version = captures["version"].parse::<u32>()?;
+                    self.version = FormatVersion(version);
+                    BeatmapItem::Version(version)
+                }
+                None => return Ok(()),
+            },
+            _ => match KEY_VALUE_RGX.captures(line) {
+                Some(captures) => {
+                    if self.section == "General" {
+                        self.read_default(&captures["key"], captures["value"].trim());
+                    }
+                    BeatmapItem::KeyValue {
+                        line: self.line_no,
+                        column: captures.name("value").map_or(1, |m| m.start() + 1),
+                        key: String::from(&captures["key"]),
+                        value: String::from(&captures["value"]),
+                    }
+                }
+                None => return Ok(()),
+            },
+        };
+        self.queue.push_back(item);
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for BeatmapReader<R> {
+    type Item = Result<BeatmapItem, BeatmapParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.queue.pop_front() {
+                return Some(Ok(item));
+            }
+            if let Some(err) = self.error.take() {
+                return Some(Err(err));
+            }
+            if self.done {
+                return None;
+            }
+
+            match self.lines.next() {
+                Some(line) => {
+                    self.line_no += 1;
+                    // a line that isn't valid UTF-8 has still been consumed, so it can be skipped
+                    let recoverable = match &line {
+                        Err(err) => err.kind() == ErrorKind::InvalidData,
+                        Ok(_) => true,
+                    };
+                    let (text, result) = match line {
+                        Ok(line) => {
+                            let result = self.read_line(&line);
+                            (Some(line), result)
+                        }
+                        Err(err) => (None, Err(err.into())),
+                    };
+                    if let Err(inner) = result {
+                        self.done = !(self.lenient && recoverable);
+                        self.error = Some(BeatmapParseError::in_line(
+                            self.line_no,
+                            text.as_deref(),
+                            inner,
+                        ));
+                    }
+                }
+                None => {
+                    self.done = true;
+                    self.flush_event();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::f64::consts::PI;
+
+use crate::hitobject::{HitObject, HitObjectKind};
+use crate::math::Point;
+use crate::spline::Spline;
+use crate::timing::{Time, TimingIndex};
+
+use super::Beatmap;
+
+/// How far (in osu!pixels) a slider travels in one beat at a slider multiplier and velocity of 1.
+const BASE_SCORING_DISTANCE: f64 = 100.0;
+
+/// Sliders longer than this (in osu!pixels) are cut short when working out their ticks.
+const MAX_SLIDER_LENGTH: f64 = 100_000.0;
+
+/// How long (in milliseconds) before the end of a slider the game checks whether it's being held.
+///
+/// The tail of a slider is judged at the end, but for scoring and combo the game has always used
+/// this earlier "last tick" instead.
+pub const LEGACY_LAST_TICK_OFFSET: f64 = 36.0;
+
+/// The first format version where slider ticks are spaced by the beat alone, rather than by how
+/// far the slider travels in a beat at the base slider velocity.
+const FIRST_BEAT_TICK_VERSION: u32 = 8;
+
+/// The kind of [`SliderEvent`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum SliderEventKind {
+    /// The start of the slider, which is hit like a circle
+    Head,
+
+    /// A tick along the slider's path
+    Tick,
+
+    /// One end of the slider, where the slider ball turns around
+    Repeat {
+        /// The direction the reverse arrow points in and the slider ball goes in afterwards, in
+        /// radians (like [`f64::atan2`])
+        angle: f64,
+    },
+
+    /// Where the game checks the end of the slider for scoring, up to
+    /// [`LEGACY_LAST_TICK_OFFSET`] before the actual end
+    LegacyLastTick,
+
+    /// The end of the slider
+    Tail,
+}
+
+/// Something that happens while a slider is being played, that's judged on its own: the head,
+/// ticks, repeats and the tail. See [`Beatmap::slider_events`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SliderEvent {
+    /// What kind of event this is
+    pub kind: SliderEventKind,
+
+    /// When it happens
+    pub time: Time,
+
+    /// Where the slider ball is at that time
+    pub pos: Point<f64>,
+
+    /// Which span (trip along the slider path) it's on, starting from 0
+    pub span_index: u32,
+
+    /// How far along the slider path it is, from 0 (the start) to 1 (the end). Spans going
+    /// backwards go from 1 to 0.
+    pub path_progress: f64,
+}
+
+impl Beatmap {
+    /// Lists the head, ticks, repeats, legacy last tick and tail of a slider, in order of time.
+    ///
+    /// The slider velocity and beat length come from the timing points in effect at the start of
+    /// the slider. Ticks are placed [`slider_tick_rate`][super::Difficulty::slider_tick_rate]
+    /// times per beat from the start of the path, and ticks too close to the end of a span are
+    /// left out. Returns `None` if the object isn't a slider or there's no timing point for it.
+    ///
+    /// ```
+    /// # use libosu::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = "osu file format v14\n\n[Difficulty]\nSliderMultiplier:1\nSliderTickRate:2\n\n[TimingPoints]\n0,500,4,2,0,100,1,0\n\n[HitObjects]\n0,0,1000,2,0,L|200:0,1,200\n";
+    /// let beatmap = Beatmap::from_str(input).unwrap();
+    /// let events = beatmap.slider_events(&beatmap.hit_objects[0]).unwrap();
+    /// let ticks = events.iter().filter(|e| e.kind == SliderEventKind::Tick).collect::<Vec<_>>();
+    /// assert_eq!(ticks.len(), 3);
+    /// assert_eq!(ticks[0].time, Time(1250.0));
+    /// assert_eq!(ticks[0].pos, Point::new(50.0, 0.0));
+    /// assert_eq!(events.last().unwrap().time, Time(2000.0));
+    /// ```
+    pub fn slider_events(&self, obj: &HitObject) -> Option<Vec<SliderEvent>> {
+        self.slider_events_in(obj, &self.timing_lookup())
+    }
+
+    /// Like [`Beatmap::slider_events`], but with an index that's reused for many sliders.
+    pub(crate) fn slider_events_in(
+        &self,
+        obj: &HitObject,
+        timing: &TimingIndex,
+    ) -> Option<Vec<SliderEvent>> {
+        let info = match &obj.kind {
+            HitObjectKind::Slider(info) => info,
+            _ => return None,
+        };
+
+        let beat_length = 60_000.0 / timing.bpm_at(obj.start_time)?;
+        let slider_velocity = timing.slider_velocity_at(obj.start_time);
+        let scoring_distance =
+            BASE_SCORING_DISTANCE * self.difficulty.slider_multiplier * slider_velocity;
+        let velocity = scoring_distance / beat_length;
+        let mut tick_distance = scoring_distance / self.difficulty.slider_tick_rate;
+        if self.version < FIRST_BEAT_TICK_VERSION {
+            tick_distance /= slider_velocity;
+        }
+
+        let mut control_points = vec![obj.pos];
+        control_points.extend(&info.control_points);
+        let spline =
+            Spline::from_control(info.kind, control_points.as_ref(), Some(info.pixel_length));
+
+        let length = info.pixel_length;
+        let tick_length = length.min(MAX_SLIDER_LENGTH);
+        let span_count = info.num_repeats.max(1);
+        let span_duration = length / velocity;
+        let start_time = obj.start_time.0;
+
+        let event = |kind, time: f64, span_index: u32, path_progress: f64| SliderEvent {
+            kind,
+            time: Time(time),
+            pos: spline.point_at_length(path_progress.clamp(0.0, 1.0) * length),
+            span_index,
+            path_progress,
+        };
+
+        let mut events = vec![SliderEvent {
+            pos: obj.pos,
+            ..event(SliderEventKind::Head, start_time, 0, 0.0)
+        }];
+
+        // ticks aren't generated too close to the end of a span
+        let tick_distance = tick_distance.max(0.0).min(tick_length);
+        let min_distance_from_end = velocity * 10.0;
+        for span in 0..span_count {
+            let span_start_time = start_time + span as f64 * span_duration;
+            let reversed = span % 2 == 1;
+
+            if tick_distance > 0.0 {
+                let mut ticks = Vec::new();
+                let mut distance = tick_distance;
+                while distance <= tick_length && distance < tick_length - min_distance_from_end {
+                    // ticks are always placed from the start of the path, so they're in the same
+                    // place on spans going backwards
+                    let path_progress = distance / length;
+                    let time_progress = if reversed {
+                        1.0 - path_progress
+                    } else {
+                        path_progress
+                    };
+                    ticks.push(event(
+                        SliderEventKind::Tick,
+                        span_start_time + time_progress * span_duration,
+                        span,
+                        path_progress,
+                    ));
+                    distance += tick_distance;
+                }
+                if reversed {
+                    ticks.reverse();
+                }
+                events.extend(ticks);
+            }
+
+            if span < span_count - 1 {
+                let path_progress = ((span + 1) % 2) as f64;
+                let angle = if reversed {
+                    spline.angle_at_length(0.0)
+                } else {
+                    // turning around at the end of the path
+                    let angle = spline.angle_at_length(length);
+                    angle - angle.signum() * PI
+                };
+                events.push(event(
+                    SliderEventKind::Repeat { angle },
+                    span_start_time + span_duration,
+                    span,
+                    path_progress,
+                ));
+            }
+        }
+
+        // the last tick is moved back from the end, but never past the middle of the slider
+        let total_duration = span_count as f64 * span_duration;
+        let final_span = span_count - 1;
+        let final_span_start_time = start_time + final_span as f64 * span_duration;
+        let last_tick_time = (start_time + total_duration / 2.0)
+            .max(final_span_start_time + span_duration - LEGACY_LAST_TICK_OFFSET);
+        let mut last_tick_progress = if span_duration > 0.0 {
+            (last_tick_time - final_span_start_time) / span_duration
+        } else {
+            1.0
+        };
+        if span_count % 2 == 0 {
+            last_tick_progress = 1.0 - last_tick_progress;
+        }
+        events.push(event(
+            SliderEventKind::LegacyLastTick,
+            last_tick_time,
+            final_span,
+            last_tick_progress,
+        ));
+
+        events.push(event(
+            SliderEventKind::Tail,
+            start_time + total_duration,
+            final_span,
+            (span_count % 2) as f64,
+        ));
+
+        Some(events)
+    }
+}
@@ -0,0 +1,260 @@
+use crate::data::Mode;
+use crate::hitobject::HitObjectKind;
+use crate::math::Point;
+use crate::spline::Spline;
+
+use super::{Beatmap, Difficulty};
+
+/// Objects closer together than this (in osu!pixels) are stacked.
+const STACK_DISTANCE: f64 = 3.0;
+
+/// The first format version that uses the current stacking algorithm. Older maps stack the way
+/// the game did back then, which only looks forward from each object.
+const FIRST_REVERSE_STACKING_VERSION: u32 = 6;
+
+/// Which game's version of stacking to follow, see [`Beatmap::stacking`].
+///
+/// Both use the same algorithms (the current one, or the old one for maps older than v6). They
+/// differ in the approach time used to decide how close in time objects have to be to stack:
+/// stable rounds it to whole milliseconds (like [`Difficulty::approach_preempt`]), lazer doesn't.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StackingAlgorithm {
+    /// osu!stable
+    Stable,
+
+    /// osu!lazer
+    Lazer,
+}
+
+/// Where a hit object is actually drawn once it's been stacked.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StackedObject {
+    /// How many objects this one is stacked on top of. Objects stacked under the end of a slider
+    /// have negative heights, and are moved down and right instead of up and left.
+    pub stack_height: i32,
+
+    /// The position of the object after stacking
+    pub pos: Point<f64>,
+
+    /// The position of the end of the object after stacking
+    pub end_pos: Point<f64>,
+}
+
+impl Difficulty {
+    /// How far (in osu!pixels) each level of a stack is moved up and to the left, which is a
+    /// tenth of the circle radius.
+    pub fn stack_offset(&self) -> f64 {
+        self.circle_size_osupx() as f64 / 10.0
+    }
+}
+
+/// The parts of a hit object that stacking looks at.
+struct StackObject {
+    kind: StackKind,
+    start_time: f64,
+    end_time: f64,
+    pos: Point<f64>,
+    end_pos: Point<f64>,
+    path_end: Point<f64>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum StackKind {
+    Circle,
+    Slider,
+    Spinner,
+}
+
+impl Beatmap {
+    /// Works out how hit objects are stacked, giving one [`StackedObject`] per hit object.
+    ///
+    /// Objects that are close together in time (the approach time times
+    /// [`stack_leniency`][Beatmap::stack_leniency]) and that are at (almost) the same position
+    /// get moved up and to the left so they can all be seen. Only osu!standard stacks objects; in
+    /// other modes every object keeps its position.
+    ///
+    /// Stacking depends on the approach rate and circle size, so to get the stacking with HardRock
+    /// or Easy, call this on the map from [`Beatmap::with_mods`].
+    pub fn stacking(&self, algorithm: StackingAlgorithm) -> Vec<StackedObject> {
+        self.stacking_with(algorithm, &self.difficulty)
+    }
+
+    /// Like [`Beatmap::stacking`], but with different difficulty settings.
+    pub(crate) fn stacking_with(
+        &self,
+        algorithm: StackingAlgorithm,
+        difficulty: &Difficulty,
+    ) -> Vec<StackedObject> {
+        let objects = self.stack_objects();
+        let mut heights = vec![0; objects.len()];
+
+        if self.mode == Mode::Osu {
+            let preempt = match algorithm {
+                StackingAlgorithm::Stable => difficulty.approach_preempt().0 as f64,
+                StackingAlgorithm::Lazer => {
+                    let ar = difficulty.approach_rate as f64;
+                    if ar < 5.0 {
+                        1200.0 + 600.0 * (5.0 - ar) / 5.0
+                    } else {
+                        1200.0 - 750.0 * (ar - 5.0) / 5.0
+                    }
+                }
+            };
+            let threshold = preempt * self.stack_leniency;
+
+            if self.version >= FIRST_REVERSE_STACKING_VERSION {
+                stack_reverse(&objects, &mut heights, threshold);
+            } else {
+                stack_forward(&objects, &mut heights, threshold);
+            }
+        }
+
+        let offset = difficulty.stack_offset();
+        objects
+            .iter()
+            .zip(heights)
+            .map(|(obj, stack_height)| {
+                let shift = stack_height as f64 * offset;
+                let shift = Point::new(shift, shift);
+                StackedObject {
+                    stack_height,
+                    pos: obj.pos - shift,
+                    end_pos: obj.end_pos - shift,
+                }
+            })
+            .collect()
+    }
+
+    fn stack_objects(&self) -> Vec<StackObject> {
+        let timing = self.timing_index();
+        self.hit_objects
+            .iter()
+            .map(|obj| {
+                let start_time = obj.start_time.0;
+                let end_time = self
+                    .hitobject_end_time_in(obj, &timing)
+                    .map(|end| end * 1000.0)
+                    .unwrap_or(start_time);
+                let (kind, path_end) = match &obj.kind {
+                    HitObjectKind::Circle => (StackKind::Circle, obj.pos),
+                    HitObjectKind::Slider(info) => {
+                        let mut control_points = vec![obj.pos];
+                        control_points.extend(&info.control_points);
+                        let spline = Spline::from_control(
+                            info.kind,
+                            control_points.as_ref(),
+                            Some(info.pixel_length),
+                        );
+                        (StackKind::Slider, spline.end_point())
+                    }
+                    HitObjectKind::Spinner(_) | HitObjectKind::Hold(_) => {
+                        (StackKind::Spinner, obj.pos)
+                    }
+                };
+                let end_pos = match &obj.kind {
+                    HitObjectKind::Slider(info) if info.num_repeats % 2 == 1 => path_end,
+                    _ => obj.pos,
+                };
+                StackObject {
+                    kind,
+                    start_time,
+                    end_time,
+                    pos: obj.pos,
+                    end_pos,
+                    path_end,
+                }
+            })
+            .collect()
+    }
+}
+
+/// The stacking algorithm used since v6, which walks backwards from the end of the map so that
+/// stacks under the end of a slider go the other way.
+fn stack_reverse(objects: &[StackObject], heights: &mut [i32], threshold: f64) {
+    for i in (1..objects.len()).rev() {
+        if heights[i] != 0 || objects[i].kind == StackKind::Spinner {
+            continue;
+        }
+
+        // the top of the stack so far
+        let mut top = i;
+        match objects[i].kind {
+            StackKind::Circle => {
+                for n in (0..i).rev() {
+                    let obj_n = &objects[n];
+                    if obj_n.kind == StackKind::Spinner {
+                        continue;
+                    }
+                    if objects[top].start_time - obj_n.end_time > threshold {
+                        break;
+                    }
+
+                    // circles stacked under the end of a slider go down and right of it
+                    if obj_n.kind == StackKind::Slider
+                        && obj_n.end_pos.distance(objects[top].pos) < STACK_DISTANCE
+                    {
+                        let offset = heights[top] - heights[n] + 1;
+                        for j in n + 1..=i {
+                            if obj_n.end_pos.distance(objects[j].pos) < STACK_DISTANCE {
+                                heights[j] -= offset;
+                            }
+                        }
+                        break;
+                    }
+
+                    if obj_n.pos.distance(objects[top].pos) < STACK_DISTANCE {
+                        heights[n] = heights[top] + 1;
+                        top = n;
+                    }
+                }
+            }
+            StackKind::Slider => {
+                for n in (0..i).rev() {
+                    let obj_n = &objects[n];
+                    if obj_n.kind == StackKind::Spinner {
+                        continue;
+                    }
+                    if objects[top].start_time - obj_n.start_time > threshold {
+                        break;
+                    }
+                    if obj_n.end_pos.distance(objects[top].pos) < STACK_DISTANCE {
+                        heights[n] = heights[top] + 1;
+                        top = n;
+                    }
+                }
+            }
+            StackKind::Spinner => unreachable!(),
+        }
+    }
+}
+
+/// The stacking algorithm used before v6, which looks forward from each object for the objects
+/// that get stacked on it.
+fn stack_forward(objects: &[StackObject], heights: &mut [i32], threshold: f64) {
+    for i in 0..objects.len() {
+        let obj = &objects[i];
+        if heights[i] != 0 && obj.kind != StackKind::Slider {
+            continue;
+        }
+
+        let mut end_time = obj.end_time;
+        let mut slider_stack = 0;
+        for j in i + 1..objects.len() {
+            if objects[j].start_time - threshold > end_time {
+                break;
+            }
+
+            if objects[j].pos.distance(obj.pos) < STACK_DISTANCE {
+                heights[i] += 1;
+                end_time = objects[j].start_time;
+            } else if objects[j].pos.distance(obj.path_end) < STACK_DISTANCE {
+                // objects at the end of a slider are moved down and right instead
+                slider_stack += 1;
+                heights[j] -= slider_stack;
+                end_time = objects[j].start_time;
+            }
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use crate::data::Mode;
+use crate::hitobject::{HitObject, HitObjectKind, SliderInfo};
+use crate::timing::TimingIndex;
+
+use super::{mania, Beatmap, SliderEventKind};
+
+/// How much faster taiko scrolls than osu!standard sliders move, which converted drumrolls are
+/// timed by.
+const TAIKO_VELOCITY_MULTIPLIER: f64 = 1.4;
+
+/// The first format version where converted taiko sliders are judged by the beat alone, rather
+/// than by the beat adjusted for slider velocity.
+const FIRST_TAIKO_BEAT_VERSION: u32 = 8;
+
+/// Gaps between juice stream events longer than this (in milliseconds) are filled with tiny
+/// droplets.
+const TINY_DROPLET_GAP: f64 = 80.0;
+
+/// Tiny droplets are never further apart than this (in milliseconds).
+const MAX_TINY_DROPLET_SPACING: f64 = 100.0;
+
+/// Counts of what's in a beatmap as it's played in some mode, see [`Beatmap::statistics`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BeatmapStatistics {
+    /// The mode the map is played in
+    pub mode: Mode,
+
+    /// The highest combo a player can get
+    pub max_combo: u32,
+
+    /// Objects that are hit once: hit circles in osu!standard, hits in taiko (including sliders
+    /// that are converted into hits), fruits that aren't part of a juice stream in catch and
+    /// notes in mania
+    pub circles: u32,
+
+    /// Sliders in osu!standard, drumrolls in taiko and juice streams in catch
+    pub sliders: u32,
+
+    /// Spinners in osu!standard, swells in taiko and banana showers in catch
+    pub spinners: u32,
+
+    /// Hold notes in mania, which are scored at the head and the tail
+    pub holds: u32,
+
+    /// Slider ticks in osu!standard and drumroll ticks in taiko
+    pub ticks: u32,
+
+    /// Slider repeats in osu!standard, and the fruits on the repeats of juice streams in catch
+    pub repeats: u32,
+
+    /// Droplets on the ticks of juice streams in catch, which count towards combo
+    pub droplets: u32,
+
+    /// Tiny droplets between the other parts of juice streams in catch, which don't count towards
+    /// combo
+    pub tiny_droplets: u32,
+}
+
+impl BeatmapStatistics {
+    fn new(mode: Mode) -> Self {
+        BeatmapStatistics {
+            mode,
+            max_combo: 0,
+            circles: 0,
+            sliders: 0,
+            spinners: 0,
+            holds: 0,
+            ticks: 0,
+            repeats: 0,
+            droplets: 0,
+            tiny_droplets: 0,
+        }
+    }
+}
+
+impl Beatmap {
+    /// Counts the objects, ticks and droplets in this map, and works out the max combo, for the
+    /// mode the map is made for.
+    pub fn statistics(&self) -> BeatmapStatistics {
+        self.statistics_for(self.mode)
+            .expect("maps can always be played in their own mode")
+    }
+
+    /// Like [`Beatmap::statistics`], but for the map converted to another mode. Only
+    /// osu!standard maps can be converted, so this returns `None` for other maps played in a
+    /// different mode.
+    ///
+    /// Maps converted to other modes are counted the way the game converts them. Converted mania
+    /// maps get notes and hold notes placed in columns by the game's legacy converter, which goes
+    /// through the whole map at once.
+    pub fn statistics_for(&self, mode: Mode) -> Option<BeatmapStatistics> {
+        if mode != self.mode && self.mode != Mode::Osu {
+            return None;
+        }
+
+        if mode == Mode::Mania && self.mode != Mode::Mania {
+            let mut stats = BeatmapStatistics::new(mode);
+            for note in mania::convert(self) {
+                if note.is_hold() {
+                    stats.holds += 1;
+                    stats.max_combo += 2;
+                } else {
+                    stats.circles += 1;
+                    stats.max_combo += 1;
+                }
+            }
+            return Some(stats);
+        }
+
+        let timing = self.timing_index();
+        let mut stats = BeatmapStatistics::new(mode);
+        for obj in self.hit_objects.iter() {
+            match mode {
+                Mode::Osu => self.count_osu(obj, &timing, &mut stats),
+                Mode::Taiko => self.count_taiko(obj, &timing, &mut stats),
+                Mode::Catch => self.count_catch(obj, &timing, &mut stats),
+                Mode::Mania => count_mania(obj, &mut stats),
+            }
+        }
+        Some(stats)
+    }
+
+    fn count_osu(&self, obj: &HitObject, timing: &TimingIndex, stats: &mut BeatmapStatistics) {
+        match &obj.kind {
+            HitObjectKind::Circle => stats.circles += 1,
+            HitObjectKind::Spinner(_) => stats.spinners += 1,
+            HitObjectKind::Hold(_) => stats.holds += 1,
+            HitObjectKind::Slider(_) => {
+                stats.sliders += 1;
+                let events = self.slider_events_in(obj, timing).unwrap_or_default();
+                for event in events.iter() {
+                    match event.kind {
+                        SliderEventKind::Tick => stats.ticks += 1,
+                        SliderEventKind::Repeat { .. } => stats.repeats += 1,
+                        SliderEventKind::Head
+                        | SliderEventKind::LegacyLastTick
+                        | SliderEventKind::Tail => continue,
+                    }
+                    stats.max_combo += 1;
+                }
+                // the head, and the end (which is judged at the last tick)
+                stats.max_combo += 2;
+                return;
+            }
+        }
+        stats.max_combo += 1;
+    }
+
+    fn count_taiko(&self, obj: &HitObject, timing: &TimingIndex, stats: &mut BeatmapStatistics) {
+        let info = match &obj.kind {
+            HitObjectKind::Slider(info) => info,
+            HitObjectKind::Circle | HitObjectKind::Hold(_) => {
+                stats.circles += 1;
+                stats.max_combo += 1;
+                return;
+            }
+            // swells don't give any combo
+            HitObjectKind::Spinner(_) => {
+                stats.spinners += 1;
+                return;
+            }
+        };
+
+        let slider = match self.taiko_slider_timing(obj, info, timing) {
+            Some(slider) => slider,
+            None => {
+                stats.sliders += 1;
+                return;
+            }
+        };
+
+        if let (true, Some(tick_spacing)) = (self.mode == Mode::Osu, slider.hit_spacing) {
+            // short sliders in converted maps are turned into a hit on every tick
+            let end_time = obj.start_time.0 + slider.duration + tick_spacing / 8.0;
+            let mut time = obj.start_time.0;
+            while time <= end_time {
+                stats.circles += 1;
+                stats.max_combo += 1;
+                time += tick_spacing;
+            }
+            return;
+        }
+
+        // drumroll ticks don't give any combo either
+        stats.sliders += 1;
+        let tick_rate = if self.difficulty.slider_tick_rate == 3.0 {
+            3.0
+        } else {
+            4.0
+        };
+        let tick_spacing = slider.beat_length / tick_rate;
+        if tick_spacing > 0.0 {
+            let end_time = obj.start_time.0 + slider.duration;
+            let mut time = obj.start_time.0;
+            while time < end_time + tick_spacing / 2.0 {
+                stats.ticks += 1;
+                time += tick_spacing;
+            }
+        }
+    }
+
+    /// Works out how long a slider lasts in taiko, and whether it's converted into hits.
+    fn taiko_slider_timing(
+        &self,
+        obj: &HitObject,
+        info: &SliderInfo,
+        timing: &TimingIndex,
+    ) -> Option<TaikoTiming> {
+        let timing_beat_length = 60_000.0 / timing.bpm_at(obj.start_time)?;
+        let slider_velocity = timing.slider_velocity_at(obj.start_time);
+        let spans = info.num_repeats.max(1) as f64;
+        let tick_rate = self.difficulty.slider_tick_rate;
+
+        let distance = info.pixel_length * spans * TAIKO_VELOCITY_MULTIPLIER;
+        let mut beat_length = timing_beat_length / slider_velocity;
+        let taiko_velocity = 100.0 * self.difficulty.slider_multiplier * TAIKO_VELOCITY_MULTIPLIER;
+        let duration = (distance / taiko_velocity * beat_length) as i32 as f64;
+        let osu_velocity = taiko_velocity * (1000.0 / beat_length);
+
+        if self.version >= FIRST_TAIKO_BEAT_VERSION {
+            beat_length = timing_beat_length;
+        }
+        let tick_spacing = (beat_length / tick_rate).min(duration / spans);
+        let hit_spacing =
+            if tick_spacing > 0.0 && distance / osu_velocity * 1000.0 < 2.0 * beat_length {
+                Some(tick_spacing)
+            } else {
+                None
+            };
+
+        Some(TaikoTiming {
+            duration,
+            beat_length: timing_beat_length,
+            hit_spacing,
+        })
+    }
+
+    fn count_catch(&self, obj: &HitObject, timing: &TimingIndex, stats: &mut BeatmapStatistics) {
+        match &obj.kind {
+            HitObjectKind::Circle | HitObjectKind::Hold(_) => {
+                stats.circles += 1;
+                stats.max_combo += 1;
+            }
+            // bananas don't give any combo
+            HitObjectKind::Spinner(_) => stats.spinners += 1,
+            HitObjectKind::Slider(_) => {
+                stats.sliders += 1;
+                let events = self.slider_events_in(obj, timing).unwrap_or_default();
+                for (i, event) in events.iter().enumerate() {
+                    if let Some(prev) = i.checked_sub(1).map(|j| &events[j]) {
+                        let gap = (event.time.0 as i32 - prev.time.0 as i32) as f64;
+                        if gap > TINY_DROPLET_GAP {
+                            let mut spacing = gap;
+                            while spacing > MAX_TINY_DROPLET_SPACING {
+                                spacing /= 2.0;
+                            }
+                            let mut t = spacing;
+                            while t < gap {
+                                stats.tiny_droplets += 1;
+                                t += spacing;
+                            }
+                        }
+                    }
+
+                    match event.kind {
+                        SliderEventKind::Tick => stats.droplets += 1,
+                        SliderEventKind::Repeat { .. } => stats.repeats += 1,
+                        SliderEventKind::Head
+                        | SliderEventKind::LegacyLastTick
+                        | SliderEventKind::Tail => (),
+                    }
+                    if event.kind != SliderEventKind::LegacyLastTick {
+                        stats.max_combo += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn count_mania(obj: &HitObject, stats: &mut BeatmapStatistics) {
+    match &obj.kind {
+        HitObjectKind::Circle => {
+            stats.circles += 1;
+            stats.max_combo += 1;
+        }
+        HitObjectKind::Slider(_) | HitObjectKind::Spinner(_) | HitObjectKind::Hold(_) => {
+            stats.holds += 1;
+            stats.max_combo += 2;
+        }
+    }
+}
+
+/// How a slider plays in taiko.
+struct TaikoTiming {
+    /// How long the drumroll lasts, in milliseconds
+    duration: f64,
+
+    /// The beat length of the timing point the slider is in
+    beat_length: f64,
+
+    /// How far apart the hits are, if the slider is converted into hits
+    hit_spacing: Option<f64>,
+}
@@ -0,0 +1,135 @@
+use std::fmt;
+
+use super::Beatmap;
+
+/// Changes to make to a beatmap for a practice variant, see [`Beatmap::make_variant`].
+///
+/// Each difficulty setting that's `Some` is set to exactly that value, the rest are left alone.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VariantOptions {
+    /// How many times as fast the variant plays, 1.0 to keep the original speed
+    pub rate: f64,
+
+    /// Approach Rate to use instead of the map's
+    pub approach_rate: Option<f32>,
+
+    /// Overall Difficulty to use instead of the map's
+    pub overall_difficulty: Option<f32>,
+
+    /// Circle Size to use instead of the map's
+    pub circle_size: Option<f32>,
+
+    /// HP Drain Rate to use instead of the map's
+    pub hp_drain_rate: Option<f32>,
+}
+
+impl Default for VariantOptions {
+    fn default() -> Self {
+        VariantOptions {
+            rate: 1.0,
+            approach_rate: None,
+            overall_difficulty: None,
+            circle_size: None,
+            hp_drain_rate: None,
+        }
+    }
+}
+
+impl VariantOptions {
+    /// Options that only change the rate.
+    pub fn with_rate(rate: f64) -> Self {
+        VariantOptions {
+            rate,
+            ..VariantOptions::default()
+        }
+    }
+
+    /// Whether these options don't change anything.
+    pub fn is_empty(&self) -> bool {
+        *self == VariantOptions::default()
+    }
+}
+
+/// Describes the changes the way they're added to the difficulty name, like `1.15x AR9.5 OD8`.
+impl fmt::Display for VariantOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        if self.rate != 1.0 {
+            parts.push(format!("{}x", self.rate));
+        }
+        let overrides = [
+            ("AR", self.approach_rate),
+            ("OD", self.overall_difficulty),
+            ("CS", self.circle_size),
+            ("HP", self.hp_drain_rate),
+        ];
+        for (name, value) in overrides.iter() {
+            if let Some(value) = value {
+                parts.push(format!("{}{}", name, value));
+            }
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+impl Beatmap {
+    /// Returns a practice variant of this beatmap: retimed to the given rate (see
+    /// [`Beatmap::change_rate`]), with the difficulty overrides applied and the changes added to
+    /// the difficulty name, so that it can be written next to the original as a new difficulty.
+    ///
+    /// The variant's beatmap ID is reset, since it isn't the map that's online. The audio file
+    /// isn't touched, so it has to be resampled to the same rate separately.
+    ///
+    /// Hit objects are retimed exactly in the returned beatmap, but the variant keeps the
+    /// original's format version, so unless that's at least
+    /// [`FIRST_LAZER_VERSION`][super::FIRST_LAZER_VERSION] their times are rounded to whole
+    /// milliseconds when it's written out. Timing points are written exactly either way.
+    ///
+    /// ```
+    /// # use libosu::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = "osu file format v14\n\n[Metadata]\nVersion:Insane\n\n[Difficulty]\nApproachRate:9\n\n[HitObjects]\n100,100,2500,1,0,0:0:0:0:\n";
+    /// let beatmap = Beatmap::from_str(input).unwrap();
+    /// let variant = beatmap.make_variant(&VariantOptions {
+    ///     rate: 1.25,
+    ///     approach_rate: Some(9.5),
+    ///     ..VariantOptions::default()
+    /// });
+    /// assert_eq!(variant.difficulty_name, "Insane 1.25x AR9.5");
+    /// assert_eq!(variant.difficulty.approach_rate, 9.5);
+    /// assert_eq!(variant.hit_objects[0].start_time, Time(2000.0));
+    /// ```
+    pub fn make_variant(&self, options: &VariantOptions) -> Beatmap {
+        let mut beatmap = self.clone();
+        if options.is_empty() {
+            return beatmap;
+        }
+
+        if options.rate != 1.0 {
+            beatmap.change_rate(options.rate);
+        }
+
+        let difficulty = &mut beatmap.difficulty;
+        if let Some(ar) = options.approach_rate {
+            difficulty.approach_rate = ar;
+        }
+        if let Some(od) = options.overall_difficulty {
+            difficulty.overall_difficulty = od;
+        }
+        if let Some(cs) = options.circle_size {
+            difficulty.circle_size = cs;
+        }
+        if let Some(hp) = options.hp_drain_rate {
+            difficulty.hp_drain_rate = hp;
+        }
+
+        beatmap.difficulty_name = if beatmap.difficulty_name.is_empty() {
+            options.to_string()
+        } else {
+            format!("{} {}", beatmap.difficulty_name, options)
+        };
+        beatmap.beatmap_id = 0;
+        beatmap
+    }
+}
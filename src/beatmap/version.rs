@@ -0,0 +1,235 @@
+use crate::events::Event;
+use crate::hitobject::{HitObject, HitObjectKind};
+use crate::timing::{Millis, TimingPoint};
+
+use super::Beatmap;
+
+/// The first format version written by osu!lazer.
+///
+/// Older versions store hit object positions and times as whole numbers, so the writer rounds
+/// them when writing a beatmap with an older version. Timing point offsets can be fractional in
+/// every version.
+pub const FIRST_LAZER_VERSION: u32 = 128;
+
+/// The first version with hit samples on hit objects and sample sets on slider edges. Their hit
+/// samples only have the sample set, addition set and custom index.
+const HIT_SAMPLE_VERSION: u32 = 10;
+
+/// The first version whose hit samples also have a volume and filename.
+const SAMPLE_VOLUME_VERSION: u32 = 12;
+
+/// The first version that has each key, for the keys that weren't in the format from the start.
+fn first_version_with_key(key: &str) -> u32 {
+    match key {
+        "AudioLeadIn" | "Countdown" | "StackLeniency" | "Mode" | "LetterboxInBreaks"
+        | "Bookmarks" | "DistanceSpacing" | "BeatDivisor" | "GridSize" | "Source" | "Tags" => 5,
+        "ApproachRate" => 8,
+        "SampleVolume"
+        | "StoryFireInFront"
+        | "UseSkinSprites"
+        | "AlwaysShowPlayfield"
+        | "OverlayPosition"
+        | "SkinPreference"
+        | "EpilepsyWarning"
+        | "CountdownOffset"
+        | "SpecialStyle"
+        | "WidescreenStoryboard"
+        | "SamplesMatchPlaybackRate"
+        | "TimelineZoom"
+        | "TitleUnicode"
+        | "ArtistUnicode"
+        | "BeatmapID"
+        | "BeatmapSetID"
+        | "SliderTrackOverride"
+        | "SliderBorder" => 10,
+        _ => 0,
+    }
+}
+
+/// A version of the .osu format, and the quirks that come with it.
+///
+/// Beatmaps always hold times the way they play in game, so reading a file applies the quirks of
+/// its version and writing undoes the quirks of the version being written. To convert a beatmap
+/// to another version, change [`Beatmap::version`] before writing it.
+///
+/// Keys, sections, timing point columns and hit sample fields that the version being written
+/// doesn't have yet are left out, so whatever they hold is lost.
+///
+/// ```
+/// # use libosu::prelude::*;
+/// let input = "osu file format v4\n\n[HitObjects]\n256,192,1000,1,0\n";
+/// let mut beatmap = input.parse::<Beatmap>().unwrap();
+/// // the game plays objects in files older than v5 24ms later than written
+/// assert_eq!(beatmap.hit_objects[0].start_time, Time(1024.0));
+///
+/// beatmap.version = 14;
+/// assert!(beatmap.to_string().contains("\n256,192,1024,1,0,"));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FormatVersion(pub u32);
+
+impl FormatVersion {
+    /// The oldest version that the game still reads
+    pub const OLDEST: FormatVersion = FormatVersion(3);
+
+    /// The newest version written by osu!stable, used for beatmaps that don't have a version
+    pub const LATEST: FormatVersion = FormatVersion(14);
+
+    /// How much later (in milliseconds) the game plays everything in files of this version than
+    /// the times written in them.
+    ///
+    /// Files older than v5 were timed against audio that started 24ms late, so the game adds that
+    /// back on to timing points, hit objects, breaks and the preview time.
+    pub fn time_offset(self) -> i32 {
+        if self.0 < 5 {
+            24
+        } else {
+            0
+        }
+    }
+
+    /// Whether hit object positions and times can be fractional, rather than whole numbers
+    pub fn has_fractional_objects(self) -> bool {
+        self.0 >= FIRST_LAZER_VERSION
+    }
+
+    /// Whether files of this version have the given key in [General], [Editor], [Metadata],
+    /// [Difficulty] or [Colours]
+    pub fn has_key(self, key: &str) -> bool {
+        let key = match key {
+            "LetterBoxInBreaks" => "LetterboxInBreaks",
+            key => key,
+        };
+        self.0 >= first_version_with_key(key)
+    }
+
+    /// Whether files of this version have the given section. [Editor] and [Colours] were added
+    /// in v5, and every other section has always been there.
+    pub fn has_section(self, section: &str) -> bool {
+        match section {
+            "Editor" | "Colours" => self.0 >= 5,
+            _ => true,
+        }
+    }
+
+    /// How many comma-separated columns timing points have in files of this version.
+    ///
+    /// v3 only has the time and beat length. v4 adds the meter, sample set and custom sample
+    /// index, v5 the volume, and v6 whether the point is uninherited and its effects.
+    pub fn timing_point_columns(self) -> usize {
+        match self.0 {
+            0..=3 => 2,
+            4 => 5,
+            5 => 6,
+            _ => 8,
+        }
+    }
+
+    /// Moves a timing point read from a file of this version to when it plays
+    pub(crate) fn decode_timing_point(self, tp: &mut TimingPoint) {
+        tp.time = tp.time + self.time_offset() as f64;
+    }
+
+    /// Moves a hit object read from a file of this version to when it plays
+    pub(crate) fn decode_hit_object(self, obj: &mut HitObject) {
+        obj.shift(self.time_offset() as f64);
+    }
+
+    /// Moves the preview time read from a file of this version to when it plays. A preview time
+    /// of -1 means there isn't one, so it's left alone.
+    pub(crate) fn decode_preview_time(self, time: Millis) -> Millis {
+        match time {
+            Millis(-1) => time,
+            time => Millis(time.0.saturating_add(self.time_offset())),
+        }
+    }
+
+    /// Gets the preview time the way it's written in a file of this version
+    pub(crate) fn encode_preview_time(self, time: Millis) -> Millis {
+        match time {
+            Millis(-1) => time,
+            time => Millis(time.0.saturating_sub(self.time_offset())),
+        }
+    }
+
+    /// Moves an event read from a file of this version to when it plays
+    pub(crate) fn decode_event(self, event: &mut Event) {
+        shift_event(event, self.time_offset());
+    }
+
+    /// Formats a timing point the way it's written in a file of this version
+    pub(crate) fn encode_timing_point(self, tp: &TimingPoint) -> String {
+        let mut tp = tp.clone();
+        tp.time = tp.time + -self.time_offset() as f64;
+        let line = tp.to_string();
+        line.split(',')
+            .take(self.timing_point_columns())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Formats a hit object the way it's written in a file of this version
+    pub(crate) fn encode_hit_object(self, obj: &HitObject) -> String {
+        let mut obj = obj.clone();
+        obj.shift(-self.time_offset() as f64);
+        if !self.has_fractional_objects() {
+            obj = obj.to_legacy();
+        }
+        let line = obj.to_string();
+        if self.0 >= SAMPLE_VOLUME_VERSION {
+            return line;
+        }
+
+        // the hit sample is always the last field
+        let mut fields = line.split(',').collect::<Vec<_>>();
+        let sample = fields.pop().unwrap_or_default();
+        if self.0 >= HIT_SAMPLE_VERSION {
+            let sample = sample.split(':').take(3).collect::<Vec<_>>().join(":");
+            fields.push(&sample);
+            return fields.join(",");
+        }
+
+        // sliders before hit samples don't have edge sample sets, and leave out edge additions
+        // when there aren't any
+        if let HitObjectKind::Slider(info) = &obj.kind {
+            fields.pop();
+            if info.edge_additions.iter().all(|additions| additions.is_empty()) {
+                fields.pop();
+            }
+        }
+        fields.join(",")
+    }
+
+    /// Gets an event the way it's written in a file of this version
+    pub(crate) fn encode_event(self, event: &Event) -> Event {
+        let mut event = event.clone();
+        shift_event(&mut event, -self.time_offset());
+        event
+    }
+}
+
+impl From<u32> for FormatVersion {
+    fn from(version: u32) -> Self {
+        FormatVersion(version)
+    }
+}
+
+/// Only breaks are moved; storyboards and videos keep the times written in the file.
+fn shift_event(event: &mut Event, offset: i32) {
+    if let Event::Break(info) = event {
+        info.start_time = Millis(info.start_time.0 + offset);
+        info.end_time = Millis(info.end_time.0 + offset);
+    }
+}
+
+impl Beatmap {
+    /// The format version this beatmap is written with: its [`version`][Beatmap::version], or
+    /// [`FormatVersion::LATEST`] if it doesn't have one.
+    pub fn format_version(&self) -> FormatVersion {
+        match self.version {
+            0 => FormatVersion::LATEST,
+            version => FormatVersion(version),
+        }
+    }
+}
@@ -27,14 +27,21 @@ impl Color {
 impl FromStr for Color {
     type Err = ParseError;
 
+    /// Parses either a bare `r,g,b` value or a whole `Combo1 : r,g,b` line
     fn from_str(line: &str) -> Result<Color, Self::Err> {
-        let mut s = line.split(":");
-        s.next().ok_or(ParseError::MissingColorComponent)?;
-        let s = s.next().ok_or(ParseError::MissingColorComponent)?.trim();
+        let s = line.rsplit(':').next().unwrap_or(line).trim();
         let s = s.split(',').collect::<Vec<_>>();
-        let red = s[0].parse::<u8>()?;
-        let green = s[1].parse::<u8>()?;
-        let blue = s[2].parse::<u8>()?;
+        let red = s[0].trim().parse::<u8>()?;
+        let green = s
+            .get(1)
+            .ok_or(ParseError::MissingColorComponent)?
+            .trim()
+            .parse::<u8>()?;
+        let blue = s
+            .get(2)
+            .ok_or(ParseError::MissingColorComponent)?
+            .trim()
+            .parse::<u8>()?;
         Ok(Color { red, green, blue })
     }
 }
@@ -1,4 +1,5 @@
 pub mod binary;
+mod resolver;
 
 use std::io;
 
@@ -10,6 +11,7 @@ use crate::{
 };
 
 pub use self::binary::{Error, ReadBytesOsu, WriteBytesOsu};
+pub use self::resolver::*;
 
 /// Result type for .db file processing
 pub type DbResult<T, E = DbError> = std::result::Result<T, E>;
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::beatmap::Beatmap;
+use crate::replay::Replay;
+
+use super::{Db, DbBeatmap};
+
+impl Db {
+    /// Find the beatmap with the given MD5 checksum.
+    pub fn find_by_hash(&self, hash: &str) -> Option<&DbBeatmap> {
+        self.beatmaps
+            .iter()
+            .find(|beatmap| beatmap.hash.eq_ignore_ascii_case(hash))
+    }
+
+    /// Find the beatmap that the replay was played on.
+    pub fn find_replay_beatmap(&self, replay: &Replay) -> Option<&DbBeatmap> {
+        self.find_by_hash(&replay.beatmap_hash)
+    }
+}
+
+impl DbBeatmap {
+    /// The location of this beatmap's .osu file, given the location of the Songs folder.
+    pub fn path(&self, songs_dir: impl AsRef<Path>) -> PathBuf {
+        songs_dir
+            .as_ref()
+            .join(&self.folder_name)
+            .join(&self.beatmap_file_name)
+    }
+}
+
+/// Finds .osu files by their MD5 checksum (see [`Beatmap::checksum_of`]), so that replays and
+/// osu!.db entries can be linked to the beatmap they're for.
+///
+/// The index can come from an osu!.db file, which is fast but can be out of date, or from hashing
+/// every .osu file in a Songs folder.
+#[derive(Clone, Debug, Default)]
+pub struct BeatmapResolver {
+    paths: HashMap<String, PathBuf>,
+}
+
+impl BeatmapResolver {
+    /// Index the beatmaps listed in an osu!.db file, which are found in the given Songs folder.
+    ///
+    /// Files aren't checked until they're resolved.
+    pub fn from_db(db: &Db, songs_dir: impl AsRef<Path>) -> Self {
+        let paths = db
+            .beatmaps
+            .iter()
+            .map(|beatmap| (beatmap.hash.to_lowercase(), beatmap.path(&songs_dir)))
+            .collect();
+        BeatmapResolver { paths }
+    }
+
+    /// Index every .osu file in a Songs folder (and its subfolders) by hashing it.
+    pub fn scan(songs_dir: impl AsRef<Path>) -> io::Result<Self> {
+        fn visit(resolver: &mut BeatmapResolver, dir: &Path) -> io::Result<()> {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    visit(resolver, &path)?;
+                } else if matches!(path.extension(), Some(ext) if ext.eq_ignore_ascii_case("osu")) {
+                    let hash = Beatmap::checksum_reader(File::open(&path)?)?;
+                    resolver.paths.insert(hash, path);
+                }
+            }
+            Ok(())
+        }
+
+        let mut resolver = BeatmapResolver::default();
+        visit(&mut resolver, songs_dir.as_ref())?;
+        Ok(resolver)
+    }
+
+    /// The number of beatmaps in the index.
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Whether the index has no beatmaps in it.
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Find the .osu file with the given checksum.
+    ///
+    /// Only returns files that still exist and still have that checksum, so out of date osu!.db
+    /// entries are skipped.
+    pub fn resolve(&self, hash: &str) -> Option<&Path> {
+        let path = self.paths.get(&hash.trim().to_lowercase())?;
+        let actual = Beatmap::checksum_reader(File::open(path).ok()?).ok()?;
+        if actual.eq_ignore_ascii_case(hash.trim()) {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    /// Find the .osu file that the replay was played on.
+    pub fn resolve_replay(&self, replay: &Replay) -> Option<&Path> {
+        self.resolve(&replay.beatmap_hash)
+    }
+}
@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 /// Result type for Error
 pub type ParseResult<T, E = ParseError> = std::result::Result<T, E>;
 
@@ -38,6 +40,79 @@ pub enum ParseError {
     #[error("invalid grid size: {0}")]
     InvalidGridSize(u8),
 
+    #[error("invalid countdown: {0}")]
+    InvalidCountdown(u8),
+
+    #[error("invalid overlay position: {0}")]
+    InvalidOverlayPosition(String),
+
+    #[error("missing field: {0}")]
+    MissingField(&'static str),
+
+    #[error("invalid storyboard layer: {0}")]
+    InvalidLayer(String),
+
+    #[error("invalid storyboard origin: {0}")]
+    InvalidOrigin(String),
+
+    #[error("invalid animation loop type: {0}")]
+    InvalidLoopType(String),
+
+    #[error("invalid storyboard command: {0}")]
+    InvalidCommand(String),
+
+    #[error("invalid easing: {0}")]
+    InvalidEasing(u32),
+
+    #[error("invalid trigger: {0}")]
+    InvalidTrigger(String),
+
+    #[error("invalid parameter: {0}")]
+    InvalidParameter(String),
+
+    #[error("storyboard command indented {0} levels has nothing to attach to")]
+    UnexpectedIndentation(usize),
+
     #[error("custom: {0}")]
     Custom(String),
+
+    /// An error in one of the comma-separated fields of a line; `index` counts from 0. Beatmap
+    /// parsing unwraps it into [`BeatmapParseError::column`](crate::beatmap::BeatmapParseError::column).
+    #[error("{inner}")]
+    Field {
+        index: usize,
+        inner: Box<ParseError>,
+    },
+}
+
+impl ParseError {
+    /// Points the error at the field at `index` of a comma-separated line. An error that already
+    /// points at a field of a nested list is re-pointed, since only the line's fields have columns.
+    pub(crate) fn in_field(self, index: usize) -> ParseError {
+        let inner = match self {
+            ParseError::Field { inner, .. } => inner,
+            err => Box::new(err),
+        };
+        ParseError::Field { index, inner }
+    }
+}
+
+/// Gets a field from a split-up line, failing with [`ParseError::MissingField`] (pointed at the
+/// missing field) instead of panicking when the line is too short.
+pub(crate) fn field<'a>(parts: &[&'a str], idx: usize, name: &'static str) -> ParseResult<&'a str> {
+    parts
+        .get(idx)
+        .copied()
+        .ok_or_else(|| ParseError::MissingField(name).in_field(idx))
+}
+
+/// Gets a field from a split-up line and parses it, pointing any error at the field.
+pub(crate) fn parse_field<T>(parts: &[&str], idx: usize, name: &'static str) -> ParseResult<T>
+where
+    T: FromStr,
+    ParseError: From<T::Err>,
+{
+    field(parts, idx, name)?
+        .parse::<T>()
+        .map_err(|err| ParseError::from(err).in_field(idx))
 }
@@ -1,13 +1,14 @@
 use std::fmt;
 use std::str::FromStr;
 
-use crate::errors::ParseError;
+use crate::errors::{field, parse_field, ParseError, ParseResult};
 use crate::math::Point;
+use crate::storyboard::{Command, Layer, LoopType, Origin};
 use crate::timing::Millis;
 
 /// Beatmap event
 #[non_exhaustive]
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Event {
     /// Background event
@@ -19,9 +20,37 @@ pub enum Event {
     /// Break event
     Break(BreakEvent),
 
-    /// Storyboard Event (not implemented)
-    // TODO: implement storyboard events!
-    Storyboard(String),
+    /// Storyboard sprite
+    Sprite(SpriteEvent),
+
+    /// Storyboard animation
+    Animation(AnimationEvent),
+
+    /// Storyboard sound sample
+    Sample(SampleEvent),
+
+    /// Any other event (such as the old background colour transformations), kept verbatim
+    Unknown(String),
+}
+
+impl Event {
+    /// Returns the commands attached to this event, if it is a sprite or animation.
+    pub fn commands(&self) -> Option<&Vec<Command>> {
+        match self {
+            Event::Sprite(evt) => Some(&evt.commands),
+            Event::Animation(evt) => Some(&evt.commands),
+            _ => None,
+        }
+    }
+
+    /// Returns the commands attached to this event mutably, if it is a sprite or animation.
+    pub fn commands_mut(&mut self) -> Option<&mut Vec<Command>> {
+        match self {
+            Event::Sprite(evt) => Some(&mut evt.commands),
+            Event::Animation(evt) => Some(&mut evt.commands),
+            _ => None,
+        }
+    }
 }
 
 /// Used in Event::Background
@@ -60,6 +89,113 @@ pub struct BreakEvent {
     pub end_time: Millis,
 }
 
+/// Used in Event::Sprite
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SpriteEvent {
+    /// The layer the sprite is drawn on
+    pub layer: Layer,
+
+    /// The point on the image that `pos` refers to
+    pub origin: Origin,
+
+    /// Location of the image relative to the beatmap directory.
+    pub filepath: String,
+
+    /// Position in osu!pixels, used unless overridden by a move command
+    pub pos: Point<f64>,
+
+    /// The commands that control this sprite
+    pub commands: Vec<Command>,
+}
+
+/// Used in Event::Animation
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AnimationEvent {
+    /// The layer the animation is drawn on
+    pub layer: Layer,
+
+    /// The point on the image that `pos` refers to
+    pub origin: Origin,
+
+    /// Location of the frames relative to the beatmap directory.
+    ///
+    /// The frame number is inserted before the extension, so `sb/dot.png` with 2 frames uses
+    /// `sb/dot0.png` and `sb/dot1.png`.
+    pub filepath: String,
+
+    /// Position in osu!pixels, used unless overridden by a move command
+    pub pos: Point<f64>,
+
+    /// The number of frames in the animation
+    pub frame_count: u32,
+
+    /// The time (in milliseconds) each frame is shown for
+    pub frame_delay: f64,
+
+    /// What to do after the last frame is shown
+    pub loop_type: LoopType,
+
+    /// The commands that control this animation
+    pub commands: Vec<Command>,
+}
+
+impl AnimationEvent {
+    /// Returns the location of the given frame's image, relative to the beatmap directory.
+    pub fn frame_path(&self, frame: u32) -> String {
+        let name_start = self.filepath.rfind(['/', '\\']).map_or(0, |i| i + 1);
+        match self.filepath[name_start..].rfind('.') {
+            Some(dot) => {
+                let (stem, ext) = self.filepath.split_at(name_start + dot);
+                format!("{}{}{}", stem, frame, ext)
+            }
+            None => format!("{}{}", self.filepath, frame),
+        }
+    }
+}
+
+/// Used in Event::Sample
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SampleEvent {
+    /// The timestamp at which the sample is played
+    pub time: Millis,
+
+    /// The layer the sample belongs to
+    pub layer: Layer,
+
+    /// Location of the audio file relative to the beatmap directory.
+    pub filepath: String,
+
+    /// Volume of the sample (from 0 to 100)
+    pub volume: u32,
+}
+
+/// Parses a single line from an events section, attaching indented storyboard commands to the
+/// sprite or animation (or loop/trigger) they're nested under.
+pub(crate) fn parse_event_line(events: &mut Vec<Event>, line: &str) -> ParseResult<()> {
+    let depth = line.chars().take_while(|c| *c == ' ' || *c == '_').count();
+    if depth == 0 {
+        events.push(Event::from_str(line)?);
+        return Ok(());
+    }
+
+    let command = Command::from_str(&line[depth..])?;
+    let mut commands = events
+        .last_mut()
+        .and_then(Event::commands_mut)
+        .ok_or(ParseError::UnexpectedIndentation(depth))?;
+    for _ in 1..depth {
+        commands = commands
+            .last_mut()
+            .and_then(Command::children_mut)
+            .ok_or(ParseError::UnexpectedIndentation(depth))?;
+    }
+    commands.push(command);
+    Ok(())
+}
+
 impl FromStr for Event {
     type Err = ParseError;
     fn from_str(line: &str) -> Result<Self, Self::Err> {
@@ -68,10 +204,12 @@ impl FromStr for Event {
 
         Ok(match evt_type {
             "0" => {
-                let filename = parts[2].trim_matches('"').to_string();
-                let offset = if let (Some(x), Some(y)) = (parts.get(3), parts.get(4)) {
-                    let x_offset = x.parse::<i32>()?;
-                    let y_offset = y.parse::<i32>()?;
+                let filename = field(&parts, 2, "background filename")?
+                    .trim_matches('"')
+                    .to_string();
+                let offset = if parts.len() > 4 {
+                    let x_offset = parse_field::<i32>(&parts, 3, "x offset")?;
+                    let y_offset = parse_field::<i32>(&parts, 4, "y offset")?;
                     Point::new(x_offset, y_offset)
                 } else {
                     Point::new(0, 0)
@@ -79,11 +217,13 @@ impl FromStr for Event {
                 Event::Background(BackgroundEvent { filename, offset })
             }
             "1" | "Video" => {
-                let start_time = parts[1].parse::<i32>()?;
-                let filename = parts[2].trim_matches('"').to_string();
-                let offset = if let (Some(x), Some(y)) = (parts.get(3), parts.get(4)) {
-                    let x_offset = x.parse::<i32>()?;
-                    let y_offset = y.parse::<i32>()?;
+                let start_time = parse_field::<i32>(&parts, 1, "video start time")?;
+                let filename = field(&parts, 2, "video filename")?
+                    .trim_matches('"')
+                    .to_string();
+                let offset = if parts.len() > 4 {
+                    let x_offset = parse_field::<i32>(&parts, 3, "x offset")?;
+                    let y_offset = parse_field::<i32>(&parts, 4, "y offset")?;
                     Point::new(x_offset, y_offset)
                 } else {
                     Point::new(0, 0)
@@ -95,14 +235,59 @@ impl FromStr for Event {
                 })
             }
             "2" | "Break" => {
-                let start_time = parts[1].parse::<i32>()?;
-                let end_time = parts[2].parse::<i32>()?;
+                let start_time = parse_field::<i32>(&parts, 1, "break start time")?;
+                let end_time = parse_field::<i32>(&parts, 2, "break end time")?;
                 Event::Break(BreakEvent {
                     start_time: Millis(start_time),
                     end_time: Millis(end_time),
                 })
             }
-            _ => Event::Storyboard(line.to_string()),
+            "4" | "Sprite" => {
+                let filepath = field(&parts, 3, "sprite filepath")?;
+                let x = parse_field::<f64>(&parts, 4, "sprite x")?;
+                let y = parse_field::<f64>(&parts, 5, "sprite y")?;
+                Event::Sprite(SpriteEvent {
+                    layer: parse_field(&parts, 1, "sprite layer")?,
+                    origin: parse_field(&parts, 2, "sprite origin")?,
+                    filepath: filepath.trim_matches('"').to_string(),
+                    pos: Point::new(x, y),
+                    commands: Vec::new(),
+                })
+            }
+            "6" | "Animation" => {
+                let filepath = field(&parts, 3, "animation filepath")?;
+                let x = parse_field::<f64>(&parts, 4, "animation x")?;
+                let y = parse_field::<f64>(&parts, 5, "animation y")?;
+                let loop_type = match parts.get(8) {
+                    Some(s) if !s.is_empty() => parse_field(&parts, 8, "animation loop type")?,
+                    _ => LoopType::default(),
+                };
+                Event::Animation(AnimationEvent {
+                    layer: parse_field(&parts, 1, "animation layer")?,
+                    origin: parse_field(&parts, 2, "animation origin")?,
+                    filepath: filepath.trim_matches('"').to_string(),
+                    pos: Point::new(x, y),
+                    frame_count: parse_field::<u32>(&parts, 6, "animation frame count")?,
+                    frame_delay: parse_field::<f64>(&parts, 7, "animation frame delay")?,
+                    loop_type,
+                    commands: Vec::new(),
+                })
+            }
+            "5" | "Sample" => {
+                let time = parse_field::<i32>(&parts, 1, "sample time")?;
+                let filepath = field(&parts, 3, "sample filepath")?;
+                let volume = match parts.get(4) {
+                    Some(s) if !s.is_empty() => parse_field::<u32>(&parts, 4, "sample volume")?,
+                    _ => 100,
+                };
+                Event::Sample(SampleEvent {
+                    time: Millis(time),
+                    layer: parse_field(&parts, 2, "sample layer")?,
+                    filepath: filepath.trim_matches('"').to_string(),
+                    volume,
+                })
+            }
+            _ => Event::Unknown(line.to_string()),
         })
     }
 }
@@ -121,7 +306,41 @@ impl fmt::Display for Event {
                 evt.start_time.0, evt.filename, evt.offset.x, evt.offset.y
             )?,
             Event::Break(evt) => write!(f, "2,{},{}", evt.start_time.0, evt.end_time.0)?,
-            Event::Storyboard(line) => write!(f, "{}", line)?,
+            Event::Sprite(evt) => {
+                write!(
+                    f,
+                    "Sprite,{},{},\"{}\",{},{}",
+                    evt.layer, evt.origin, evt.filepath, evt.pos.x, evt.pos.y
+                )?;
+                for command in evt.commands.iter() {
+                    writeln!(f)?;
+                    command.fmt_indented(f, 1)?;
+                }
+            }
+            Event::Animation(evt) => {
+                write!(
+                    f,
+                    "Animation,{},{},\"{}\",{},{},{},{},{}",
+                    evt.layer,
+                    evt.origin,
+                    evt.filepath,
+                    evt.pos.x,
+                    evt.pos.y,
+                    evt.frame_count,
+                    evt.frame_delay,
+                    evt.loop_type
+                )?;
+                for command in evt.commands.iter() {
+                    writeln!(f)?;
+                    command.fmt_indented(f, 1)?;
+                }
+            }
+            Event::Sample(evt) => write!(
+                f,
+                "Sample,{},{},\"{}\",{}",
+                evt.time.0, evt.layer as u8, evt.filepath, evt.volume
+            )?,
+            Event::Unknown(line) => write!(f, "{}", line)?,
         }
 
         Ok(())
@@ -4,11 +4,11 @@ use std::str::FromStr;
 
 use num::FromPrimitive;
 
-use crate::errors::{ParseError, ParseResult};
+use crate::errors::{field, parse_field, ParseError, ParseResult};
 use crate::hitsounds::{Additions, SampleInfo, SampleSet};
 use crate::math::Point;
 use crate::spline::Spline;
-use crate::timing::Millis;
+use crate::timing::Time;
 
 /// Distinguishes between different types of slider splines.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
@@ -50,7 +50,7 @@ pub struct SliderInfo {
     pub kind: SliderSplineKind,
 
     /// The control points that make up the body of the slider.
-    pub control_points: Vec<Point<i32>>,
+    pub control_points: Vec<Point<f64>>,
 
     /// The number of times this slider should repeat.
     pub num_repeats: u32,
@@ -70,7 +70,7 @@ pub struct SliderInfo {
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SpinnerInfo {
     /// The time at which the slider ends.
-    pub end_time: Millis,
+    pub end_time: Time,
 }
 
 /// Extra information provided by a mania hold.
@@ -78,7 +78,7 @@ pub struct SpinnerInfo {
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct HoldInfo {
     /// The time at which the hold ends.
-    pub end_time: Millis,
+    pub end_time: Time,
 }
 
 /// Distinguishes between different types of hit objects.
@@ -125,10 +125,10 @@ impl HitObjectKind {
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct HitObject {
     /// The position on the map at which this hit object is located (head for sliders).
-    pub pos: Point<i32>,
+    pub pos: Point<f64>,
 
     /// When this hit object occurs during the map.
-    pub start_time: Millis,
+    pub start_time: Time,
 
     /// The kind of HitObject this represents (circle, slider, spinner).
     pub kind: HitObjectKind,
@@ -152,7 +152,7 @@ impl HitObject {
         match &self.kind {
             HitObjectKind::Slider(info) => {
                 if info.num_repeats % 2 == 0 {
-                    self.pos.to_float().expect("f64 converts to float")
+                    self.pos
                 } else {
                     let mut control_points = vec![self.pos];
                     control_points.extend(&info.control_points);
@@ -164,9 +164,41 @@ impl HitObject {
                     spline.end_point()
                 }
             }
-            _ => self.pos.to_float().expect("f64 converts to float"),
+            _ => self.pos,
         }
     }
+
+    /// Moves this hit object (including the end of spinners and holds) by some number of
+    /// milliseconds.
+    pub fn shift(&mut self, offset: f64) {
+        self.start_time = self.start_time + offset;
+        match &mut self.kind {
+            HitObjectKind::Spinner(SpinnerInfo { end_time })
+            | HitObjectKind::Hold(HoldInfo { end_time }) => *end_time = *end_time + offset,
+            HitObjectKind::Circle | HitObjectKind::Slider(_) => (),
+        }
+    }
+
+    /// Returns a copy of this hit object with its position, times and control points rounded
+    /// to whole numbers, the way format versions before
+    /// [`FIRST_LAZER_VERSION`][crate::beatmap::FIRST_LAZER_VERSION] store them.
+    pub fn to_legacy(&self) -> HitObject {
+        let round = |p: Point<f64>| Point::new(p.x.round(), p.y.round());
+        let mut obj = self.clone();
+        obj.pos = round(obj.pos);
+        obj.start_time = Time(obj.start_time.0.round());
+        match &mut obj.kind {
+            HitObjectKind::Circle => (),
+            HitObjectKind::Slider(info) => {
+                for point in info.control_points.iter_mut() {
+                    *point = round(*point);
+                }
+            }
+            HitObjectKind::Spinner(SpinnerInfo { end_time })
+            | HitObjectKind::Hold(HoldInfo { end_time }) => *end_time = Time(end_time.0.round()),
+        }
+        obj
+    }
 }
 
 impl Ord for HitObject {
@@ -196,15 +228,15 @@ impl FromStr for HitObject {
     fn from_str(input: &str) -> Result<HitObject, Self::Err> {
         let parts = input.split(',').collect::<Vec<_>>();
 
-        let x = parts[0].parse::<i32>()?;
-        let y = parts[1].parse::<i32>()?;
-        let timestamp = parts[2].parse::<i32>()?;
-        let obj_type = parts[3].parse::<i32>()?;
-        let additions_bits = parts[4].parse::<u32>()?;
+        let x = parse_field::<f64>(&parts, 0, "x")?;
+        let y = parse_field::<f64>(&parts, 1, "y")?;
+        let timestamp = parse_field::<f64>(&parts, 2, "time")?;
+        let obj_type = parse_field::<i32>(&parts, 3, "type")?;
+        let additions_bits = parse_field::<u32>(&parts, 4, "hitsound")?;
         let additions = Additions::from_bits(additions_bits)
-            .ok_or(ParseError::InvalidAdditions(additions_bits))?;
+            .ok_or_else(|| ParseError::InvalidAdditions(additions_bits).in_field(4))?;
 
-        let start_time = Millis(timestamp);
+        let start_time = Time(timestamp);
 
         // color is the top 3 bits of the "type" string, since there's a possible of 8 different
         // combo colors max
@@ -217,7 +249,7 @@ impl FromStr for HitObject {
             o if (o & (1 << 0)) != 0 => {
                 if let Some(s) = parts.get(5) {
                     if !s.is_empty() {
-                        sample_info = SampleInfo::from_str(s)?;
+                        sample_info = SampleInfo::from_str(s).map_err(|err| err.in_field(5))?;
                     }
                 }
                 HitObjectKind::Circle
@@ -225,13 +257,15 @@ impl FromStr for HitObject {
 
             //slider
             o if (o & (1 << 1)) != 0 => {
-                let mut ctl_parts = parts[5].split('|').collect::<Vec<_>>();
-                let num_repeats = parts[6].parse::<u32>()?;
+                let mut ctl_parts = field(&parts, 5, "slider curve")?
+                    .split('|')
+                    .collect::<Vec<_>>();
+                let num_repeats = parse_field::<u32>(&parts, 6, "slider repeats")?;
                 let slider_type = ctl_parts.remove(0);
 
                 // slider duration = pixelLength / (100.0 * SliderMultiplier) * BeatDuration
                 // from the osu wiki
-                let pixel_length = parts[7].parse::<f64>()?;
+                let pixel_length = parse_field::<f64>(&parts, 7, "slider length")?;
 
                 let edge_additions = if parts.len() > 8 {
                     parts[8]
@@ -241,7 +275,8 @@ impl FromStr for HitObject {
                                 Additions::from_bits(b).ok_or(ParseError::InvalidAdditions(b))
                             })
                         })
-                        .collect::<Result<Vec<_>, _>>()?
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|err| err.in_field(8))?
                 } else {
                     vec![Additions::empty()]
                 };
@@ -251,20 +286,24 @@ impl FromStr for HitObject {
                         .split('|')
                         .map(|s| {
                             let s2 = s.split(':').collect::<Vec<_>>();
-                            let normal = s2[0].parse::<u32>()?;
-                            let additions = s2[1].parse::<u32>()?;
+                            let normal = field(&s2, 0, "edge sample set")?.parse::<u32>()?;
+                            let additions = field(&s2, 1, "edge addition set")?.parse::<u32>()?;
                             Ok((
-                                SampleSet::from_u32(normal).unwrap(),
-                                SampleSet::from_u32(additions).unwrap(),
+                                SampleSet::from_u32(normal)
+                                    .ok_or(ParseError::InvalidSampleSet(normal))?,
+                                SampleSet::from_u32(additions)
+                                    .ok_or(ParseError::InvalidSampleSet(additions))?,
                             ))
                         })
-                        .collect::<ParseResult<Vec<_>>>()?
+                        .collect::<ParseResult<Vec<_>>>()
+                        .map_err(|err| err.in_field(9))?
                 } else {
                     vec![(SampleSet::None, SampleSet::None)]
                 };
 
                 if parts.len() > 10 {
-                    sample_info = SampleInfo::from_str(parts[10])?;
+                    sample_info =
+                        SampleInfo::from_str(parts[10]).map_err(|err| err.in_field(10))?;
                 }
 
                 HitObjectKind::Slider(SliderInfo {
@@ -274,15 +313,18 @@ impl FromStr for HitObject {
                         "B" => SliderSplineKind::Bezier,
                         "C" => SliderSplineKind::Catmull,
                         "P" => SliderSplineKind::Perfect,
-                        s => return Err(ParseError::InvalidSliderType(s.to_owned())),
+                        s => return Err(ParseError::InvalidSliderType(s.to_owned()).in_field(5)),
                     },
                     control_points: ctl_parts
                         .into_iter()
                         .map(|s| {
                             let p = s.split(':').collect::<Vec<_>>();
-                            Point::new(p[0].parse::<i32>().unwrap(), p[1].parse::<i32>().unwrap())
+                            let x = field(&p, 0, "control point x")?.parse::<f64>()?;
+                            let y = field(&p, 1, "control point y")?.parse::<f64>()?;
+                            Ok(Point::new(x, y))
                         })
-                        .collect(),
+                        .collect::<ParseResult<_>>()
+                        .map_err(|err| err.in_field(5))?,
                     pixel_length,
                     edge_additions,
                     edge_samplesets,
@@ -291,33 +333,37 @@ impl FromStr for HitObject {
 
             // spinner
             o if (o & (1 << 3)) != 0 => {
-                let end_time = parts[5].parse::<i32>()?;
+                let end_time = parse_field::<f64>(&parts, 5, "spinner end time")?;
                 if let Some(s) = parts.get(6) {
                     if !s.is_empty() {
-                        sample_info = SampleInfo::from_str(s)?;
+                        sample_info = SampleInfo::from_str(s).map_err(|err| err.in_field(6))?;
                     }
                 }
                 HitObjectKind::Spinner(SpinnerInfo {
-                    end_time: Millis(end_time),
+                    end_time: Time(end_time),
                 })
             }
 
             // mania hold
             o if (o & (1 << 7)) != 0 => {
-                let last_parts = parts[5].split(':').collect::<Vec<_>>();
-                let end_time = last_parts[0].parse::<i32>()?;
+                let last_parts = field(&parts, 5, "hold end time")?
+                    .split(':')
+                    .collect::<Vec<_>>();
+                let end_time = last_parts[0]
+                    .parse::<f64>()
+                    .map_err(|err| ParseError::from(err).in_field(5))?;
                 if let Some(s) = last_parts.get(1..) {
                     let s = s.join(":");
                     if !s.is_empty() {
-                        sample_info = SampleInfo::from_str(&s)?;
+                        sample_info = SampleInfo::from_str(&s).map_err(|err| err.in_field(5))?;
                     }
                 }
                 HitObjectKind::Hold(HoldInfo {
-                    end_time: Millis(end_time),
+                    end_time: Time(end_time),
                 })
             }
             o => {
-                return Err(ParseError::InvalidObjectType(o));
+                return Err(ParseError::InvalidObjectType(o).in_field(3));
             }
         };
 
@@ -0,0 +1,449 @@
+use crate::beatmap::{Beatmap, SliderEventKind};
+use crate::hitobject::HitObjectKind;
+use crate::timing::{InheritedTimingInfo, Time, TimingPoint, TimingPointKind};
+
+use super::{Additions, HitSound, HitSoundKind, SampleSet};
+
+/// Options for [`Beatmap::copy_hitsounds_to`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HitsoundCopyOptions {
+    /// How far apart (in milliseconds) a hit in the source and a hit in the target can be and
+    /// still be treated as the same hit
+    pub tolerance: f64,
+
+    /// Whether to also copy the sample sets, custom indices and volumes of the source's timing
+    /// points, so slider ticks and slides sound the same as well. Timing points are added to the
+    /// target where it doesn't already have one.
+    pub copy_sample_points: bool,
+}
+
+impl Default for HitsoundCopyOptions {
+    fn default() -> Self {
+        HitsoundCopyOptions {
+            tolerance: 5.0,
+            copy_sample_points: true,
+        }
+    }
+}
+
+/// What happened when copying hitsounds, see [`Beatmap::copy_hitsounds_to`].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HitsoundCopyReport {
+    /// How many hits in the source were copied to at least one hit in the target
+    pub copied: usize,
+
+    /// How many timing points were added to the target
+    pub added_timing_points: usize,
+
+    /// The sounds of hits in the source that have no hit in the target to go on
+    pub unplaced: Vec<HitSound>,
+}
+
+/// The sounds of one hit in the source map, as they're heard.
+struct SourceHit {
+    time: f64,
+    additions: Additions,
+    sample_set: SampleSet,
+    addition_set: SampleSet,
+    custom_index: u32,
+    volume: u32,
+    filename: Option<String>,
+    slider_whistle: bool,
+    sounds: Vec<HitSound>,
+}
+
+/// A place in the target map where a hit object is hit: a circle, a spinner's end, a hold's head
+/// or a slider's head, repeat or tail.
+struct TargetHit {
+    time: f64,
+    object_index: usize,
+    edge: Option<usize>,
+    source: Option<usize>,
+}
+
+impl Beatmap {
+    /// Copies the hitsounds of this map onto another difficulty of it.
+    ///
+    /// Every hit in the target (including slider repeats and tails) gets the additions, sample
+    /// sets, custom index and volume of the hit in this map at the same time, give or take the
+    /// [`tolerance`][HitsoundCopyOptions::tolerance], so it sounds the same. Hits with nothing to
+    /// copy from are made silent apart from the hit normal. Where a custom index or volume can't be
+    /// set on the hit object itself, such as on a slider's repeats, a timing point with that index
+    /// and volume is added to the target.
+    ///
+    /// Hits in this map that there was nowhere to put are listed in the report.
+    ///
+    /// ```
+    /// # use libosu::prelude::*;
+    /// # use std::str::FromStr;
+    /// let source = "osu file format v14\n\n[TimingPoints]\n0,500,4,2,0,60,1,0\n\n[HitObjects]\n0,0,1000,1,2,0:0:0:0:\n0,0,1500,1,8,0:0:0:0:\n";
+    /// let target = "osu file format v14\n\n[TimingPoints]\n0,500,4,1,0,100,1,0\n\n[HitObjects]\n0,0,1002,1,0,0:0:0:0:\n";
+    /// let source = Beatmap::from_str(source).unwrap();
+    /// let mut target = Beatmap::from_str(target).unwrap();
+    ///
+    /// let report = source.copy_hitsounds_to(&mut target, &HitsoundCopyOptions::default());
+    /// assert_eq!(report.copied, 1);
+    /// assert_eq!(report.unplaced[0].time, Time(1500.0));
+    /// assert_eq!(target.hit_objects[0].additions, Additions::WHISTLE);
+    /// assert_eq!(target.hitsounds()[1].filename, "soft-hitwhistle.wav");
+    /// ```
+    pub fn copy_hitsounds_to(
+        &self,
+        target: &mut Beatmap,
+        options: &HitsoundCopyOptions,
+    ) -> HitsoundCopyReport {
+        let mut report = HitsoundCopyReport::default();
+        if options.copy_sample_points {
+            report.added_timing_points = self.copy_sample_points_to(target);
+        }
+
+        let source_hits = self.source_hits();
+        let mut target_hits = target.target_hits();
+        let mut placed = vec![false; source_hits.len()];
+        for hit in target_hits.iter_mut() {
+            // prefer the closest hit that hasn't been used yet, for maps with several objects at
+            // the same time
+            let from = source_hits.partition_point(|src| src.time < hit.time - options.tolerance);
+            let to = source_hits.partition_point(|src| src.time <= hit.time + options.tolerance);
+            let nearby = (from..to).map(|i| (i, &source_hits[i]));
+            let closest =
+                |(i, src): &(usize, &SourceHit)| (placed[*i], Time((src.time - hit.time).abs()));
+            hit.source = nearby.min_by_key(closest).map(|(i, _)| i);
+            if let Some(i) = hit.source {
+                placed[i] = true;
+            }
+        }
+
+        // the source hits of every hit of each hit object, for sliders to see all of their edges
+        let mut object_edges = vec![Vec::new(); target.hit_objects.len()];
+        for hit in target_hits.iter() {
+            object_edges[hit.object_index].push(hit.source.map(|i| &source_hits[i]));
+        }
+        let point_samples = {
+            let timing = target.timing_index();
+            target_hits
+                .iter()
+                .map(|hit| target.timing_samples_at(hit.time, &timing))
+                .collect::<Vec<_>>()
+        };
+        for (hit, point_samples) in target_hits.iter().zip(point_samples) {
+            let edges = &object_edges[hit.object_index];
+            target.set_hit_samples(hit, &source_hits, edges, point_samples);
+        }
+
+        // custom indices and volumes that couldn't be set on the hit objects go in timing points,
+        // in order so that each one only changes what comes after it. Timing points are added as
+        // this goes, so they're looked up again for every hit.
+        for hit in target_hits.iter() {
+            let source = match hit.source {
+                Some(i) => &source_hits[i],
+                None => continue,
+            };
+            let sample_info = &target.hit_objects[hit.object_index].sample_info;
+            let (_, point_index, point_volume) =
+                target.timing_samples_at(hit.time, &target.timing_lookup());
+            let custom_index = match sample_info.custom_index {
+                index if index > 0 => index as u32,
+                _ => point_index,
+            };
+            let volume = match sample_info.sample_volume {
+                volume if volume > 0 => volume as u32,
+                _ => point_volume,
+            };
+            if custom_index == source.custom_index && volume == source.volume {
+                continue;
+            }
+
+            // a timing point just after the hit still applies to it, so that one is changed
+            let time = target
+                .sample_point_at(hit.time, &target.timing_lookup())
+                .map(|tp| tp.time.0.max(hit.time))
+                .unwrap_or(hit.time);
+            let (point, added) = match target.sample_point_mut(time) {
+                Some(point) => point,
+                None => continue,
+            };
+            if custom_index != source.custom_index {
+                point.sample_index = source.custom_index;
+            }
+            if volume != source.volume {
+                point.volume = source.volume as u16;
+            }
+            report.added_timing_points += added as usize;
+        }
+
+        for (src, placed) in source_hits.into_iter().zip(placed) {
+            if placed {
+                report.copied += 1;
+            } else {
+                report.unplaced.extend(src.sounds);
+            }
+        }
+        report
+    }
+
+    /// Makes the sample settings of the target's timing points match this map's, adding timing
+    /// points to the target where this map's settings change. Returns how many were added.
+    fn copy_sample_points_to(&self, target: &mut Beatmap) -> usize {
+        let mut added = 0;
+        let mut previous: Option<&TimingPoint> = None;
+        for tp in self.timing_points.iter() {
+            let changed = previous.is_none_or(|prev| {
+                prev.sample_set != tp.sample_set
+                    || prev.sample_index != tp.sample_index
+                    || prev.volume != tp.volume
+            });
+            if changed {
+                if let Some((_, was_added)) = target.sample_point_mut(tp.time.0) {
+                    added += was_added as usize;
+                }
+            }
+            previous = Some(tp);
+        }
+
+        let timing = self.timing_lookup();
+        for tp in target.timing_points.iter_mut() {
+            let source = timing
+                .point_at(tp.time)
+                .or_else(|| self.timing_points.first());
+            if let Some(source) = source {
+                tp.sample_set = source.sample_set;
+                tp.sample_index = source.sample_index;
+                tp.volume = source.volume;
+            }
+        }
+        added
+    }
+
+    /// The timing point at exactly the given time, which is added (keeping the slider velocity
+    /// and kiai in effect) if there isn't one already. Returns whether it was added, or `None` if
+    /// the time is before the first timing point.
+    fn sample_point_mut(&mut self, time: f64) -> Option<(&mut TimingPoint, bool)> {
+        let time = Time(time);
+        let index = self.timing_points.partition_point(|tp| tp.time <= time);
+        if index > 0 && self.timing_points[index - 1].time == time {
+            return Some((&mut self.timing_points[index - 1], false));
+        }
+
+        let current = self.timing_points.get(index.checked_sub(1)?)?.clone();
+        let slider_velocity = self.get_slider_velocity_at_time(time);
+        self.timing_points.insert(
+            index,
+            TimingPoint {
+                time,
+                kind: TimingPointKind::Inherited(InheritedTimingInfo { slider_velocity }),
+                ..current
+            },
+        );
+        Some((&mut self.timing_points[index], true))
+    }
+
+    /// Groups the sounds of this map into the hits that make them, in order of time.
+    fn source_hits(&self) -> Vec<SourceHit> {
+        let sounds = self.hitsounds();
+        let mut slider_whistles = vec![false; self.hit_objects.len()];
+        for sound in sounds.iter() {
+            if sound.kind == HitSoundKind::SliderWhistle {
+                slider_whistles[sound.object_index] = true;
+            }
+        }
+
+        let mut hits: Vec<SourceHit> = Vec::new();
+        for sound in sounds.into_iter() {
+            let addition = match sound.kind {
+                HitSoundKind::Normal => Additions::empty(),
+                HitSoundKind::Whistle => Additions::WHISTLE,
+                HitSoundKind::Finish => Additions::FINISH,
+                HitSoundKind::Clap => Additions::CLAP,
+                HitSoundKind::SliderSlide
+                | HitSoundKind::SliderWhistle
+                | HitSoundKind::SliderTick => continue,
+            };
+
+            let same_hit = hits.last().is_some_and(|hit| {
+                hit.time == sound.time.0 && hit.sounds[0].object_index == sound.object_index
+            });
+            if !same_hit {
+                let obj = &self.hit_objects[sound.object_index];
+                hits.push(SourceHit {
+                    time: sound.time.0,
+                    additions: Additions::empty(),
+                    sample_set: sound.sample_set,
+                    addition_set: sound.addition_set,
+                    custom_index: sound.custom_index,
+                    volume: sound.volume,
+                    filename: obj.sample_info.filename.clone(),
+                    slider_whistle: slider_whistles[sound.object_index],
+                    sounds: Vec::new(),
+                });
+            }
+            let hit = hits.last_mut().expect("a hit was just added");
+            hit.additions |= addition;
+            hit.sounds.push(sound);
+        }
+        hits
+    }
+
+    /// Lists where each hit object in this map is hit, in order of time.
+    fn target_hits(&self) -> Vec<TargetHit> {
+        let timing = self.timing_index();
+        let mut hits = Vec::new();
+        for (object_index, obj) in self.hit_objects.iter().enumerate() {
+            let hit = |time: f64, edge| TargetHit {
+                time,
+                object_index,
+                edge,
+                source: None,
+            };
+            match &obj.kind {
+                HitObjectKind::Circle | HitObjectKind::Hold(_) => {
+                    hits.push(hit(obj.start_time.0, None))
+                }
+                HitObjectKind::Spinner(info) => hits.push(hit(info.end_time.0, None)),
+                HitObjectKind::Slider(_) => {
+                    let events = self.slider_events_in(obj, &timing).unwrap_or_default();
+                    let edges = events.iter().filter(|event| match event.kind {
+                        SliderEventKind::Head
+                        | SliderEventKind::Repeat { .. }
+                        | SliderEventKind::Tail => true,
+                        SliderEventKind::Tick | SliderEventKind::LegacyLastTick => false,
+                    });
+                    for (edge, event) in edges.enumerate() {
+                        hits.push(hit(event.time.0, Some(edge)));
+                    }
+                }
+            }
+        }
+        hits.sort_by_key(|hit| Time(hit.time));
+        hits
+    }
+
+    /// Sets the additions and sample sets of a hit in this map to sound like the source hit. The
+    /// custom index and volume are set too if the hit object can hold them.
+    ///
+    /// `edges` are the source hits of each of the hit object's hits, and `point_samples` are the
+    /// samples the hit gets from the timing points.
+    fn set_hit_samples(
+        &mut self,
+        hit: &TargetHit,
+        source_hits: &[SourceHit],
+        edges: &[Option<&SourceHit>],
+        point_samples: (SampleSet, u32, u32),
+    ) {
+        let source = hit.source.map(|i| &source_hits[i]);
+        let (point_set, point_index, point_volume) = point_samples;
+        let own_sample_set = |set: SampleSet| {
+            if set == point_set {
+                SampleSet::None
+            } else {
+                set
+            }
+        };
+        let own_addition_set = |src: &SourceHit| {
+            if src.addition_set == src.sample_set {
+                SampleSet::None
+            } else {
+                src.addition_set
+            }
+        };
+        let own_index = |index: u32| {
+            if index == point_index {
+                0
+            } else {
+                index as i32
+            }
+        };
+        let own_volume = |volume: u32| {
+            if volume == point_volume {
+                0
+            } else {
+                volume as i32
+            }
+        };
+
+        let obj = &mut self.hit_objects[hit.object_index];
+        let edge = match (hit.edge, &mut obj.kind) {
+            (Some(edge), HitObjectKind::Slider(info)) => Some((edge, info)),
+            _ => None,
+        };
+        let (edge, info) = match edge {
+            Some(edge) => edge,
+            None => {
+                let info = &mut obj.sample_info;
+                match source {
+                    Some(src) => {
+                        obj.additions = src.additions;
+                        info.sample_set = own_sample_set(src.sample_set);
+                        info.addition_set = own_addition_set(src);
+                        info.custom_index = own_index(src.custom_index);
+                        info.sample_volume = own_volume(src.volume);
+                        info.filename = src.filename.clone();
+                    }
+                    None => {
+                        obj.additions = Additions::empty();
+                        *info = Default::default();
+                    }
+                }
+                return;
+            }
+        };
+
+        info.edge_additions.resize(edges.len(), Additions::empty());
+        info.edge_samplesets
+            .resize(edges.len(), (SampleSet::None, SampleSet::None));
+
+        if edge == 0 {
+            // the head also sets the slider's own samples, which its body and ticks use, and its
+            // custom index and volume if all of its edges have the same ones
+            let same_samples = edges.iter().all(|other| match (other, source) {
+                (Some(other), Some(src)) => {
+                    (other.custom_index, other.volume) == (src.custom_index, src.volume)
+                }
+                (other, src) => other.is_none() && src.is_none(),
+            });
+            let sample_info = &mut obj.sample_info;
+            *sample_info = Default::default();
+            obj.additions = Additions::empty();
+            if let Some(src) = source {
+                sample_info.sample_set = own_sample_set(src.sample_set);
+                sample_info.addition_set = own_addition_set(src);
+                if src.slider_whistle {
+                    obj.additions = Additions::WHISTLE;
+                }
+                if same_samples {
+                    sample_info.custom_index = own_index(src.custom_index);
+                    sample_info.sample_volume = own_volume(src.volume);
+                }
+            }
+        }
+
+        let (additions, sample_sets) = match source {
+            Some(src) => {
+                let object_set = match obj.sample_info.sample_set {
+                    SampleSet::None => point_set,
+                    object_set => object_set,
+                };
+                let sample_set = if src.sample_set == object_set {
+                    SampleSet::None
+                } else {
+                    src.sample_set
+                };
+                let object_addition_set = match obj.sample_info.addition_set {
+                    SampleSet::None => src.sample_set,
+                    object_addition_set => object_addition_set,
+                };
+                let addition_set = if src.addition_set == object_addition_set {
+                    SampleSet::None
+                } else {
+                    src.addition_set
+                };
+                (src.additions, (sample_set, addition_set))
+            }
+            None => (Additions::empty(), (SampleSet::None, SampleSet::None)),
+        };
+        info.edge_additions[edge] = additions;
+        info.edge_samplesets[edge] = sample_sets;
+    }
+}
@@ -3,7 +3,13 @@ use std::str::FromStr;
 
 use num::FromPrimitive;
 
-use crate::errors::ParseError;
+use crate::errors::{field, ParseError};
+
+mod copy;
+mod resolve;
+
+pub use self::copy::*;
+pub use self::resolve::*;
 
 /// A set of hitsound samples.
 ///
@@ -27,6 +33,18 @@ pub enum SampleSet {
     Drum = 3,
 }
 
+impl SampleSet {
+    /// The name used for this sample set in sample file names, like the `soft` in
+    /// `soft-hitwhistle.wav`. [`SampleSet::None`] is played as [`SampleSet::Normal`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            SampleSet::None | SampleSet::Normal => "normal",
+            SampleSet::Soft => "soft",
+            SampleSet::Drum => "drum",
+        }
+    }
+}
+
 #[allow(non_upper_case_globals)]
 bitflags! {
     /// A representation of hitsound additions.
@@ -83,13 +101,11 @@ impl FromStr for SampleInfo {
         let mut sample = SampleInfo::default();
         let extra_parts = line.split(':').collect::<Vec<_>>();
 
-        let sample_set = extra_parts[0].parse::<u32>()?;
-        sample.sample_set = SampleSet::from_u32(sample_set)
-            .unwrap_or(SampleSet::None);
+        let sample_set = field(&extra_parts, 0, "sample set")?.parse::<u32>()?;
+        sample.sample_set = SampleSet::from_u32(sample_set).unwrap_or(SampleSet::None);
 
-        let addition_set = extra_parts[1].parse::<u32>()?;
-        sample.addition_set = SampleSet::from_u32(addition_set)
-            .unwrap_or(SampleSet::None);
+        let addition_set = field(&extra_parts, 1, "addition set")?.parse::<u32>()?;
+        sample.addition_set = SampleSet::from_u32(addition_set).unwrap_or(SampleSet::None);
 
         if let Some(custom_index) = extra_parts.get(2) {
             sample.custom_index = custom_index.parse::<i32>()?;
@@ -97,7 +113,10 @@ impl FromStr for SampleInfo {
         if let Some(sample_volume) = extra_parts.get(3) {
             sample.sample_volume = sample_volume.parse::<i32>()?;
         }
-        sample.filename = extra_parts.get(4).copied().map(str::to_string);
+        sample.filename = extra_parts
+            .get(4)
+            .filter(|filename| !filename.is_empty())
+            .map(|filename| filename.to_string());
 
         Ok(sample)
     }
@@ -107,12 +126,12 @@ impl fmt::Display for SampleInfo {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "{}:{}:{}:{}:{:?}",
+            "{}:{}:{}:{}:{}",
             self.sample_set as u32,
             self.addition_set as u32,
             self.custom_index,
             self.sample_volume,
-            self.filename
+            self.filename.as_deref().unwrap_or("")
         )
     }
 }
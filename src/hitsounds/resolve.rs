@@ -0,0 +1,359 @@
+use std::fmt;
+
+use crate::beatmap::{Beatmap, SliderEventKind};
+use crate::hitobject::{HitObject, HitObjectKind, SpinnerInfo};
+use crate::timing::{Time, TimingIndex, TimingPoint};
+
+use super::{Additions, SampleInfo, SampleSet};
+
+/// Sample settings are taken from the timing point in effect this many milliseconds after a hit,
+/// so that timing points placed slightly late still apply.
+const CONTROL_POINT_LENIENCY: f64 = 5.0;
+
+/// Which sample a [`HitSound`] plays.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum HitSoundKind {
+    /// The hit normal sound, which plays on every hit
+    Normal,
+
+    /// The whistle addition
+    Whistle,
+
+    /// The finish (cymbal) addition
+    Finish,
+
+    /// The clap addition
+    Clap,
+
+    /// The sound that loops while a slider is being held
+    SliderSlide,
+
+    /// The whistle that loops while a slider with a whistle is being held
+    SliderWhistle,
+
+    /// The sound of a slider tick
+    SliderTick,
+}
+
+impl HitSoundKind {
+    /// The name of the sample in file names, like the `hitwhistle` in `soft-hitwhistle.wav`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            HitSoundKind::Normal => "hitnormal",
+            HitSoundKind::Whistle => "hitwhistle",
+            HitSoundKind::Finish => "hitfinish",
+            HitSoundKind::Clap => "hitclap",
+            HitSoundKind::SliderSlide => "sliderslide",
+            HitSoundKind::SliderWhistle => "sliderwhistle",
+            HitSoundKind::SliderTick => "slidertick",
+        }
+    }
+
+    /// Whether this sound comes from the addition sample set rather than the normal one.
+    pub fn is_addition(&self) -> bool {
+        matches!(
+            self,
+            HitSoundKind::Whistle
+                | HitSoundKind::Finish
+                | HitSoundKind::Clap
+                | HitSoundKind::SliderWhistle
+        )
+    }
+}
+
+/// A sound that's played during a beatmap, with all of its settings worked out. See
+/// [`Beatmap::hitsounds`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HitSound {
+    /// When the sound plays
+    pub time: Time,
+
+    /// When a looping sound (a slider slide or whistle) stops
+    pub end_time: Option<Time>,
+
+    /// The index of the hit object that makes the sound
+    pub object_index: usize,
+
+    /// Which sample is played
+    pub kind: HitSoundKind,
+
+    /// The sample set of the hit normal, slide and tick sounds
+    pub sample_set: SampleSet,
+
+    /// The sample set of the additions
+    pub addition_set: SampleSet,
+
+    /// Which of the beatmap's custom samples to use. 0 means the skin's sample is always used,
+    /// and 1 means the beatmap's sample without a number.
+    pub custom_index: u32,
+
+    /// The volume, from 0 to 100
+    pub volume: u32,
+
+    /// The file that's played, like `soft-hitwhistle2.wav`. If the beatmap doesn't have it, the
+    /// skin's sample with the same name (without the number) is played instead.
+    pub filename: String,
+}
+
+impl fmt::Display for HitSound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} ({}%)", self.time, self.filename, self.volume)
+    }
+}
+
+/// The sample settings of one hit, before they're turned into sounds.
+struct Hit<'a> {
+    time: f64,
+    /// When the sample settings are taken from the timing points
+    sample_time: f64,
+    object_index: usize,
+    sample_set: SampleSet,
+    addition_set: SampleSet,
+    additions: Additions,
+    sample_info: &'a SampleInfo,
+}
+
+impl Beatmap {
+    /// Works out every sound the hit objects in this map make when they're hit, in order of time.
+    ///
+    /// Each sound's sample sets, custom index and volume come from the hit object (or the edge of
+    /// a slider) if it sets them, otherwise from the timing point in effect at that time, and
+    /// finally from the beatmap's [`sample_set`][Beatmap::sample_set]. Additions use the normal
+    /// sample set unless they have their own. Hit objects with a custom sample file play only
+    /// that file.
+    ///
+    /// Sliders play their edge sounds at the head, each repeat and the tail, a tick sound on every
+    /// tick, and slide (and whistle) sounds for as long as they're held. The slide sounds use the
+    /// timing point in effect at the end of the slider. Spinners are heard at the end.
+    ///
+    /// ```
+    /// # use libosu::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = "osu file format v14\n\n[General]\nSampleSet: Soft\n\n[TimingPoints]\n0,500,4,0,2,60,1,0\n\n[HitObjects]\n0,0,1000,1,2,0:0:0:0:\n";
+    /// let beatmap = Beatmap::from_str(input).unwrap();
+    /// let sounds = beatmap.hitsounds();
+    /// assert_eq!(sounds.len(), 2);
+    /// assert_eq!(sounds[0].filename, "soft-hitnormal2.wav");
+    /// assert_eq!(sounds[1].filename, "soft-hitwhistle2.wav");
+    /// assert_eq!(sounds[1].volume, 60);
+    /// ```
+    pub fn hitsounds(&self) -> Vec<HitSound> {
+        let timing = self.timing_index();
+        let mut sounds = Vec::new();
+        for (object_index, obj) in self.hit_objects.iter().enumerate() {
+            let hit = |time: f64, edge: Option<usize>| {
+                let (mut additions, mut sample_set, mut addition_set) = (
+                    obj.additions,
+                    obj.sample_info.sample_set,
+                    obj.sample_info.addition_set,
+                );
+                if let (Some(edge), HitObjectKind::Slider(info)) = (edge, &obj.kind) {
+                    if let Some(edge_additions) = info.edge_additions.get(edge) {
+                        additions = *edge_additions;
+                    }
+                    if let Some((edge_sample_set, edge_addition_set)) =
+                        info.edge_samplesets.get(edge)
+                    {
+                        if *edge_sample_set != SampleSet::None {
+                            sample_set = *edge_sample_set;
+                        }
+                        if *edge_addition_set != SampleSet::None {
+                            addition_set = *edge_addition_set;
+                        }
+                    }
+                }
+                Hit {
+                    time,
+                    sample_time: time,
+                    object_index,
+                    sample_set,
+                    addition_set,
+                    additions,
+                    sample_info: &obj.sample_info,
+                }
+            };
+
+            match &obj.kind {
+                HitObjectKind::Circle | HitObjectKind::Hold(_) => {
+                    self.push_hit(&mut sounds, &timing, &hit(obj.start_time.0, None));
+                }
+                HitObjectKind::Spinner(SpinnerInfo { end_time }) => {
+                    self.push_hit(&mut sounds, &timing, &hit(end_time.0, None));
+                }
+                HitObjectKind::Slider(_) => {
+                    let events = self.slider_events_in(obj, &timing).unwrap_or_default();
+                    let mut edge = 0;
+                    for event in events.iter() {
+                        match event.kind {
+                            SliderEventKind::Head
+                            | SliderEventKind::Repeat { .. }
+                            | SliderEventKind::Tail => {
+                                self.push_hit(&mut sounds, &timing, &hit(event.time.0, Some(edge)));
+                                edge += 1;
+                            }
+                            SliderEventKind::Tick => {
+                                let tick = hit(event.time.0, None);
+                                self.push_sound(
+                                    &mut sounds,
+                                    &timing,
+                                    &tick,
+                                    HitSoundKind::SliderTick,
+                                    None,
+                                );
+                            }
+                            SliderEventKind::LegacyLastTick => (),
+                        }
+                    }
+                    if let Some(tail) = events.last() {
+                        // the body takes its samples from the end of the slider
+                        let body = Hit {
+                            sample_time: tail.time.0,
+                            ..hit(obj.start_time.0, None)
+                        };
+                        self.push_slide(&mut sounds, &timing, obj, &body, tail.time);
+                    }
+                }
+            }
+        }
+
+        sounds.sort_by_key(|sound| sound.time);
+        sounds
+    }
+
+    /// The timing point whose sample settings apply to hits at the given time.
+    pub(super) fn sample_point_at<'a>(
+        &self,
+        time: f64,
+        timing: &TimingIndex<'a>,
+    ) -> Option<&'a TimingPoint> {
+        timing
+            .point_at(time + CONTROL_POINT_LENIENCY)
+            .or_else(|| timing.points().first())
+    }
+
+    /// The sample set, custom index and volume that hits at the given time get from the timing
+    /// points, when they don't set their own.
+    pub(super) fn timing_samples_at(
+        &self,
+        time: f64,
+        timing: &TimingIndex,
+    ) -> (SampleSet, u32, u32) {
+        let point = self.sample_point_at(time, timing);
+        let mut sample_set = point.map(|tp| tp.sample_set).unwrap_or(SampleSet::None);
+        if sample_set == SampleSet::None {
+            sample_set = self.sample_set;
+        }
+        if sample_set == SampleSet::None {
+            sample_set = SampleSet::Normal;
+        }
+        let custom_index = point.map(|tp| tp.sample_index).unwrap_or(0);
+        let volume = point
+            .map(|tp| tp.volume as u32)
+            .unwrap_or(self.sample_volume);
+        (sample_set, custom_index, volume)
+    }
+
+    fn push_hit(&self, sounds: &mut Vec<HitSound>, timing: &TimingIndex, hit: &Hit) {
+        self.push_sound(sounds, timing, hit, HitSoundKind::Normal, None);
+        // a custom sample replaces all of the hit's sounds
+        if hit.sample_info.filename.is_some() {
+            return;
+        }
+
+        let additions = [
+            (Additions::WHISTLE, HitSoundKind::Whistle),
+            (Additions::FINISH, HitSoundKind::Finish),
+            (Additions::CLAP, HitSoundKind::Clap),
+        ];
+        for (addition, kind) in additions.iter() {
+            if hit.additions.contains(*addition) {
+                self.push_sound(sounds, timing, hit, *kind, None);
+            }
+        }
+    }
+
+    fn push_slide(
+        &self,
+        sounds: &mut Vec<HitSound>,
+        timing: &TimingIndex,
+        obj: &HitObject,
+        hit: &Hit,
+        end_time: Time,
+    ) {
+        self.push_sound(
+            sounds,
+            timing,
+            hit,
+            HitSoundKind::SliderSlide,
+            Some(end_time),
+        );
+        if obj.additions.contains(Additions::WHISTLE) {
+            self.push_sound(
+                sounds,
+                timing,
+                hit,
+                HitSoundKind::SliderWhistle,
+                Some(end_time),
+            );
+        }
+    }
+
+    fn push_sound(
+        &self,
+        sounds: &mut Vec<HitSound>,
+        timing: &TimingIndex,
+        hit: &Hit,
+        kind: HitSoundKind,
+        end_time: Option<Time>,
+    ) {
+        let (point_set, point_index, point_volume) =
+            self.timing_samples_at(hit.sample_time, timing);
+        let sample_set = match hit.sample_set {
+            SampleSet::None => point_set,
+            sample_set => sample_set,
+        };
+        let addition_set = match hit.addition_set {
+            SampleSet::None => sample_set,
+            addition_set => addition_set,
+        };
+        let custom_index = match hit.sample_info.custom_index {
+            index if index > 0 => index as u32,
+            _ => point_index,
+        };
+        let volume = match hit.sample_info.sample_volume {
+            volume if volume > 0 => volume as u32,
+            _ => point_volume,
+        };
+
+        let filename = match &hit.sample_info.filename {
+            Some(filename) if kind == HitSoundKind::Normal => filename.clone(),
+            _ => {
+                let set = if kind.is_addition() {
+                    addition_set
+                } else {
+                    sample_set
+                };
+                let index = if custom_index > 1 {
+                    custom_index.to_string()
+                } else {
+                    String::new()
+                };
+                format!("{}-{}{}.wav", set.name(), kind.name(), index)
+            }
+        };
+
+        sounds.push(HitSound {
+            time: Time(hit.time),
+            end_time,
+            object_index: hit.object_index,
+            kind,
+            sample_set,
+            addition_set,
+            custom_index,
+            volume,
+            filename,
+        });
+    }
+}
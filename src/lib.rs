@@ -58,9 +58,11 @@ pub mod hitobject;
 pub mod hitsounds;
 /// math
 pub mod math;
+pub mod osz;
 pub mod replay;
 /// calculating slider body shapes.
 pub mod spline;
+pub mod storyboard;
 /// timing and timing points.
 pub mod timing;
 
@@ -80,8 +82,10 @@ pub mod prelude {
     pub use crate::hitobject::*;
     pub use crate::hitsounds::*;
     pub use crate::math::*;
+    pub use crate::osz::*;
     pub use crate::replay::*;
     pub use crate::spline::*;
+    pub use crate::storyboard::*;
     pub use crate::timing::*;
     pub use ordered_float::*;
 }
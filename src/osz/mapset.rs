@@ -0,0 +1,334 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::beatmap::Beatmap;
+use crate::events::Event;
+use crate::storyboard::Storyboard;
+use crate::utils::normalize_path;
+
+use super::{BeatmapSet, OszError, OszResult};
+
+/// The hitsound samples a beatmap can replace with its own files.
+const SAMPLE_NAMES: &[&str] = &[
+    "hitnormal",
+    "hitwhistle",
+    "hitfinish",
+    "hitclap",
+    "slidertick",
+    "sliderslide",
+    "sliderwhistle",
+];
+
+/// The metadata and general settings that have to be the same in every difficulty of a set.
+#[allow(clippy::type_complexity)]
+const SHARED_FIELDS: &[(&str, fn(&Beatmap) -> String)] = &[
+    ("Artist", |b| b.artist.clone()),
+    ("ArtistUnicode", |b| b.artist_unicode.clone()),
+    ("Title", |b| b.title.clone()),
+    ("TitleUnicode", |b| b.title_unicode.clone()),
+    ("Creator", |b| b.creator.clone()),
+    ("Source", |b| b.source.clone()),
+    ("Tags", |b| b.tags.join(" ")),
+    ("BeatmapSetID", |b| b.beatmap_set_id.to_string()),
+    ("AudioFilename", |b| b.audio_filename.clone()),
+    ("PreviewTime", |b| b.preview_time.0.to_string()),
+];
+
+/// A beatmap set with all of its difficulties (and its storyboard) parsed.
+///
+/// This is what the rules that span difficulties are checked on, see [`Mapset::check`].
+#[derive(Clone, Debug)]
+pub struct Mapset {
+    /// All the files in the set, including the difficulties and storyboard
+    pub files: BeatmapSet,
+
+    /// The difficulties, alongside their paths in the set
+    pub difficulties: Vec<(String, Beatmap)>,
+
+    /// The .osb storyboard shared by the difficulties, if there is one
+    pub storyboard: Option<Storyboard>,
+}
+
+/// A problem found by [`Mapset::check`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MapsetDiagnostic {
+    /// The path of the difficulty the problem is in, if it's tied to one
+    pub difficulty: Option<String>,
+
+    /// What the problem is
+    pub kind: MapsetDiagnosticKind,
+}
+
+/// The kinds of problems [`Mapset::check`] looks for.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MapsetDiagnosticKind {
+    /// A field that should be the same in every difficulty is different from the first one
+    Inconsistent {
+        /// The name of the field, as it's written in the .osu file
+        field: String,
+
+        /// The value in the first difficulty
+        expected: String,
+
+        /// The value in this difficulty
+        found: String,
+    },
+
+    /// A romanized field has characters that aren't ASCII
+    NotRomanized {
+        /// The name of the field, as it's written in the .osu file
+        field: String,
+    },
+
+    /// A unicode field only has ASCII characters, but doesn't match its romanized field
+    UnicodeMismatch {
+        /// The name of the unicode field, as it's written in the .osu file
+        field: String,
+    },
+
+    /// A file that is referenced isn't in the set
+    MissingFile {
+        /// The path as written in the difficulty or storyboard
+        path: String,
+    },
+
+    /// A numbered hitsound sample (like `soft-hitclap2.wav`) that hit objects play isn't in the
+    /// set, so the skin's sample is played instead
+    MissingSample {
+        /// The name of the sample file
+        path: String,
+    },
+
+    /// A file in the set isn't used by any difficulty or the storyboard
+    UnusedFile {
+        /// The path of the file in the set
+        path: String,
+    },
+}
+
+impl fmt::Display for MapsetDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(difficulty) = &self.difficulty {
+            write!(f, "{}: ", difficulty)?;
+        }
+        match &self.kind {
+            MapsetDiagnosticKind::Inconsistent {
+                field,
+                expected,
+                found,
+            } => write!(f, "{} is {:?}, but {:?} elsewhere", field, found, expected),
+            MapsetDiagnosticKind::NotRomanized { field } => {
+                write!(f, "{} has non-ASCII characters", field)
+            }
+            MapsetDiagnosticKind::UnicodeMismatch { field } => {
+                write!(f, "{} doesn't match its romanized field", field)
+            }
+            MapsetDiagnosticKind::MissingFile { path } => write!(f, "missing file {}", path),
+            MapsetDiagnosticKind::MissingSample { path } => {
+                write!(f, "missing hitsound sample {}", path)
+            }
+            MapsetDiagnosticKind::UnusedFile { path } => write!(f, "unused file {}", path),
+        }
+    }
+}
+
+impl Mapset {
+    /// Parse all the difficulties and the storyboard in a beatmap set.
+    pub fn new(files: BeatmapSet) -> OszResult<Mapset> {
+        let parse_error = |path: &str| {
+            let path = path.to_owned();
+            move |inner| OszError::Parse { path, inner }
+        };
+
+        let difficulties = files
+            .beatmaps()
+            .map(|(path, beatmap)| Ok((path.to_owned(), beatmap.map_err(parse_error(path))?)))
+            .collect::<OszResult<Vec<_>>>()?;
+        let storyboard = match (files.storyboard_file(), files.storyboard()) {
+            (Some(path), Some(storyboard)) => Some(storyboard.map_err(parse_error(path))?),
+            _ => None,
+        };
+
+        Ok(Mapset {
+            files,
+            difficulties,
+            storyboard,
+        })
+    }
+
+    /// Checks the rules that span all the difficulties in the set.
+    ///
+    /// Metadata, the beatmap set ID, the audio file and the preview time have to be the same
+    /// everywhere, romanized fields have to be ASCII, and every referenced file has to exist. Files
+    /// that nothing refers to are reported as unused; custom hitsound samples count as used if
+    /// any timing point or object uses their sample index. Numbered samples that objects play but
+    /// the set lacks are reported too, since the skin's sample is played in their place.
+    pub fn check(&self) -> Vec<MapsetDiagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut diagnostic = |difficulty: Option<&str>, kind| {
+            diagnostics.push(MapsetDiagnostic {
+                difficulty: difficulty.map(String::from),
+                kind,
+            })
+        };
+
+        // metadata
+        if let Some((_, first)) = self.difficulties.first() {
+            for (path, beatmap) in self.difficulties.iter().skip(1) {
+                for (field, value) in SHARED_FIELDS.iter() {
+                    let (expected, found) = (value(first), value(beatmap));
+                    if expected != found {
+                        let field = String::from(*field);
+                        let kind = MapsetDiagnosticKind::Inconsistent {
+                            field,
+                            expected,
+                            found,
+                        };
+                        diagnostic(Some(path), kind);
+                    }
+                }
+            }
+        }
+        for (path, beatmap) in self.difficulties.iter() {
+            let pairs = [
+                (
+                    "Artist",
+                    &beatmap.artist,
+                    "ArtistUnicode",
+                    &beatmap.artist_unicode,
+                ),
+                (
+                    "Title",
+                    &beatmap.title,
+                    "TitleUnicode",
+                    &beatmap.title_unicode,
+                ),
+            ];
+            for (field, romanized, unicode_field, unicode) in pairs.iter() {
+                if !romanized.is_ascii() {
+                    let field = String::from(*field);
+                    diagnostic(Some(path), MapsetDiagnosticKind::NotRomanized { field });
+                } else if !unicode.is_empty() && unicode.is_ascii() && unicode != romanized {
+                    let field = String::from(*unicode_field);
+                    diagnostic(Some(path), MapsetDiagnosticKind::UnicodeMismatch { field });
+                }
+            }
+        }
+
+        // files
+        let mut used = HashSet::new();
+        let mut sample_indices = HashSet::new();
+        for (path, beatmap) in self.difficulties.iter() {
+            used.insert(normalize_path(path));
+            sample_indices.extend(beatmap.timing_points.iter().map(|tp| tp.sample_index));
+            for obj in beatmap.hit_objects.iter() {
+                if obj.sample_info.custom_index > 0 {
+                    sample_indices.insert(obj.sample_info.custom_index as u32);
+                }
+            }
+
+            let mut referenced = vec![beatmap.audio_filename.clone()];
+            referenced.extend(
+                beatmap
+                    .hit_objects
+                    .iter()
+                    .filter_map(|obj| obj.sample_info.filename.clone())
+                    .filter(|name| !name.is_empty()),
+            );
+            referenced.extend(beatmap.events.iter().flat_map(event_files));
+            for file in referenced.iter() {
+                if !self.files.contains(file) {
+                    let kind = MapsetDiagnosticKind::MissingFile { path: file.clone() };
+                    diagnostic(Some(path), kind);
+                } else {
+                    used.insert(normalize_path(file));
+                }
+            }
+
+            // custom filenames were checked above, and the game looks for numbered samples with
+            // any of the audio extensions
+            let mut missing_samples = Vec::new();
+            for sound in beatmap.hitsounds() {
+                if sound.custom_index <= 1
+                    || sample_index(&sound.filename).is_none()
+                    || referenced.contains(&sound.filename)
+                    || missing_samples.contains(&sound.filename)
+                {
+                    continue;
+                }
+                let stem = sound.filename.trim_end_matches(".wav");
+                let found = ["wav", "ogg", "mp3"]
+                    .iter()
+                    .any(|ext| self.files.contains(&format!("{}.{}", stem, ext)));
+                if !found {
+                    missing_samples.push(sound.filename);
+                }
+            }
+            for file in missing_samples {
+                diagnostic(
+                    Some(path),
+                    MapsetDiagnosticKind::MissingSample { path: file },
+                );
+            }
+        }
+        if let (Some(path), Some(storyboard)) = (self.files.storyboard_file(), &self.storyboard) {
+            used.insert(normalize_path(path));
+            for file in storyboard.events.iter().flat_map(event_files) {
+                if !self.files.contains(&file) {
+                    diagnostic(None, MapsetDiagnosticKind::MissingFile { path: file });
+                } else {
+                    used.insert(normalize_path(&file));
+                }
+            }
+        }
+
+        for path in self.files.file_names() {
+            let is_used = used.contains(&normalize_path(path))
+                || matches!(sample_index(path), Some(index) if sample_indices.contains(&index));
+            if !is_used {
+                let path = String::from(path);
+                diagnostic(None, MapsetDiagnosticKind::UnusedFile { path });
+            }
+        }
+
+        diagnostics
+    }
+}
+
+/// The files an event refers to.
+fn event_files(evt: &Event) -> Vec<String> {
+    match evt {
+        Event::Background(bg) => vec![bg.filename.clone()],
+        Event::Video(video) => vec![video.filename.clone()],
+        Event::Sprite(sprite) => vec![sprite.filepath.clone()],
+        Event::Animation(anim) => (0..anim.frame_count.max(1))
+            .map(|i| anim.frame_path(i))
+            .collect(),
+        Event::Sample(sample) => vec![sample.filepath.clone()],
+        _ => Vec::new(),
+    }
+}
+
+/// The sample index a file would be used for, if it's named like a custom hitsound sample
+/// (`soft-hitclap2.wav`, with no number meaning index 1).
+fn sample_index(path: &str) -> Option<u32> {
+    let path = normalize_path(path);
+    let (stem, ext) = path.rsplit_once('.')?;
+    if !matches!(ext, "wav" | "ogg" | "mp3") {
+        return None;
+    }
+
+    let (set, rest) = stem.split_once('-')?;
+    if !matches!(set, "normal" | "soft" | "drum") {
+        return None;
+    }
+    let index = SAMPLE_NAMES
+        .iter()
+        .find_map(|name| rest.strip_prefix(name))?;
+    match index {
+        "" => Some(1),
+        _ => index.parse().ok(),
+    }
+}
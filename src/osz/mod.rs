@@ -0,0 +1,272 @@
+//! Beatmap sets, and the .osz archives they're distributed in
+//!
+//! A [`BeatmapSet`] holds every file of a mapset in memory: the .osu difficulties, the .osb
+//! storyboard, and the audio, images and videos they refer to. With the `osz` feature enabled,
+//! sets can be read from and written to .osz files, which are plain zip archives.
+//!
+//! ```no_run
+//! # use std::fs::File;
+//! # use libosu::prelude::*;
+//! # #[cfg(feature = "osz")]
+//! # fn invisible() -> anyhow::Result<()> {
+//! #
+//! let set = BeatmapSet::read_osz(File::open("set.osz")?)?;
+//! for (name, beatmap) in set.beatmaps() {
+//!     let beatmap = beatmap?;
+//!     println!("{}: {} objects", name, beatmap.hit_objects.len());
+//!     let audio = set.audio(&beatmap);
+//! }
+//!
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! To check the rules that span all the difficulties of a set, parse it into a [`Mapset`].
+
+mod mapset;
+
+use std::fs;
+use std::io::{self, Cursor};
+#[cfg(feature = "osz")]
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+
+use crate::beatmap::{Beatmap, BeatmapParseError};
+use crate::events::Event;
+use crate::storyboard::{read_image_size, Assets, Storyboard};
+use crate::utils::normalize_path;
+
+pub use self::mapset::*;
+
+/// The most memory (in bytes) reserved up front for a file read out of an archive. The size in the
+/// zip header can be anything, so bigger files grow as they're read instead.
+#[cfg(feature = "osz")]
+const MAX_PREALLOCATION: usize = 1 << 20;
+
+/// Result type for .osz file processing
+pub type OszResult<T, E = OszError> = std::result::Result<T, E>;
+
+/// Errors that could occur while processing .osz files
+#[allow(missing_docs)]
+#[derive(Debug, Error)]
+pub enum OszError {
+    /// IO Error
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+
+    /// The archive isn't a valid zip file
+    #[cfg(feature = "osz")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "osz")))]
+    #[error("zip error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    /// A difficulty or storyboard in the set couldn't be parsed
+    #[error("error parsing {path}: {inner}")]
+    Parse {
+        path: String,
+        #[source]
+        inner: BeatmapParseError,
+    },
+}
+
+/// All the files of a beatmap set, kept in memory.
+///
+/// Paths are compared the way the game compares them: case-insensitively, and with either kind
+/// of slash.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BeatmapSet {
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl BeatmapSet {
+    /// Create an empty beatmap set
+    pub fn new() -> Self {
+        BeatmapSet::default()
+    }
+
+    /// Read a beatmap set out of a .osz archive.
+    #[cfg(feature = "osz")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "osz")))]
+    pub fn read_osz(reader: impl Read + Seek) -> OszResult<BeatmapSet> {
+        let mut archive = zip::ZipArchive::new(reader)?;
+        let mut set = BeatmapSet::new();
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            if file.is_dir() {
+                continue;
+            }
+
+            let mut data = Vec::with_capacity((file.size() as usize).min(MAX_PREALLOCATION));
+            file.read_to_end(&mut data)?;
+            set.insert_file(file.name(), data);
+        }
+        Ok(set)
+    }
+
+    /// Read a beatmap set out of a beatmap folder on disk, including the files in its
+    /// subfolders.
+    pub fn read_dir(path: impl AsRef<Path>) -> OszResult<BeatmapSet> {
+        fn visit(set: &mut BeatmapSet, dir: &Path, prefix: &str) -> OszResult<()> {
+            let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+            entries.sort_by_key(|entry| entry.file_name());
+            for entry in entries {
+                let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+                if entry.file_type()?.is_dir() {
+                    visit(set, &entry.path(), &format!("{}/", name))?;
+                } else {
+                    set.insert_file(name, fs::read(entry.path())?);
+                }
+            }
+            Ok(())
+        }
+
+        let mut set = BeatmapSet::new();
+        visit(&mut set, path.as_ref(), "")?;
+        Ok(set)
+    }
+
+    /// Write the beatmap set out as a .osz archive.
+    #[cfg(feature = "osz")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "osz")))]
+    pub fn write_osz(&self, writer: impl Write + Seek) -> OszResult<()> {
+        let mut archive = zip::ZipWriter::new(writer);
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for (name, data) in self.files.iter() {
+            archive.start_file(name.as_str(), options)?;
+            archive.write_all(data)?;
+        }
+        archive.finish()?;
+        Ok(())
+    }
+
+    /// The paths of all the files in the set, in the order they were added.
+    pub fn file_names(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Whether the set contains a file at the given path.
+    pub fn contains(&self, path: &str) -> bool {
+        self.position(path).is_some()
+    }
+
+    /// The contents of the file at the given path.
+    pub fn file(&self, path: &str) -> Option<&[u8]> {
+        self.position(path).map(|i| self.files[i].1.as_slice())
+    }
+
+    /// Open the file at the given path for reading.
+    pub fn open(&self, path: &str) -> Option<Cursor<&[u8]>> {
+        self.file(path).map(Cursor::new)
+    }
+
+    /// Add a file to the set, returning the contents of the file it replaced (if any).
+    pub fn insert_file(&mut self, path: impl Into<String>, data: Vec<u8>) -> Option<Vec<u8>> {
+        let path = path.into();
+        match self.position(&path) {
+            Some(i) => Some(std::mem::replace(&mut self.files[i], (path, data)).1),
+            None => {
+                self.files.push((path, data));
+                None
+            }
+        }
+    }
+
+    /// Remove a file from the set, returning its contents.
+    pub fn remove_file(&mut self, path: &str) -> Option<Vec<u8>> {
+        self.position(path).map(|i| self.files.remove(i).1)
+    }
+
+    /// The paths of the .osu difficulties in the set.
+    pub fn difficulties(&self) -> impl Iterator<Item = &str> {
+        self.file_names().filter(|name| has_extension(name, "osu"))
+    }
+
+    /// Parse the difficulty at the given path. Returns `None` if there's no such file.
+    pub fn beatmap(&self, path: &str) -> Option<Result<Beatmap, BeatmapParseError>> {
+        self.open(path).map(Beatmap::parse)
+    }
+
+    /// Parse all the difficulties in the set, alongside their paths.
+    pub fn beatmaps(
+        &self,
+    ) -> impl Iterator<Item = (&str, Result<Beatmap, BeatmapParseError>)> + '_ {
+        self.files
+            .iter()
+            .filter(|(name, _)| has_extension(name, "osu"))
+            .map(|(name, data)| (name.as_str(), Beatmap::parse(data.as_slice())))
+    }
+
+    /// Add a difficulty to the set under the name the game would give it (see
+    /// [`BeatmapSet::difficulty_file_name`]), returning that name.
+    pub fn insert_beatmap(&mut self, beatmap: &Beatmap) -> String {
+        let name = BeatmapSet::difficulty_file_name(beatmap);
+        self.insert_file(name.clone(), beatmap.to_string().into_bytes());
+        name
+    }
+
+    /// The file name the game gives a difficulty: `Artist - Title (Creator) [Version].osu`,
+    /// without any characters that aren't allowed in file names.
+    pub fn difficulty_file_name(beatmap: &Beatmap) -> String {
+        format!(
+            "{} - {} ({}) [{}].osu",
+            beatmap.artist, beatmap.title, beatmap.creator, beatmap.difficulty_name
+        )
+        .chars()
+        .filter(|c| !matches!(c, '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|'))
+        .collect()
+    }
+
+    /// The path of the .osb storyboard shared by the difficulties, if there is one.
+    pub fn storyboard_file(&self) -> Option<&str> {
+        self.file_names().find(|name| has_extension(name, "osb"))
+    }
+
+    /// Parse the .osb storyboard shared by the difficulties, if there is one.
+    pub fn storyboard(&self) -> Option<Result<Storyboard, BeatmapParseError>> {
+        let name = self.storyboard_file()?;
+        self.open(name).map(Storyboard::parse)
+    }
+
+    /// Open the audio file of a difficulty.
+    pub fn audio(&self, beatmap: &Beatmap) -> Option<Cursor<&[u8]>> {
+        self.open(&beatmap.audio_filename)
+    }
+
+    /// Open the background image of a difficulty.
+    pub fn background(&self, beatmap: &Beatmap) -> Option<Cursor<&[u8]>> {
+        beatmap.events.iter().find_map(|evt| match evt {
+            Event::Background(bg) => self.open(&bg.filename),
+            _ => None,
+        })
+    }
+
+    /// Open the video of a difficulty.
+    pub fn video(&self, beatmap: &Beatmap) -> Option<Cursor<&[u8]>> {
+        beatmap.events.iter().find_map(|evt| match evt {
+            Event::Video(video) => self.open(&video.filename),
+            _ => None,
+        })
+    }
+
+    fn position(&self, path: &str) -> Option<usize> {
+        let path = normalize_path(path);
+        self.files
+            .iter()
+            .position(|(name, _)| normalize_path(name) == path)
+    }
+}
+
+impl Assets for BeatmapSet {
+    fn exists(&self, path: &str) -> bool {
+        self.contains(path)
+    }
+
+    fn image_size(&self, path: &str) -> Option<(u32, u32)> {
+        self.file(path).and_then(read_image_size)
+    }
+}
+
+fn has_extension(name: &str, extension: &str) -> bool {
+    matches!(name.rsplit_once('.'), Some((_, ext)) if ext.eq_ignore_ascii_case(extension))
+}
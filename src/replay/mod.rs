@@ -60,6 +60,7 @@
 //! [1]: https://osu.ppy.sh/wiki/en/osu%21_File_Formats/Osr_%28file_format%29
 
 mod actions;
+mod verify;
 
 use std::io::{Read, Write};
 
@@ -69,6 +70,7 @@ use crate::data::{Mode, Mods};
 use crate::db::{ReadBytesOsu, WriteBytesOsu};
 
 pub use self::actions::{Buttons, ReplayAction, ReplayActionData};
+pub use self::verify::*;
 
 /// Result type for Replay processing
 pub type ReplayResult<T, E = ReplayError> = std::result::Result<T, E>;
@@ -302,7 +304,7 @@ impl Replay {
         w.write_u16::<LittleEndian>(self.count_miss)?;
         w.write_u32::<LittleEndian>(self.score)?;
         w.write_u16::<LittleEndian>(self.max_combo)?;
-        w.write_u8(if self.perfect { 0 } else { 1 })?;
+        w.write_u8(if self.perfect { 1 } else { 0 })?;
         w.write_u32::<LittleEndian>(self.mods.bits())?;
         w.write_uleb128_string(
             &self
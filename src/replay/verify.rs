@@ -0,0 +1,262 @@
+use std::fmt;
+
+use crate::beatmap::Beatmap;
+use crate::data::{Grade, Mode, Mods};
+
+use super::Replay;
+
+/// The first osu! version that stores the RNG seed at the end of the action data.
+#[cfg(feature = "replay-data")]
+const FIRST_SEEDED_VERSION: u32 = 20130319;
+
+/// A problem found by [`Replay::verify`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ReplayIssue {
+    /// The replay hash doesn't match the one computed from the header
+    ReplayHashMismatch {
+        /// The hash computed from the header
+        expected: String,
+
+        /// The hash stored in the replay
+        found: String,
+    },
+
+    /// The beatmap hash isn't an MD5 hash
+    InvalidBeatmapHash(String),
+
+    /// The beatmap hash doesn't match the checksum of the given beatmap
+    BeatmapHashMismatch {
+        /// The checksum of the beatmap
+        expected: String,
+
+        /// The hash stored in the replay
+        found: String,
+    },
+
+    /// The replay has no action data at all
+    MissingActionData,
+
+    /// The action data couldn't be decompressed or parsed
+    InvalidActionData(String),
+
+    /// The action data has no RNG seed, even though the game version stores one
+    MissingRngSeed,
+
+    /// The action data ends before the last point on the life graph
+    ActionDataTooShort {
+        /// When the last action happens
+        duration: i32,
+
+        /// When the last point on the life graph is
+        life_graph_end: i32,
+    },
+}
+
+impl fmt::Display for ReplayIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayIssue::ReplayHashMismatch { expected, found } => {
+                write!(f, "replay hash is {}, expected {}", found, expected)
+            }
+            ReplayIssue::InvalidBeatmapHash(hash) => write!(f, "invalid beatmap hash: {:?}", hash),
+            ReplayIssue::BeatmapHashMismatch { expected, found } => {
+                write!(f, "beatmap hash is {}, expected {}", found, expected)
+            }
+            ReplayIssue::MissingActionData => write!(f, "missing action data"),
+            ReplayIssue::InvalidActionData(err) => write!(f, "invalid action data: {}", err),
+            ReplayIssue::MissingRngSeed => write!(f, "action data has no rng seed"),
+            ReplayIssue::ActionDataTooShort {
+                duration,
+                life_graph_end,
+            } => write!(
+                f,
+                "action data ends at {}ms, but the life graph goes on until {}ms",
+                duration, life_graph_end
+            ),
+        }
+    }
+}
+
+/// The result of [`Replay::verify`].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ReplayReport {
+    /// Everything that's inconsistent, in the order it was checked: the replay hash, the beatmap
+    /// hash, then the action data
+    pub issues: Vec<ReplayIssue>,
+}
+
+impl ReplayReport {
+    /// Whether no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl Replay {
+    /// The grade the game gives this play, worked out from the hit counts and mods.
+    pub fn grade(&self) -> Grade {
+        let (c300, c100, c50) = (
+            self.count_300 as f64,
+            self.count_100 as f64,
+            self.count_50 as f64,
+        );
+        let (geki, katu, miss) = (
+            self.count_geki as f64,
+            self.count_katu as f64,
+            self.count_miss as f64,
+        );
+
+        // osu! and taiko grade on the ratio of 300s, catch and mania on accuracy
+        let ratio_grade = |total: f64| {
+            if total == 0.0 {
+                return Grade::None;
+            }
+            let (r300, r50) = (c300 / total, c50 / total);
+            if r300 == 1.0 {
+                Grade::SS
+            } else if r300 > 0.9 && r50 <= 0.01 && miss == 0.0 {
+                Grade::S
+            } else if (r300 > 0.8 && miss == 0.0) || r300 > 0.9 {
+                Grade::A
+            } else if (r300 > 0.7 && miss == 0.0) || r300 > 0.8 {
+                Grade::B
+            } else if r300 > 0.6 {
+                Grade::C
+            } else {
+                Grade::D
+            }
+        };
+        let accuracy_grade = |total: f64, hits: f64, thresholds: [f64; 4]| {
+            if total == 0.0 {
+                return Grade::None;
+            }
+            let acc = hits / total;
+            match thresholds.iter().position(|&threshold| acc > threshold) {
+                _ if acc == 1.0 => Grade::SS,
+                Some(0) => Grade::S,
+                Some(1) => Grade::A,
+                Some(2) => Grade::B,
+                Some(3) => Grade::C,
+                _ => Grade::D,
+            }
+        };
+
+        let grade = match self.mode {
+            Mode::Osu => ratio_grade(c300 + c100 + c50 + miss),
+            Mode::Taiko => ratio_grade(c300 + c100 + miss),
+            Mode::Catch => accuracy_grade(
+                c300 + c100 + c50 + katu + miss,
+                c300 + c100 + c50,
+                [0.98, 0.94, 0.9, 0.85],
+            ),
+            Mode::Mania => accuracy_grade(
+                300.0 * (geki + c300 + katu + c100 + c50 + miss),
+                300.0 * (geki + c300) + 200.0 * katu + 100.0 * c100 + 50.0 * c50,
+                [0.95, 0.9, 0.8, 0.7],
+            ),
+        };
+
+        let hidden = self
+            .mods
+            .intersects(Mods::Hidden | Mods::Flashlight | Mods::FadeIn);
+        match grade {
+            Grade::SS if hidden => Grade::SSH,
+            Grade::S if hidden => Grade::SH,
+            grade => grade,
+        }
+    }
+
+    /// Computes the replay hash the way the game does when it saves a replay, from the combo,
+    /// player name, beatmap hash, score and grade in the header.
+    pub fn compute_replay_hash(&self) -> String {
+        let grade = match self.grade() {
+            Grade::SSH => "XH",
+            Grade::SH => "SH",
+            Grade::SS => "X",
+            Grade::S => "S",
+            Grade::A => "A",
+            Grade::B => "B",
+            Grade::C => "C",
+            Grade::D => "D",
+            Grade::F => "F",
+            Grade::None => "N",
+        };
+        let input = format!(
+            "{}osu{}{}{}{}",
+            self.max_combo, self.player_username, self.beatmap_hash, self.score, grade,
+        );
+        format!("{:x}", md5::compute(input))
+    }
+
+    /// Replaces the replay hash with one computed from the header (see
+    /// [`Replay::compute_replay_hash`]). Call this after changing the header or the action data.
+    pub fn update_replay_hash(&mut self) {
+        self.replay_hash = self.compute_replay_hash();
+    }
+
+    /// Checks that the replay is consistent: that the replay hash matches the header, that the
+    /// beatmap hash matches the .osu file the replay is for (if given), and that the action data
+    /// is present and covers the whole play.
+    ///
+    /// The action data is only checked in detail with the `replay-data` feature enabled.
+    pub fn verify(&self, beatmap: Option<&[u8]>) -> ReplayReport {
+        let mut issues = Vec::new();
+
+        let expected = self.compute_replay_hash();
+        if !expected.eq_ignore_ascii_case(&self.replay_hash) {
+            issues.push(ReplayIssue::ReplayHashMismatch {
+                expected,
+                found: self.replay_hash.clone(),
+            });
+        }
+
+        let hash = &self.beatmap_hash;
+        if hash.len() != 32 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            issues.push(ReplayIssue::InvalidBeatmapHash(hash.clone()));
+        } else if let Some(beatmap) = beatmap {
+            let expected = Beatmap::checksum_of(beatmap);
+            if !expected.eq_ignore_ascii_case(hash) {
+                issues.push(ReplayIssue::BeatmapHashMismatch {
+                    expected,
+                    found: hash.clone(),
+                });
+            }
+        }
+
+        if self.action_data.is_empty() {
+            issues.push(ReplayIssue::MissingActionData);
+        } else {
+            #[cfg(feature = "replay-data")]
+            self.verify_action_data(&mut issues);
+        }
+
+        ReplayReport { issues }
+    }
+
+    #[cfg(feature = "replay-data")]
+    fn verify_action_data(&self, issues: &mut Vec<ReplayIssue>) {
+        let action_data = match self.parse_action_data() {
+            Ok(action_data) => action_data,
+            Err(err) => {
+                issues.push(ReplayIssue::InvalidActionData(err.to_string()));
+                return;
+            }
+        };
+
+        if action_data.rng_seed.is_none() && self.version >= FIRST_SEEDED_VERSION {
+            issues.push(ReplayIssue::MissingRngSeed);
+        }
+
+        let duration = action_data.frames.iter().map(|frame| frame.time.0).sum();
+        if let Some(&(life_graph_end, _)) = self.life_graph.last() {
+            if duration < life_graph_end {
+                issues.push(ReplayIssue::ActionDataTooShort {
+                    duration,
+                    life_graph_end,
+                });
+            }
+        }
+    }
+}
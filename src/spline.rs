@@ -1,3 +1,4 @@
+use num::NumCast;
 use ordered_float::NotNan;
 
 use crate::hitobject::SliderSplineKind;
@@ -19,32 +20,27 @@ impl Spline {
     ///
     /// Pixel length gives the length in osu!pixels that the slider should be. If it's not given,
     /// the full slider will be rendered.
-    pub fn from_control(
+    ///
+    /// The control points can be either whole or fractional.
+    pub fn from_control<T: Copy + NumCast>(
         kind: SliderSplineKind,
-        control_points: &[Point<i32>],
+        control_points: &[Point<T>],
         pixel_length: Option<f64>,
     ) -> Self {
         // no matter what, if there's 2 control points, it's linear
         let mut kind = kind;
-        let mut control_points = control_points.to_vec();
-        if control_points.len() == 2 {
+        let mut points = control_points
+            .iter()
+            .map(|p| p.to_float::<f64>().expect("numbers convert to f64"))
+            .collect::<Vec<_>>();
+        if points.len() == 2 {
             kind = SliderSplineKind::Linear;
         }
-        if control_points.len() == 3
-            && Math::is_line(
-                control_points[0].to_float::<f64>().unwrap(),
-                control_points[1].to_float::<f64>().unwrap(),
-                control_points[2].to_float::<f64>().unwrap(),
-            )
-        {
+        if points.len() == 3 && Math::is_line(points[0], points[1], points[2]) {
             kind = SliderSplineKind::Linear;
-            control_points.remove(1);
+            points.remove(1);
         }
 
-        let points = control_points
-            .iter()
-            .map(|p| Point::new(p.x as f64, p.y as f64))
-            .collect::<Vec<_>>();
         let spline_points = match kind {
             SliderSplineKind::Linear => {
                 let start = points[0];
@@ -105,8 +101,8 @@ impl Spline {
                     if multipart_segment || i == points.len() - 1 {
                         let sub = &points[last_index..i + 1];
                         if sub.len() == 2 {
-                            output.push(points[0]);
-                            output.push(points[1]);
+                            output.push(sub[0]);
+                            output.push(sub[1]);
                         } else {
                             create_singlebezier(&mut output, sub);
                         }
@@ -119,7 +115,7 @@ impl Spline {
                 }
                 output
             }
-            _ => todo!(),
+            SliderSplineKind::Catmull => create_catmull(&points),
         };
 
         let mut cumulative_lengths = Vec::with_capacity(spline_points.len());
@@ -185,14 +181,28 @@ impl Spline {
         self.spline_points.last().cloned().unwrap()
     }
 
-    /// Calculate the angle at the given length on the slider
-    fn angle_at_length(&self, length: f64) -> P {
-        let _length_notnan = NotNan::new(length).unwrap();
-        // match self.cumulative_lengths.binary_search(&length_notnan) {
-        //     Ok(_) => {}
-        //     Err(_) => {}
-        // }
-        todo!()
+    /// Calculate the direction (in radians, like [`f64::atan2`]) the slider ball is moving in
+    /// after it has traveled a distance of `length` into the slider.
+    pub fn angle_at_length(&self, length: f64) -> f64 {
+        let n = self.spline_points.len();
+        if n < 2 {
+            return 0.0;
+        }
+
+        // the segment the length falls on, skipping over any that have no length
+        let idx = self
+            .cumulative_lengths
+            .iter()
+            .position(|cumul_length| cumul_length.into_inner() >= length)
+            .unwrap_or(n - 1)
+            .max(1);
+        let segment = |i: usize| self.spline_points[i] - self.spline_points[i - 1];
+        let direction = (idx..n)
+            .chain((1..idx).rev())
+            .map(segment)
+            .find(|d| d.x != 0.0 || d.y != 0.0)
+            .unwrap_or_else(|| segment(idx));
+        direction.y.atan2(direction.x)
     }
 
     /// Calculate the point at which the slider ball would be after it has traveled a distance of
@@ -203,7 +213,7 @@ impl Spline {
             Ok(idx) => self.spline_points[idx],
             Err(idx) => {
                 let n = self.spline_points.len();
-                if idx == 0 && self.spline_points.len() > 2 {
+                if idx == 0 {
                     return self.spline_points[0];
                 } else if idx == n {
                     return self.spline_points[n - 1];
@@ -274,6 +284,53 @@ fn is_flat_enough(control_points: &[P], tolerance_sq: f64) -> bool {
     true
 }
 
+/// Approximates a Catmull-Rom spline through the points, the same way the game does (with a
+/// fixed number of segments between each pair of points).
+fn create_catmull(points: &[P]) -> Vec<P> {
+    const DETAIL: usize = 50;
+
+    let count = points.len();
+    let mut output = Vec::with_capacity(count.saturating_sub(1) * DETAIL * 2);
+    for i in 0..count.saturating_sub(1) {
+        let v1 = if i > 0 { points[i - 1] } else { points[i] };
+        let v2 = points[i];
+        let v3 = if i < count - 1 {
+            points[i + 1]
+        } else {
+            v2 + v2 - v1
+        };
+        let v4 = if i < count - 2 {
+            points[i + 2]
+        } else {
+            v3 + v3 - v2
+        };
+
+        for c in 0..DETAIL {
+            output.push(catmull_point(v1, v2, v3, v4, c as f64 / DETAIL as f64));
+            output.push(catmull_point(
+                v1,
+                v2,
+                v3,
+                v4,
+                (c + 1) as f64 / DETAIL as f64,
+            ));
+        }
+    }
+    output
+}
+
+fn catmull_point(v1: P, v2: P, v3: P, v4: P, t: f64) -> P {
+    let t2 = t * t;
+    let t3 = t * t2;
+    let axis = |a: f64, b: f64, c: f64, d: f64| {
+        0.5 * (2.0 * b
+            + (-a + c) * t
+            + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2
+            + (-a + 3.0 * b - 3.0 * c + d) * t3)
+    };
+    P::new(axis(v1.x, v2.x, v3.x, v4.x), axis(v1.y, v2.y, v3.y, v4.y))
+}
+
 fn create_singlebezier(output: &mut Vec<P>, control_points: &[P]) {
     let count = control_points.len();
     const TOLERANCE: f64 = 0.25;
@@ -0,0 +1,473 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use byteorder::{BigEndian, ByteOrder};
+
+use crate::beatmap::Beatmap;
+use crate::events::Event;
+use crate::math::Point;
+use crate::timing::Millis;
+use crate::utils::normalize_path;
+
+use super::{SpriteState, SpriteTimeline, Storyboard};
+
+/// The area of the 4:3 playfield in osu!pixels, which is what SB load is measured against.
+const SCREEN_AREA: f64 = 640.0 * 480.0;
+
+/// How far the storyboard extends past the 4:3 area on either side in widescreen (16:9).
+const WIDESCREEN_MARGIN: f64 = (480.0 * 16.0 / 9.0 - 640.0) / 2.0;
+
+/// The most times the SB load is sampled at. Storyboards that are around for longer than this
+/// many steps are sampled less often.
+const MAX_LOAD_SAMPLES: f64 = 100_000.0;
+
+/// Somewhere to look up the files a storyboard refers to.
+pub trait Assets {
+    /// Whether the file at the given path (relative to the beatmap directory) exists.
+    fn exists(&self, path: &str) -> bool;
+
+    /// The size of the image at the given path in pixels, if it exists and can be read.
+    fn image_size(&self, path: &str) -> Option<(u32, u32)>;
+}
+
+/// Looks up storyboard files in a beatmap directory on disk.
+///
+/// Image sizes are read from PNG and JPEG headers.
+#[derive(Clone, Debug)]
+pub struct DirectoryAssets(pub PathBuf);
+
+impl DirectoryAssets {
+    fn resolve(&self, path: &str) -> PathBuf {
+        let path = path.trim_matches('"').replace('\\', "/");
+        self.0.join(Path::new(&path))
+    }
+}
+
+impl Assets for DirectoryAssets {
+    fn exists(&self, path: &str) -> bool {
+        self.resolve(path).is_file()
+    }
+
+    fn image_size(&self, path: &str) -> Option<(u32, u32)> {
+        let mut data = Vec::new();
+        File::open(self.resolve(path))
+            .ok()?
+            .read_to_end(&mut data)
+            .ok()?;
+        read_image_size(&data)
+    }
+}
+
+/// Reads the width and height out of a PNG or JPEG image.
+pub fn read_image_size(data: &[u8]) -> Option<(u32, u32)> {
+    const PNG_MAGIC: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    if data.starts_with(PNG_MAGIC) {
+        let header = data.get(16..24)?;
+        return Some((
+            BigEndian::read_u32(&header[0..4]),
+            BigEndian::read_u32(&header[4..8]),
+        ));
+    }
+
+    if data.starts_with(&[0xff, 0xd8]) {
+        let mut idx = 2;
+        while idx + 4 <= data.len() {
+            if data[idx] != 0xff {
+                return None;
+            }
+            let marker = data[idx + 1];
+            if marker == 0xff {
+                idx += 1;
+                continue;
+            }
+
+            let len = BigEndian::read_u16(&data[idx + 2..idx + 4]) as usize;
+            let is_frame = (0xc0..=0xcf).contains(&marker) && ![0xc4, 0xc8, 0xcc].contains(&marker);
+            if is_frame {
+                let frame = data.get(idx + 5..idx + 9)?;
+                let height = BigEndian::read_u16(&frame[0..2]) as u32;
+                let width = BigEndian::read_u16(&frame[2..4]) as u32;
+                return Some((width, height));
+            }
+            idx += 2 + len;
+        }
+    }
+
+    None
+}
+
+/// Settings for [`Storyboard::analyze`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AnalysisOptions {
+    /// How often to sample the SB load. Storyboards that last very long are sampled less often,
+    /// so that the load isn't sampled more than a hundred thousand times.
+    pub step: Millis,
+
+    /// SB load above which a [`StoryboardDiagnosticKind::HighLoad`] is reported
+    pub load_threshold: f64,
+
+    /// Whether the storyboard is shown in widescreen, which makes more of it visible
+    pub widescreen: bool,
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        AnalysisOptions {
+            step: Millis(100),
+            load_threshold: 5.0,
+            widescreen: false,
+        }
+    }
+}
+
+impl AnalysisOptions {
+    /// The default options, using the widescreen setting of the given beatmap.
+    pub fn for_beatmap(beatmap: &Beatmap) -> Self {
+        AnalysisOptions {
+            widescreen: beatmap.widescreen_storyboard,
+            ..AnalysisOptions::default()
+        }
+    }
+}
+
+/// A problem found while analyzing a storyboard.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StoryboardDiagnostic {
+    /// When the problem happens, if it's tied to a point in time
+    pub time: Option<Millis>,
+
+    /// The index of the event (in [`Storyboard::events`]) this is about, if any
+    pub event_index: Option<usize>,
+
+    /// What the problem is
+    pub kind: StoryboardDiagnosticKind,
+}
+
+/// The kinds of problems [`Storyboard::analyze`] looks for.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StoryboardDiagnosticKind {
+    /// The SB load stays above the threshold from `start` until `end`
+    HighLoad {
+        /// The first sample above the threshold
+        start: Millis,
+
+        /// The last sample above the threshold
+        end: Millis,
+
+        /// The highest load in this range
+        peak: f64,
+    },
+
+    /// The sprite or animation is never visible, because it's transparent, scaled down to
+    /// nothing or off screen for its whole lifetime
+    NeverVisible,
+
+    /// The command (at the given index of the sprite's commands) has no visible effect
+    UselessCommand {
+        /// Index into the sprite's top-level commands
+        command_index: usize,
+    },
+
+    /// A file that is referenced doesn't exist
+    MissingFile {
+        /// The path as written in the storyboard
+        path: String,
+    },
+}
+
+/// The result of [`Storyboard::analyze`].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Analysis {
+    /// The SB load at every sampled time. Only the times when some sprite or animation is around
+    /// are sampled, along with the end of each of those stretches of time.
+    ///
+    /// A load of 1.0 means the equivalent of one fully opaque image covering the whole 4:3
+    /// screen is being drawn.
+    pub load: Vec<(Millis, f64)>,
+
+    /// Problems found in the storyboard: SB load problems first, then problems with each
+    /// sprite and its commands, then missing files
+    pub diagnostics: Vec<StoryboardDiagnostic>,
+}
+
+impl Analysis {
+    /// The highest SB load reached, and the first time it's reached.
+    pub fn peak_load(&self) -> Option<(Millis, f64)> {
+        self.load
+            .iter()
+            .copied()
+            .fold(None, |peak, (time, load)| match peak {
+                Some((_, peak_load)) if peak_load >= load => peak,
+                _ => Some((time, load)),
+            })
+    }
+}
+
+/// The screen area covered by a sprite, taking rotation and the edges of the screen into
+/// account.
+fn visible_area(
+    state: &SpriteState,
+    origin: Point<f64>,
+    size: (u32, u32),
+    widescreen: bool,
+) -> f64 {
+    let width = size.0 as f64 * state.scale.x.abs();
+    let height = size.1 as f64 * state.scale.y.abs();
+    let area = width * height;
+    if area == 0.0 {
+        return 0.0;
+    }
+
+    let (sin, cos) = state.rotation.sin_cos();
+    let corners = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)]
+        .iter()
+        .map(|(u, v)| {
+            let x = (u - origin.x) * width;
+            let y = (v - origin.y) * height;
+            (
+                state.pos.x + x * cos - y * sin,
+                state.pos.y + x * sin + y * cos,
+            )
+        });
+
+    let (mut left, mut top) = (f64::INFINITY, f64::INFINITY);
+    let (mut right, mut bottom) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for (x, y) in corners {
+        left = left.min(x);
+        right = right.max(x);
+        top = top.min(y);
+        bottom = bottom.max(y);
+    }
+
+    let margin = if widescreen { WIDESCREEN_MARGIN } else { 0.0 };
+    let clipped_width = (right.min(640.0 + margin) - left.max(-margin)).max(0.0);
+    let clipped_height = (bottom.min(480.0) - top.max(0.0)).max(0.0);
+    let bounds_area = (right - left) * (bottom - top);
+    area * (clipped_width * clipped_height) / bounds_area
+}
+
+impl Storyboard {
+    /// Analyzes this storyboard the way the ranking criteria look at it: how much is drawn over
+    /// time (the SB load), and which sprites, commands and files are useless or missing.
+    ///
+    /// Pass a storyboard [merged][Storyboard::merge] with the beatmap to include the beatmap's
+    /// background and video in the SB load.
+    pub fn analyze(&self, assets: &impl Assets, options: &AnalysisOptions) -> Analysis {
+        let timelines = self
+            .events
+            .iter()
+            .map(SpriteTimeline::new)
+            .collect::<Vec<_>>();
+        let origin_of = |evt: &Event| match evt {
+            Event::Sprite(sprite) => sprite.origin.fraction(),
+            Event::Animation(anim) => anim.origin.fraction(),
+            _ => Point::new(0.0, 0.0),
+        };
+        let image_of = |evt: &Event, state: &SpriteState| match evt {
+            Event::Sprite(sprite) => Some(sprite.filepath.clone()),
+            Event::Animation(anim) => Some(anim.frame_path(state.frame.unwrap_or(0))),
+            _ => None,
+        };
+
+        let mut diagnostics = Vec::new();
+
+        // sb load
+        let used_images = self
+            .events
+            .iter()
+            .filter_map(|evt| match evt {
+                Event::Sprite(sprite) => Some(normalize_path(&sprite.filepath)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let background_shown = self.events.iter().any(|evt| match evt {
+            Event::Background(bg) => !used_images.contains(&normalize_path(&bg.filename)),
+            _ => false,
+        });
+        let video_start = self
+            .events
+            .iter()
+            .filter_map(|evt| match evt {
+                Event::Video(video) => Some(video.start_time.0 as f64),
+                _ => None,
+            })
+            .fold(None, |acc: Option<f64>, t| {
+                Some(acc.map_or(t, |a| a.min(t)))
+            });
+
+        // only the times when something is around are sampled
+        let lifetime = |tl: &SpriteTimeline| {
+            (
+                tl.start_time().max(i32::MIN as f64),
+                tl.end_time().min(i32::MAX as f64),
+            )
+        };
+        let mut order = (0..timelines.len())
+            .filter(|i| {
+                let timeline = timelines[*i].as_ref();
+                timeline.is_some_and(|tl| tl.start_time() < tl.end_time())
+            })
+            .collect::<Vec<_>>();
+        let start_of = |i: usize| timelines[i].as_ref().map_or(0.0, |tl| lifetime(tl).0);
+        let end_of = |i: usize| timelines[i].as_ref().map_or(0.0, |tl| lifetime(tl).1);
+        order.sort_by(|a, b| start_of(*a).total_cmp(&start_of(*b)));
+        let mut ranges: Vec<(f64, f64)> = Vec::new();
+        for &i in order.iter() {
+            let (start, end) = (start_of(i), end_of(i));
+            match ranges.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => ranges.push((start, end)),
+            }
+        }
+
+        let length = ranges.iter().map(|(start, end)| end - start).sum::<f64>();
+        let step = (options.step.0.max(1) as i64).max((length / MAX_LOAD_SAMPLES).ceil() as i64);
+        let mut times = Vec::new();
+        for (i, &(start, end)) in ranges.iter().enumerate() {
+            let mut time = start.floor() as i64;
+            while (time as f64) < end {
+                times.push(time);
+                time += step;
+            }
+            // the load drops back down in the gap before the next range
+            let gap = end.ceil() as i64;
+            if ranges
+                .get(i + 1)
+                .is_some_and(|next| gap < next.0.floor() as i64)
+            {
+                times.push(gap);
+            }
+        }
+
+        // the sprites around at the current time, in order
+        let mut active = Vec::new();
+        let mut next = 0;
+        let mut load = Vec::with_capacity(times.len());
+        for time in times {
+            let time = Millis(time as i32);
+            while next < order.len() && start_of(order[next]) <= time.0 as f64 {
+                let i = order[next];
+                let pos = active.partition_point(|j| *j < i);
+                active.insert(pos, i);
+                next += 1;
+            }
+            active.retain(|i| end_of(*i) > time.0 as f64);
+
+            let mut total = 0.0;
+            if background_shown {
+                total += 1.0;
+            }
+            if video_start.is_some_and(|start| time.0 as f64 >= start) {
+                total += 1.0;
+            }
+
+            for &i in active.iter() {
+                let evt = &self.events[i];
+                let state = match timelines[i].as_ref().and_then(|tl| tl.state_at(time)) {
+                    Some(state) => state,
+                    None => continue,
+                };
+                let size = match image_of(evt, &state).and_then(|path| assets.image_size(&path)) {
+                    Some(size) => size,
+                    None => continue,
+                };
+                let area = visible_area(&state, origin_of(evt), size, options.widescreen);
+                total += area * state.opacity / SCREEN_AREA;
+            }
+
+            load.push((time, total));
+        }
+
+        let mut high: Option<(Millis, Millis, f64)> = None;
+        for &(time, value) in load.iter().chain([(Millis(i32::MAX), 0.0)].iter()) {
+            if value > options.load_threshold {
+                high = Some(match high {
+                    Some((start, _, peak)) => (start, time, peak.max(value)),
+                    None => (time, time, value),
+                });
+            } else if let Some((start, end, peak)) = high.take() {
+                diagnostics.push(StoryboardDiagnostic {
+                    time: Some(start),
+                    event_index: None,
+                    kind: StoryboardDiagnosticKind::HighLoad { start, end, peak },
+                });
+            }
+        }
+
+        // sprites that are never visible, and commands that do nothing
+        for (i, (evt, timeline)) in self.events.iter().zip(timelines.iter()).enumerate() {
+            let timeline = match timeline {
+                Some(timeline) => timeline,
+                None => continue,
+            };
+
+            let visible = timeline.key_times().into_iter().any(|time| {
+                let state = match timeline.state_at(time.floor() as i32) {
+                    Some(state) => state,
+                    None => return false,
+                };
+                if state.opacity <= 0.0 || state.scale.x == 0.0 || state.scale.y == 0.0 {
+                    return false;
+                }
+                match image_of(evt, &state).and_then(|path| assets.image_size(&path)) {
+                    Some(size) => {
+                        visible_area(&state, origin_of(evt), size, options.widescreen) > 0.0
+                    }
+                    None => true,
+                }
+            });
+            if !visible {
+                diagnostics.push(StoryboardDiagnostic {
+                    time: None,
+                    event_index: Some(i),
+                    kind: StoryboardDiagnosticKind::NeverVisible,
+                });
+            }
+
+            for (j, command) in evt.commands().into_iter().flatten().enumerate() {
+                if timeline.is_redundant(command) {
+                    diagnostics.push(StoryboardDiagnostic {
+                        time: Some(command.start_time()),
+                        event_index: Some(i),
+                        kind: StoryboardDiagnosticKind::UselessCommand { command_index: j },
+                    });
+                }
+            }
+        }
+
+        // missing files
+        for (i, evt) in self.events.iter().enumerate() {
+            let (time, paths) = match evt {
+                Event::Background(bg) => (None, vec![bg.filename.clone()]),
+                Event::Video(video) => (Some(video.start_time), vec![video.filename.clone()]),
+                Event::Sprite(sprite) => (None, vec![sprite.filepath.clone()]),
+                Event::Animation(anim) => (
+                    None,
+                    (0..anim.frame_count.max(1))
+                        .map(|frame| anim.frame_path(frame))
+                        .collect(),
+                ),
+                Event::Sample(sample) => (Some(sample.time), vec![sample.filepath.clone()]),
+                _ => continue,
+            };
+
+            for path in paths {
+                if !assets.exists(&path) {
+                    diagnostics.push(StoryboardDiagnostic {
+                        time,
+                        event_index: Some(i),
+                        kind: StoryboardDiagnosticKind::MissingFile { path },
+                    });
+                }
+            }
+        }
+
+        Analysis { load, diagnostics }
+    }
+}
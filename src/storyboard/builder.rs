@@ -0,0 +1,646 @@
+use crate::beatmap::Beatmap;
+use crate::color::Color;
+use crate::events::{AnimationEvent, Event, SpriteEvent};
+use crate::math::Point;
+use crate::timing::Millis;
+
+use super::{
+    command_bounds, Command, Easing, Interpolate, Layer, LoopInfo, LoopType, Origin, Parameter,
+    Storyboard, TriggerInfo, Tween,
+};
+
+/// How close two values have to be for the optimizer to consider them the same.
+const TOLERANCE: f64 = 1e-3;
+
+/// The most commands the optimizer puts in the body of a loop it finds.
+const MAX_LOOP_LENGTH: usize = 64;
+
+/// Builds a storyboard sprite or animation out of commands, for generating storyboards from
+/// code.
+///
+/// Commands are added to the innermost loop or trigger group that is still open. When the
+/// sprite is built, its commands are [optimized][optimize_commands].
+///
+/// ```
+/// # use libosu::prelude::*;
+/// let mut sprite = SpriteBuilder::sprite(Layer::Background, Origin::Centre, "sb/glow.png", Point::new(320.0, 240.0));
+/// sprite
+///     .fade(Easing::Linear, 0, 500, 0.0, 1.0)
+///     .start_loop(500, 4)
+///     .rotate(Easing::Linear, 0, 1000, 0.0, std::f64::consts::PI)
+///     .end_group();
+///
+/// let mut storyboard = Storyboard::default();
+/// storyboard.events.push(sprite.build());
+/// ```
+#[derive(Clone, Debug)]
+pub struct SpriteBuilder {
+    event: Event,
+    groups: Vec<Command>,
+}
+
+impl SpriteBuilder {
+    /// Starts building a sprite.
+    pub fn sprite(
+        layer: Layer,
+        origin: Origin,
+        filepath: impl Into<String>,
+        pos: Point<f64>,
+    ) -> Self {
+        SpriteBuilder {
+            event: Event::Sprite(SpriteEvent {
+                layer,
+                origin,
+                filepath: filepath.into(),
+                pos,
+                commands: Vec::new(),
+            }),
+            groups: Vec::new(),
+        }
+    }
+
+    /// Starts building an animation.
+    pub fn animation(
+        layer: Layer,
+        origin: Origin,
+        filepath: impl Into<String>,
+        pos: Point<f64>,
+        frame_count: u32,
+        frame_delay: f64,
+        loop_type: LoopType,
+    ) -> Self {
+        SpriteBuilder {
+            event: Event::Animation(AnimationEvent {
+                layer,
+                origin,
+                filepath: filepath.into(),
+                pos,
+                frame_count,
+                frame_delay,
+                loop_type,
+                commands: Vec::new(),
+            }),
+            groups: Vec::new(),
+        }
+    }
+
+    /// Adds any command to the current group.
+    pub fn command(&mut self, command: Command) -> &mut Self {
+        let commands = match self.groups.last_mut() {
+            Some(group) => group.children_mut(),
+            None => self.event.commands_mut(),
+        };
+        commands
+            .expect("sprite builders only hold sprites, animations, loops and triggers")
+            .push(command);
+        self
+    }
+
+    /// Fades the sprite's opacity.
+    pub fn fade(
+        &mut self,
+        easing: Easing,
+        start_time: impl Into<Millis>,
+        end_time: impl Into<Millis>,
+        from: f64,
+        to: f64,
+    ) -> &mut Self {
+        self.command(Command::Fade(Tween::new(
+            easing, start_time, end_time, from, to,
+        )))
+    }
+
+    /// Moves the sprite.
+    pub fn move_to(
+        &mut self,
+        easing: Easing,
+        start_time: impl Into<Millis>,
+        end_time: impl Into<Millis>,
+        from: Point<f64>,
+        to: Point<f64>,
+    ) -> &mut Self {
+        self.command(Command::Move(Tween::new(
+            easing, start_time, end_time, from, to,
+        )))
+    }
+
+    /// Moves the sprite horizontally.
+    pub fn move_x(
+        &mut self,
+        easing: Easing,
+        start_time: impl Into<Millis>,
+        end_time: impl Into<Millis>,
+        from: f64,
+        to: f64,
+    ) -> &mut Self {
+        self.command(Command::MoveX(Tween::new(
+            easing, start_time, end_time, from, to,
+        )))
+    }
+
+    /// Moves the sprite vertically.
+    pub fn move_y(
+        &mut self,
+        easing: Easing,
+        start_time: impl Into<Millis>,
+        end_time: impl Into<Millis>,
+        from: f64,
+        to: f64,
+    ) -> &mut Self {
+        self.command(Command::MoveY(Tween::new(
+            easing, start_time, end_time, from, to,
+        )))
+    }
+
+    /// Scales the sprite uniformly.
+    pub fn scale(
+        &mut self,
+        easing: Easing,
+        start_time: impl Into<Millis>,
+        end_time: impl Into<Millis>,
+        from: f64,
+        to: f64,
+    ) -> &mut Self {
+        self.command(Command::Scale(Tween::new(
+            easing, start_time, end_time, from, to,
+        )))
+    }
+
+    /// Scales the sprite horizontally and vertically.
+    pub fn vector_scale(
+        &mut self,
+        easing: Easing,
+        start_time: impl Into<Millis>,
+        end_time: impl Into<Millis>,
+        from: Point<f64>,
+        to: Point<f64>,
+    ) -> &mut Self {
+        self.command(Command::VectorScale(Tween::new(
+            easing, start_time, end_time, from, to,
+        )))
+    }
+
+    /// Rotates the sprite (in radians, clockwise).
+    pub fn rotate(
+        &mut self,
+        easing: Easing,
+        start_time: impl Into<Millis>,
+        end_time: impl Into<Millis>,
+        from: f64,
+        to: f64,
+    ) -> &mut Self {
+        self.command(Command::Rotate(Tween::new(
+            easing, start_time, end_time, from, to,
+        )))
+    }
+
+    /// Changes the sprite's colour tint.
+    pub fn color(
+        &mut self,
+        easing: Easing,
+        start_time: impl Into<Millis>,
+        end_time: impl Into<Millis>,
+        from: Color,
+        to: Color,
+    ) -> &mut Self {
+        self.command(Command::Color(Tween::new(
+            easing, start_time, end_time, from, to,
+        )))
+    }
+
+    /// Flips the sprite horizontally between the given times.
+    pub fn flip_horizontal(
+        &mut self,
+        start_time: impl Into<Millis>,
+        end_time: impl Into<Millis>,
+    ) -> &mut Self {
+        self.command(Command::Parameter(Tween::constant(
+            start_time,
+            end_time,
+            Parameter::FlipHorizontal,
+        )))
+    }
+
+    /// Flips the sprite vertically between the given times.
+    pub fn flip_vertical(
+        &mut self,
+        start_time: impl Into<Millis>,
+        end_time: impl Into<Millis>,
+    ) -> &mut Self {
+        self.command(Command::Parameter(Tween::constant(
+            start_time,
+            end_time,
+            Parameter::FlipVertical,
+        )))
+    }
+
+    /// Uses additive blending between the given times.
+    pub fn additive(
+        &mut self,
+        start_time: impl Into<Millis>,
+        end_time: impl Into<Millis>,
+    ) -> &mut Self {
+        self.command(Command::Parameter(Tween::constant(
+            start_time,
+            end_time,
+            Parameter::AdditiveBlend,
+        )))
+    }
+
+    /// Opens a loop group. Commands added until the matching [`SpriteBuilder::end_group`] are
+    /// relative to the start of each iteration.
+    pub fn start_loop(&mut self, start_time: impl Into<Millis>, loop_count: u32) -> &mut Self {
+        self.groups.push(Command::Loop(LoopInfo {
+            start_time: start_time.into(),
+            loop_count,
+            commands: Vec::new(),
+        }));
+        self
+    }
+
+    /// Opens a trigger group. Commands added until the matching [`SpriteBuilder::end_group`]
+    /// are relative to the time the trigger fires.
+    pub fn start_trigger(
+        &mut self,
+        trigger: impl Into<String>,
+        start_time: impl Into<Millis>,
+        end_time: impl Into<Millis>,
+        group: Option<i32>,
+    ) -> &mut Self {
+        self.groups.push(Command::Trigger(TriggerInfo {
+            trigger: trigger.into(),
+            start_time: start_time.into(),
+            end_time: end_time.into(),
+            group,
+            commands: Vec::new(),
+        }));
+        self
+    }
+
+    /// Closes the innermost open loop or trigger group.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no open groups.
+    pub fn end_group(&mut self) -> &mut Self {
+        let group = self.groups.pop().expect("no group to end");
+        self.command(group)
+    }
+
+    /// Finishes the sprite, closing any groups that are still open and optimizing its
+    /// commands.
+    pub fn build(mut self) -> Event {
+        while !self.groups.is_empty() {
+            self.end_group();
+        }
+
+        let mut event = self.event;
+        if let Some(commands) = event.commands_mut() {
+            optimize_commands(commands);
+        }
+        event
+    }
+}
+
+/// Values that the optimizer knows how to compare.
+trait Keyframe: Interpolate {
+    fn approx_eq(self, other: Self) -> bool;
+}
+
+impl Keyframe for f64 {
+    fn approx_eq(self, other: Self) -> bool {
+        (self - other).abs() < TOLERANCE
+    }
+}
+
+impl Keyframe for Point<f64> {
+    fn approx_eq(self, other: Self) -> bool {
+        self.x.approx_eq(other.x) && self.y.approx_eq(other.y)
+    }
+}
+
+impl Keyframe for Color {
+    fn approx_eq(self, other: Self) -> bool {
+        self == other
+    }
+}
+
+/// Turns tweens that don't change their value into constant tweens.
+fn simplify_tween<T: Keyframe>(tween: &mut Tween<T>) {
+    if tween.values.len() == 2 && tween.values[0].approx_eq(tween.values[1]) {
+        tween.values.truncate(1);
+        tween.easing = Easing::Linear;
+    }
+}
+
+/// Combines two tweens of the same property into one, if the result looks the same.
+fn merge_tweens<T: Keyframe>(a: &Tween<T>, b: &Tween<T>) -> Option<Tween<T>> {
+    if a.values.len() > 2 || b.values.len() > 2 || b.start_time < a.end_time {
+        return None;
+    }
+
+    let a_start = a.values[0];
+    let a_end = *a.values.last().unwrap();
+    let b_start = b.values[0];
+    let b_end = *b.values.last().unwrap();
+
+    // holding the same value, possibly with a gap in between (which holds it too)
+    if a.values.len() == 1 && b.values.len() == 1 && a_start.approx_eq(b_start) {
+        return Some(Tween::constant(a.start_time, b.end_time, a_start));
+    }
+
+    // two linear tweens that line up, with the keyframe in the middle on the straight line
+    // between the outer two
+    let linear = |t: &Tween<T>| t.easing == Easing::Linear || t.values.len() == 1;
+    if linear(a) && linear(b) && a.end_time == b.start_time && a_end.approx_eq(b_start) {
+        let duration = (b.end_time.0 - a.start_time.0) as f64;
+        if duration <= 0.0 {
+            return None;
+        }
+        let progress = (a.end_time.0 - a.start_time.0) as f64 / duration;
+        if a_start.interpolate(b_end, progress).approx_eq(a_end) {
+            let mut tween = Tween::new(Easing::Linear, a.start_time, b.end_time, a_start, b_end);
+            simplify_tween(&mut tween);
+            return Some(tween);
+        }
+    }
+
+    None
+}
+
+/// Pulls all the commands of one kind out of the list, merges them, and puts them back.
+fn merge_kind<T: Keyframe>(
+    commands: &mut Vec<Command>,
+    extract: impl Fn(&Command) -> Option<&Tween<T>>,
+    wrap: impl Fn(Tween<T>) -> Command,
+) {
+    let mut tweens = commands
+        .iter()
+        .filter_map(|cmd| extract(cmd).cloned())
+        .collect::<Vec<_>>();
+    if tweens.is_empty() {
+        return;
+    }
+    commands.retain(|cmd| extract(cmd).is_none());
+    tweens.sort_by_key(|t| t.start_time);
+
+    let mut merged: Vec<Tween<T>> = Vec::with_capacity(tweens.len());
+    for mut tween in tweens {
+        simplify_tween(&mut tween);
+        if let Some(last) = merged.last_mut() {
+            if let Some(combined) = merge_tweens(last, &tween) {
+                *last = combined;
+                continue;
+            }
+        }
+        merged.push(tween);
+    }
+    commands.extend(merged.into_iter().map(wrap));
+}
+
+/// Combines overlapping or touching parameter commands for the same parameter.
+fn merge_parameters(commands: &mut Vec<Command>) {
+    let is_ranged = |cmd: &Command| match cmd {
+        Command::Parameter(t) => t.values.len() == 1 && t.start_time != t.end_time,
+        _ => false,
+    };
+    let mut tweens = commands
+        .iter()
+        .filter(|cmd| is_ranged(cmd))
+        .filter_map(|cmd| match cmd {
+            Command::Parameter(t) => Some(t.clone()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    commands.retain(|cmd| !is_ranged(cmd));
+    tweens.sort_by_key(|t| (t.values[0] as u8, t.start_time));
+
+    let mut merged: Vec<Tween<Parameter>> = Vec::with_capacity(tweens.len());
+    for tween in tweens {
+        if let Some(last) = merged.last_mut() {
+            if last.values[0] == tween.values[0] && tween.start_time <= last.end_time {
+                last.end_time = last.end_time.max(tween.end_time);
+                continue;
+            }
+        }
+        merged.push(tween);
+    }
+    commands.extend(merged.into_iter().map(Command::Parameter));
+}
+
+/// Replaces runs of commands that repeat at a fixed interval with loops.
+fn find_loops(commands: Vec<Command>) -> Vec<Command> {
+    // commands near the ends of time can't always be moved, and then they don't repeat
+    let repeats =
+        |a: &Command, b: &Command, offset: i64| a.checked_shift(offset).is_some_and(|a| &a == b);
+
+    let mut result = Vec::with_capacity(commands.len());
+    let mut i = 0;
+    while i < commands.len() {
+        // (block length, repetitions, commands saved)
+        let mut best: Option<(usize, usize, usize)> = None;
+        let (mut start, mut end) = (Millis(i32::MAX), Millis(i32::MIN));
+        for len in 1..=((commands.len() - i) / 2).min(MAX_LOOP_LENGTH) {
+            let last = &commands[i + len - 1];
+            if last.children().is_some() {
+                break;
+            }
+            start = start.min(last.start_time());
+            end = end.max(last.end_time());
+            let period = end.0 as i64 - start.0 as i64;
+            // the block can only repeat if the command after it is the first one, shifted
+            if period <= 0 || !repeats(&commands[i], &commands[i + len], period) {
+                continue;
+            }
+            // and it has to fit in a loop, where times are relative to the loop's start
+            let relative = -(start.0 as i64);
+            if commands[i..i + len]
+                .iter()
+                .any(|cmd| cmd.checked_shift(relative).is_none())
+            {
+                continue;
+            }
+
+            let mut count = 1;
+            while i + (count + 1) * len <= commands.len()
+                && (0..len).all(|j| {
+                    repeats(
+                        &commands[i + j],
+                        &commands[i + count * len + j],
+                        period * count as i64,
+                    )
+                })
+            {
+                count += 1;
+            }
+
+            // a loop costs an extra line
+            let saved = (len * count).saturating_sub(len + 1);
+            if count >= 2 && saved > best.map_or(0, |(_, _, saved)| saved) {
+                best = Some((len, count, saved));
+            }
+        }
+
+        match best {
+            Some((len, count, _)) => {
+                let block = &commands[i..i + len];
+                let (start, _) = command_bounds(block).unwrap();
+                let children = block
+                    .iter()
+                    .map(|cmd| {
+                        cmd.checked_shift(-(start.0 as i64))
+                            .expect("loop blocks are checked to fit when they're found")
+                    })
+                    .collect();
+                result.push(Command::Loop(LoopInfo {
+                    start_time: start,
+                    loop_count: count as u32,
+                    commands: children,
+                }));
+                i += len * count;
+            }
+            None => {
+                result.push(commands[i].clone());
+                i += 1;
+            }
+        }
+    }
+
+    result
+}
+
+fn merge_all(commands: &mut Vec<Command>) {
+    for cmd in commands.iter_mut() {
+        if let Some(children) = cmd.children_mut() {
+            merge_all(children);
+        }
+    }
+
+    merge_kind(
+        commands,
+        |cmd| match cmd {
+            Command::Fade(t) => Some(t),
+            _ => None,
+        },
+        Command::Fade,
+    );
+    merge_kind(
+        commands,
+        |cmd| match cmd {
+            Command::Scale(t) => Some(t),
+            _ => None,
+        },
+        Command::Scale,
+    );
+    merge_kind(
+        commands,
+        |cmd| match cmd {
+            Command::VectorScale(t) => Some(t),
+            _ => None,
+        },
+        Command::VectorScale,
+    );
+    merge_kind(
+        commands,
+        |cmd| match cmd {
+            Command::Rotate(t) => Some(t),
+            _ => None,
+        },
+        Command::Rotate,
+    );
+    merge_kind(
+        commands,
+        |cmd| match cmd {
+            Command::Color(t) => Some(t),
+            _ => None,
+        },
+        Command::Color,
+    );
+    merge_parameters(commands);
+
+    // M, MX and MY all drive the same position, so merging one kind could change which one
+    // wins where they overlap
+    let move_kinds = [
+        commands.iter().any(|cmd| matches!(cmd, Command::Move(_))),
+        commands.iter().any(|cmd| matches!(cmd, Command::MoveX(_))),
+        commands.iter().any(|cmd| matches!(cmd, Command::MoveY(_))),
+    ];
+    let has_move = move_kinds[0];
+    if !has_move || move_kinds.iter().filter(|present| **present).count() == 1 {
+        merge_kind(
+            commands,
+            |cmd| match cmd {
+                Command::Move(t) => Some(t),
+                _ => None,
+            },
+            Command::Move,
+        );
+    }
+    if !has_move {
+        merge_kind(
+            commands,
+            |cmd| match cmd {
+                Command::MoveX(t) => Some(t),
+                _ => None,
+            },
+            Command::MoveX,
+        );
+        merge_kind(
+            commands,
+            |cmd| match cmd {
+                Command::MoveY(t) => Some(t),
+                _ => None,
+            },
+            Command::MoveY,
+        );
+    }
+
+    commands.sort_by_key(Command::start_time);
+}
+
+/// Rewrites a list of commands into a shorter one that looks the same in game.
+///
+/// This turns tweens that don't change anything into constant ones, merges consecutive
+/// commands that hold the same value, drops keyframes that lie on the straight line between
+/// their neighbours, and turns commands that repeat at a fixed interval into loops.
+pub fn optimize_commands(commands: &mut Vec<Command>) {
+    merge_all(commands);
+    *commands = find_loops(std::mem::take(commands));
+}
+
+impl Storyboard {
+    /// Optimizes the commands of every sprite and animation in this storyboard.
+    ///
+    /// See [`optimize_commands`] for what this does.
+    pub fn optimize(&mut self) {
+        for event in self.events.iter_mut() {
+            if let Some(commands) = event.commands_mut() {
+                optimize_commands(commands);
+            }
+        }
+    }
+
+    /// Replaces the storyboard objects in the given beatmap's events with the ones in this
+    /// storyboard, for storyboards that only apply to a single difficulty.
+    ///
+    /// The beatmap's background, video, breaks and any other events are kept.
+    pub fn write_into(&self, beatmap: &mut Beatmap) {
+        beatmap.events.retain(|evt| {
+            !matches!(
+                evt,
+                Event::Sprite(_) | Event::Animation(_) | Event::Sample(_)
+            )
+        });
+        beatmap.events.extend(
+            self.events
+                .iter()
+                .filter(|evt| {
+                    matches!(
+                        evt,
+                        Event::Sprite(_) | Event::Animation(_) | Event::Sample(_)
+                    )
+                })
+                .cloned(),
+        );
+    }
+}
@@ -0,0 +1,678 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use num::FromPrimitive;
+
+use crate::color::Color;
+use crate::errors::{field, parse_field, ParseError, ParseResult};
+use crate::hitsounds::{Additions, SampleSet};
+use crate::math::Point;
+use crate::timing::Millis;
+
+/// Easing function applied to a storyboard command between its start and end values.
+///
+/// The numbering follows the one used in the .osu/.osb file formats.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[allow(missing_docs)]
+pub enum Easing {
+    #[default]
+    Linear = 0,
+    EasingOut = 1,
+    EasingIn = 2,
+    QuadIn = 3,
+    QuadOut = 4,
+    QuadInOut = 5,
+    CubicIn = 6,
+    CubicOut = 7,
+    CubicInOut = 8,
+    QuartIn = 9,
+    QuartOut = 10,
+    QuartInOut = 11,
+    QuintIn = 12,
+    QuintOut = 13,
+    QuintInOut = 14,
+    SineIn = 15,
+    SineOut = 16,
+    SineInOut = 17,
+    ExpoIn = 18,
+    ExpoOut = 19,
+    ExpoInOut = 20,
+    CircIn = 21,
+    CircOut = 22,
+    CircInOut = 23,
+    ElasticIn = 24,
+    ElasticOut = 25,
+    ElasticHalfOut = 26,
+    ElasticQuarterOut = 27,
+    ElasticInOut = 28,
+    BackIn = 29,
+    BackOut = 30,
+    BackInOut = 31,
+    BounceIn = 32,
+    BounceOut = 33,
+    BounceInOut = 34,
+}
+
+/// Parameters that can be toggled by the `P` command.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Parameter {
+    /// Flip the image horizontally (`H`)
+    FlipHorizontal,
+
+    /// Flip the image vertically (`V`)
+    FlipVertical,
+
+    /// Use additive-colour blending instead of alpha-blending (`A`)
+    AdditiveBlend,
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Parameter::FlipHorizontal => 'H',
+                Parameter::FlipVertical => 'V',
+                Parameter::AdditiveBlend => 'A',
+            }
+        )
+    }
+}
+
+/// A change of some value over a period of time.
+///
+/// `values` holds at least one value. A single value means the value is held for the entire
+/// duration. If there is more than one, the command is shorthand for several consecutive
+/// commands of the same duration: the first goes from `values[0]` to `values[1]` between
+/// `start_time` and `end_time`, the next from `values[1]` to `values[2]` over the following
+/// span of the same length, and so on.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Tween<T> {
+    /// The easing used to interpolate between the values
+    pub easing: Easing,
+
+    /// When the (first) transition starts
+    pub start_time: Millis,
+
+    /// When the (first) transition ends
+    pub end_time: Millis,
+
+    /// The values that are transitioned between
+    pub values: Vec<T>,
+}
+
+impl<T> Tween<T> {
+    /// Creates a tween going from `start` to `end` between the given times.
+    pub fn new(
+        easing: Easing,
+        start_time: impl Into<Millis>,
+        end_time: impl Into<Millis>,
+        start: T,
+        end: T,
+    ) -> Self {
+        Tween {
+            easing,
+            start_time: start_time.into(),
+            end_time: end_time.into(),
+            values: vec![start, end],
+        }
+    }
+
+    /// Creates a tween that holds a single value between the given times.
+    pub fn constant(start_time: impl Into<Millis>, end_time: impl Into<Millis>, value: T) -> Self {
+        Tween {
+            easing: Easing::Linear,
+            start_time: start_time.into(),
+            end_time: end_time.into(),
+            values: vec![value],
+        }
+    }
+
+    /// The time at which the last of the (possibly repeated) transitions ends.
+    pub fn last_end_time(&self) -> Millis {
+        let segments = self.values.len().saturating_sub(1).max(1) as i64;
+        let duration = self.end_time.0 as i64 - self.start_time.0 as i64;
+        saturating_millis(self.start_time.0 as i64 + duration.saturating_mul(segments))
+    }
+}
+
+/// Commands nested under an `L` (loop) command.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LoopInfo {
+    /// When the first iteration of the loop starts
+    pub start_time: Millis,
+
+    /// How many times the loop runs
+    pub loop_count: u32,
+
+    /// The commands in the loop, with times relative to the start of each iteration
+    pub commands: Vec<Command>,
+}
+
+impl LoopInfo {
+    /// The number of times the loop actually runs (the game runs it at least once).
+    pub fn iterations(&self) -> u32 {
+        self.loop_count.max(1)
+    }
+
+    /// The length of a single iteration, from the earliest start to the latest end of the
+    /// nested commands.
+    pub fn iteration_duration(&self) -> i32 {
+        match command_bounds(&self.commands) {
+            Some((start, end)) => saturating_millis(end.0 as i64 - start.0 as i64).0,
+            None => 0,
+        }
+    }
+}
+
+/// Commands nested under a `T` (trigger) command.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TriggerInfo {
+    /// The name of the trigger condition, such as `Passing`, `Failing` or `HitSoundClap`
+    pub trigger: String,
+
+    /// When the trigger starts listening
+    pub start_time: Millis,
+
+    /// When the trigger stops listening
+    pub end_time: Millis,
+
+    /// The trigger group; only one trigger in a group can be active at once
+    pub group: Option<i32>,
+
+    /// The commands run when the trigger fires, with times relative to the activation time
+    pub commands: Vec<Command>,
+}
+
+impl TriggerInfo {
+    /// Parses the trigger name into the condition it listens for, or `None` if the trigger type
+    /// isn't recognized.
+    pub fn condition(&self) -> Option<TriggerCondition> {
+        TriggerCondition::from_str(&self.trigger).ok()
+    }
+}
+
+/// The condition a trigger listens for.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TriggerCondition {
+    /// The player goes from failing to passing
+    Passing,
+
+    /// The player goes from passing to failing
+    Failing,
+
+    /// A hitsound is played. Fields that are `None` match anything.
+    ///
+    /// This is written as `HitSound[SampleSet][AdditionsSampleSet][Addition][CustomSampleSet]`,
+    /// so for example `HitSoundSoftClap` or `HitSoundAllDrumWhistle2`.
+    HitSound {
+        /// The sample set of the hitsound
+        sample_set: Option<SampleSet>,
+
+        /// The sample set of the additions
+        addition_set: Option<SampleSet>,
+
+        /// An addition that has to be played
+        addition: Option<Additions>,
+
+        /// The custom sample index
+        custom_index: Option<i32>,
+    },
+}
+
+impl FromStr for TriggerCondition {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<TriggerCondition, Self::Err> {
+        fn take_sample_set(s: &mut &str) -> Option<Option<SampleSet>> {
+            for (name, set) in &[
+                ("All", None),
+                ("Normal", Some(SampleSet::Normal)),
+                ("Soft", Some(SampleSet::Soft)),
+                ("Drum", Some(SampleSet::Drum)),
+            ] {
+                if let Some(rest) = s.strip_prefix(name) {
+                    *s = rest;
+                    return Some(*set);
+                }
+            }
+            None
+        }
+
+        match s {
+            "Passing" => return Ok(TriggerCondition::Passing),
+            "Failing" => return Ok(TriggerCondition::Failing),
+            _ => (),
+        }
+
+        let mut rest = s
+            .strip_prefix("HitSound")
+            .ok_or_else(|| ParseError::InvalidTrigger(s.to_owned()))?;
+        let sample_set = take_sample_set(&mut rest).flatten();
+        let addition_set = take_sample_set(&mut rest).flatten();
+
+        let mut addition = None;
+        for (name, flag) in &[
+            ("Whistle", Additions::WHISTLE),
+            ("Finish", Additions::FINISH),
+            ("Clap", Additions::CLAP),
+        ] {
+            if let Some(r) = rest.strip_prefix(name) {
+                rest = r;
+                addition = Some(*flag);
+                break;
+            }
+        }
+
+        let custom_index = if rest.is_empty() {
+            None
+        } else {
+            Some(
+                rest.parse::<i32>()
+                    .map_err(|_| ParseError::InvalidTrigger(s.to_owned()))?,
+            )
+        };
+
+        Ok(TriggerCondition::HitSound {
+            sample_set,
+            addition_set,
+            addition,
+            custom_index,
+        })
+    }
+}
+
+/// A single storyboard command attached to a sprite or animation.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Command {
+    /// Opacity (`F`), from 0 to 1
+    Fade(Tween<f64>),
+
+    /// Position (`M`)
+    Move(Tween<Point<f64>>),
+
+    /// Horizontal position (`MX`)
+    MoveX(Tween<f64>),
+
+    /// Vertical position (`MY`)
+    MoveY(Tween<f64>),
+
+    /// Uniform scale (`S`)
+    Scale(Tween<f64>),
+
+    /// Separate horizontal and vertical scale (`V`)
+    VectorScale(Tween<Point<f64>>),
+
+    /// Rotation in radians, clockwise (`R`)
+    Rotate(Tween<f64>),
+
+    /// Colour tint (`C`)
+    Color(Tween<Color>),
+
+    /// Toggles a parameter for the duration of the command (`P`)
+    Parameter(Tween<Parameter>),
+
+    /// Loop (`L`)
+    Loop(LoopInfo),
+
+    /// Trigger (`T`)
+    Trigger(TriggerInfo),
+}
+
+impl Command {
+    /// The time at which this command starts having an effect.
+    ///
+    /// For loops, this is the start of the earliest nested command in the first iteration.
+    pub fn start_time(&self) -> Millis {
+        match self {
+            Command::Fade(t)
+            | Command::MoveX(t)
+            | Command::MoveY(t)
+            | Command::Scale(t)
+            | Command::Rotate(t) => t.start_time,
+            Command::Move(t) | Command::VectorScale(t) => t.start_time,
+            Command::Color(t) => t.start_time,
+            Command::Parameter(t) => t.start_time,
+            Command::Loop(info) => match command_bounds(&info.commands) {
+                Some((start, _)) => saturating_millis(info.start_time.0 as i64 + start.0 as i64),
+                None => info.start_time,
+            },
+            Command::Trigger(info) => info.start_time,
+        }
+    }
+
+    /// The time at which this command stops having an effect.
+    ///
+    /// For loops, this is the end of the latest nested command in the last iteration.
+    pub fn end_time(&self) -> Millis {
+        match self {
+            Command::Fade(t)
+            | Command::MoveX(t)
+            | Command::MoveY(t)
+            | Command::Scale(t)
+            | Command::Rotate(t) => t.last_end_time(),
+            Command::Move(t) | Command::VectorScale(t) => t.last_end_time(),
+            Command::Color(t) => t.last_end_time(),
+            Command::Parameter(t) => t.last_end_time(),
+            Command::Loop(info) => match command_bounds(&info.commands) {
+                Some((start, end)) => {
+                    let repeats = info.iterations() as i64 - 1;
+                    let duration = end.0 as i64 - start.0 as i64;
+                    let last_start = info.start_time.0 as i64 + duration.saturating_mul(repeats);
+                    saturating_millis(last_start.saturating_add(end.0 as i64))
+                }
+                None => info.start_time,
+            },
+            Command::Trigger(info) => info.end_time,
+        }
+    }
+
+    /// Returns the commands nested under this one, if it is a loop or a trigger.
+    pub fn children(&self) -> Option<&Vec<Command>> {
+        match self {
+            Command::Loop(info) => Some(&info.commands),
+            Command::Trigger(info) => Some(&info.commands),
+            _ => None,
+        }
+    }
+
+    /// Returns the commands nested under this one mutably, if it is a loop or a trigger.
+    pub fn children_mut(&mut self) -> Option<&mut Vec<Command>> {
+        match self {
+            Command::Loop(info) => Some(&mut info.commands),
+            Command::Trigger(info) => Some(&mut info.commands),
+            _ => None,
+        }
+    }
+
+    /// Moves this command by the given number of milliseconds. Times that would go past the
+    /// earliest or latest time there is are kept at it.
+    ///
+    /// Commands nested under loops and triggers are relative to their parent, so they're left
+    /// alone.
+    pub fn shift(&mut self, offset: i32) {
+        self.shift_times(|time| Some(time.saturating_add(offset)));
+    }
+
+    /// Returns this command moved by the given number of milliseconds, or `None` if any of its
+    /// times wouldn't fit in [`Millis`]. Nested commands are left alone, as with
+    /// [`Command::shift`].
+    pub fn checked_shift(&self, offset: i64) -> Option<Command> {
+        let mut cmd = self.clone();
+        cmd.shift_times(|time| i32::try_from(time as i64 + offset).ok())?;
+        Some(cmd)
+    }
+
+    fn shift_times(&mut self, shift: impl Fn(i32) -> Option<i32>) -> Option<()> {
+        let shift = |time: &mut Millis| -> Option<()> {
+            time.0 = shift(time.0)?;
+            Some(())
+        };
+        let (start_time, end_time) = match self {
+            Command::Fade(t)
+            | Command::MoveX(t)
+            | Command::MoveY(t)
+            | Command::Scale(t)
+            | Command::Rotate(t) => (&mut t.start_time, Some(&mut t.end_time)),
+            Command::Move(t) | Command::VectorScale(t) => {
+                (&mut t.start_time, Some(&mut t.end_time))
+            }
+            Command::Color(t) => (&mut t.start_time, Some(&mut t.end_time)),
+            Command::Parameter(t) => (&mut t.start_time, Some(&mut t.end_time)),
+            Command::Loop(info) => (&mut info.start_time, None),
+            Command::Trigger(info) => (&mut info.start_time, Some(&mut info.end_time)),
+        };
+        shift(start_time)?;
+        if let Some(end_time) = end_time {
+            shift(end_time)?;
+        }
+        Some(())
+    }
+
+    /// Writes this command (and any nested commands) indented to the given depth.
+    pub(crate) fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        write!(f, "{:1$}", "", depth)?;
+        match self {
+            Command::Fade(t) => write_tween(f, "F", t)?,
+            Command::Move(t) => write_tween(f, "M", t)?,
+            Command::MoveX(t) => write_tween(f, "MX", t)?,
+            Command::MoveY(t) => write_tween(f, "MY", t)?,
+            Command::Scale(t) => write_tween(f, "S", t)?,
+            Command::VectorScale(t) => write_tween(f, "V", t)?,
+            Command::Rotate(t) => write_tween(f, "R", t)?,
+            Command::Color(t) => write_tween(f, "C", t)?,
+            Command::Parameter(t) => write_tween(f, "P", t)?,
+            Command::Loop(info) => write!(f, "L,{},{}", info.start_time.0, info.loop_count)?,
+            Command::Trigger(info) => {
+                write!(
+                    f,
+                    "T,{},{},{}",
+                    info.trigger, info.start_time.0, info.end_time.0
+                )?;
+                if let Some(group) = info.group {
+                    write!(f, ",{}", group)?;
+                }
+            }
+        }
+
+        if let Some(children) = self.children() {
+            for child in children.iter() {
+                writeln!(f)?;
+                child.fmt_indented(f, depth + 1)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Converts a time worked out in `i64` to milliseconds, keeping times too far away to be stored at
+/// the earliest or latest time there is.
+pub(crate) fn saturating_millis(time: i64) -> Millis {
+    Millis(time.clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+}
+
+/// Returns the earliest start time and latest end time of the given commands.
+pub(crate) fn command_bounds(commands: &[Command]) -> Option<(Millis, Millis)> {
+    let start = commands.iter().map(Command::start_time).min()?;
+    let end = commands.iter().map(Command::end_time).max()?;
+    Some((start, end))
+}
+
+/// Values that can be carried by a storyboard command, along with how they are laid out in the
+/// comma-separated command line.
+trait CommandValue: Sized {
+    /// The number of comma-separated fields a single value takes up
+    const FIELDS: usize;
+
+    fn parse_fields(fields: &[&str]) -> ParseResult<Self>;
+
+    fn write_fields(&self, f: &mut fmt::Formatter) -> fmt::Result;
+}
+
+impl CommandValue for f64 {
+    const FIELDS: usize = 1;
+
+    fn parse_fields(fields: &[&str]) -> ParseResult<Self> {
+        Ok(fields[0].parse::<f64>()?)
+    }
+
+    fn write_fields(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl CommandValue for Point<f64> {
+    const FIELDS: usize = 2;
+
+    fn parse_fields(fields: &[&str]) -> ParseResult<Self> {
+        Ok(Point::new(
+            fields[0].parse::<f64>()?,
+            fields[1].parse::<f64>()?,
+        ))
+    }
+
+    fn write_fields(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{}", self.x, self.y)
+    }
+}
+
+impl CommandValue for Color {
+    const FIELDS: usize = 3;
+
+    fn parse_fields(fields: &[&str]) -> ParseResult<Self> {
+        Ok(Color::new(
+            fields[0].parse::<u8>()?,
+            fields[1].parse::<u8>()?,
+            fields[2].parse::<u8>()?,
+        ))
+    }
+
+    fn write_fields(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl CommandValue for Parameter {
+    const FIELDS: usize = 1;
+
+    fn parse_fields(fields: &[&str]) -> ParseResult<Self> {
+        match fields[0] {
+            "H" => Ok(Parameter::FlipHorizontal),
+            "V" => Ok(Parameter::FlipVertical),
+            "A" => Ok(Parameter::AdditiveBlend),
+            s => Err(ParseError::InvalidParameter(s.to_owned())),
+        }
+    }
+
+    fn write_fields(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// Parses the fields after a command's name, which starts at field 1 of the line.
+fn parse_tween<T: CommandValue>(parts: &[&str]) -> ParseResult<Tween<T>> {
+    if parts.len() < 4 {
+        return Err(ParseError::MissingField("command values").in_field(parts.len() + 1));
+    }
+
+    let easing = parts[0]
+        .parse::<u32>()
+        .map_err(|err| ParseError::from(err).in_field(1))?;
+    let easing =
+        Easing::from_u32(easing).ok_or_else(|| ParseError::InvalidEasing(easing).in_field(1))?;
+    let start_time = Millis(
+        parts[1]
+            .parse::<i32>()
+            .map_err(|err| ParseError::from(err).in_field(2))?,
+    );
+    let end_time = if parts[2].is_empty() {
+        start_time
+    } else {
+        Millis(
+            parts[2]
+                .parse::<i32>()
+                .map_err(|err| ParseError::from(err).in_field(3))?,
+        )
+    };
+
+    let chunks = parts[3..].chunks_exact(T::FIELDS);
+    if !chunks.remainder().is_empty() {
+        return Err(ParseError::MissingField("command values").in_field(parts.len() + 1));
+    }
+    let values = chunks
+        .enumerate()
+        .map(|(i, fields)| T::parse_fields(fields).map_err(|err| err.in_field(4 + i * T::FIELDS)))
+        .collect::<ParseResult<Vec<_>>>()?;
+
+    Ok(Tween {
+        easing,
+        start_time,
+        end_time,
+        values,
+    })
+}
+
+fn write_tween<T: CommandValue>(
+    f: &mut fmt::Formatter,
+    name: &str,
+    tween: &Tween<T>,
+) -> fmt::Result {
+    write!(f, "{},{},{},", name, tween.easing as u8, tween.start_time.0)?;
+    if tween.end_time != tween.start_time {
+        write!(f, "{}", tween.end_time.0)?;
+    }
+    for value in tween.values.iter() {
+        write!(f, ",")?;
+        value.write_fields(f)?;
+    }
+    Ok(())
+}
+
+impl FromStr for Command {
+    type Err = ParseError;
+
+    /// Parses a single command line, with the leading indentation already removed.
+    ///
+    /// Commands nested under loops and triggers are not attached here; that is done by whoever
+    /// is tracking the indentation.
+    fn from_str(line: &str) -> Result<Command, Self::Err> {
+        let parts = line.trim_end().split(',').collect::<Vec<_>>();
+        let args = &parts[1..];
+
+        Ok(match parts[0] {
+            "F" => Command::Fade(parse_tween(args)?),
+            "M" => Command::Move(parse_tween(args)?),
+            "MX" => Command::MoveX(parse_tween(args)?),
+            "MY" => Command::MoveY(parse_tween(args)?),
+            "S" => Command::Scale(parse_tween(args)?),
+            "V" => Command::VectorScale(parse_tween(args)?),
+            "R" => Command::Rotate(parse_tween(args)?),
+            "C" => Command::Color(parse_tween(args)?),
+            "P" => Command::Parameter(parse_tween(args)?),
+            "L" => {
+                let start_time = parse_field::<i32>(&parts, 1, "loop start time")?;
+                let loop_count = parse_field::<u32>(&parts, 2, "loop count")?;
+                Command::Loop(LoopInfo {
+                    start_time: Millis(start_time),
+                    loop_count,
+                    commands: Vec::new(),
+                })
+            }
+            "T" => {
+                let trigger = field(&parts, 1, "trigger type")?.to_string();
+                let start_time = parse_field::<i32>(&parts, 2, "trigger start time")?;
+                let end_time = parse_field::<i32>(&parts, 3, "trigger end time")?;
+                let group = match parts.get(4) {
+                    Some(s) if !s.is_empty() => {
+                        Some(parse_field::<i32>(&parts, 4, "trigger group")?)
+                    }
+                    _ => None,
+                };
+                Command::Trigger(TriggerInfo {
+                    trigger,
+                    start_time: Millis(start_time),
+                    end_time: Millis(end_time),
+                    group,
+                    commands: Vec::new(),
+                })
+            }
+            s => return Err(ParseError::InvalidCommand(s.to_owned())),
+        })
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
@@ -0,0 +1,826 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+use crate::color::Color;
+use crate::events::Event;
+use crate::hitsounds::{Additions, SampleSet};
+use crate::math::Point;
+use crate::timing::Millis;
+
+use super::{
+    command_bounds, Command, Easing, LoopType, Parameter, Storyboard, TriggerCondition,
+    TriggerInfo, Tween,
+};
+
+impl Easing {
+    /// Applies this easing function to a progress value between 0 and 1.
+    ///
+    /// These are the same curves that the game uses.
+    pub fn apply(self, t: f64) -> f64 {
+        const ELASTIC_CONST: f64 = 2.0 * PI / 0.3;
+        const ELASTIC_CONST2: f64 = 0.3 / 4.0;
+        const BACK_CONST: f64 = 1.70158;
+        const BACK_CONST2: f64 = BACK_CONST * 1.525;
+        const BOUNCE_CONST: f64 = 1.0 / 2.75;
+
+        // used to make the expo and elastic curves start and end exactly at 0 and 1
+        const EXPO_OFFSET: f64 = 0.00048828125;
+        const ELASTIC_OFFSET_FULL: f64 = 0.00048828125;
+        const ELASTIC_OFFSET_HALF: f64 = 0.000244140625;
+        const ELASTIC_OFFSET_QUARTER: f64 = 0.0001220703125;
+        const IN_OUT_ELASTIC_OFFSET: f64 = 0.000244140625;
+
+        fn out_bounce(t: f64) -> f64 {
+            if t < BOUNCE_CONST {
+                7.5625 * t * t
+            } else if t < 2.0 * BOUNCE_CONST {
+                let t = t - 1.5 * BOUNCE_CONST;
+                7.5625 * t * t + 0.75
+            } else if t < 2.5 * BOUNCE_CONST {
+                let t = t - 2.25 * BOUNCE_CONST;
+                7.5625 * t * t + 0.9375
+            } else {
+                let t = t - 2.625 * BOUNCE_CONST;
+                7.5625 * t * t + 0.984375
+            }
+        }
+
+        match self {
+            Easing::Linear => t,
+            Easing::EasingIn | Easing::QuadIn => t * t,
+            Easing::EasingOut | Easing::QuadOut => t * (2.0 - t),
+            Easing::QuadInOut => {
+                if t < 0.5 {
+                    t * t * 2.0
+                } else {
+                    let t = t - 1.0;
+                    t * t * -2.0 + 1.0
+                }
+            }
+            Easing::CubicIn => t.powi(3),
+            Easing::CubicOut => (t - 1.0).powi(3) + 1.0,
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    t.powi(3) * 4.0
+                } else {
+                    (t - 1.0).powi(3) * 4.0 + 1.0
+                }
+            }
+            Easing::QuartIn => t.powi(4),
+            Easing::QuartOut => 1.0 - (t - 1.0).powi(4),
+            Easing::QuartInOut => {
+                if t < 0.5 {
+                    t.powi(4) * 8.0
+                } else {
+                    (t - 1.0).powi(4) * -8.0 + 1.0
+                }
+            }
+            Easing::QuintIn => t.powi(5),
+            Easing::QuintOut => (t - 1.0).powi(5) + 1.0,
+            Easing::QuintInOut => {
+                if t < 0.5 {
+                    t.powi(5) * 16.0
+                } else {
+                    (t - 1.0).powi(5) * 16.0 + 1.0
+                }
+            }
+            Easing::SineIn => 1.0 - (t * PI * 0.5).cos(),
+            Easing::SineOut => (t * PI * 0.5).sin(),
+            Easing::SineInOut => 0.5 - 0.5 * (PI * t).cos(),
+            Easing::ExpoIn => 2.0f64.powf(10.0 * (t - 1.0)) + EXPO_OFFSET * (t - 1.0),
+            Easing::ExpoOut => -(2.0f64.powf(-10.0 * t)) + 1.0 + EXPO_OFFSET * t,
+            Easing::ExpoInOut => {
+                if t < 0.5 {
+                    0.5 * (2.0f64.powf(20.0 * t - 10.0) + EXPO_OFFSET * (2.0 * t - 1.0))
+                } else {
+                    1.0 - 0.5 * (2.0f64.powf(-20.0 * t + 10.0) + EXPO_OFFSET * (-2.0 * t + 1.0))
+                }
+            }
+            Easing::CircIn => 1.0 - (1.0 - t * t).sqrt(),
+            Easing::CircOut => (1.0 - (t - 1.0) * (t - 1.0)).sqrt(),
+            Easing::CircInOut => {
+                let t = t * 2.0;
+                if t < 1.0 {
+                    0.5 - 0.5 * (1.0 - t * t).sqrt()
+                } else {
+                    let t = t - 2.0;
+                    0.5 + 0.5 * (1.0 - t * t).sqrt()
+                }
+            }
+            Easing::ElasticIn => {
+                -(2.0f64.powf(-10.0 + 10.0 * t))
+                    * ((1.0 - ELASTIC_CONST2 - t) * ELASTIC_CONST).sin()
+                    + ELASTIC_OFFSET_FULL * (1.0 - t)
+            }
+            Easing::ElasticOut => {
+                2.0f64.powf(-10.0 * t) * ((t - ELASTIC_CONST2) * ELASTIC_CONST).sin() + 1.0
+                    - ELASTIC_OFFSET_FULL * t
+            }
+            Easing::ElasticHalfOut => {
+                2.0f64.powf(-10.0 * t) * ((0.5 * t - ELASTIC_CONST2) * ELASTIC_CONST).sin() + 1.0
+                    - ELASTIC_OFFSET_HALF * t
+            }
+            Easing::ElasticQuarterOut => {
+                2.0f64.powf(-10.0 * t) * ((0.25 * t - ELASTIC_CONST2) * ELASTIC_CONST).sin() + 1.0
+                    - ELASTIC_OFFSET_QUARTER * t
+            }
+            Easing::ElasticInOut => {
+                let t = t * 2.0;
+                if t < 1.0 {
+                    -0.5 * (2.0f64.powf(-10.0 + 10.0 * t)
+                        * ((1.0 - ELASTIC_CONST2 * 1.5 - t) * ELASTIC_CONST / 1.5).sin()
+                        - IN_OUT_ELASTIC_OFFSET * (1.0 - t))
+                } else {
+                    let t = t - 1.0;
+                    0.5 * (2.0f64.powf(-10.0 * t)
+                        * ((t - ELASTIC_CONST2 * 1.5) * ELASTIC_CONST / 1.5).sin()
+                        - IN_OUT_ELASTIC_OFFSET * t)
+                        + 1.0
+                }
+            }
+            Easing::BackIn => t * t * ((BACK_CONST + 1.0) * t - BACK_CONST),
+            Easing::BackOut => {
+                let t = t - 1.0;
+                t * t * ((BACK_CONST + 1.0) * t + BACK_CONST) + 1.0
+            }
+            Easing::BackInOut => {
+                let t = t * 2.0;
+                if t < 1.0 {
+                    0.5 * t * t * ((BACK_CONST2 + 1.0) * t - BACK_CONST2)
+                } else {
+                    let t = t - 2.0;
+                    0.5 * (t * t * ((BACK_CONST2 + 1.0) * t + BACK_CONST2) + 2.0)
+                }
+            }
+            Easing::BounceIn => 1.0 - out_bounce(1.0 - t),
+            Easing::BounceOut => out_bounce(t),
+            Easing::BounceInOut => {
+                if t < 0.5 {
+                    0.5 - 0.5 * out_bounce(1.0 - t * 2.0)
+                } else {
+                    out_bounce((t - 0.5) * 2.0) * 0.5 + 0.5
+                }
+            }
+        }
+    }
+}
+
+/// The most commands a single loop is unrolled into when building a [`SpriteTimeline`]. Later
+/// iterations of loops that repeat more often than this are left out.
+const MAX_UNROLLED_COMMANDS: usize = 250_000;
+
+/// Values that can be interpolated by storyboard commands.
+pub trait Interpolate: Copy {
+    /// Returns the value `t` of the way from `self` to `other`, where `t` is usually (but not
+    /// always, because of easing overshoot) between 0 and 1.
+    fn interpolate(self, other: Self, t: f64) -> Self;
+}
+
+impl Interpolate for f64 {
+    fn interpolate(self, other: Self, t: f64) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for Point<f64> {
+    fn interpolate(self, other: Self, t: f64) -> Self {
+        Point::new(
+            self.x.interpolate(other.x, t),
+            self.y.interpolate(other.y, t),
+        )
+    }
+}
+
+impl Interpolate for Color {
+    fn interpolate(self, other: Self, t: f64) -> Self {
+        let channel = |a: u8, b: u8| {
+            (a as f64)
+                .interpolate(b as f64, t)
+                .round()
+                .clamp(0.0, 255.0) as u8
+        };
+        Color::new(
+            channel(self.red, other.red),
+            channel(self.green, other.green),
+            channel(self.blue, other.blue),
+        )
+    }
+}
+
+/// How a sprite is blended with whatever is drawn beneath it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BlendMode {
+    /// Regular alpha blending
+    Alpha,
+
+    /// Additive blending (`P,...,A`)
+    Additive,
+}
+
+/// The state of a sprite or animation at a single point in time.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SpriteState {
+    /// Position in osu!pixels
+    pub pos: Point<f64>,
+
+    /// Horizontal and vertical scale, combining the `S` and `V` commands
+    pub scale: Point<f64>,
+
+    /// Rotation in radians, clockwise
+    pub rotation: f64,
+
+    /// Colour tint
+    pub color: Color,
+
+    /// Opacity from 0 to 1
+    pub opacity: f64,
+
+    /// Whether the image is flipped horizontally
+    pub flip_horizontal: bool,
+
+    /// Whether the image is flipped vertically
+    pub flip_vertical: bool,
+
+    /// How the sprite is blended
+    pub blend_mode: BlendMode,
+
+    /// The frame being shown, if this is an animation
+    pub frame: Option<u32>,
+}
+
+/// A trigger being fired during gameplay.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TriggerFiring {
+    /// When the trigger fired
+    pub time: Millis,
+
+    /// What caused the trigger to fire
+    pub kind: TriggerFiringKind,
+}
+
+/// The cause of a [`TriggerFiring`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TriggerFiringKind {
+    /// The player went from failing to passing
+    Passing,
+
+    /// The player went from passing to failing
+    Failing,
+
+    /// A hitsound was played
+    HitSound {
+        /// The sample set of the hitsound
+        sample_set: SampleSet,
+
+        /// The sample set of the additions
+        addition_set: SampleSet,
+
+        /// The additions that were played
+        additions: Additions,
+
+        /// The custom sample index
+        custom_index: i32,
+    },
+}
+
+impl TriggerCondition {
+    /// Whether the given firing satisfies this condition.
+    pub fn matches(&self, firing: &TriggerFiringKind) -> bool {
+        match (self, firing) {
+            (TriggerCondition::Passing, TriggerFiringKind::Passing) => true,
+            (TriggerCondition::Failing, TriggerFiringKind::Failing) => true,
+            (
+                TriggerCondition::HitSound {
+                    sample_set,
+                    addition_set,
+                    addition,
+                    custom_index,
+                },
+                TriggerFiringKind::HitSound {
+                    sample_set: fired_sample_set,
+                    addition_set: fired_addition_set,
+                    additions: fired_additions,
+                    custom_index: fired_custom_index,
+                },
+            ) => {
+                sample_set.is_none_or(|s| s == *fired_sample_set)
+                    && addition_set.is_none_or(|s| s == *fired_addition_set)
+                    && addition.is_none_or(|a| fired_additions.contains(a))
+                    && custom_index.is_none_or(|i| i == *fired_custom_index)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Segment<T> {
+    easing: Easing,
+    start_time: f64,
+    end_time: f64,
+    start: T,
+    end: T,
+}
+
+/// All the segments that control one property of a sprite.
+#[derive(Clone, Debug)]
+struct Timeline<T> {
+    segments: Vec<Segment<T>>,
+}
+
+impl<T> Default for Timeline<T> {
+    fn default() -> Self {
+        Timeline {
+            segments: Vec::new(),
+        }
+    }
+}
+
+impl<T: Interpolate> Timeline<T> {
+    fn push<U: Copy>(&mut self, tween: &Tween<U>, offset: f64, map: impl Fn(U) -> T) {
+        let start_time = tween.start_time.0 as f64 + offset;
+        let duration = tween.end_time.0 as f64 - tween.start_time.0 as f64;
+
+        if tween.values.len() == 1 {
+            let value = map(tween.values[0]);
+            self.segments.push(Segment {
+                easing: tween.easing,
+                start_time,
+                end_time: start_time + duration,
+                start: value,
+                end: value,
+            });
+            return;
+        }
+
+        for (i, pair) in tween.values.windows(2).enumerate() {
+            let start_time = start_time + duration * i as f64;
+            self.segments.push(Segment {
+                easing: tween.easing,
+                start_time,
+                end_time: start_time + duration,
+                start: map(pair[0]),
+                end: map(pair[1]),
+            });
+        }
+    }
+
+    fn sort(&mut self) {
+        self.segments
+            .sort_by(|a, b| a.start_time.partial_cmp(&b.start_time).unwrap());
+    }
+
+    fn first(&self) -> Option<&Segment<T>> {
+        self.segments.first()
+    }
+
+    fn bounds(&self) -> Option<(f64, f64)> {
+        let start = self.segments.first()?.start_time;
+        let end = self
+            .segments
+            .iter()
+            .map(|s| s.end_time)
+            .fold(f64::NEG_INFINITY, f64::max);
+        Some((start, end))
+    }
+
+    /// The value of this property at the given time, or `None` if no command ever touches it.
+    ///
+    /// Before the first command starts, the property holds that command's start value.
+    fn value_at(&self, time: f64) -> Option<T> {
+        let idx = self.segments.partition_point(|s| s.start_time <= time);
+        let segment = match idx {
+            0 => return self.segments.first().map(|s| s.start),
+            i => &self.segments[i - 1],
+        };
+
+        if time >= segment.end_time {
+            return Some(segment.end);
+        }
+
+        let progress = (time - segment.start_time) / (segment.end_time - segment.start_time);
+        Some(
+            segment
+                .start
+                .interpolate(segment.end, segment.easing.apply(progress)),
+        )
+    }
+
+    /// The value this property has been given by segments that start strictly before the given
+    /// time, if any.
+    fn value_before(&self, time: f64) -> Option<T> {
+        let idx = self.segments.partition_point(|s| s.start_time < time);
+        let segment = &self.segments[idx.checked_sub(1)?];
+        if time >= segment.end_time {
+            return Some(segment.end);
+        }
+
+        let progress = (time - segment.start_time) / (segment.end_time - segment.start_time);
+        Some(
+            segment
+                .start
+                .interpolate(segment.end, segment.easing.apply(progress)),
+        )
+    }
+
+    fn key_times(&self) -> impl Iterator<Item = f64> + '_ {
+        self.segments
+            .iter()
+            .flat_map(|s| vec![s.start_time, (s.start_time + s.end_time) / 2.0, s.end_time])
+    }
+}
+
+/// Whether a tween only ever holds the value the property already had before it started.
+fn holds_previous_value<T, U>(
+    timeline: &Timeline<T>,
+    tween: &Tween<U>,
+    map: impl Fn(U) -> T,
+) -> bool
+where
+    T: Interpolate + PartialEq,
+    U: Copy,
+{
+    let start_time = tween.start_time.0 as f64;
+    match timeline.value_before(start_time) {
+        Some(previous) => tween.values.iter().all(|v| map(*v) == previous),
+        None => false,
+    }
+}
+
+/// The time ranges a parameter is active for.
+#[derive(Clone, Debug, Default)]
+struct ParameterTimeline {
+    ranges: Vec<(f64, f64)>,
+}
+
+impl ParameterTimeline {
+    fn is_active(&self, time: f64) -> bool {
+        self.ranges.iter().any(|(start, end)| {
+            // parameter commands that start and end at the same time apply for the whole lifetime
+            start == end || (*start <= time && time < *end)
+        })
+    }
+}
+
+/// The fully expanded timeline of a sprite or animation, which can be queried for the
+/// sprite's state at any time.
+///
+/// Loops are unrolled when the timeline is built, stopping after a quarter of a million commands
+/// or once the iterations start after the latest time there is. Triggers only have an effect if
+/// the timeline is built with [`SpriteTimeline::with_triggers`].
+#[derive(Clone, Debug)]
+pub struct SpriteTimeline {
+    base_pos: Point<f64>,
+    animation: Option<(u32, f64, LoopType)>,
+    start_time: f64,
+    end_time: f64,
+
+    x: Timeline<f64>,
+    y: Timeline<f64>,
+    scale: Timeline<f64>,
+    vector_scale: Timeline<Point<f64>>,
+    rotation: Timeline<f64>,
+    color: Timeline<Color>,
+    alpha: Timeline<f64>,
+    flip_horizontal: ParameterTimeline,
+    flip_vertical: ParameterTimeline,
+    additive: ParameterTimeline,
+}
+
+impl SpriteTimeline {
+    /// Builds the timeline of a sprite or animation event, ignoring any triggers.
+    ///
+    /// Returns `None` if the event isn't a sprite or animation.
+    pub fn new(event: &Event) -> Option<SpriteTimeline> {
+        SpriteTimeline::with_triggers(event, &[])
+    }
+
+    /// Builds the timeline of a sprite or animation event, running the commands of any trigger
+    /// that one of the given firings activates.
+    ///
+    /// Only one trigger in a trigger group can be active at once: each activation cuts off the
+    /// commands of the previous activation in the same group.
+    pub fn with_triggers(event: &Event, firings: &[TriggerFiring]) -> Option<SpriteTimeline> {
+        let (base_pos, animation, commands) = match event {
+            Event::Sprite(evt) => (evt.pos, None, &evt.commands),
+            Event::Animation(evt) => (
+                evt.pos,
+                Some((evt.frame_count, evt.frame_delay, evt.loop_type)),
+                &evt.commands,
+            ),
+            _ => return None,
+        };
+
+        let mut timeline = SpriteTimeline {
+            base_pos,
+            animation,
+            start_time: 0.0,
+            end_time: 0.0,
+            x: Timeline::default(),
+            y: Timeline::default(),
+            scale: Timeline::default(),
+            vector_scale: Timeline::default(),
+            rotation: Timeline::default(),
+            color: Timeline::default(),
+            alpha: Timeline::default(),
+            flip_horizontal: ParameterTimeline::default(),
+            flip_vertical: ParameterTimeline::default(),
+            additive: ParameterTimeline::default(),
+        };
+
+        let mut triggers = Vec::new();
+        timeline.add_commands(commands, 0.0, None, &mut triggers);
+        timeline.compute_lifetime();
+
+        for (trigger, time, cutoff) in trigger_activations(&triggers, firings) {
+            timeline.add_commands(&trigger.commands, time, cutoff, &mut Vec::new());
+        }
+
+        timeline.x.sort();
+        timeline.y.sort();
+        timeline.scale.sort();
+        timeline.vector_scale.sort();
+        timeline.rotation.sort();
+        timeline.color.sort();
+        timeline.alpha.sort();
+        Some(timeline)
+    }
+
+    fn add_commands<'a>(
+        &mut self,
+        commands: &'a [Command],
+        offset: f64,
+        cutoff: Option<f64>,
+        triggers: &mut Vec<&'a TriggerInfo>,
+    ) {
+        for command in commands.iter() {
+            if let Some(cutoff) = cutoff {
+                if command.start_time().0 as f64 + offset >= cutoff {
+                    continue;
+                }
+            }
+
+            match command {
+                Command::Fade(t) => self.alpha.push(t, offset, |v| v),
+                Command::Move(t) => {
+                    self.x.push(t, offset, |p| p.x);
+                    self.y.push(t, offset, |p| p.y);
+                }
+                Command::MoveX(t) => self.x.push(t, offset, |v| v),
+                Command::MoveY(t) => self.y.push(t, offset, |v| v),
+                Command::Scale(t) => self.scale.push(t, offset, |v| v),
+                Command::VectorScale(t) => self.vector_scale.push(t, offset, |v| v),
+                Command::Rotate(t) => self.rotation.push(t, offset, |v| v),
+                Command::Color(t) => self.color.push(t, offset, |v| v),
+                Command::Parameter(t) => {
+                    let range = (
+                        t.start_time.0 as f64 + offset,
+                        t.last_end_time().0 as f64 + offset,
+                    );
+                    for parameter in t.values.iter() {
+                        match parameter {
+                            Parameter::FlipHorizontal => &mut self.flip_horizontal,
+                            Parameter::FlipVertical => &mut self.flip_vertical,
+                            Parameter::AdditiveBlend => &mut self.additive,
+                        }
+                        .ranges
+                        .push(range);
+                    }
+                }
+                Command::Loop(info) => {
+                    let duration = info.iteration_duration() as f64;
+                    let max_iterations = MAX_UNROLLED_COMMANDS / info.commands.len().max(1);
+                    // iterations that start after the cutoff (or the latest time there is) can't
+                    // do anything
+                    let last_start = cutoff.unwrap_or(i32::MAX as f64);
+                    let first_command =
+                        command_bounds(&info.commands).map_or(0, |(start, _)| start.0);
+                    for i in 0..(info.iterations() as usize).min(max_iterations) {
+                        let offset = offset + info.start_time.0 as f64 + duration * i as f64;
+                        if offset + first_command as f64 >= last_start {
+                            break;
+                        }
+                        self.add_commands(&info.commands, offset, cutoff, triggers);
+                    }
+                }
+                Command::Trigger(info) => triggers.push(info),
+            }
+        }
+    }
+
+    /// Works out when the sprite exists, from the commands that were added so far.
+    ///
+    /// The sprite exists from the start of its earliest command to the end of its latest one,
+    /// except that if the first fade command starts at an opacity of 0, the sprite doesn't
+    /// exist until that fade starts.
+    fn compute_lifetime(&mut self) {
+        let bounds = [
+            self.x.bounds(),
+            self.y.bounds(),
+            self.scale.bounds(),
+            self.vector_scale.bounds(),
+            self.rotation.bounds(),
+            self.color.bounds(),
+            self.alpha.bounds(),
+        ];
+        let parameters = self
+            .flip_horizontal
+            .ranges
+            .iter()
+            .chain(self.flip_vertical.ranges.iter())
+            .chain(self.additive.ranges.iter())
+            .copied();
+
+        let mut start_time = f64::INFINITY;
+        let mut end_time = f64::NEG_INFINITY;
+        for (start, end) in bounds.iter().flatten().copied().chain(parameters) {
+            start_time = start_time.min(start);
+            end_time = end_time.max(end);
+        }
+
+        self.alpha.sort();
+        if let Some(first_fade) = self.alpha.first() {
+            if first_fade.start == 0.0 {
+                start_time = start_time.max(first_fade.start_time);
+            }
+        }
+
+        self.start_time = start_time;
+        self.end_time = end_time;
+    }
+
+    /// Returns every time at which something about the sprite might change, within its lifetime.
+    pub(crate) fn key_times(&self) -> Vec<f64> {
+        let mut times = self
+            .x
+            .key_times()
+            .chain(self.y.key_times())
+            .chain(self.scale.key_times())
+            .chain(self.vector_scale.key_times())
+            .chain(self.rotation.key_times())
+            .chain(self.color.key_times())
+            .chain(self.alpha.key_times())
+            .chain(vec![self.start_time])
+            .filter(|t| *t >= self.start_time && *t < self.end_time)
+            .collect::<Vec<_>>();
+        times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        times.dedup();
+        times
+    }
+
+    /// Whether the given (top-level) command of this sprite has no effect, because it only
+    /// holds the value that was already there and doesn't extend the sprite's lifetime.
+    pub(crate) fn is_redundant(&self, command: &Command) -> bool {
+        let start = command.start_time().0 as f64;
+        let end = command.end_time().0 as f64;
+        if start < self.start_time || end > self.end_time {
+            return false;
+        }
+
+        match command {
+            Command::Fade(t) => holds_previous_value(&self.alpha, t, |v| v),
+            Command::Move(t) => {
+                holds_previous_value(&self.x, t, |p| p.x)
+                    && holds_previous_value(&self.y, t, |p| p.y)
+            }
+            Command::MoveX(t) => holds_previous_value(&self.x, t, |v| v),
+            Command::MoveY(t) => holds_previous_value(&self.y, t, |v| v),
+            Command::Scale(t) => holds_previous_value(&self.scale, t, |v| v),
+            Command::VectorScale(t) => holds_previous_value(&self.vector_scale, t, |v| v),
+            Command::Rotate(t) => holds_previous_value(&self.rotation, t, |v| v),
+            Command::Color(t) => holds_previous_value(&self.color, t, |v| v),
+            Command::Parameter(_) => false,
+            Command::Loop(info) => info.commands.is_empty(),
+            Command::Trigger(info) => info.commands.is_empty() || info.condition().is_none(),
+        }
+    }
+
+    /// The time at which the sprite starts existing
+    pub fn start_time(&self) -> f64 {
+        self.start_time
+    }
+
+    /// The time at which the sprite stops existing
+    pub fn end_time(&self) -> f64 {
+        self.end_time
+    }
+
+    /// Returns the sprite's state at the given time, or `None` if the sprite doesn't exist at
+    /// that time.
+    pub fn state_at(&self, time: impl Into<Millis>) -> Option<SpriteState> {
+        let time = time.into().0 as f64;
+        if time < self.start_time || time >= self.end_time {
+            return None;
+        }
+
+        let scale = self.scale.value_at(time).unwrap_or(1.0);
+        let vector_scale = self
+            .vector_scale
+            .value_at(time)
+            .unwrap_or_else(|| Point::new(1.0, 1.0));
+
+        let frame = self.animation.map(|(frame_count, frame_delay, loop_type)| {
+            let frame_count = frame_count.max(1);
+            let frame = if frame_delay > 0.0 {
+                ((time - self.start_time) / frame_delay) as u32
+            } else {
+                0
+            };
+            match loop_type {
+                LoopType::LoopForever => frame % frame_count,
+                LoopType::LoopOnce => frame.min(frame_count - 1),
+            }
+        });
+
+        Some(SpriteState {
+            pos: Point::new(
+                self.x.value_at(time).unwrap_or(self.base_pos.x),
+                self.y.value_at(time).unwrap_or(self.base_pos.y),
+            ),
+            scale: vector_scale * scale,
+            rotation: self.rotation.value_at(time).unwrap_or(0.0),
+            color: self
+                .color
+                .value_at(time)
+                .unwrap_or_else(|| Color::new(255, 255, 255)),
+            opacity: self.alpha.value_at(time).unwrap_or(1.0),
+            flip_horizontal: self.flip_horizontal.is_active(time),
+            flip_vertical: self.flip_vertical.is_active(time),
+            blend_mode: if self.additive.is_active(time) {
+                BlendMode::Additive
+            } else {
+                BlendMode::Alpha
+            },
+            frame,
+        })
+    }
+}
+
+/// Works out which triggers are activated by the given firings, and when.
+///
+/// Each activation comes with the time at which the next activation in the same trigger group
+/// cuts it off, if any.
+fn trigger_activations<'a>(
+    triggers: &[&'a TriggerInfo],
+    firings: &[TriggerFiring],
+) -> Vec<(&'a TriggerInfo, f64, Option<f64>)> {
+    let mut firings = firings.iter().collect::<Vec<_>>();
+    firings.sort_by_key(|firing| firing.time);
+
+    let mut activations: Vec<(&'a TriggerInfo, f64, Option<f64>)> = Vec::new();
+    let mut last_in_group = HashMap::new();
+    for firing in firings {
+        for trigger in triggers.iter() {
+            if firing.time < trigger.start_time || firing.time > trigger.end_time {
+                continue;
+            }
+            match trigger.condition() {
+                Some(condition) if condition.matches(&firing.kind) => (),
+                _ => continue,
+            }
+
+            let time = firing.time.0 as f64;
+            let group = trigger.group.unwrap_or(0);
+            if let Some(&prev) = last_in_group.get(&group) {
+                let prev: &mut (&TriggerInfo, f64, Option<f64>) = &mut activations[prev];
+                prev.2 = Some(time);
+            }
+            last_in_group.insert(group, activations.len());
+            activations.push((trigger, time, None));
+        }
+    }
+
+    activations
+}
+
+impl Storyboard {
+    /// Returns the state of every sprite and animation that exists at the given time, along
+    /// with its index into `events`.
+    ///
+    /// Sprites are returned in the order they're declared, so sorting them by their event's
+    /// layer (with a stable sort) gives the order the game draws them in.
+    pub fn states_at(&self, time: impl Into<Millis>) -> Vec<(usize, SpriteState)> {
+        self.states_at_with_triggers(time, &[])
+    }
+
+    /// Same as [`Storyboard::states_at`], but runs triggers activated by the given firings.
+    pub fn states_at_with_triggers(
+        &self,
+        time: impl Into<Millis>,
+        firings: &[TriggerFiring],
+    ) -> Vec<(usize, SpriteState)> {
+        let time = time.into();
+        self.events
+            .iter()
+            .enumerate()
+            .filter_map(|(i, evt)| {
+                let timeline = SpriteTimeline::with_triggers(evt, firings)?;
+                Some((i, timeline.state_at(time)?))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,191 @@
+//! Storyboard objects and commands.
+//!
+//! Storyboard sprites, animations and samples are stored as [`Event`][crate::events::Event]s,
+//! either in the `[Events]` section of a .osu file or in a separate .osb file. Sprites and
+//! animations carry a list of [`Command`]s that describe how they change over time.
+
+mod analysis;
+mod builder;
+mod command;
+mod eval;
+mod osb;
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::errors::ParseError;
+use crate::math::Point;
+
+pub use self::analysis::*;
+pub use self::builder::*;
+pub use self::command::*;
+pub use self::eval::*;
+pub use self::osb::*;
+
+/// The layer a storyboard object is drawn on.
+///
+/// Layers are drawn in the order they're declared here, so objects on `Foreground` are drawn
+/// above objects on `Background`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Layer {
+    /// Drawn behind everything else
+    Background = 0,
+
+    /// Only shown while the player is failing
+    Fail = 1,
+
+    /// Only shown while the player is passing
+    Pass = 2,
+
+    /// Drawn above the other storyboard layers, but below hit objects
+    Foreground = 3,
+
+    /// Drawn above hit objects
+    Overlay = 4,
+}
+
+impl FromStr for Layer {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Layer, Self::Err> {
+        Ok(match s {
+            "Background" | "0" => Layer::Background,
+            "Fail" | "1" => Layer::Fail,
+            "Pass" | "2" => Layer::Pass,
+            "Foreground" | "3" => Layer::Foreground,
+            "Overlay" | "4" => Layer::Overlay,
+            s => return Err(ParseError::InvalidLayer(s.to_owned())),
+        })
+    }
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Layer::Background => "Background",
+                Layer::Fail => "Fail",
+                Layer::Pass => "Pass",
+                Layer::Foreground => "Foreground",
+                Layer::Overlay => "Overlay",
+            }
+        )
+    }
+}
+
+/// The point on a sprite's image that its position refers to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[allow(missing_docs)]
+pub enum Origin {
+    TopLeft = 0,
+    Centre = 1,
+    CentreLeft = 2,
+    TopRight = 3,
+    BottomCentre = 4,
+    TopCentre = 5,
+    Custom = 6,
+    CentreRight = 7,
+    BottomLeft = 8,
+    BottomRight = 9,
+}
+
+impl Origin {
+    /// Where the origin is on the image, as a fraction of the image's width and height.
+    ///
+    /// `Custom` behaves like `TopLeft`.
+    pub fn fraction(&self) -> Point<f64> {
+        match self {
+            Origin::TopLeft | Origin::Custom => Point::new(0.0, 0.0),
+            Origin::Centre => Point::new(0.5, 0.5),
+            Origin::CentreLeft => Point::new(0.0, 0.5),
+            Origin::TopRight => Point::new(1.0, 0.0),
+            Origin::BottomCentre => Point::new(0.5, 1.0),
+            Origin::TopCentre => Point::new(0.5, 0.0),
+            Origin::CentreRight => Point::new(1.0, 0.5),
+            Origin::BottomLeft => Point::new(0.0, 1.0),
+            Origin::BottomRight => Point::new(1.0, 1.0),
+        }
+    }
+}
+
+impl FromStr for Origin {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Origin, Self::Err> {
+        Ok(match s {
+            "TopLeft" | "0" => Origin::TopLeft,
+            "Centre" | "1" => Origin::Centre,
+            "CentreLeft" | "2" => Origin::CentreLeft,
+            "TopRight" | "3" => Origin::TopRight,
+            "BottomCentre" | "4" => Origin::BottomCentre,
+            "TopCentre" | "5" => Origin::TopCentre,
+            "Custom" | "6" => Origin::Custom,
+            "CentreRight" | "7" => Origin::CentreRight,
+            "BottomLeft" | "8" => Origin::BottomLeft,
+            "BottomRight" | "9" => Origin::BottomRight,
+            s => return Err(ParseError::InvalidOrigin(s.to_owned())),
+        })
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Origin::TopLeft => "TopLeft",
+                Origin::Centre => "Centre",
+                Origin::CentreLeft => "CentreLeft",
+                Origin::TopRight => "TopRight",
+                Origin::BottomCentre => "BottomCentre",
+                Origin::TopCentre => "TopCentre",
+                Origin::Custom => "Custom",
+                Origin::CentreRight => "CentreRight",
+                Origin::BottomLeft => "BottomLeft",
+                Origin::BottomRight => "BottomRight",
+            }
+        )
+    }
+}
+
+/// How an animation behaves after showing its last frame.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LoopType {
+    /// Start again from the first frame
+    #[default]
+    LoopForever,
+
+    /// Stay on the last frame
+    LoopOnce,
+}
+
+impl FromStr for LoopType {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<LoopType, Self::Err> {
+        Ok(match s {
+            "LoopForever" | "0" => LoopType::LoopForever,
+            "LoopOnce" | "1" => LoopType::LoopOnce,
+            s => return Err(ParseError::InvalidLoopType(s.to_owned())),
+        })
+    }
+}
+
+impl fmt::Display for LoopType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                LoopType::LoopForever => "LoopForever",
+                LoopType::LoopOnce => "LoopOnce",
+            }
+        )
+    }
+}
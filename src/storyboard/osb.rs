@@ -0,0 +1,164 @@
+use std::borrow::Cow;
+use std::fmt::{self, Write as _};
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+use std::str::FromStr;
+
+use crate::beatmap::{Beatmap, BeatmapParseError, SECTION_HEADER_RGX};
+use crate::events::{parse_event_line, Event};
+use crate::utils::write_fmt_io;
+
+/// A storyboard loaded from a .osb file.
+///
+/// The .osb file holds the parts of the storyboard that are shared between all the difficulties
+/// in a mapset. Storyboard events that only apply to a single difficulty live in that
+/// difficulty's [`Beatmap::events`][crate::beatmap::Beatmap::events]; use
+/// [`Storyboard::merge`] to get everything the game will actually show.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Storyboard {
+    /// Variables declared in the `[Variables]` section, in declaration order.
+    ///
+    /// These have already been substituted into `events`, and are only kept so they can be
+    /// written back out.
+    pub variables: Vec<(String, String)>,
+
+    /// The events declared in the `[Events]` section
+    pub events: Vec<Event>,
+}
+
+/// Parses a `$name=value` line from a `[Variables]` section.
+pub(crate) fn parse_variable(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+    if !line.starts_with('$') {
+        return None;
+    }
+
+    let (name, value) = line.split_once('=')?;
+    Some((name.to_owned(), value.to_owned()))
+}
+
+/// Substitutes variables into a line, in declaration order (which is what the game does).
+pub(crate) fn expand_variables<'a>(line: &'a str, variables: &[(String, String)]) -> Cow<'a, str> {
+    if variables.is_empty() || !line.contains('$') {
+        return Cow::Borrowed(line);
+    }
+
+    let mut line = line.to_owned();
+    for (name, value) in variables.iter() {
+        line = line.replace(name.as_str(), value);
+    }
+    Cow::Owned(line)
+}
+
+impl FromStr for Storyboard {
+    type Err = BeatmapParseError;
+
+    fn from_str(input: &str) -> Result<Storyboard, Self::Err> {
+        let mut curs = Cursor::new(input);
+        Storyboard::parse(&mut curs)
+    }
+}
+
+impl Storyboard {
+    /// Parse a storyboard from any `Read`er
+    pub fn parse(reader: impl Read) -> Result<Storyboard, BeatmapParseError> {
+        let reader = BufReader::new(reader);
+
+        let mut section = String::new();
+        let mut storyboard = Storyboard::default();
+
+        for (i, line) in reader.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.map_err(|err| BeatmapParseError {
+                line: line_no,
+                column: None,
+                inner: err.into(),
+            })?;
+            let line = line.as_ref();
+
+            if let Some(captures) = SECTION_HEADER_RGX.captures(line) {
+                section = String::from(&captures["name"]);
+                continue;
+            }
+
+            // skip empty lines and comments
+            if line.trim().is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            match section.as_ref() {
+                "Variables" => {
+                    if let Some(variable) = parse_variable(line) {
+                        storyboard.variables.push(variable);
+                    }
+                }
+                "Events" => {
+                    let expanded = expand_variables(line, &storyboard.variables);
+                    // columns in the expanded line only match the file if nothing was substituted
+                    let text = Some(line).filter(|line| *line == expanded);
+                    parse_event_line(&mut storyboard.events, &expanded)
+                        .map_err(|err| BeatmapParseError::in_line(line_no, text, err))?;
+                }
+                _ => (),
+            }
+        }
+
+        Ok(storyboard)
+    }
+
+    /// Write this storyboard to any `Write`r, line by line
+    pub fn write(&self, w: impl Write) -> Result<(), std::io::Error> {
+        write_fmt_io(w, |f| write!(f, "{}", self))
+    }
+
+    /// Combines this storyboard with the difficulty-specific storyboard events in the given
+    /// beatmap.
+    ///
+    /// The beatmap's sprites, animations and samples are placed after the ones from this
+    /// storyboard, so they're drawn on top of them within the same layer, just like in game. The
+    /// beatmap's background and video are included too, since they're shown behind the
+    /// storyboard.
+    pub fn merge(&self, beatmap: &Beatmap) -> Storyboard {
+        let mut events = self.events.clone();
+        events.extend(
+            beatmap
+                .events
+                .iter()
+                .filter(|evt| {
+                    matches!(
+                        evt,
+                        Event::Background(_)
+                            | Event::Video(_)
+                            | Event::Sprite(_)
+                            | Event::Animation(_)
+                            | Event::Sample(_)
+                    )
+                })
+                .cloned(),
+        );
+
+        Storyboard {
+            variables: self.variables.clone(),
+            events,
+        }
+    }
+}
+
+impl fmt::Display for Storyboard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.variables.is_empty() {
+            writeln!(f, "[Variables]")?;
+            for (name, value) in self.variables.iter() {
+                writeln!(f, "{}={}", name, value)?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f, "[Events]")?;
+        for event in self.events.iter() {
+            writeln!(f, "{}", event)?;
+        }
+
+        Ok(())
+    }
+}
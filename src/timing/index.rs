@@ -0,0 +1,238 @@
+use std::borrow::Cow;
+
+use crate::hitsounds::SampleSet;
+
+use super::{InheritedTimingInfo, Time, TimingPoint, TimingPointKind, UninheritedTimingInfo};
+
+/// How close (in beats) a position has to be to a whole beat or measure to be counted as on it,
+/// so that rounding errors don't put it at the end of the one before.
+pub(super) const EPSILON: f64 = 1e-6;
+
+/// The slowest slider velocity multiplier the game allows.
+const MIN_SLIDER_VELOCITY: f64 = 0.1;
+
+/// The fastest slider velocity multiplier the game allows.
+const MAX_SLIDER_VELOCITY: f64 = 10.0;
+
+/// Where a moment falls in the beats and measures of its timing section, see
+/// [`TimingIndex::beat_position_at`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BeatPosition {
+    /// When the timing section (the uninherited timing point) starts
+    pub section_start: Time,
+
+    /// How many beats into the section it is, which can be fractional
+    pub beats: f64,
+
+    /// Which measure of the section it's in, counting from 0
+    pub measure: i64,
+
+    /// How many beats into the measure it is, from 0 up to the meter
+    pub beat_in_measure: f64,
+}
+
+/// The sample settings in effect at some time, see [`TimingIndex::samples_at`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimingSamples {
+    /// The sample set of hits that don't set their own
+    pub sample_set: SampleSet,
+
+    /// The custom sample index of hits that don't set their own
+    pub sample_index: u32,
+
+    /// The volume of hits that don't set their own
+    pub volume: u16,
+}
+
+/// Answers questions about the timing of a beatmap at any time, by binary search.
+///
+/// Building the index goes through the timing points once, so build one index and reuse it when
+/// looking up many times, like [`Beatmap::timing_index`][crate::beatmap::Beatmap::timing_index]
+/// does.
+///
+/// The timing points are expected to be sorted by time, like they are in
+/// [`Beatmap::timing_points`][crate::beatmap::Beatmap::timing_points] after parsing. Where two
+/// timing points are at the same time, the later one in the list wins.
+#[derive(Clone, Debug)]
+pub struct TimingIndex<'a> {
+    pub(super) points: &'a [TimingPoint],
+
+    /// The timing sections, if they've been indexed
+    sections: Option<Sections>,
+}
+
+/// Where the timing sections (uninherited timing points) of a map are.
+#[derive(Clone, Debug)]
+pub(super) struct Sections {
+    /// The positions of the uninherited points in the timing points
+    pub(super) uninherited: Vec<usize>,
+
+    /// The number of the first measure of each timing section, counting across the whole map
+    pub(super) measure_starts: Vec<i64>,
+}
+
+impl Sections {
+    fn new(points: &[TimingPoint]) -> Self {
+        let uninherited = points
+            .iter()
+            .enumerate()
+            .filter(|(_, tp)| matches!(tp.kind, TimingPointKind::Uninherited(_)))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        // every section starts a new measure, even if the one before it isn't finished
+        let mut measure_starts = vec![0];
+        for pair in uninherited.windows(2) {
+            let (start, end) = (&points[pair[0]], &points[pair[1]]);
+            let (mpb, meter) = section_timing(start);
+            let measures = ((end.time.0 - start.time.0) / (mpb * meter) - EPSILON).ceil();
+            let last = measure_starts[measure_starts.len() - 1];
+            measure_starts.push(last + measures.max(0.0) as i64);
+        }
+        measure_starts.truncate(uninherited.len());
+
+        Sections {
+            uninherited,
+            measure_starts,
+        }
+    }
+}
+
+impl<'a> TimingIndex<'a> {
+    /// Builds an index over the given timing points.
+    pub fn new(points: &'a [TimingPoint]) -> Self {
+        TimingIndex {
+            points,
+            sections: Some(Sections::new(points)),
+        }
+    }
+
+    /// A lookup over the given timing points that doesn't index the timing sections, for looking
+    /// up a single time without going through all of the points. Finding the timing section
+    /// searches back from the time instead.
+    pub(crate) fn unindexed(points: &'a [TimingPoint]) -> Self {
+        TimingIndex {
+            points,
+            sections: None,
+        }
+    }
+
+    /// The timing sections, indexing them now if they haven't been.
+    pub(super) fn sections(&self) -> Cow<'_, Sections> {
+        match &self.sections {
+            Some(sections) => Cow::Borrowed(sections),
+            None => Cow::Owned(Sections::new(self.points)),
+        }
+    }
+
+    /// The timing points in the index.
+    pub fn points(&self) -> &'a [TimingPoint] {
+        self.points
+    }
+
+    /// The last timing point (inherited or not) at or before the given time.
+    pub fn point_at(&self, time: impl Into<Time>) -> Option<&'a TimingPoint> {
+        let index = self.points_until(time.into());
+        index.checked_sub(1).map(|i| &self.points[i])
+    }
+
+    /// How many timing points there are at or before the given time.
+    fn points_until(&self, time: Time) -> usize {
+        self.points.partition_point(|tp| tp.time <= time)
+    }
+
+    /// The uninherited timing point whose timing section the given time is in, or `None` if it's
+    /// before the first one.
+    pub fn uninherited_at(&self, time: impl Into<Time>) -> Option<&'a TimingPoint> {
+        let time = time.into();
+        match &self.sections {
+            Some(sections) => {
+                let uninherited = &sections.uninherited;
+                let index = uninherited.partition_point(|i| self.points[*i].time <= time);
+                index.checked_sub(1).map(|i| &self.points[uninherited[i]])
+            }
+            None => self.points[..self.points_until(time)]
+                .iter()
+                .rev()
+                .find(|tp| matches!(tp.kind, TimingPointKind::Uninherited(_))),
+        }
+    }
+
+    /// The beat length (in milliseconds) and meter of the timing section the given time is in.
+    pub fn timing_at(&self, time: impl Into<Time>) -> Option<&'a UninheritedTimingInfo> {
+        match &self.uninherited_at(time)?.kind {
+            TimingPointKind::Uninherited(info) => Some(info),
+            TimingPointKind::Inherited(_) => unreachable!("only uninherited points are indexed"),
+        }
+    }
+
+    /// The BPM at the given time, or `None` if it's before the first uninherited timing point.
+    pub fn bpm_at(&self, time: impl Into<Time>) -> Option<f64> {
+        self.timing_at(time).map(|info| 60_000.0 / info.mpb)
+    }
+
+    /// The slider velocity multiplier at the given time. Uninherited timing points reset it to 1.
+    /// Like the game, it's kept between 0.1 and 10 however the timing point sets it.
+    pub fn slider_velocity_at(&self, time: impl Into<Time>) -> f64 {
+        match self.point_at(time).map(|tp| &tp.kind) {
+            Some(TimingPointKind::Inherited(InheritedTimingInfo { slider_velocity })) => {
+                slider_velocity.clamp(MIN_SLIDER_VELOCITY, MAX_SLIDER_VELOCITY)
+            }
+            Some(TimingPointKind::Uninherited(_)) | None => 1.0,
+        }
+    }
+
+    /// Whether kiai time is on at the given time.
+    pub fn kiai_at(&self, time: impl Into<Time>) -> bool {
+        self.point_at(time).is_some_and(|tp| tp.kiai)
+    }
+
+    /// The sample settings at the given time, or `None` if it's before the first timing point.
+    pub fn samples_at(&self, time: impl Into<Time>) -> Option<TimingSamples> {
+        self.point_at(time).map(|tp| TimingSamples {
+            sample_set: tp.sample_set,
+            sample_index: tp.sample_index,
+            volume: tp.volume,
+        })
+    }
+
+    /// Where the given time is in the beats and measures of its timing section. Times before the
+    /// first uninherited timing point are counted backwards from it, so they have negative beats.
+    pub fn beat_position_at(&self, time: impl Into<Time>) -> Option<BeatPosition> {
+        let time = time.into();
+        let sections = self.sections();
+        let point = &self.points[sections.uninherited[section_at(self.points, &sections, time)?]];
+        let (mpb, meter) = section_timing(point);
+
+        let beats = (time.0 - point.time.0) / mpb;
+        let measure = (beats / meter + EPSILON).floor();
+        Some(BeatPosition {
+            section_start: point.time,
+            beats,
+            measure: measure as i64,
+            beat_in_measure: (beats - measure * meter).max(0.0),
+        })
+    }
+}
+
+/// Which timing section (as an index into the uninherited points) the given time is counted in.
+/// Times before the first section are counted in it.
+pub(super) fn section_at(points: &[TimingPoint], sections: &Sections, time: Time) -> Option<usize> {
+    if sections.uninherited.is_empty() {
+        return None;
+    }
+    let index = sections
+        .uninherited
+        .partition_point(|i| points[*i].time <= time);
+    Some(index.saturating_sub(1))
+}
+
+/// The beat length and meter of an uninherited timing point.
+pub(super) fn section_timing(point: &TimingPoint) -> (f64, f64) {
+    match &point.kind {
+        TimingPointKind::Uninherited(info) => (info.mpb, info.meter.max(1) as f64),
+        TimingPointKind::Inherited(_) => unreachable!("only uninherited points start sections"),
+    }
+}
@@ -1,10 +1,15 @@
+mod index;
+mod musical;
 mod point;
 
 use std::{
+    cmp::Ordering,
     fmt,
     ops::{Add, Deref, Sub},
 };
 
+pub use self::index::*;
+pub use self::musical::*;
 pub use self::point::*;
 
 /// A struct representing a location in time as milliseconds (i32)
@@ -59,3 +64,111 @@ impl Sub<Millis> for Millis {
         self.0 - rhs.0
     }
 }
+
+/// A precise location in time, in (possibly fractional) milliseconds
+///
+/// Hit objects and timing points use this, since timing point offsets are often fractional and
+/// lazer-written beatmaps can place objects between whole milliseconds. [`Time::millis`] rounds
+/// it to the whole-millisecond [`Millis`] used everywhere else.
+///
+/// Times are ordered with [`f64::total_cmp`], so they can be sorted and used as keys.
+#[derive(Copy, Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Time(pub f64);
+
+impl Time {
+    /// Converts from seconds to a Time
+    pub fn from_seconds(secs: f64) -> Time {
+        Time(secs * 1000.0)
+    }
+
+    /// Converts this Time to seconds
+    pub fn as_seconds(&self) -> f64 {
+        self.0 / 1000.0
+    }
+
+    /// Rounds this Time to the nearest whole millisecond
+    pub fn millis(&self) -> Millis {
+        Millis(self.0.round() as i32)
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!("{}ms", self.0))
+    }
+}
+
+impl PartialEq for Time {
+    fn eq(&self, other: &Time) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Time {}
+
+impl Ord for Time {
+    fn cmp(&self, other: &Time) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl PartialOrd for Time {
+    fn partial_cmp(&self, other: &Time) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl From<f64> for Time {
+    fn from(v: f64) -> Self {
+        Self(v)
+    }
+}
+
+impl From<i32> for Time {
+    fn from(v: i32) -> Self {
+        Self(v as f64)
+    }
+}
+
+impl From<Millis> for Time {
+    fn from(v: Millis) -> Self {
+        Self(v.0 as f64)
+    }
+}
+
+impl PartialEq<Millis> for Time {
+    fn eq(&self, other: &Millis) -> bool {
+        *self == Time::from(*other)
+    }
+}
+
+impl PartialOrd<Millis> for Time {
+    fn partial_cmp(&self, other: &Millis) -> Option<Ordering> {
+        self.partial_cmp(&Time::from(*other))
+    }
+}
+
+impl Add<Time> for Time {
+    type Output = Time;
+
+    fn add(self, rhs: Time) -> Self::Output {
+        Time(self.0 + rhs.0)
+    }
+}
+
+impl Add<f64> for Time {
+    type Output = Time;
+
+    fn add(self, rhs: f64) -> Self::Output {
+        Time(self.0 + rhs)
+    }
+}
+
+impl Sub<Time> for Time {
+    type Output = f64;
+
+    fn sub(self, rhs: Time) -> Self::Output {
+        self.0 - rhs.0
+    }
+}
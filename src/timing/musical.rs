@@ -0,0 +1,161 @@
+use std::fmt;
+
+use super::index::{section_at, section_timing, EPSILON};
+use super::{Time, TimingIndex};
+
+/// The beat snap divisors the editor offers, from whole beats to sixteenths.
+pub const SNAP_DIVISORS: [u32; 8] = [1, 2, 3, 4, 6, 8, 12, 16];
+
+/// How far (in milliseconds) a time has to be from a tick to not be snapped to it. Hit objects are
+/// placed on whole milliseconds, and the editor's rounding can leave them over a millisecond off
+/// the exact tick.
+const SNAP_LENIENCY: f64 = 2.0;
+
+/// A place in a beatmap's music, counted in measures and beats, see [`TimingIndex::to_musical`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MusicalPosition {
+    /// Which measure it's in, counting from 0 at the first uninherited timing point. Each timing
+    /// section starts a new measure, even if the one before it isn't finished.
+    pub measure: i64,
+
+    /// Which beat of the measure it's on, counting from 0
+    pub beat: u32,
+
+    /// How far into the beat it is, from 0 up to 1
+    pub fraction: f64,
+}
+
+impl MusicalPosition {
+    /// The position at the start of the given measure and beat.
+    pub fn new(measure: i64, beat: u32) -> Self {
+        MusicalPosition {
+            measure,
+            beat,
+            fraction: 0.0,
+        }
+    }
+}
+
+impl fmt::Display for MusicalPosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.measure, self.beat as f64 + self.fraction)
+    }
+}
+
+impl<'a> TimingIndex<'a> {
+    /// Works out the measure and beat the given time falls on. Times before the first uninherited
+    /// timing point are counted backwards from it. Returns `None` if there are no uninherited
+    /// timing points.
+    ///
+    /// ```
+    /// # use libosu::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = "osu file format v14\n\n[TimingPoints]\n1000,500,4,2,0,100,1,0\n3000,250,3,2,0,100,1,0\n";
+    /// let beatmap = Beatmap::from_str(input).unwrap();
+    /// let timing = beatmap.timing_index();
+    /// let pos = timing.to_musical(Millis(3875)).unwrap();
+    /// assert_eq!(pos.measure, 2);
+    /// assert_eq!(pos.beat, 0);
+    /// assert_eq!(pos.fraction, 0.5);
+    /// assert_eq!(timing.from_musical(&pos), Some(Time(3875.0)));
+    /// ```
+    pub fn to_musical(&self, time: impl Into<Time>) -> Option<MusicalPosition> {
+        let time = time.into();
+        let sections = self.sections();
+        let section = section_at(self.points, &sections, time)?;
+        let point = &self.points[sections.uninherited[section]];
+        let (mpb, meter) = section_timing(point);
+
+        let beats = (time.0 - point.time.0) / mpb;
+        let measure = (beats / meter + EPSILON).floor();
+        let beat_in_measure = (beats - measure * meter).max(0.0);
+        let beat = (beat_in_measure + EPSILON).floor();
+        Some(MusicalPosition {
+            measure: sections.measure_starts[section] + measure as i64,
+            beat: beat as u32,
+            fraction: (beat_in_measure - beat).max(0.0),
+        })
+    }
+
+    /// Works out when the given measure and beat is. Returns `None` if there are no uninherited
+    /// timing points.
+    pub fn from_musical(&self, pos: &MusicalPosition) -> Option<Time> {
+        let sections = self.sections();
+        if sections.uninherited.is_empty() {
+            return None;
+        }
+        let section = sections
+            .measure_starts
+            .partition_point(|start| *start <= pos.measure)
+            .saturating_sub(1);
+        let point = &self.points[sections.uninherited[section]];
+        let (mpb, meter) = section_timing(point);
+
+        let measures = (pos.measure - sections.measure_starts[section]) as f64;
+        let beats = measures * meter + pos.beat as f64 + pos.fraction;
+        Some(Time(point.time.0 + beats * mpb))
+    }
+
+    /// The closest tick to the given time when the beat is divided into `divisor` parts, like the
+    /// editor's beat snap. Ticks are counted from the start of each timing section, and the start
+    /// of the next section is a tick too. Returns `None` if there are no uninherited timing
+    /// points.
+    ///
+    /// The tick usually isn't a whole millisecond; [`Time::millis`] rounds it like the editor.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `divisor` is 0.
+    pub fn nearest_tick(&self, time: impl Into<Time>, divisor: u32) -> Option<Time> {
+        assert!(divisor > 0, "the snap divisor must be at least 1");
+        let time = time.into();
+        let sections = self.sections();
+        let section = section_at(self.points, &sections, time)?;
+        let point = &self.points[sections.uninherited[section]];
+        let (mpb, _) = section_timing(point);
+
+        let tick_length = mpb / divisor as f64;
+        let ticks = ((time.0 - point.time.0) / tick_length).round();
+        let mut tick = point.time.0 + ticks * tick_length;
+
+        if let Some(next) = sections.uninherited.get(section + 1) {
+            let next = self.points[*next].time.0;
+            if tick > next || (next - time.0).abs() < (tick - time.0).abs() {
+                tick = next;
+            }
+        }
+        Some(Time(tick))
+    }
+
+    /// How far (in milliseconds) the given time is from the closest tick for the divisor, negative
+    /// if it's early. See [`TimingIndex::nearest_tick`].
+    pub fn snap_offset(&self, time: impl Into<Time>, divisor: u32) -> Option<f64> {
+        let time = time.into();
+        self.nearest_tick(time, divisor).map(|tick| time.0 - tick.0)
+    }
+
+    /// The smallest of the [editor's divisors][SNAP_DIVISORS] that the given time is snapped to,
+    /// allowing for it being rounded to a whole millisecond. Returns `None` if it isn't snapped to
+    /// any of them (or there are no uninherited timing points).
+    ///
+    /// ```
+    /// # use libosu::prelude::*;
+    /// # use std::str::FromStr;
+    /// let input = "osu file format v14\n\n[TimingPoints]\n1000,300,4,2,0,100,1,0\n";
+    /// let beatmap = Beatmap::from_str(input).unwrap();
+    /// let timing = beatmap.timing_index();
+    /// assert_eq!(timing.snap_divisor(Millis(1600)), Some(1));
+    /// assert_eq!(timing.snap_divisor(Millis(1150)), Some(2));
+    /// assert_eq!(timing.snap_divisor(Millis(1100)), Some(3));
+    /// assert_eq!(timing.snap_divisor(Millis(1019)), Some(16));
+    /// assert_eq!(timing.snap_divisor(Millis(1010)), None);
+    /// ```
+    pub fn snap_divisor(&self, time: impl Into<Time>) -> Option<u32> {
+        let time = time.into();
+        SNAP_DIVISORS.iter().copied().find(|divisor| {
+            self.snap_offset(time, *divisor)
+                .is_some_and(|offset| offset.abs() < SNAP_LENIENCY)
+        })
+    }
+}
@@ -2,9 +2,9 @@ use std::cmp::Ordering;
 use std::fmt;
 use std::str::FromStr;
 
-use crate::errors::ParseError;
+use crate::errors::{parse_field, ParseError};
 use crate::hitsounds::SampleSet;
-use crate::timing::Millis;
+use crate::timing::Time;
 
 /// Info for uninherited timing point
 #[derive(Clone, Debug)]
@@ -43,8 +43,8 @@ pub enum TimingPointKind {
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TimingPoint {
-    /// The timestamp of this timing point, represented as a `TimeLocation`.
-    pub time: Millis,
+    /// The timestamp of this timing point, which is often a fraction of a millisecond.
+    pub time: Time,
 
     /// Whether or not Kiai time should be on for this timing point.
     pub kiai: bool,
@@ -85,58 +85,73 @@ impl PartialOrd for TimingPoint {
 impl FromStr for TimingPoint {
     type Err = ParseError;
 
+    /// Parses a timing point, using the game's defaults for the beatmap's sample set and
+    /// volume if the line doesn't have them.
     fn from_str(input: &str) -> Result<TimingPoint, Self::Err> {
+        TimingPoint::parse_with_defaults(input, SampleSet::Normal, 100)
+    }
+}
+
+impl TimingPoint {
+    /// Parses a timing point, filling in fields that older format versions leave out.
+    ///
+    /// Lines without a sample set or volume use the beatmap's `SampleSet` and `SampleVolume`,
+    /// and lines without the uninherited field are inherited if their beat length is negative.
+    pub fn parse_with_defaults(
+        input: &str,
+        default_sample_set: SampleSet,
+        default_volume: u16,
+    ) -> Result<TimingPoint, ParseError> {
         let parts = input.split(',').collect::<Vec<_>>();
 
-        let timestamp = parts[0].parse::<f64>()?;
-        let mpb = parts[1].parse::<f64>()?;
+        let timestamp = parse_field::<f64>(&parts, 0, "timing point time")?;
+        let mpb = parse_field::<f64>(&parts, 1, "timing point beat length")?;
 
         let mut meter = 4;
         if let Some(new_meter) = parts.get(2) {
             if !new_meter.is_empty() {
-                meter = new_meter.parse::<u32>()?;
+                meter = parse_field::<u32>(&parts, 2, "timing point meter")?;
             }
         }
 
-        let mut sample_set = 0;
+        let mut sample_set = default_sample_set as u32;
         if let Some(new_sample_set) = parts.get(3) {
             if !new_sample_set.is_empty() {
-                sample_set = new_sample_set.parse::<i32>()?;
+                sample_set = parse_field::<u32>(&parts, 3, "timing point sample set")?;
             }
         }
 
         let mut sample_index = 0;
         if let Some(new_sample_index) = parts.get(4) {
             if !new_sample_index.is_empty() {
-                sample_index = new_sample_index.parse::<u32>()?;
+                sample_index = parse_field::<u32>(&parts, 4, "timing point sample index")?;
             }
         }
 
-        // TODO: is the default supposed to be 0..?
-        let mut volume = 0;
+        let mut volume = default_volume;
         if let Some(new_volume) = parts.get(5) {
             if !new_volume.is_empty() {
-                volume = new_volume.parse::<u16>()?;
+                volume = parse_field::<u16>(&parts, 5, "timing point volume")?;
             }
         }
 
-        let mut inherited = false;
+        let mut inherited = mpb < 0.0;
         if let Some(new_inherited) = parts.get(6) {
             if !new_inherited.is_empty() {
-                inherited = new_inherited.parse::<i32>()? == 0;
+                inherited = parse_field::<i32>(&parts, 6, "timing point uninherited")? == 0;
             }
         }
 
         let mut kiai = false;
         if let Some(new_kiai) = parts.get(7) {
             if !new_kiai.is_empty() {
-                kiai = new_kiai.parse::<i32>()? > 0;
+                kiai = parse_field::<i32>(&parts, 7, "timing point effects")? > 0;
             }
         }
 
         // calculate bpm from mpb
         let _ = 60_000.0 / mpb;
-        let time = Millis(timestamp.round() as i32);
+        let time = Time(timestamp);
 
         let timing_point = TimingPoint {
             kind: if inherited {
@@ -152,7 +167,7 @@ impl FromStr for TimingPoint {
                 1 => SampleSet::Normal,
                 2 => SampleSet::Soft,
                 3 => SampleSet::Drum,
-                _ => panic!("Invalid sample set '{}'.", sample_set),
+                _ => return Err(ParseError::InvalidSampleSet(sample_set).in_field(3)),
             },
             sample_index,
             volume,
@@ -4,3 +4,46 @@ pub mod serde_notnan {
 }
 #[cfg(feature = "serde")]
 pub use self::serde_notnan::*;
+
+use std::fmt;
+use std::io;
+
+/// Adapts an `io::Write`r so that `fmt::Write`-based formatting code can write straight into
+/// it, without building the whole output in a `String` first.
+pub(crate) struct IoWriter<W> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
+}
+
+/// Runs some formatting code against an `io::Write`r, returning the io error that stopped it
+/// (if any).
+pub(crate) fn write_fmt_io<W: io::Write>(
+    w: W,
+    f: impl FnOnce(&mut IoWriter<W>) -> fmt::Result,
+) -> io::Result<()> {
+    let mut writer = IoWriter {
+        inner: w,
+        error: None,
+    };
+    match f(&mut writer) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(writer
+            .error
+            .unwrap_or_else(|| io::Error::other("formatter error"))),
+    }
+}
+
+/// Normalizes a path referenced from a beatmap, so that paths the game would treat as the same
+/// file compare equal.
+pub(crate) fn normalize_path(path: &str) -> String {
+    path.trim_matches('"').replace('\\', "/").to_lowercase()
+}
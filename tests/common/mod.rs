@@ -0,0 +1,25 @@
+//! Beatmaps shared by the integration tests
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::str::FromStr;
+
+use anyhow::Result;
+use libosu::prelude::*;
+
+/// Builds a beatmap of the given format version out of sections, each given as its name and the
+/// lines that go in it.
+pub fn beatmap(version: u32, sections: &[(&str, &str)]) -> Result<Beatmap> {
+    let mut input = format!("osu file format v{}\n", version);
+    for (name, lines) in sections {
+        input += &format!("\n[{}]\n{}\n", name, lines);
+    }
+    Ok(Beatmap::from_str(&input)?)
+}
+
+/// Reads one of the beatmaps in `tests/files`.
+pub fn real_map(name: &str) -> Result<Beatmap> {
+    let input = fs::read_to_string(format!("tests/files/{}", name))?;
+    Ok(Beatmap::from_str(&input)?)
+}
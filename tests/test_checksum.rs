@@ -0,0 +1,82 @@
+//! Beatmaps should be found by the MD5 checksums replays and osu!.db refer to them by
+
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::str::FromStr;
+
+use anyhow::Result;
+use libosu::prelude::*;
+
+#[test]
+fn test_checksums() -> Result<()> {
+    let contents = fs::read("tests/files/774965.osu")?;
+    let hash = "5a236361eb08650d7696199203781765";
+    assert_eq!(Beatmap::checksum_of(&contents), hash);
+    assert_eq!(Beatmap::checksum_reader(contents.as_slice())?, hash);
+    assert!(Beatmap::verify_checksum(&contents, &hash.to_uppercase()));
+    assert!(!Beatmap::verify_checksum(b"", hash));
+
+    // the document keeps the original layout, so it keeps the original checksum too
+    let doc = BeatmapDocument::from_str(std::str::from_utf8(&contents)?)?;
+    assert_eq!(doc.checksum(), hash);
+
+    let beatmap = doc.into_beatmap();
+    assert_eq!(
+        beatmap.checksum(),
+        Beatmap::checksum_of(beatmap.to_string())
+    );
+    Ok(())
+}
+
+#[test]
+fn test_resolve_replay() -> Result<()> {
+    let songs = std::env::temp_dir().join(format!("libosu-test-songs-{}", std::process::id()));
+    let folder = songs.join("774965 Reol - Control");
+    fs::create_dir_all(&folder)?;
+    fs::copy("tests/files/774965.osu", folder.join("Reol - Control.osu"))?;
+    fs::copy("tests/files/129891.osu", songs.join("129891.osu"))?;
+
+    let mut replay = Replay::parse(&mut File::open("tests/files/replay_with_life.osr")?)?;
+    replay.beatmap_hash = String::from("5a236361eb08650d7696199203781765");
+
+    let result = (|| -> Result<()> {
+        let resolver = BeatmapResolver::scan(&songs)?;
+        assert_eq!(resolver.len(), 2);
+        assert_eq!(
+            resolver.resolve_replay(&replay),
+            Some(folder.join("Reol - Control.osu").as_path())
+        );
+        assert_eq!(resolver.resolve("0123456789abcdef0123456789abcdef"), None);
+
+        // files that changed since they were indexed don't match anymore
+        fs::write(folder.join("Reol - Control.osu"), "osu file format v14\n")?;
+        assert_eq!(resolver.resolve_replay(&replay), None);
+        Ok(())
+    })();
+    fs::remove_dir_all(&songs)?;
+    result
+}
+
+#[test]
+fn test_db_lookup() -> Result<()> {
+    let db = Db::parse(BufReader::new(File::open("tests/files/osu.db")?))?;
+    let first = &db.beatmaps[0];
+    assert_eq!(db.find_by_hash(&first.hash.to_uppercase()), Some(first));
+
+    let mut replay = Replay::parse(&mut File::open("tests/files/replay_with_life.osr")?)?;
+    assert_eq!(db.find_replay_beatmap(&replay), None);
+    replay.beatmap_hash = first.hash.clone();
+    assert_eq!(db.find_replay_beatmap(&replay), Some(first));
+
+    // the database says where the file should be, but it isn't there
+    let resolver = BeatmapResolver::from_db(&db, "/nonexistent/Songs");
+    assert_eq!(resolver.len(), db.beatmaps.len());
+    assert_eq!(resolver.resolve_replay(&replay), None);
+    assert_eq!(
+        first.path("Songs"),
+        std::path::Path::new("Songs")
+            .join(&first.folder_name)
+            .join(&first.beatmap_file_name)
+    );
+    Ok(())
+}
@@ -0,0 +1,91 @@
+//! Hit objects should get the combo numbers and colours the game gives them
+
+use anyhow::Result;
+use libosu::prelude::*;
+
+mod common;
+
+fn numbers(combos: &[ComboInfo]) -> Vec<(u32, u32, usize)> {
+    combos
+        .iter()
+        .map(|combo| (combo.combo_number, combo.index_in_combo, combo.color_index))
+        .collect()
+}
+
+/// A circle, a spinner that isn't marked as a new combo, then two more circles.
+const SPINNER_OBJECTS: &str = "0,0,1000,1,0,0:0:0:0:
+256,192,2000,8,0,3000,0:0:0:0:
+0,0,4000,1,0,0:0:0:0:
+0,0,4100,1,0,0:0:0:0:";
+
+#[test]
+fn test_default_colors() -> Result<()> {
+    let objects = "0,0,1000,5,0,0:0:0:0:
+0,0,1100,5,0,0:0:0:0:
+0,0,1200,5,0,0:0:0:0:
+0,0,1300,5,0,0:0:0:0:";
+    let beatmap = common::beatmap(14, &[("HitObjects", objects)])?;
+    assert_eq!(beatmap.combo_colors(), &DEFAULT_COMBO_COLORS[..]);
+
+    // the colours wrap around, starting from the second
+    let combos = beatmap.combos();
+    assert_eq!(
+        combos.iter().map(|c| c.color_index).collect::<Vec<_>>(),
+        vec![1, 2, 3, 0]
+    );
+    assert_eq!(combos[3].color, DEFAULT_COMBO_COLORS[0]);
+    assert!(combos.iter().all(|c| c.last_in_combo));
+    Ok(())
+}
+
+#[test]
+fn test_spinners() -> Result<()> {
+    // the spinner isn't marked as a new combo, so from v9 it doesn't break the combo
+    let combos = common::beatmap(14, &[("HitObjects", SPINNER_OBJECTS)])?.combos();
+    assert_eq!(
+        numbers(&combos),
+        vec![(1, 1, 1), (1, 2, 1), (1, 3, 1), (1, 4, 1)]
+    );
+
+    // before that every spinner does
+    let combos = common::beatmap(8, &[("HitObjects", SPINNER_OBJECTS)])?.combos();
+    assert_eq!(
+        numbers(&combos),
+        vec![(1, 1, 1), (1, 2, 1), (2, 1, 2), (2, 2, 2)]
+    );
+    assert!(combos[1].last_in_combo);
+    assert!(!combos[2].last_in_combo);
+
+    // spinners marked as a new combo pass their skipped colours on to the next object
+    let objects = SPINNER_OBJECTS.replace("2000,8,", "2000,28,");
+    let combos = common::beatmap(14, &[("HitObjects", &objects)])?.combos();
+    assert_eq!(
+        numbers(&combos),
+        vec![(1, 1, 1), (1, 2, 1), (2, 1, 3), (2, 2, 3)]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_map_colors() -> Result<()> {
+    let beatmap = common::real_map("774965.osu")?;
+    let combos = beatmap.combos();
+    assert_eq!(combos.len(), beatmap.hit_objects.len());
+    assert_eq!(beatmap.combo_colors().len(), 4);
+
+    // every new combo starts at 1 and moves on to a later colour
+    for (i, (obj, combo)) in beatmap.hit_objects.iter().zip(combos.iter()).enumerate() {
+        if obj.new_combo && !obj.kind.is_spinner() {
+            assert_eq!(combo.index_in_combo, 1);
+            if i > 0 {
+                assert_eq!(combo.combo_number, combos[i - 1].combo_number + 1);
+                assert!(combos[i - 1].last_in_combo);
+            }
+        } else if i > 0 {
+            assert_eq!(combo.index_in_combo, combos[i - 1].index_in_combo + 1);
+            assert_eq!(combo.color_index, combos[i - 1].color_index);
+        }
+        assert_eq!(combo.color, beatmap.colors[combo.color_index]);
+    }
+    Ok(())
+}
@@ -0,0 +1,350 @@
+//! Hit objects should make the sounds the game plays for them
+
+use anyhow::Result;
+use libosu::prelude::*;
+
+mod common;
+
+/// Sounds default to the normal set
+const HITSOUND_GENERAL: (&str, &str) = ("General", "SampleSet: Normal");
+
+/// Ticks twice a beat, and 100 osu!pixels a beat at 1x slider velocity
+const HITSOUND_DIFFICULTY: (&str, &str) = ("Difficulty", "SliderMultiplier:1\nSliderTickRate:2");
+
+fn filenames(sounds: &[HitSound]) -> Vec<&str> {
+    sounds.iter().map(|sound| sound.filename.as_str()).collect()
+}
+
+#[test]
+fn test_sample_sets() -> Result<()> {
+    let timing = "0,500,4,2,3,70,1,0\n2000,-100,4,3,0,40,0,0";
+    let objects = "0,0,1000,1,8,0:0:0:0:
+0,0,1100,1,6,1:3:0:0:
+0,0,1200,1,2,0:0:7:90:
+0,0,1997,1,4,0:0:0:0:";
+    let beatmap = common::beatmap(
+        14,
+        &[
+            HITSOUND_GENERAL,
+            HITSOUND_DIFFICULTY,
+            ("TimingPoints", timing),
+            ("HitObjects", objects),
+        ],
+    )?;
+    let sounds = beatmap.hitsounds();
+    assert_eq!(
+        filenames(&sounds),
+        vec![
+            // the timing point's set and index
+            "soft-hitnormal3.wav",
+            "soft-hitclap3.wav",
+            // the object's own sets
+            "normal-hitnormal3.wav",
+            "drum-hitwhistle3.wav",
+            "drum-hitfinish3.wav",
+            // the object's own index and volume
+            "soft-hitnormal7.wav",
+            "soft-hitwhistle7.wav",
+            // close enough to the next timing point to use it
+            "drum-hitnormal.wav",
+            "drum-hitfinish.wav",
+        ]
+    );
+    assert_eq!(sounds[0].volume, 70);
+    assert_eq!(sounds[5].volume, 90);
+    assert_eq!(sounds[5].custom_index, 7);
+    assert_eq!(sounds[7].volume, 40);
+    assert_eq!(sounds[7].custom_index, 0);
+    assert!(sounds.iter().all(|sound| sound.end_time.is_none()));
+    Ok(())
+}
+
+#[test]
+fn test_custom_sample() -> Result<()> {
+    let beatmap = common::beatmap(
+        14,
+        &[
+            HITSOUND_GENERAL,
+            HITSOUND_DIFFICULTY,
+            ("TimingPoints", "0,500,4,2,1,100,1,0"),
+            ("HitObjects", "0,0,1000,1,2,0:0:0:0:beep.wav"),
+        ],
+    )?;
+    let sounds = beatmap.hitsounds();
+    assert_eq!(filenames(&sounds), vec!["beep.wav"]);
+    assert_eq!(sounds[0].kind, HitSoundKind::Normal);
+    Ok(())
+}
+
+#[test]
+fn test_slider() -> Result<()> {
+    let timing = "0,500,4,1,0,100,1,0\n1600,-100,4,2,2,50,0,0";
+    let slider = "0,0,1000,2,2,L|200:0,2,100,8|0|4,0:0|3:0|0:2,0:0:0:0:";
+    let beatmap = common::beatmap(
+        14,
+        &[
+            HITSOUND_GENERAL,
+            HITSOUND_DIFFICULTY,
+            ("TimingPoints", timing),
+            ("HitObjects", slider),
+        ],
+    )?;
+    let sounds = beatmap.hitsounds();
+
+    let sound_at = |time: f64| {
+        sounds
+            .iter()
+            .filter(|sound| sound.time == Time(time) && sound.end_time.is_none())
+            .map(|sound| sound.filename.as_str())
+            .collect::<Vec<_>>()
+    };
+    // the head uses its own edge additions
+    assert_eq!(
+        sound_at(1000.0),
+        vec!["normal-hitnormal.wav", "normal-hitclap.wav"]
+    );
+    assert_eq!(sound_at(1250.0), vec!["normal-slidertick.wav"]);
+    // the repeat has its own sample set
+    assert_eq!(sound_at(1500.0), vec!["drum-hitnormal.wav"]);
+    // ticks and the tail use the timing point they're in
+    assert_eq!(sound_at(1750.0), vec!["soft-slidertick2.wav"]);
+    assert_eq!(
+        sound_at(2000.0),
+        vec!["soft-hitnormal2.wav", "soft-hitfinish2.wav"]
+    );
+
+    // the body slides for the whole slider, with a whistle since the slider has one, using the
+    // timing point at the end of the slider
+    let slides = sounds
+        .iter()
+        .filter(|sound| sound.end_time.is_some())
+        .collect::<Vec<_>>();
+    assert_eq!(slides.len(), 2);
+    assert_eq!(slides[0].kind, HitSoundKind::SliderSlide);
+    assert_eq!(slides[0].filename, "soft-sliderslide2.wav");
+    assert_eq!(slides[0].volume, 50);
+    assert_eq!(slides[1].kind, HitSoundKind::SliderWhistle);
+    assert_eq!(slides[0].time, Time(1000.0));
+    assert_eq!(slides[1].end_time, Some(Time(2000.0)));
+    Ok(())
+}
+
+#[test]
+fn test_real_map() -> Result<()> {
+    let beatmap = common::real_map("adamas-hitsounds.osu")?;
+    let sounds = beatmap.hitsounds();
+    assert!(sounds.windows(2).all(|w| w[0].time <= w[1].time));
+
+    // every object plays a hit normal
+    let normals = sounds
+        .iter()
+        .filter(|sound| sound.kind == HitSoundKind::Normal)
+        .count();
+    assert!(normals >= beatmap.hit_objects.len());
+
+    // the first slider only has a whistle on its tail
+    let first = sounds
+        .iter()
+        .filter(|sound| sound.object_index == 0 && sound.kind.is_addition())
+        .collect::<Vec<_>>();
+    assert_eq!(first.len(), 1);
+    assert_eq!(first[0].filename, "soft-hitwhistle.wav");
+    assert_eq!(first[0].volume, 50);
+
+    // the sliders after the 5325ms timing point (normal set, custom index 1) play the beatmap's
+    // samples without a number: a finish on the first head, and a soft tail from its edge set
+    let sounds = sounds
+        .iter()
+        .filter(|sound| sound.object_index == 13 || sound.object_index == 14)
+        .filter(|sound| sound.kind != HitSoundKind::SliderSlide)
+        .map(|sound| (sound.object_index, sound.filename.as_str(), sound.volume))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        sounds,
+        vec![
+            (13, "normal-hitnormal.wav", 50),
+            (13, "normal-hitfinish.wav", 50),
+            (13, "soft-hitnormal.wav", 50),
+            (14, "normal-hitnormal.wav", 50),
+            (14, "normal-hitclap.wav", 50),
+            (14, "normal-hitnormal.wav", 50),
+            (14, "normal-hitwhistle.wav", 50),
+        ]
+    );
+    Ok(())
+}
+
+fn edge_sounds(sounds: &[HitSound]) -> Vec<(f64, &str, u32)> {
+    sounds
+        .iter()
+        .filter(|sound| sound.end_time.is_none() && sound.kind != HitSoundKind::SliderTick)
+        .map(|sound| (sound.time.0, sound.filename.as_str(), sound.volume))
+        .collect()
+}
+
+#[test]
+fn test_copy_to_slider() -> Result<()> {
+    let timing = "0,500,4,1,0,100,1,0\n1250,-100,4,1,0,30,0,0";
+    let source = common::beatmap(
+        14,
+        &[
+            HITSOUND_GENERAL,
+            HITSOUND_DIFFICULTY,
+            ("TimingPoints", timing),
+            (
+                "HitObjects",
+                "0,0,1000,1,2,0:0:0:0:
+0,0,1500,1,8,3:0:0:0:
+0,0,2000,1,4,0:2:3:80:
+0,0,3000,1,0,0:0:0:0:",
+            ),
+        ],
+    )?;
+    let mut target = common::beatmap(
+        14,
+        &[
+            HITSOUND_GENERAL,
+            HITSOUND_DIFFICULTY,
+            ("TimingPoints", "0,500,4,2,0,100,1,0"),
+            ("HitObjects", "0,0,1000,2,0,L|200:0,2,100"),
+        ],
+    )?;
+
+    let report = source.copy_hitsounds_to(&mut target, &HitsoundCopyOptions::default());
+    assert_eq!(report.copied, 3);
+    assert_eq!(filenames(&report.unplaced), vec!["normal-hitnormal.wav"]);
+    assert_eq!(report.unplaced[0].time, Time(3000.0));
+
+    // the repeat and tail sound like the circles, with a timing point for the tail's index and
+    // volume and the copied volume change
+    let source_sounds = source.hitsounds();
+    let target_sounds = target.hitsounds();
+    assert_eq!(
+        edge_sounds(&target_sounds),
+        edge_sounds(&source_sounds[..source_sounds.len() - 1])
+    );
+    assert_eq!(report.added_timing_points, 2);
+    assert_eq!(target.timing_points.len(), 3);
+    assert_eq!(target.timing_points[1].time, Time(1250.0));
+    assert_eq!(target.timing_points[2].time, Time(2000.0));
+    assert_eq!(target.timing_points[2].sample_index, 3);
+    assert_eq!(target.timing_points[2].volume, 80);
+
+    // the slider's velocity doesn't change
+    assert_eq!(target.get_slider_velocity_at_time(1500), 1.0);
+    assert_eq!(
+        target.get_slider_duration(&target.hit_objects[0]),
+        Some(1.0)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_copy_clears_target() -> Result<()> {
+    let source = common::beatmap(
+        14,
+        &[
+            HITSOUND_GENERAL,
+            HITSOUND_DIFFICULTY,
+            ("TimingPoints", "0,500,4,1,0,100,1,0"),
+            ("HitObjects", "0,0,1000,1,0,0:0:0:0:"),
+        ],
+    )?;
+    let mut target = common::beatmap(
+        14,
+        &[
+            HITSOUND_GENERAL,
+            HITSOUND_DIFFICULTY,
+            ("TimingPoints", "0,500,4,1,0,100,1,0"),
+            (
+                "HitObjects",
+                "0,0,1003,1,14,3:3:0:0:\n0,0,2000,1,8,0:0:0:0:",
+            ),
+        ],
+    )?;
+    let options = HitsoundCopyOptions {
+        copy_sample_points: false,
+        ..HitsoundCopyOptions::default()
+    };
+    let report = source.copy_hitsounds_to(&mut target, &options);
+    assert_eq!(report.copied, 1);
+    assert!(report.unplaced.is_empty());
+    assert_eq!(
+        filenames(&target.hitsounds()),
+        vec!["normal-hitnormal.wav", "normal-hitnormal.wav"]
+    );
+    assert_eq!(
+        target.hit_objects[0].sample_info.sample_set,
+        SampleSet::None
+    );
+    Ok(())
+}
+
+#[test]
+fn test_copy_real_map() -> Result<()> {
+    let source = common::real_map("adamas-hitsounds.osu")?;
+    let mut target = common::real_map("adamas-nohitsounds.osu")?;
+    let report = source.copy_hitsounds_to(&mut target, &HitsoundCopyOptions::default());
+    assert!(report.copied > 0);
+
+    let source_sounds = source.hitsounds();
+    let source_sounds = edge_sounds(&source_sounds);
+    let target_sounds = target.hitsounds();
+    for (time, filename, volume) in edge_sounds(&target_sounds) {
+        if source_sounds
+            .iter()
+            .any(|(t, _, _)| (t - time).abs() <= 5.0)
+        {
+            assert!(
+                source_sounds
+                    .iter()
+                    .any(|(t, f, v)| (t - time).abs() <= 5.0 && *f == filename && *v == volume),
+                "{}ms {} {}",
+                time,
+                filename,
+                volume
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_copy_scales() -> Result<()> {
+    // matching every hit against every source hit would take hundreds of millions of steps
+    let objects = |additions: u32| {
+        (0..20_000)
+            .map(|i| format!("0,0,{},1,{},0:0:0:0:", i * 50, additions * (i % 2)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let source = common::beatmap(
+        14,
+        &[
+            HITSOUND_GENERAL,
+            HITSOUND_DIFFICULTY,
+            ("TimingPoints", "0,500,4,1,0,100,1,0"),
+            ("HitObjects", &objects(2)),
+        ],
+    )?;
+    let mut target = common::beatmap(
+        14,
+        &[
+            HITSOUND_GENERAL,
+            HITSOUND_DIFFICULTY,
+            ("TimingPoints", "0,500,4,1,0,100,1,0"),
+            ("HitObjects", &objects(0)),
+        ],
+    )?;
+
+    let start = std::time::Instant::now();
+    let report = source.copy_hitsounds_to(&mut target, &HitsoundCopyOptions::default());
+    assert_eq!(report.copied, 20_000);
+    assert_eq!(target.hit_objects[1].additions, Additions::WHISTLE);
+    assert!(
+        start.elapsed() < std::time::Duration::from_secs(20),
+        "copying took {:?}",
+        start.elapsed()
+    );
+    Ok(())
+}
@@ -0,0 +1,177 @@
+//! Broken beatmaps should produce diagnostics, never panics
+
+use std::fs;
+use std::str::FromStr;
+
+use anyhow::Result;
+use libosu::{errors::ParseError, prelude::*};
+
+const BROKEN: &str = "\u{feff}osu file format v14
+
+[General]
+AudioFilename: audio.mp3
+Mode: 9
+
+[Difficulty]
+CircleSize:4
+ApproachRate:fast
+
+[Events]
+0,0
+Sprite,Foreground,Centre,\"a.png\",320
+ F,0,0,1000,0,1
+Sprite,Foreground,Centre,\"b.png\",320,240
+ F,0,0,1000,0,1
+ L,0
+  F,0,0,100
+
+[Fonts]
+Size: 12
+
+[TimingPoints]
+1000,500,4,7
+0,500,4,1,0,100,1,0
+500
+-250,-100,4,1,0,100,0,0
+
+[Colours]
+Combo1 : 255,128
+Combo2 : 0, 202, 255
+
+[HitObjects]
+1,2
+256,192,3000,2,0
+256,192,3000,2,0,B|1:,1,100
+256,192,2000,2,0,B|300:200,1,100,0|0,0:0|9:0,0:0:0:0:
+256,192,2000,1,0,0:0:0:0:
+256,192,1000,5,0,0:0:0:0:
+256,192,4000,12
+";
+
+#[test]
+fn test_lenient_collects_diagnostics() {
+    let (beatmap, diagnostics) = Beatmap::from_str_lenient(BROKEN);
+
+    // the good parts are all still there
+    assert_eq!(beatmap.version, 14);
+    assert_eq!(beatmap.audio_filename, "audio.mp3");
+    assert_eq!(beatmap.difficulty.circle_size, 4.0);
+    assert_eq!(beatmap.events.len(), 1);
+    match &beatmap.events[0] {
+        // the broken loop and the command inside it are dropped
+        Event::Sprite(sprite) => assert_eq!(sprite.commands.len(), 1),
+        event => panic!("unexpected {:?}", event),
+    }
+    assert_eq!(beatmap.timing_points.len(), 2);
+    assert_eq!(beatmap.colors, vec![Color::new(0, 202, 255)]);
+    let times = beatmap
+        .hit_objects
+        .iter()
+        .map(|obj| obj.start_time.0)
+        .collect::<Vec<_>>();
+    assert_eq!(times, vec![1000.0, 2000.0]);
+
+    let summary = diagnostics
+        .iter()
+        .map(|d| (d.line, d.severity, d.section.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (5, Severity::Error, "General"),
+            (9, Severity::Error, "Difficulty"),
+            (12, Severity::Error, "Events"),
+            (13, Severity::Error, "Events"),
+            (17, Severity::Error, "Events"),
+            (20, Severity::Warning, "Fonts"),
+            (24, Severity::Error, "TimingPoints"),
+            (26, Severity::Error, "TimingPoints"),
+            (27, Severity::Warning, "TimingPoints"),
+            (30, Severity::Error, "Colours"),
+            (34, Severity::Error, "HitObjects"),
+            (35, Severity::Error, "HitObjects"),
+            (36, Severity::Error, "HitObjects"),
+            (37, Severity::Error, "HitObjects"),
+            (39, Severity::Warning, "HitObjects"),
+            (40, Severity::Error, "HitObjects"),
+        ]
+    );
+
+    // settings errors point at the value
+    assert_eq!(diagnostics[0].column, Some(7));
+    assert_eq!(diagnostics[1].column, Some(14));
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "error on line 5:7 [General]: invalid game mode: 9"
+    );
+
+    // object errors point at the field that failed, or the end of a line that's too short
+    let columns = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .map(|d| (d.line, d.column))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        columns,
+        vec![
+            (5, Some(7)),
+            (9, Some(14)),
+            (12, Some(4)),
+            (13, Some(37)),
+            (17, Some(5)),
+            (24, Some(12)),
+            (26, Some(4)),
+            (30, None),
+            (34, Some(4)),
+            (35, Some(17)),
+            (36, Some(18)),
+            (37, Some(38)),
+            (40, Some(16)),
+        ]
+    );
+    assert_eq!(
+        diagnostics[10].to_string(),
+        "error on line 34:4 [HitObjects]: missing field: time"
+    );
+}
+
+#[test]
+fn test_lenient_matches_strict() -> Result<()> {
+    for path in &["tests/files/129891.osu", "tests/files/774965.osu"] {
+        let contents = fs::read_to_string(path)?;
+        let strict = Beatmap::from_str(&contents)?;
+        let (lenient, diagnostics) = Beatmap::from_str_lenient(&contents);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert_eq!(lenient.to_string(), strict.to_string());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_strict_errors_instead_of_panics() {
+    let lines = &[
+        "1,2",
+        "256,192,3000,2,0",
+        "256,192,3000,2,0,B|1:,1,100",
+        "256,192,3000,2,0,B|1:2,1,100,0|0,0:0|9:0",
+        "256,192,3000,8,0",
+        "256,192,3000,128,0",
+    ];
+    for line in lines {
+        let input = format!("osu file format v14\n\n[HitObjects]\n{}\n", line);
+        let err = Beatmap::from_str(&input).unwrap_err();
+        assert_eq!(err.line, 4);
+    }
+
+    let input = "osu file format v14\n\n[TimingPoints]\n0,500,4,7\n";
+    let err = Beatmap::from_str(input).unwrap_err();
+    assert!(matches!(err.inner, ParseError::InvalidSampleSet(7)));
+    assert_eq!(err.column, Some(9));
+    assert_eq!(err.to_string(), "error on line 4:9: invalid sample set: 7");
+
+    let input = "osu file format v14\n\n[Colours]\nCombo1 : 255,128\n";
+    assert!(Beatmap::from_str(input).is_err());
+
+    let input = "osu file format v14\n\n[Events]\n2,100\n";
+    assert!(Beatmap::from_str(input).is_err());
+}
@@ -0,0 +1,247 @@
+//! Applying mods to a beatmap should give the map as it actually plays
+
+use std::fs;
+use std::str::FromStr;
+
+use anyhow::Result;
+use libosu::prelude::*;
+
+fn control() -> Result<Beatmap> {
+    Ok(Beatmap::from_str(&fs::read_to_string(
+        "tests/files/774965.osu",
+    )?)?)
+}
+
+#[test]
+fn test_no_mods() -> Result<()> {
+    let beatmap = control()?;
+    assert_eq!(
+        beatmap.with_mods(Mods::None).to_string(),
+        beatmap.to_string()
+    );
+    // mods that don't change the map don't change anything either
+    let hidden = beatmap.with_mods(Mods::Hidden | Mods::NoFail);
+    assert_eq!(hidden.to_string(), beatmap.to_string());
+    Ok(())
+}
+
+#[test]
+fn test_hard_rock() -> Result<()> {
+    let beatmap = control()?.with_mods(Mods::HardRock);
+    assert_eq!(beatmap.difficulty.circle_size, 5.2);
+    assert_eq!(beatmap.difficulty.hp_drain_rate, 9.1);
+    assert_eq!(beatmap.difficulty.approach_rate, 10.0);
+    assert_eq!(beatmap.difficulty.overall_difficulty, 10.0);
+
+    let obj = &beatmap.hit_objects[0];
+    assert_eq!(obj.pos, Point::new(83.0, 325.0));
+    assert_eq!(obj.start_time, Time(1132.0));
+    match &obj.kind {
+        HitObjectKind::Slider(info) => assert_eq!(
+            info.control_points,
+            vec![Point::new(102.0, 282.0), Point::new(147.0, 259.0)]
+        ),
+        kind => panic!("unexpected {:?}", kind),
+    }
+
+    // flipping twice gets back where we started
+    let mut twice = beatmap.clone();
+    twice.apply_mods(Mods::HardRock);
+    assert_eq!(twice.hit_objects[0].pos, Point::new(83.0, 59.0));
+
+    // only osu!standard is flipped
+    let mut mania = control()?;
+    mania.mode = Mode::Mania;
+    let mania = mania.with_mods(Mods::HardRock);
+    assert_eq!(mania.hit_objects[0].pos, Point::new(83.0, 59.0));
+    assert_eq!(mania.difficulty.circle_size, 4.0);
+    Ok(())
+}
+
+#[test]
+fn test_hard_rock_storyboard() -> Result<()> {
+    let mut beatmap = control()?;
+    let mut sprite = SpriteBuilder::sprite(
+        Layer::Foreground,
+        Origin::TopLeft,
+        "sb/bar.png",
+        Point::new(100.0, 40.0),
+    );
+    sprite
+        .fade(Easing::Linear, 1000, 2000, 0.0, 1.0)
+        .rotate(Easing::Linear, 1000, 2000, 0.0, 1.0);
+    beatmap.events.push(sprite.build());
+
+    let flipped = beatmap.with_mods(Mods::HardRock);
+    match flipped.events.last() {
+        Some(Event::Sprite(sprite)) => {
+            // the image hangs down from its top left corner, so it has to hang up once flipped
+            assert_eq!(sprite.origin, Origin::BottomLeft);
+            assert_eq!(sprite.pos, Point::new(100.0, 440.0));
+            assert_eq!(
+                sprite.commands,
+                vec![
+                    Command::Parameter(Tween::constant(1000, 1000, Parameter::FlipVertical)),
+                    Command::Fade(Tween::new(Easing::Linear, 1000, 2000, 0.0, 1.0)),
+                    Command::Rotate(Tween::new(Easing::Linear, 1000, 2000, -0.0, -1.0)),
+                ]
+            );
+        }
+        event => panic!("unexpected {:?}", event),
+    }
+
+    // flipping twice gets back where we started
+    let twice = flipped.with_mods(Mods::HardRock);
+    assert_eq!(twice.events.last(), beatmap.events.last());
+    Ok(())
+}
+
+#[test]
+fn test_easy() -> Result<()> {
+    let beatmap = control()?.with_mods(Mods::Easy);
+    assert_eq!(beatmap.difficulty.circle_size, 2.0);
+    assert_eq!(beatmap.difficulty.hp_drain_rate, 3.25);
+    assert!((beatmap.difficulty.approach_rate - 4.6).abs() < 1e-5);
+    assert_eq!(beatmap.difficulty.overall_difficulty, 4.5);
+    assert_eq!(beatmap.hit_objects[0].pos, Point::new(83.0, 59.0));
+    Ok(())
+}
+
+#[test]
+fn test_mods_apply_without_speed_mods() {
+    let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+    let hr = mods_apply(Mods::HardRock, 9.0, 8.0, 4.0, 5.0);
+    assert_eq!(hr.speed_mul, 1.0);
+    assert!(close(hr.ar, 10.0));
+    assert!(close(hr.od, 10.0));
+    assert!(close(hr.cs, 5.2));
+    assert!(close(hr.hp, 7.0));
+
+    let ez = mods_apply(Mods::Easy, 9.0, 8.0, 4.0, 5.0);
+    assert_eq!(ez.speed_mul, 1.0);
+    assert!(close(ez.ar, 4.5));
+    assert!(close(ez.od, 4.0));
+    assert!(close(ez.cs, 2.0));
+    assert!(close(ez.hp, 2.5));
+
+    // mods that don't change the map leave everything alone
+    let hd = mods_apply(Mods::Hidden, 9.0, 8.0, 4.0, 5.0);
+    assert_eq!((hd.speed_mul, hd.ar, hd.od), (1.0, 9.0, 8.0));
+    assert_eq!((hd.cs, hd.hp), (4.0, 5.0));
+}
+
+#[test]
+fn test_rate_change() -> Result<()> {
+    let original = control()?;
+    let beatmap = original.with_mods(Mods::DoubleTime);
+    assert_eq!(beatmap.difficulty.circle_size, 4.0);
+    assert_eq!(beatmap.difficulty.hp_drain_rate, 6.5);
+    assert!(beatmap.difficulty.approach_rate > 10.0);
+
+    for (a, b) in original.hit_objects.iter().zip(beatmap.hit_objects.iter()) {
+        assert!((a.start_time.0 / 1.5 - b.start_time.0).abs() < 1e-9);
+    }
+    let tp = &beatmap.timing_points[0];
+    assert!((tp.time.0 - -1777.0 / 1.5).abs() < 1e-9);
+    match &tp.kind {
+        TimingPointKind::Uninherited(info) => {
+            assert!((info.mpb - 363.636363636364 / 1.5).abs() < 1e-9)
+        }
+        kind => panic!("unexpected {:?}", kind),
+    }
+    assert_eq!(beatmap.preview_time, Millis(56997));
+    assert_eq!(beatmap.bookmarks[0], 755);
+    assert!(beatmap.events.iter().any(|evt| matches!(
+        evt,
+        Event::Break(BreakEvent {
+            start_time: Millis(123069),
+            end_time: Millis(126435),
+        })
+    )));
+
+    // the map plays for the same number of beats, just faster
+    assert_eq!(beatmap.max_combo(), original.max_combo());
+    assert_eq!(
+        beatmap
+            .get_slider_duration(&beatmap.hit_objects[0])
+            .map(|d| (d * 1000.0).round()),
+        original
+            .get_slider_duration(&original.hit_objects[0])
+            .map(|d| (d * 1000.0 / 1.5).round())
+    );
+
+    // nightcore is the same speed as double time, half time slows things down
+    let nightcore = original.with_mods(Mods::Nightcore);
+    assert_eq!(nightcore.to_string(), beatmap.to_string());
+    let half_time = original.with_mods(Mods::HalfTime);
+    assert_eq!(half_time.preview_time, Millis(113993));
+    assert!(half_time.difficulty.approach_rate < 9.2);
+    Ok(())
+}
+
+#[test]
+fn test_practice_variant() -> Result<()> {
+    let original = control()?;
+    let options = VariantOptions {
+        rate: 1.15,
+        approach_rate: Some(9.5),
+        circle_size: Some(4.2),
+        ..VariantOptions::default()
+    };
+    let variant = original.make_variant(&options);
+    assert_eq!(variant.difficulty_name, "Max Control! 1.15x AR9.5 CS4.2");
+    assert_eq!(variant.beatmap_id, 0);
+    assert_eq!(variant.difficulty.approach_rate, 9.5);
+    assert_eq!(variant.difficulty.circle_size, 4.2);
+    assert_eq!(
+        variant.difficulty.overall_difficulty,
+        original.difficulty.overall_difficulty
+    );
+
+    for (a, b) in original.hit_objects.iter().zip(variant.hit_objects.iter()) {
+        assert!((a.start_time.0 / 1.15 - b.start_time.0).abs() < 1e-9);
+    }
+    match &variant.timing_points[0].kind {
+        TimingPointKind::Uninherited(info) => {
+            assert!((info.mpb - 363.636363636364 / 1.15).abs() < 1e-9)
+        }
+        kind => panic!("unexpected {:?}", kind),
+    }
+    assert_eq!(
+        variant.bookmarks[0],
+        (original.bookmarks[0] as f64 / 1.15).round() as i32
+    );
+
+    // the variant is written out as a difficulty of its own
+    let written = Beatmap::from_str(&variant.to_string())?;
+    assert_eq!(written.difficulty_name, variant.difficulty_name);
+    assert_eq!(written.difficulty.approach_rate, 9.5);
+    assert_eq!(written.timing_points, variant.timing_points);
+    assert_eq!(written.hit_objects.len(), variant.hit_objects.len());
+    for (a, b) in variant.hit_objects.iter().zip(written.hit_objects.iter()) {
+        assert_eq!(b.start_time, Time(a.start_time.0.round()));
+    }
+    assert!(variant
+        .hit_objects
+        .iter()
+        .any(|obj| obj.start_time.0.fract() != 0.0));
+
+    // lazer's format keeps them exact
+    let mut lazer = variant.clone();
+    lazer.version = FIRST_LAZER_VERSION;
+    let written = Beatmap::from_str(&lazer.to_string())?;
+    for (a, b) in variant.hit_objects.iter().zip(written.hit_objects.iter()) {
+        assert_eq!(b.start_time, a.start_time);
+    }
+
+    // overrides alone don't retime anything
+    let ar_only = original.make_variant(&VariantOptions {
+        approach_rate: Some(9.5),
+        ..VariantOptions::default()
+    });
+    assert_eq!(ar_only.difficulty_name, "Max Control! AR9.5");
+    assert_eq!(ar_only.hit_objects, original.hit_objects);
+    assert_eq!(original.make_variant(&VariantOptions::default()), original);
+    Ok(())
+}
@@ -0,0 +1,183 @@
+//! Beatmap sets should keep their files, and survive being zipped up and read back
+
+use std::fs;
+use std::io::Read;
+
+use anyhow::Result;
+use libosu::prelude::*;
+
+fn adamas_set() -> Result<BeatmapSet> {
+    let mut set = BeatmapSet::new();
+    for path in &[
+        "tests/files/adamas-hitsounds.osu",
+        "tests/files/adamas-nohitsounds.osu",
+    ] {
+        let name = path.rsplit('/').next().unwrap();
+        set.insert_file(name, fs::read(path)?);
+    }
+    set.insert_file("audio.mp3", b"ID3 not really audio".to_vec());
+    set.insert_file("bg/3.jpg", b"not really an image".to_vec());
+    set.insert_file(
+        "Storyboard.osb",
+        b"[Events]\nSprite,Foreground,Centre,\"bg\\3.jpg\",320,240\n F,0,0,1000,0,1\n".to_vec(),
+    );
+    Ok(set)
+}
+
+#[test]
+fn test_beatmap_set() -> Result<()> {
+    let mut set = adamas_set()?;
+    assert_eq!(
+        set.difficulties().collect::<Vec<_>>(),
+        vec!["adamas-hitsounds.osu", "adamas-nohitsounds.osu"]
+    );
+
+    for (_, beatmap) in set.beatmaps() {
+        let beatmap = beatmap?;
+        assert_eq!(beatmap.audio_filename, "audio.mp3");
+
+        let mut audio = Vec::new();
+        set.audio(&beatmap).unwrap().read_to_end(&mut audio)?;
+        assert_eq!(audio, b"ID3 not really audio");
+
+        // the background is referenced as "3.jpg", which isn't in the set
+        assert!(set.background(&beatmap).is_none());
+    }
+
+    // paths are matched the way the game matches them
+    assert!(set.contains("BG\\3.JPG"));
+    let storyboard = set.storyboard().unwrap()?;
+    assert_eq!(storyboard.events.len(), 1);
+
+    let mut beatmap = set.beatmap("ADAMAS-HITSOUNDS.osu").unwrap()?;
+    beatmap.difficulty_name = String::from("Copy?");
+    let name = set.insert_beatmap(&beatmap);
+    assert_eq!(
+        name,
+        format!(
+            "{} - {} ({}) [Copy].osu",
+            beatmap.artist, beatmap.title, beatmap.creator
+        )
+    );
+    assert_eq!(set.difficulties().count(), 3);
+    assert!(set.remove_file(&name).is_some());
+    assert_eq!(set.difficulties().count(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_mapset_check() -> Result<()> {
+    let mut set = adamas_set()?;
+    set.insert_file("3.jpg", b"not really an image".to_vec());
+    set.insert_file("soft-hitclap.wav", Vec::new());
+    set.insert_file("soft-hitclap2.wav", Vec::new());
+    set.insert_file("notes.txt", Vec::new());
+    let mapset = Mapset::new(set.clone())?;
+    assert_eq!(mapset.difficulties.len(), 2);
+    assert!(mapset.storyboard.is_some());
+
+    let unused = |path: &str| MapsetDiagnostic {
+        difficulty: None,
+        kind: MapsetDiagnosticKind::UnusedFile {
+            path: String::from(path),
+        },
+    };
+    assert_eq!(
+        mapset.check(),
+        vec![unused("soft-hitclap2.wav"), unused("notes.txt")]
+    );
+
+    let mut beatmap = set.beatmap("adamas-nohitsounds.osu").unwrap()?;
+    beatmap.title = String::from("ADAMAS (TV Size)");
+    beatmap.artist = String::from("リサ");
+    beatmap.audio_filename = String::from("other.mp3");
+    set.insert_file("adamas-nohitsounds.osu", beatmap.to_string().into_bytes());
+    set.remove_file("3.jpg");
+
+    let diagnostics = Mapset::new(set)?.check();
+    let summary = diagnostics
+        .iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            "adamas-nohitsounds.osu: Artist is \"リサ\", but \"LiSA\" elsewhere",
+            "adamas-nohitsounds.osu: Title is \"ADAMAS (TV Size)\", but \"ADAMAS\" elsewhere",
+            "adamas-nohitsounds.osu: AudioFilename is \"other.mp3\", but \"audio.mp3\" elsewhere",
+            "adamas-nohitsounds.osu: Artist has non-ASCII characters",
+            "adamas-nohitsounds.osu: TitleUnicode doesn't match its romanized field",
+            "adamas-hitsounds.osu: missing file 3.jpg",
+            "adamas-nohitsounds.osu: missing file other.mp3",
+            "adamas-nohitsounds.osu: missing file 3.jpg",
+            "unused file soft-hitclap2.wav",
+            "unused file notes.txt",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_mapset_missing_samples() -> Result<()> {
+    let mut set = BeatmapSet::new();
+    set.insert_file(
+        "map.osu",
+        b"osu file format v14\n\n[General]\nAudioFilename: audio.mp3\n\n\
+          [TimingPoints]\n0,500,4,2,2,60,1,0\n\n\
+          [HitObjects]\n256,192,1000,1,2,0:0:0:0:\n256,192,1500,1,0,0:0:3:0:\n\
+          256,192,2000,1,0,0:0:4:0:soft-hitnormal4.wav\n"
+            .to_vec(),
+    );
+    set.insert_file("audio.mp3", Vec::new());
+    set.insert_file("soft-hitnormal2.ogg", Vec::new());
+
+    let summary = Mapset::new(set)?
+        .check()
+        .iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            "map.osu: missing file soft-hitnormal4.wav",
+            "map.osu: missing hitsound sample soft-hitwhistle2.wav",
+            "map.osu: missing hitsound sample soft-hitnormal3.wav",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_read_dir() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("libosu-test-set-{}", std::process::id()));
+    fs::create_dir_all(dir.join("sb"))?;
+    fs::copy("tests/files/774965.osu", dir.join("774965.osu"))?;
+    fs::write(dir.join("sb").join("star.png"), b"star")?;
+
+    let set = BeatmapSet::read_dir(&dir);
+    fs::remove_dir_all(&dir)?;
+    let set = set?;
+    assert_eq!(
+        set.file_names().collect::<Vec<_>>(),
+        vec!["774965.osu", "sb/star.png"]
+    );
+    assert_eq!(set.file("SB\\Star.png"), Some(&b"star"[..]));
+    Ok(())
+}
+
+#[cfg(feature = "osz")]
+#[test]
+fn test_osz_roundtrip() -> Result<()> {
+    use std::io::Cursor;
+
+    let set = adamas_set()?;
+    let mut osz = Cursor::new(Vec::new());
+    set.write_osz(&mut osz)?;
+
+    osz.set_position(0);
+    let read = BeatmapSet::read_osz(osz)?;
+    assert_eq!(read, set);
+
+    assert!(BeatmapSet::read_osz(Cursor::new(b"not a zip file".to_vec())).is_err());
+    Ok(())
+}
@@ -0,0 +1,80 @@
+//! Make sure the incremental reader and the streaming writer agree with the full parser
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+
+use anyhow::Result;
+use libosu::prelude::*;
+
+const TEST_FILES: &[&str] = &[
+    "tests/files/129891.osu",
+    "tests/files/1595588.osu",
+    "tests/files/774965.osu",
+    "tests/files/804683.osu",
+    "tests/files/adamas-hitsounds.osu",
+];
+
+#[test]
+fn test_parse_header() -> Result<()> {
+    for path in TEST_FILES {
+        let beatmap = Beatmap::parse(File::open(path)?)?;
+        let header = Beatmap::parse_header(File::open(path)?)?;
+
+        assert_eq!(header.version, beatmap.version);
+        assert_eq!(header.title, beatmap.title);
+        assert_eq!(header.difficulty_name, beatmap.difficulty_name);
+        assert_eq!(header.beatmap_id, beatmap.beatmap_id);
+        assert_eq!(header.difficulty, beatmap.difficulty);
+        assert!(header.events.is_empty());
+        assert!(header.hit_objects.is_empty());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_reader_items() -> Result<()> {
+    let input = fs::read_to_string("tests/files/129891.osu")?;
+    let beatmap = input.parse::<Beatmap>()?;
+
+    let mut sections = Vec::new();
+    let mut hit_objects = 0;
+    let mut events = Vec::new();
+    for item in BeatmapReader::new(input.as_bytes()) {
+        match item? {
+            BeatmapItem::Section(name) => sections.push(name),
+            BeatmapItem::HitObject(_) => hit_objects += 1,
+            BeatmapItem::Event(event) => events.push(event),
+            _ => (),
+        }
+    }
+
+    assert_eq!(
+        sections,
+        &[
+            "General",
+            "Editor",
+            "Metadata",
+            "Difficulty",
+            "Events",
+            "TimingPoints",
+            "Colours",
+            "HitObjects"
+        ]
+    );
+    assert_eq!(hit_objects, beatmap.hit_objects.len());
+    assert_eq!(events, beatmap.events);
+
+    Ok(())
+}
+
+#[test]
+fn test_streaming_write() -> Result<()> {
+    let beatmap = Beatmap::parse(File::open("tests/files/129891.osu")?)?;
+
+    let mut buf = Vec::new();
+    beatmap.write(BufWriter::new(&mut buf))?;
+    assert_eq!(String::from_utf8(buf)?, beatmap.to_string());
+
+    Ok(())
+}
@@ -4,8 +4,8 @@ use std::path::Path;
 
 use anyhow::Result;
 use libosu::{
-    data::{Mode, Mods},
-    replay::{Buttons, Replay, ReplayActionData},
+    data::{Grade, Mode, Mods},
+    replay::{Buttons, Replay, ReplayActionData, ReplayIssue},
     timing::Millis,
 };
 
@@ -221,3 +221,120 @@ fn test_replay_parse() -> Result<()> {
     assert_eq!(replay.target_practice_total_accuracy, None);
     Ok(())
 }
+
+#[test]
+fn test_replay_grade() -> Result<()> {
+    let grades = [
+        ("tests/files/replay-osu_1816113_2892542031.osr", Grade::S),
+        ("tests/files/replay-osu_2058788_3017707256.osr", Grade::A),
+        ("tests/files/replay_with_life.osr", Grade::B),
+    ];
+    for (path, grade) in grades.iter() {
+        let replay = Replay::parse(&mut File::open(path)?)?;
+        assert_eq!(replay.grade(), *grade, "{}", path);
+    }
+
+    let mut replay = Replay::parse(&mut File::open(grades[0].0)?)?;
+    replay.count_300 += replay.count_100;
+    replay.count_100 = 0;
+    replay.mods = Mods::Hidden;
+    assert_eq!(replay.grade(), Grade::SSH);
+    Ok(())
+}
+
+#[test]
+fn test_replay_verify() -> Result<()> {
+    // replays saved by the game carry the hash computed from their header
+    for path in [
+        "tests/files/replay-osu_1816113_2892542031.osr",
+        "tests/files/replay-osu_2058788_3017707256.osr",
+    ]
+    .iter()
+    {
+        let replay = Replay::parse(&mut File::open(path)?)?;
+        assert_eq!(replay.compute_replay_hash(), replay.replay_hash, "{}", path);
+        assert!(replay.verify(None).is_ok(), "{}", path);
+    }
+
+    let mut replay = Replay::parse(&mut File::open(
+        "tests/files/replay-osu_1816113_2892542031.osr",
+    )?)?;
+    let beatmap = b"osu file format v14\n";
+
+    // editing the header changes the hash, and updating it fixes the report again
+    let hash = replay.replay_hash.clone();
+    replay.score += 1;
+    let report = replay.verify(None);
+    assert_eq!(
+        report.issues,
+        vec![ReplayIssue::ReplayHashMismatch {
+            expected: replay.compute_replay_hash(),
+            found: hash.clone(),
+        }]
+    );
+    assert_ne!(replay.compute_replay_hash(), hash);
+    replay.update_replay_hash();
+    assert!(replay.verify(None).is_ok());
+
+    let report = replay.verify(Some(beatmap));
+    assert_eq!(
+        report.issues,
+        vec![ReplayIssue::BeatmapHashMismatch {
+            expected: String::from("36f70c48e16e2b13c3c93609eafdaff9"),
+            found: replay.beatmap_hash.clone(),
+        }]
+    );
+
+    replay.beatmap_hash = String::from("36f70c48e16e2b13c3c93609eafdaff9");
+    replay.update_replay_hash();
+    assert!(replay.verify(Some(beatmap)).is_ok());
+
+    replay.beatmap_hash = String::from("nope");
+    replay.action_data.clear();
+    replay.update_replay_hash();
+    assert_eq!(
+        replay.verify(None).issues,
+        vec![
+            ReplayIssue::InvalidBeatmapHash(String::from("nope")),
+            ReplayIssue::MissingActionData,
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_replay_write_perfect() -> Result<()> {
+    let mut replay = Replay::parse(&mut File::open("tests/files/replay_with_life.osr")?)?;
+    for perfect in [true, false].iter() {
+        replay.perfect = *perfect;
+        let mut contents = Vec::new();
+        replay.write(&mut contents)?;
+        let replay2 = Replay::parse(&mut Cursor::new(&contents))?;
+        assert_eq!(replay2.perfect, *perfect);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "replay-data")]
+#[test]
+fn test_replay_verify_action_data() -> Result<()> {
+    let mut replay = Replay::parse(&mut File::open("tests/files/replay_with_life.osr")?)?;
+    replay.update_replay_hash();
+    assert!(replay.verify(None).is_ok());
+
+    replay.life_graph.push((200_000, 1.0));
+    assert_eq!(
+        replay.verify(None).issues,
+        vec![ReplayIssue::ActionDataTooShort {
+            duration: 130732,
+            life_graph_end: 200_000,
+        }]
+    );
+
+    replay.action_data = vec![1, 2, 3];
+    assert!(matches!(
+        replay.verify(None).issues[..],
+        [ReplayIssue::InvalidActionData(_)]
+    ));
+    Ok(())
+}
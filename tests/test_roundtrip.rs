@@ -0,0 +1,243 @@
+//! Make sure beatmaps can be written back out without touching the lines that didn't change
+
+use std::fs;
+use std::str::FromStr;
+
+use anyhow::Result;
+use libosu::prelude::*;
+
+const TEST_FILES: &[&str] = &[
+    "tests/files/129891.osu",
+    "tests/files/1595588.osu",
+    "tests/files/774965.osu",
+    "tests/files/804683.osu",
+    "tests/files/adamas-hitsounds.osu",
+    "tests/files/adamas-nohitsounds.osu",
+];
+
+fn changed_lines(a: &str, b: &str) -> Vec<(usize, String)> {
+    a.lines()
+        .zip(b.lines())
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+        .map(|(i, (_, b))| (i, b.to_owned()))
+        .collect()
+}
+
+#[test]
+fn test_unmodified_roundtrip() -> Result<()> {
+    for path in TEST_FILES {
+        let input = fs::read_to_string(path)?;
+        let doc = BeatmapDocument::from_str(&input)?;
+
+        let mut output = Vec::new();
+        doc.write(&mut output)?;
+        assert!(output == input.as_bytes(), "{} changed", path);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_modified_roundtrip() -> Result<()> {
+    let input = fs::read_to_string("tests/files/129891.osu")?;
+    let mut doc = BeatmapDocument::from_str(&input)?;
+
+    doc.beatmap.title = String::from("Modified");
+    doc.beatmap.hit_objects[5].pos = Point::new(1.0, 2.0);
+    let output = doc.to_string();
+    assert_eq!(output.lines().count(), input.lines().count());
+
+    let changed = changed_lines(&input, &output);
+    assert_eq!(changed.len(), 2, "{:?}", changed);
+    assert_eq!(changed[0].1, "Title:Modified");
+    // the changed object is written the way the rest of the file's version writes them
+    assert_eq!(changed[1].1, "1,2,3753,1,0");
+
+    // removing an object only removes its line, and adding one puts it next to its neighbours
+    doc.beatmap.hit_objects.remove(5);
+    let output = doc.to_string();
+    assert_eq!(output.lines().count(), input.lines().count() - 1);
+
+    let mut new_object = doc.beatmap.hit_objects[9].clone();
+    new_object.pos = Point::new(3.0, 4.0);
+    doc.beatmap.hit_objects.insert(10, new_object.clone());
+    let output = doc.to_string();
+    let lines = output.lines().collect::<Vec<_>>();
+    let idx = lines
+        .iter()
+        .position(|line| *line == "3,4,5103,2,0,B|120:64|128:136,1,95")
+        .unwrap();
+    assert_eq!(
+        HitObject::from_str(lines[idx - 1])?.to_string(),
+        doc.beatmap.hit_objects[9].to_string()
+    );
+
+    // everything still parses back to the same beatmap
+    let reparsed = Beatmap::from_str(&output)?;
+    assert_eq!(reparsed.title, doc.beatmap.title);
+    let rendered = |objs: &[HitObject]| objs.iter().map(|o| o.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        rendered(&reparsed.hit_objects),
+        rendered(&doc.beatmap.hit_objects)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_preserves_unknown_lines() -> Result<()> {
+    let input = "osu file format v14\r\n\r\n[General]\r\nAudioFilename: a.mp3\r\nSomethingNew: 1\r\n\r\n[Fancy]\r\nwhatever\r\n\r\n[Colours]\r\n// comment\r\nCombo1 : 1,2,3\r\n";
+    let mut doc = BeatmapDocument::from_str(input)?;
+    assert_eq!(doc.to_string(), input);
+
+    doc.beatmap.audio_filename = String::from("b.mp3");
+    doc.beatmap.countdown = Countdown::Double;
+    doc.beatmap.colors.push(Color::new(4, 5, 6));
+    assert_eq!(
+        doc.to_string(),
+        "osu file format v14\r\n\r\n[General]\r\nAudioFilename: b.mp3\r\nSomethingNew: 1\r\nCountdown: 3\r\n\r\n[Fancy]\r\nwhatever\r\n\r\n[Colours]\r\n// comment\r\nCombo1 : 1,2,3\r\nCombo2 : 4,5,6\r\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_general_and_colours_keys() -> Result<()> {
+    let input = "osu file format v14\n\n[General]\nAudioFilename: a.mp3\nAudioLeadIn: 0\nAudioHash: 0123456789abcdef\nPreviewTime: -1\nCountdown: 2\nSampleSet: Soft\nSampleVolume: 80\nStackLeniency: 0.7\nMode: 3\nLetterboxInBreaks: 1\nStoryFireInFront: 0\nUseSkinSprites: 1\nAlwaysShowPlayfield: 1\nOverlayPosition: Above\nSkinPreference: some skin\nEpilepsyWarning: 1\nCountdownOffset: 2\nSpecialStyle: 1\nWidescreenStoryboard: 1\nSamplesMatchPlaybackRate: 1\n\n[Colours]\nCombo1 : 255,0,0\nSliderTrackOverride : 1,2,3\nSliderBorder : 4,5,6\n";
+    let beatmap = Beatmap::from_str(input)?;
+    assert_eq!(beatmap.audio_hash, "0123456789abcdef");
+    assert_eq!(beatmap.countdown, Countdown::Half);
+    assert_eq!(beatmap.sample_volume, 80);
+    assert!(!beatmap.story_fire_in_front);
+    assert!(beatmap.use_skin_sprites);
+    assert!(beatmap.always_show_playfield);
+    assert_eq!(beatmap.overlay_position, OverlayPosition::Above);
+    assert_eq!(beatmap.skin_preference, "some skin");
+    assert!(beatmap.epilepsy_warning);
+    assert_eq!(beatmap.countdown_offset, 2);
+    assert!(beatmap.special_style);
+    assert!(beatmap.widescreen_storyboard);
+    assert!(beatmap.samples_match_playback_rate);
+    assert_eq!(beatmap.colors, vec![Color::new(255, 0, 0)]);
+    assert_eq!(beatmap.slider_track_override, Some(Color::new(1, 2, 3)));
+    assert_eq!(beatmap.slider_border, Some(Color::new(4, 5, 6)));
+
+    // the plain writer keeps everything
+    let reparsed = Beatmap::from_str(&beatmap.to_string())?;
+    assert_eq!(reparsed, beatmap);
+
+    // and the document writer only touches what changed
+    let mut doc = BeatmapDocument::from_str(input)?;
+    doc.beatmap.colors.push(Color::new(0, 0, 255));
+    doc.beatmap.slider_track_override = None;
+    doc.beatmap.overlay_position = OverlayPosition::Below;
+    assert_eq!(
+        doc.to_string(),
+        input
+            .replace("OverlayPosition: Above", "OverlayPosition: Below")
+            .replace(
+                "Combo1 : 255,0,0\nSliderTrackOverride : 1,2,3\n",
+                "Combo1 : 255,0,0\nCombo2 : 0,0,255\n"
+            )
+    );
+
+    // keys that weren't in the file get added where they belong
+    let mut doc =
+        BeatmapDocument::from_str("osu file format v14\n\n[General]\nAudioFilename: a.mp3\n")?;
+    doc.beatmap.slider_border = Some(Color::new(7, 8, 9));
+    doc.beatmap.samples_match_playback_rate = true;
+    assert_eq!(
+        doc.to_string(),
+        "osu file format v14\n\n[General]\nAudioFilename: a.mp3\nSamplesMatchPlaybackRate: 1\n\n[Colours]\nSliderBorder : 7,8,9\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_fractional_precision() -> Result<()> {
+    let lazer = "osu file format v128\n\n[TimingPoints]\n1234.5678,333.333333333333,4,2,0,50,1,0\n\n[HitObjects]\n256.5,192.25,1500.75,2,0,B|300.5:200|320:180.125,1,100.5\n64,64,2000.5,12,0,3000.25,0:0:0:0:\n";
+    let beatmap = Beatmap::from_str(lazer)?;
+    assert_eq!(beatmap.timing_points[0].time, Time(1234.5678));
+    assert_eq!(beatmap.hit_objects[0].pos, Point::new(256.5, 192.25));
+    assert_eq!(beatmap.hit_objects[0].start_time, Time(1500.75));
+    assert_eq!(beatmap.hit_objects[0].start_time.millis(), Millis(1501));
+
+    // lazer versions keep every fraction
+    let written = beatmap.to_string();
+    assert!(written.contains("\n1234.5678,333.333333333333,4,2,0,50,1,0\n"));
+    assert!(written.contains("\n256.5,192.25,1500.75,2,0,B|300.5:200|320:180.125,1,100.5,"));
+    assert!(written.contains("\n64,64,2000.5,12,0,3000.25,0:0:0:0:\n"));
+    let reparsed = Beatmap::from_str(&written)?;
+    assert_eq!(
+        reparsed.hit_objects[1].to_string(),
+        beatmap.hit_objects[1].to_string()
+    );
+
+    // older versions get whole numbers for hit objects, but keep fractional timing points
+    let mut legacy = beatmap.clone();
+    legacy.version = 14;
+    let written = legacy.to_string();
+    assert!(written.contains("\n1234.5678,333.333333333333,4,2,0,50,1,0\n"));
+    assert!(written.contains("\n257,192,1501,2,0,B|301:200|320:180,1,100.5,"));
+    assert!(written.contains("\n64,64,2001,12,0,3000,0:0:0:0:\n"));
+
+    // the document writer follows the same rule for objects it has to write
+    let mut doc = BeatmapDocument::from_str(lazer)?;
+    doc.beatmap.version = 14;
+    doc.beatmap.hit_objects[1].start_time = Time(2100.4);
+    let written = doc.to_string();
+    assert!(written.starts_with("osu file format v14\n"));
+    assert!(written.contains("\n64,64,2100,12,0,3000,0:0:0:0:\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_comments_in_commands() -> Result<()> {
+    let input = "osu file format v14\n\n[Events]\n//Storyboard Layer 3 (Foreground)\nSprite,Foreground,Centre,\"a.png\",320,240\n F,0,0,1000,0,1\n// note\n\n M,0,0,1000,0,0,100,100\n\nSprite,Foreground,Centre,\"b.png\",320,240\n F,0,0,1000,0,1\n";
+    let mut doc = BeatmapDocument::from_str(input)?;
+    assert_eq!(doc.to_string(), input);
+
+    // the comment goes with the sprite whose commands it's in
+    doc.beatmap.events.remove(0);
+    assert_eq!(
+        doc.to_string(),
+        "osu file format v14\n\n[Events]\n//Storyboard Layer 3 (Foreground)\n\nSprite,Foreground,Centre,\"b.png\",320,240\n F,0,0,1000,0,1\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_large_changes() -> Result<()> {
+    let objects = (0..5000)
+        .map(|i| format!("{},192,{},1,0,0:0:0:0:", i % 512, i * 10))
+        .collect::<Vec<_>>();
+    let input = format!(
+        "osu file format v14\n\n[HitObjects]\n{}\n",
+        objects.join("\n")
+    );
+    let mut doc = BeatmapDocument::from_str(&input)?;
+
+    // changes all over a long list are written out without comparing every pair of objects
+    let start = std::time::Instant::now();
+    doc.beatmap.hit_objects[0].pos = Point::new(1.0, 2.0);
+    doc.beatmap.hit_objects[4999].pos = Point::new(3.0, 4.0);
+    for obj in doc.beatmap.hit_objects[1..4999].iter_mut().step_by(2) {
+        obj.pos.y = 100.0;
+    }
+    let output = doc.to_string();
+    let reparsed = Beatmap::from_str(&output)?;
+    let rendered = |objs: &[HitObject]| objs.iter().map(|o| o.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        rendered(&reparsed.hit_objects),
+        rendered(&doc.beatmap.hit_objects)
+    );
+    assert!(
+        start.elapsed() < std::time::Duration::from_secs(20),
+        "writing took {:?}",
+        start.elapsed()
+    );
+    Ok(())
+}
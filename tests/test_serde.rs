@@ -4,7 +4,7 @@ use std::path::Path;
 use std::str::FromStr;
 
 use libosu::{
-    beatmap::Beatmap,
+    beatmap::{Beatmap, Countdown},
     data::Mode,
     events::{BackgroundEvent, BreakEvent, Event},
     hitsounds::SampleSet,
@@ -27,7 +27,7 @@ fn parse_taeyang_remote_control() {
     assert_eq!(beatmap.audio_filename, "control.mp3");
     assert_eq!(beatmap.audio_leadin, Millis(1000));
     assert_eq!(beatmap.preview_time, Millis(85495));
-    assert_eq!(beatmap.countdown, false);
+    assert_eq!(beatmap.countdown, Countdown::None);
     assert_eq!(beatmap.sample_set, SampleSet::Normal);
     assert_eq!(beatmap.stack_leniency, 0.8);
     assert_eq!(beatmap.mode, Mode::Osu);
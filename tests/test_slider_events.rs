@@ -0,0 +1,218 @@
+//! Sliders should be broken up into the head, ticks, repeats and tail the game judges
+
+use anyhow::Result;
+use libosu::prelude::*;
+
+mod common;
+
+/// Ticks twice a beat, and 100 osu!pixels a beat at 1x slider velocity
+const SLIDER_DIFFICULTY: (&str, &str) = ("Difficulty", "SliderMultiplier:1\nSliderTickRate:2");
+
+fn times(events: &[SliderEvent], kind: SliderEventKind) -> Vec<f64> {
+    events
+        .iter()
+        .filter(|evt| evt.kind == kind)
+        .map(|evt| evt.time.0)
+        .collect()
+}
+
+#[test]
+fn test_repeats() -> Result<()> {
+    let beatmap = common::beatmap(
+        14,
+        &[
+            SLIDER_DIFFICULTY,
+            ("TimingPoints", "0,500,4,2,0,100,1,0"),
+            ("HitObjects", "0,0,1000,2,0,L|200:0,3,200"),
+        ],
+    )?;
+    let events = beatmap.slider_events(&beatmap.hit_objects[0]).unwrap();
+    assert_eq!(events.len(), 1 + 9 + 2 + 2);
+
+    assert_eq!(events[0].kind, SliderEventKind::Head);
+    assert_eq!(
+        times(&events, SliderEventKind::Tick),
+        vec![1250.0, 1500.0, 1750.0, 2250.0, 2500.0, 2750.0, 3250.0, 3500.0, 3750.0]
+    );
+
+    // ticks going back along the path are in the same places, but in reverse order
+    let tick = &events[5];
+    assert_eq!(tick.span_index, 1);
+    assert_eq!(tick.path_progress, 0.75);
+    assert_eq!(tick.pos, Point::new(150.0, 0.0));
+
+    // the first repeat turns around at the end of the path, the second at the start
+    let repeats = events
+        .iter()
+        .filter_map(|evt| match evt.kind {
+            SliderEventKind::Repeat { angle } => Some((evt, angle)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(repeats.len(), 2);
+    assert_eq!(repeats[0].0.time, Time(2000.0));
+    assert_eq!(repeats[0].0.pos, Point::new(200.0, 0.0));
+    assert!((repeats[0].1.cos() - -1.0).abs() < 1e-9);
+    assert_eq!(repeats[1].0.time, Time(3000.0));
+    assert_eq!(repeats[1].0.pos, Point::new(0.0, 0.0));
+    assert!(repeats[1].1.abs() < 1e-9);
+
+    // the last tick is 36ms before the end
+    let last_tick = &events[events.len() - 2];
+    assert_eq!(last_tick.kind, SliderEventKind::LegacyLastTick);
+    assert_eq!(last_tick.time, Time(4000.0 - LEGACY_LAST_TICK_OFFSET));
+    assert_eq!(last_tick.span_index, 2);
+    assert!((last_tick.pos.x - 192.8).abs() < 1e-9);
+
+    let tail = events.last().unwrap();
+    assert_eq!(tail.kind, SliderEventKind::Tail);
+    assert_eq!(tail.time, Time(4000.0));
+    assert_eq!(tail.pos, Point::new(200.0, 0.0));
+    assert_eq!(tail.path_progress, 1.0);
+    Ok(())
+}
+
+#[test]
+fn test_slider_velocity() -> Result<()> {
+    // at double velocity, ticks are twice as far apart but at the same times
+    let timing = "0,500,4,2,0,100,1,0\n500,-50,4,2,0,100,0,0";
+    let slider = "0,0,1000,2,0,L|400:0,1,400";
+    let beatmap = common::beatmap(
+        14,
+        &[
+            SLIDER_DIFFICULTY,
+            ("TimingPoints", timing),
+            ("HitObjects", slider),
+        ],
+    )?;
+    let events = beatmap.slider_events(&beatmap.hit_objects[0]).unwrap();
+    assert_eq!(
+        times(&events, SliderEventKind::Tick),
+        vec![1250.0, 1500.0, 1750.0]
+    );
+    assert_eq!(events[1].pos, Point::new(100.0, 0.0));
+
+    // old maps space ticks as if the velocity hadn't changed
+    let beatmap = common::beatmap(
+        7,
+        &[
+            SLIDER_DIFFICULTY,
+            ("TimingPoints", timing),
+            ("HitObjects", slider),
+        ],
+    )?;
+    let events = beatmap.slider_events(&beatmap.hit_objects[0]).unwrap();
+    assert_eq!(times(&events, SliderEventKind::Tick).len(), 7);
+    assert_eq!(events[1].time, Time(1125.0));
+
+    // the velocity is kept between 0.1 and 10
+    let slider = "0,0,1000,2,0,L|100:0,1,100";
+    let slow = common::beatmap(
+        14,
+        &[
+            SLIDER_DIFFICULTY,
+            (
+                "TimingPoints",
+                "0,500,4,2,0,100,1,0\n500,-5000,4,2,0,100,0,0",
+            ),
+            ("HitObjects", slider),
+        ],
+    )?;
+    let events = slow.slider_events(&slow.hit_objects[0]).unwrap();
+    assert_eq!(events.last().unwrap().time, Time(6000.0));
+    let fast = common::beatmap(
+        14,
+        &[
+            SLIDER_DIFFICULTY,
+            ("TimingPoints", "0,500,4,2,0,100,1,0\n500,-1,4,2,0,100,0,0"),
+            ("HitObjects", slider),
+        ],
+    )?;
+    let events = fast.slider_events(&fast.hit_objects[0]).unwrap();
+    assert_eq!(events.last().unwrap().time, Time(1050.0));
+    Ok(())
+}
+
+#[test]
+fn test_long_slider() -> Result<()> {
+    // ticks stop after 100000 osu!pixels, but the slider lasts for its whole length
+    let timing = "0,500,4,2,0,100,1,0\n500,-10,4,2,0,100,0,0";
+    let beatmap = common::beatmap(
+        14,
+        &[
+            SLIDER_DIFFICULTY,
+            ("TimingPoints", timing),
+            ("HitObjects", "0,0,1000,2,0,L|150000:0,1,150000"),
+        ],
+    )?;
+    let events = beatmap.slider_events(&beatmap.hit_objects[0]).unwrap();
+    let ticks = times(&events, SliderEventKind::Tick);
+    assert_eq!(ticks.len(), 199);
+    assert_eq!(ticks[198], 1000.0 + 99_500.0 / 2.0);
+
+    let tail = events.last().unwrap();
+    assert_eq!(tail.time, Time(76_000.0));
+    assert_eq!(tail.pos, Point::new(150_000.0, 0.0));
+    Ok(())
+}
+
+#[test]
+fn test_short_slider() -> Result<()> {
+    // the last tick doesn't go past the middle of the slider
+    let beatmap = common::beatmap(
+        14,
+        &[
+            SLIDER_DIFFICULTY,
+            ("TimingPoints", "0,500,4,2,0,100,1,0"),
+            ("HitObjects", "0,0,1000,2,0,L|10:0,1,10"),
+        ],
+    )?;
+    let events = beatmap.slider_events(&beatmap.hit_objects[0]).unwrap();
+    let kinds = events.iter().map(|evt| evt.kind).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            SliderEventKind::Head,
+            SliderEventKind::LegacyLastTick,
+            SliderEventKind::Tail
+        ]
+    );
+    assert_eq!(events[1].time, Time(1025.0));
+    assert_eq!(events[2].time, Time(1050.0));
+
+    // only sliders have events
+    let circles = common::beatmap(
+        14,
+        &[
+            SLIDER_DIFFICULTY,
+            ("TimingPoints", "0,500,4,2,0,100,1,0"),
+            ("HitObjects", "0,0,1000,1,0,0:0:0:0:"),
+        ],
+    )?;
+    assert_eq!(circles.slider_events(&circles.hit_objects[0]), None);
+    Ok(())
+}
+
+#[test]
+fn test_real_map() -> Result<()> {
+    let beatmap = common::real_map("774965.osu")?;
+    for obj in beatmap
+        .hit_objects
+        .iter()
+        .filter(|obj| obj.kind.is_slider())
+    {
+        let events = beatmap.slider_events(obj).unwrap();
+        assert!(events.windows(2).all(|w| w[0].time <= w[1].time));
+        assert_eq!(events[0].pos, obj.pos);
+
+        let tail = events.last().unwrap();
+        let duration = beatmap.get_slider_duration(obj).unwrap() * 1000.0;
+        assert!((tail.time.0 - obj.start_time.0 - duration).abs() < 1e-6);
+        let repeats = match &obj.kind {
+            HitObjectKind::Slider(info) => info.num_repeats,
+            _ => unreachable!(),
+        };
+        assert_eq!(tail.path_progress, (repeats % 2) as f64);
+    }
+    Ok(())
+}
@@ -0,0 +1,143 @@
+//! Stacking should move hit objects to where the game actually draws them
+
+use anyhow::Result;
+use libosu::prelude::*;
+
+mod common;
+
+/// Three circles on top of each other, then a slider with two circles stacked on its end, then a
+/// slider that repeats back to its start with a circle on the far end of its path.
+const STACKED_MAP: &[(&str, &str)] = &[
+    ("General", "StackLeniency: 0.7"),
+    (
+        "Difficulty",
+        "CircleSize:4\nApproachRate:10\nSliderMultiplier:1\nSliderTickRate:1",
+    ),
+    ("TimingPoints", "0,500,4,2,0,100,1,0"),
+    (
+        "HitObjects",
+        "100,100,1000,1,0,0:0:0:0:
+100,100,1100,1,0,0:0:0:0:
+100,100,1200,1,0,0:0:0:0:
+200,200,2000,2,0,L|300:200,1,100
+300,200,2600,1,0,0:0:0:0:
+300,200,2700,1,0,0:0:0:0:
+200,300,4000,2,0,L|300:300,2,100
+300,300,5100,1,0,0:0:0:0:",
+    ),
+];
+
+fn heights(stacked: &[StackedObject]) -> Vec<i32> {
+    stacked.iter().map(|obj| obj.stack_height).collect()
+}
+
+#[test]
+fn test_stacking() -> Result<()> {
+    let beatmap = common::beatmap(14, STACKED_MAP)?;
+    let stacked = beatmap.stacking(StackingAlgorithm::Stable);
+    assert_eq!(heights(&stacked), vec![2, 1, 0, 0, -1, -2, 0, 0]);
+
+    // each level of the stack moves up and left by a tenth of the radius
+    let offset = beatmap.difficulty.stack_offset();
+    assert!((offset - 3.648).abs() < 1e-5);
+    assert_eq!(
+        stacked[0].pos,
+        Point::new(100.0 - 2.0 * offset, 100.0 - 2.0 * offset)
+    );
+    assert_eq!(stacked[2].pos, Point::new(100.0, 100.0));
+    // and down and right under the end of a slider
+    assert_eq!(
+        stacked[5].pos,
+        Point::new(300.0 + 2.0 * offset, 200.0 + 2.0 * offset)
+    );
+    assert_eq!(stacked[3].end_pos, Point::new(300.0, 200.0));
+
+    // other modes don't stack
+    let mut taiko = beatmap.clone();
+    taiko.mode = Mode::Taiko;
+    assert!(taiko
+        .stacking(StackingAlgorithm::Stable)
+        .iter()
+        .all(|obj| obj.stack_height == 0));
+
+    // with a lower leniency the circles are too far apart
+    let mut strict = beatmap;
+    strict.stack_leniency = 0.2;
+    assert_eq!(
+        heights(&strict.stacking(StackingAlgorithm::Stable)),
+        vec![0; 8]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_old_stacking() -> Result<()> {
+    // before v6 the far end of a slider's path counts, even if the slider ends where it started
+    let beatmap = common::beatmap(5, STACKED_MAP)?;
+    assert_eq!(
+        heights(&beatmap.stacking(StackingAlgorithm::Lazer)),
+        vec![2, 1, 0, 0, -1, -2, 0, -1]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_stable_and_lazer() -> Result<()> {
+    // stable rounds the 550.5ms approach time of AR9.33 to 551ms, so only it stacks these
+    let beatmap = common::beatmap(
+        14,
+        &[
+            ("General", "StackLeniency: 1"),
+            ("Difficulty", "ApproachRate:9.33"),
+            (
+                "HitObjects",
+                "100,100,1000,1,0,0:0:0:0:\n100,100,1551,1,0,0:0:0:0:",
+            ),
+        ],
+    )?;
+    assert_eq!(
+        heights(&beatmap.stacking(StackingAlgorithm::Stable)),
+        vec![1, 0]
+    );
+    assert_eq!(
+        heights(&beatmap.stacking(StackingAlgorithm::Lazer)),
+        vec![0, 0]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_diff_calc_stacking() -> Result<()> {
+    let beatmap = common::real_map("774965.osu")?;
+    let stacked = beatmap.stacking(StackingAlgorithm::Stable);
+    assert_eq!(stacked.len(), beatmap.hit_objects.len());
+    assert!(stacked.iter().any(|obj| obj.stack_height != 0));
+
+    let plain = DiffCalc::new(&beatmap).calc(Mods::None, None)?;
+    let with_stacking = DiffCalc::new(&beatmap)
+        .with_stacking(StackingAlgorithm::Stable)
+        .calc(Mods::None, None)?;
+    assert!(with_stacking.total_stars.is_finite());
+    assert_ne!(with_stacking.aim_stars, plain.aim_stars);
+    Ok(())
+}
+
+#[test]
+fn test_real_map_stacks() -> Result<()> {
+    // FREEDOM DiVE has two circles and a slider on top of each other at 13473ms, which the game
+    // draws as a stack of three with the slider at the bottom
+    let beatmap = common::real_map("129891.osu")?;
+    let stacked = beatmap.stacking(StackingAlgorithm::Stable);
+    assert_eq!(heights(&stacked[55..58]), vec![2, 1, 0]);
+
+    // CS4 circles have a radius of 36.48, so each level moves 3.648 up and to the left
+    let offset = beatmap.difficulty.stack_offset();
+    assert!((offset - 3.648).abs() < 1e-6);
+    assert_eq!(
+        stacked[55].pos,
+        Point::new(480.0 - 2.0 * offset, 112.0 - 2.0 * offset)
+    );
+    assert_eq!(stacked[56].pos, Point::new(480.0 - offset, 112.0 - offset));
+    assert_eq!(stacked[57].pos, Point::new(480.0, 112.0));
+    Ok(())
+}
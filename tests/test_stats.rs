@@ -0,0 +1,138 @@
+//! Max combo and object counts should match the game in every mode
+
+use anyhow::Result;
+use libosu::prelude::*;
+
+mod common;
+
+/// A tick every beat, and 100 osu!pixels a beat at 1x slider velocity
+const STATS_DIFFICULTY: (&str, &str) = ("Difficulty", "SliderMultiplier:1\nSliderTickRate:1");
+
+/// A beat every 500ms from the start
+const STATS_TIMING: (&str, &str) = ("TimingPoints", "0,500,4,2,0,100,1,0");
+
+#[test]
+fn test_osu_stats() -> Result<()> {
+    let beatmap = common::real_map("129891.osu")?;
+    let stats = beatmap.statistics();
+    assert_eq!(stats.mode, Mode::Osu);
+    // the max combo the game gives FREEDOM DiVE [FOUR DIMENSIONS]
+    assert_eq!(stats.max_combo, 2385);
+    assert_eq!(beatmap.max_combo(), 2385);
+    assert_eq!(stats.circles, 1646);
+    assert_eq!(stats.sliders, 335);
+    assert_eq!(stats.spinners, 2);
+    assert_eq!(stats.repeats, 67);
+    Ok(())
+}
+
+#[test]
+fn test_converted_stats() -> Result<()> {
+    let beatmap = common::real_map("774965.osu")?;
+    let osu = beatmap.statistics();
+
+    // juice streams have a fruit at each end and repeat, and a droplet on each tick, but banana
+    // showers don't give any combo
+    let catch = beatmap.statistics_for(Mode::Catch).unwrap();
+    assert_eq!(catch.droplets, osu.ticks);
+    assert_eq!(catch.repeats, osu.repeats);
+    assert!(catch.tiny_droplets > 0);
+    assert_eq!(catch.max_combo, osu.max_combo - osu.spinners);
+
+    let taiko = beatmap.statistics_for(Mode::Taiko).unwrap();
+    assert_eq!(taiko.max_combo, taiko.circles);
+    assert_eq!(taiko.spinners, osu.spinners);
+
+    // every object becomes at least one note, and the conversion is the same every time
+    let mania = beatmap.statistics_for(Mode::Mania).unwrap();
+    assert_eq!(mania.max_combo, mania.circles + 2 * mania.holds);
+    assert!(mania.circles + mania.holds >= beatmap.hit_objects.len() as u32);
+    assert_eq!(beatmap.statistics_for(Mode::Mania), Some(mania));
+
+    // maps made for other modes can't be converted
+    let mut mania = beatmap;
+    mania.mode = Mode::Mania;
+    assert_eq!(mania.statistics_for(Mode::Taiko), None);
+    assert_eq!(mania.statistics().mode, Mode::Mania);
+    Ok(())
+}
+
+#[test]
+fn test_taiko_sliders() -> Result<()> {
+    // short sliders are turned into hits when converted, long ones into drumrolls
+    let objects = "0,0,1000,2,0,L|100:0,1,100\n0,0,3000,2,0,L|300:0,1,300\n0,0,6000,12,0,7000";
+    let stats = common::beatmap(
+        14,
+        &[STATS_DIFFICULTY, STATS_TIMING, ("HitObjects", objects)],
+    )?
+    .statistics_for(Mode::Taiko)
+    .unwrap();
+    assert_eq!(stats.circles, 2);
+    assert_eq!(stats.sliders, 1);
+    assert_eq!(stats.ticks, 13);
+    assert_eq!(stats.spinners, 1);
+    assert_eq!(stats.max_combo, 2);
+
+    // taiko maps always have drumrolls
+    let stats = common::beatmap(
+        14,
+        &[
+            ("General", "Mode: 1"),
+            STATS_DIFFICULTY,
+            STATS_TIMING,
+            ("HitObjects", objects),
+        ],
+    )?
+    .statistics();
+    assert_eq!(stats.circles, 0);
+    assert_eq!(stats.sliders, 2);
+    assert_eq!(stats.ticks, 5 + 13);
+    assert_eq!(stats.max_combo, 0);
+    Ok(())
+}
+
+#[test]
+fn test_mania_holds() -> Result<()> {
+    let objects = "64,192,1000,1,0,0:0:0:0:\n192,192,1000,128,0,1500:0:0:0:0:";
+    let stats = common::beatmap(
+        14,
+        &[
+            ("General", "Mode: 3"),
+            STATS_DIFFICULTY,
+            STATS_TIMING,
+            ("HitObjects", objects),
+        ],
+    )?
+    .statistics();
+    assert_eq!(stats.circles, 1);
+    assert_eq!(stats.holds, 1);
+    assert_eq!(stats.max_combo, 3);
+    Ok(())
+}
+
+#[test]
+fn test_converted_mania_notes() -> Result<()> {
+    let mania_stats = |objects: &str| -> Result<BeatmapStatistics> {
+        Ok(common::beatmap(
+            14,
+            &[STATS_DIFFICULTY, STATS_TIMING, ("HitObjects", objects)],
+        )?
+        .statistics_for(Mode::Mania)
+        .unwrap())
+    };
+
+    // spinners become hold notes, unless they're too short to hold
+    let stats = mania_stats("256,192,1000,12,0,1200")?;
+    assert_eq!((stats.circles, stats.holds, stats.max_combo), (0, 1, 2));
+    let stats = mania_stats("256,192,1000,12,0,1050")?;
+    assert_eq!((stats.circles, stats.holds, stats.max_combo), (1, 0, 1));
+
+    // quickly repeating sliders become a note on each end and repeat...
+    let stats = mania_stats("0,0,1000,2,0,L|20:0,2,20")?;
+    assert_eq!((stats.circles, stats.holds), (3, 0));
+
+    // ...or a single hold note if they repeat even faster
+    let stats = mania_stats("0,0,1000,2,0,L|16:0,2,16")?;
+    assert_eq!((stats.circles, stats.holds), (0, 1));
+    Ok(())
+}
@@ -0,0 +1,612 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::Result;
+use libosu::{
+    beatmap::Beatmap,
+    color::Color,
+    events::{Event, SampleEvent, SpriteEvent},
+    hitsounds::{Additions, SampleSet},
+    math::Point,
+    storyboard::{
+        AnalysisOptions, Assets, BlendMode, Command, Easing, Layer, LoopInfo, LoopType, Origin,
+        Parameter, SpriteBuilder, SpriteTimeline, Storyboard, StoryboardDiagnosticKind,
+        TriggerFiring, TriggerFiringKind, Tween,
+    },
+    timing::Millis,
+};
+
+const EVENTS_OSU: &str = r#"osu file format v14
+
+[Events]
+//Background and Video events
+0,0,"bg.jpg",0,0
+//Storyboard Layer 0 (Background)
+Sprite,Background,Centre,"sb\glow.png",320,240
+ F,0,1000,2000,0,1
+ M,1,1000,2000,320,240,400,200
+_C,0,1000,,255,128,0
+ P,0,1000,3000,A
+ L,2000,4
+  R,0,0,500,0,3.14159
+  S,2,0,500,1,1.5,1
+ T,HitSoundClap,0,60000,1
+  V,0,0,100,1,1,2,0.5
+//Storyboard Layer 3 (Foreground)
+Animation,Foreground,TopLeft,"sb/dot.png",0,0,4,50,LoopOnce
+ MX,0,0,500,0,640
+//Storyboard Sound Samples
+Sample,1500,0,"sb/boom.wav",80
+3,100,163,162,255
+"#;
+
+#[test]
+fn test_parse_storyboard_events() -> Result<()> {
+    let beatmap = Beatmap::from_str(EVENTS_OSU)?;
+    assert_eq!(beatmap.events.len(), 5);
+
+    let sprite = match &beatmap.events[1] {
+        Event::Sprite(sprite) => sprite,
+        evt => panic!("expected a sprite, got {:?}", evt),
+    };
+    assert_eq!(sprite.layer, Layer::Background);
+    assert_eq!(sprite.origin, Origin::Centre);
+    assert_eq!(sprite.filepath, "sb\\glow.png");
+    assert_eq!(sprite.pos, Point::new(320.0, 240.0));
+    assert_eq!(sprite.commands.len(), 6);
+    assert_eq!(
+        sprite.commands[0],
+        Command::Fade(Tween::new(Easing::Linear, 1000, 2000, 0.0, 1.0))
+    );
+    assert_eq!(
+        sprite.commands[1],
+        Command::Move(Tween::new(
+            Easing::EasingOut,
+            1000,
+            2000,
+            Point::new(320.0, 240.0),
+            Point::new(400.0, 200.0)
+        ))
+    );
+    assert_eq!(
+        sprite.commands[2],
+        Command::Color(Tween::constant(1000, 1000, Color::new(255, 128, 0)))
+    );
+    assert_eq!(
+        sprite.commands[3],
+        Command::Parameter(Tween::constant(1000, 3000, Parameter::AdditiveBlend))
+    );
+
+    match &sprite.commands[4] {
+        Command::Loop(info) => {
+            assert_eq!(info.start_time, Millis(2000));
+            assert_eq!(info.loop_count, 4);
+            assert_eq!(info.commands.len(), 2);
+            match &info.commands[1] {
+                Command::Scale(tween) => {
+                    assert_eq!(tween.easing, Easing::EasingIn);
+                    assert_eq!(tween.values, &[1.0, 1.5, 1.0]);
+                    assert_eq!(tween.last_end_time(), Millis(1000));
+                }
+                cmd => panic!("expected a scale command, got {:?}", cmd),
+            }
+        }
+        cmd => panic!("expected a loop, got {:?}", cmd),
+    }
+
+    match &sprite.commands[5] {
+        Command::Trigger(info) => {
+            assert_eq!(info.trigger, "HitSoundClap");
+            assert_eq!(info.group, Some(1));
+            assert_eq!(info.commands.len(), 1);
+        }
+        cmd => panic!("expected a trigger, got {:?}", cmd),
+    }
+
+    match &beatmap.events[2] {
+        Event::Animation(anim) => {
+            assert_eq!(anim.layer, Layer::Foreground);
+            assert_eq!(anim.frame_count, 4);
+            assert_eq!(anim.frame_delay, 50.0);
+            assert_eq!(anim.loop_type, LoopType::LoopOnce);
+            assert_eq!(anim.commands.len(), 1);
+        }
+        evt => panic!("expected an animation, got {:?}", evt),
+    }
+
+    assert_eq!(
+        beatmap.events[3],
+        Event::Sample(SampleEvent {
+            time: Millis(1500),
+            layer: Layer::Background,
+            filepath: String::from("sb/boom.wav"),
+            volume: 80,
+        })
+    );
+    assert_eq!(
+        beatmap.events[4],
+        Event::Unknown(String::from("3,100,163,162,255"))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_storyboard_roundtrip() -> Result<()> {
+    let beatmap = Beatmap::from_str(EVENTS_OSU)?;
+    let reexported = beatmap.to_string();
+    let beatmap2 = Beatmap::from_str(&reexported)?;
+    assert_eq!(beatmap.events, beatmap2.events);
+    Ok(())
+}
+
+#[test]
+fn test_orphaned_command() {
+    let input = "osu file format v14\n\n[Events]\n F,0,0,1000,1\n";
+    let err = Beatmap::from_str(input).unwrap_err();
+    assert_eq!(err.line, 4);
+}
+
+const STORYBOARD_OSB: &str = r#"[Variables]
+$white=255,255,255
+$glow="sb/glow.png"
+
+[Events]
+//Background and Video events
+//Storyboard Layer 0 (Background)
+Sprite,Background,Centre,$glow,320,240
+ C,0,0,,$white
+//Storyboard Layer 3 (Foreground)
+Sprite,Foreground,TopLeft,"sb/bar.png",0,0
+ F,0,0,500,1,0
+"#;
+
+#[test]
+fn test_parse_osb() -> Result<()> {
+    let storyboard = Storyboard::from_str(STORYBOARD_OSB)?;
+    assert_eq!(storyboard.variables.len(), 2);
+    assert_eq!(storyboard.events.len(), 2);
+
+    match &storyboard.events[0] {
+        Event::Sprite(sprite) => {
+            assert_eq!(sprite.filepath, "sb/glow.png");
+            assert_eq!(
+                sprite.commands[0],
+                Command::Color(Tween::constant(0, 0, Color::new(255, 255, 255)))
+            );
+        }
+        evt => panic!("expected a sprite, got {:?}", evt),
+    }
+
+    let mut buf = Vec::new();
+    storyboard.write(&mut buf)?;
+    let storyboard2 = Storyboard::parse(buf.as_slice())?;
+    assert_eq!(storyboard, storyboard2);
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_osb() -> Result<()> {
+    let storyboard = Storyboard::from_str(STORYBOARD_OSB)?;
+    let beatmap = Beatmap::from_str(EVENTS_OSU)?;
+    let merged = storyboard.merge(&beatmap);
+
+    // the unknown event isn't shown by the storyboard, so only the background, sprite,
+    // animation and sample get merged
+    assert_eq!(merged.events.len(), 6);
+    assert_eq!(merged.events[..2], storyboard.events[..]);
+    assert_eq!(merged.events[2], beatmap.events[0]);
+    assert_eq!(merged.events[3], beatmap.events[1]);
+
+    Ok(())
+}
+
+const TIMELINE_OSB: &str = r#"[Events]
+Sprite,Background,Centre,"sb/a.png",320,240
+ F,0,1000,2000,0,1
+ M,0,1000,2000,0,0,100,200
+ S,0,1500,,2
+ V,0,1000,2000,1,1,2,1
+ P,0,1000,1000,A
+Sprite,Foreground,Centre,"sb/b.png",0,0
+ L,1000,3
+  F,0,0,100,1,0
+ T,HitSoundClap,0,10000
+  MX,0,0,100,0,100
+Animation,Foreground,Centre,"sb/c.png",0,0,3,100,LoopOnce
+ F,0,0,2000,1
+"#;
+
+#[test]
+fn test_easing_endpoints() {
+    for i in 0..=34 {
+        let easing: Easing = num::FromPrimitive::from_u32(i).unwrap();
+        assert!(easing.apply(0.0).abs() < 0.001, "{:?} at 0", easing);
+        assert!((easing.apply(1.0) - 1.0).abs() < 0.001, "{:?} at 1", easing);
+    }
+}
+
+#[test]
+fn test_sprite_state() -> Result<()> {
+    let storyboard = Storyboard::from_str(TIMELINE_OSB)?;
+    let timeline = SpriteTimeline::new(&storyboard.events[0]).unwrap();
+
+    // the first fade starts at 0 opacity, so that's when the sprite starts existing
+    assert_eq!(timeline.start_time(), 1000.0);
+    assert_eq!(timeline.end_time(), 2000.0);
+    assert_eq!(timeline.state_at(999), None);
+    assert_eq!(timeline.state_at(2000), None);
+
+    let state = timeline.state_at(1500).unwrap();
+    assert!((state.opacity - 0.5).abs() < 0.001);
+    assert_eq!(state.pos, Point::new(50.0, 100.0));
+    assert_eq!(state.scale, Point::new(3.0, 2.0));
+    assert_eq!(state.blend_mode, BlendMode::Additive);
+
+    // before the scale command, the scale command's start value is used
+    let state = timeline.state_at(1000).unwrap();
+    assert_eq!(state.scale, Point::new(2.0, 2.0));
+    assert_eq!(state.opacity, 0.0);
+
+    Ok(())
+}
+
+#[test]
+fn test_loops_and_triggers() -> Result<()> {
+    let storyboard = Storyboard::from_str(TIMELINE_OSB)?;
+    let timeline = SpriteTimeline::new(&storyboard.events[1]).unwrap();
+    assert_eq!(timeline.start_time(), 1000.0);
+    assert_eq!(timeline.end_time(), 1300.0);
+    let state = timeline.state_at(1250).unwrap();
+    assert!((state.opacity - 0.5).abs() < 0.001);
+    assert_eq!(state.pos, Point::new(0.0, 0.0));
+
+    let clap = TriggerFiring {
+        time: Millis(1200),
+        kind: TriggerFiringKind::HitSound {
+            sample_set: SampleSet::Soft,
+            addition_set: SampleSet::Soft,
+            additions: Additions::CLAP | Additions::WHISTLE,
+            custom_index: 0,
+        },
+    };
+    let timeline = SpriteTimeline::with_triggers(&storyboard.events[1], &[clap]).unwrap();
+    let state = timeline.state_at(1250).unwrap();
+    assert_eq!(state.pos, Point::new(50.0, 0.0));
+
+    let states = storyboard.states_at(1250);
+    let indices = states.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+    assert_eq!(indices, &[0, 1, 2]);
+    assert_eq!(states[2].1.frame, Some(2));
+
+    Ok(())
+}
+
+const LONG_LOOP_OSB: &str = r#"[Events]
+Sprite,Foreground,Centre,"sb/dot.png",320,240
+ L,0,2000000
+  F,0,0,2000,1,0
+"#;
+
+#[test]
+fn test_long_loops() -> Result<()> {
+    // the loop would end long after the latest time there is
+    let storyboard = Storyboard::from_str(LONG_LOOP_OSB)?;
+    let commands = storyboard.events[0].commands().unwrap();
+    assert_eq!(commands[0].start_time(), Millis(0));
+    assert_eq!(commands[0].end_time(), Millis(i32::MAX));
+
+    // it's only unrolled as far as it can be seen
+    let start = std::time::Instant::now();
+    let timeline = SpriteTimeline::new(&storyboard.events[0]).unwrap();
+    let state = timeline.state_at(4_001_000).unwrap();
+    assert!((state.opacity - 0.5).abs() < 1e-9);
+    assert!(timeline.end_time() <= i32::MAX as f64);
+    assert!(
+        start.elapsed() < std::time::Duration::from_secs(20),
+        "building the timeline took {:?}",
+        start.elapsed()
+    );
+
+    let tween = Tween::new(Easing::Linear, -2_000_000_000, 2_000_000_000, 1.0, 0.0);
+    assert_eq!(tween.last_end_time(), Millis(2_000_000_000));
+    Ok(())
+}
+
+#[test]
+fn test_extreme_times() -> Result<()> {
+    // a fade from the earliest time there is to the latest lasts longer than an i32 can hold
+    let storyboard = Storyboard::from_str(
+        "[Events]\nSprite,Foreground,Centre,\"sb/dot.png\",320,240\n F,0,-2147483648,2147483647,0,1\n",
+    )?;
+    let states = storyboard.states_at(0);
+    assert_eq!(states.len(), 1);
+    assert!((states[0].1.opacity - 0.5).abs() < 1e-6);
+
+    let assets = MemoryAssets(vec![("sb/dot.png", (10, 10))].into_iter().collect());
+    let analysis = storyboard.analyze(&assets, &AnalysisOptions::default());
+    assert!(!analysis.load.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_optimize_extreme_times() -> Result<()> {
+    // repeating the first fade would go past the latest time there is
+    let mut storyboard = Storyboard::from_str(
+        "[Events]\nSprite,Foreground,Centre,\"sb/dot.png\",320,240\n F,0,2147483000,2147483600,0,1\n F,0,2147483600,2147483640,1,1\n",
+    )?;
+    storyboard.optimize();
+    let commands = storyboard.events[0].commands().unwrap();
+    assert_eq!(commands.len(), 2);
+    assert!(commands.iter().all(|cmd| cmd.children().is_none()));
+    Ok(())
+}
+
+struct MemoryAssets(HashMap<&'static str, (u32, u32)>);
+
+impl Assets for MemoryAssets {
+    fn exists(&self, path: &str) -> bool {
+        self.0.contains_key(path)
+    }
+
+    fn image_size(&self, path: &str) -> Option<(u32, u32)> {
+        self.0.get(path).copied()
+    }
+}
+
+const ANALYSIS_OSB: &str = r#"[Events]
+0,0,"bg.jpg",0,0
+Sprite,Background,TopLeft,"sb/full.png",0,0
+ F,0,0,1000,1
+ F,0,500,1000,1
+Sprite,Foreground,Centre,"sb/full.png",-1000,240
+ F,0,0,1000,1
+Sprite,Foreground,Centre,"sb/half.png",320,240
+ F,0,500,1000,0.5
+Animation,Foreground,Centre,"sb/anim.png",320,240,2,100,LoopForever
+ F,0,0,1000,0
+Sample,0,0,"sb/missing.wav",100
+"#;
+
+#[test]
+fn test_analyze_storyboard() -> Result<()> {
+    let storyboard = Storyboard::from_str(ANALYSIS_OSB)?;
+    let assets = MemoryAssets(
+        vec![
+            ("bg.jpg", (1366, 768)),
+            ("sb/full.png", (640, 480)),
+            ("sb/half.png", (320, 480)),
+            ("sb/anim0.png", (10, 10)),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    let options = AnalysisOptions {
+        load_threshold: 2.0,
+        ..AnalysisOptions::default()
+    };
+    let analysis = storyboard.analyze(&assets, &options);
+
+    // background, the full screen sprite, and then the half screen sprite at half opacity
+    assert_eq!(analysis.load.len(), 10);
+    assert_eq!(analysis.load[0], (Millis(0), 2.0));
+    assert_eq!(analysis.load[5], (Millis(500), 2.25));
+    assert_eq!(analysis.peak_load(), Some((Millis(500), 2.25)));
+
+    let kinds = analysis
+        .diagnostics
+        .iter()
+        .map(|diag| (diag.event_index, diag.kind.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            (
+                None,
+                StoryboardDiagnosticKind::HighLoad {
+                    start: Millis(500),
+                    end: Millis(900),
+                    peak: 2.25,
+                }
+            ),
+            (
+                Some(1),
+                StoryboardDiagnosticKind::UselessCommand { command_index: 1 }
+            ),
+            (Some(2), StoryboardDiagnosticKind::NeverVisible),
+            (Some(4), StoryboardDiagnosticKind::NeverVisible),
+            (
+                Some(4),
+                StoryboardDiagnosticKind::MissingFile {
+                    path: String::from("sb/anim1.png")
+                }
+            ),
+            (
+                Some(5),
+                StoryboardDiagnosticKind::MissingFile {
+                    path: String::from("sb/missing.wav")
+                }
+            ),
+        ]
+    );
+
+    Ok(())
+}
+
+const SPARSE_OSB: &str = r#"[Events]
+Sprite,Foreground,TopLeft,"sb/full.png",0,0
+ F,0,0,1000,1
+Sprite,Foreground,TopLeft,"sb/full.png",0,0
+ F,0,2000000000,2000001000,1
+"#;
+
+#[test]
+fn test_analyze_sparse_storyboard() -> Result<()> {
+    let assets = MemoryAssets(vec![("sb/full.png", (640, 480))].into_iter().collect());
+    let options = AnalysisOptions::default();
+
+    // the time between the sprites isn't sampled, apart from where the load drops
+    let storyboard = Storyboard::from_str(SPARSE_OSB)?;
+    let analysis = storyboard.analyze(&assets, &options);
+    assert_eq!(analysis.load.len(), 21);
+    assert_eq!(analysis.load[9], (Millis(900), 1.0));
+    assert_eq!(analysis.load[10], (Millis(1000), 0.0));
+    assert_eq!(analysis.load[11], (Millis(2_000_000_000), 1.0));
+
+    // and a storyboard that lasts for weeks is sampled less often
+    let long = SPARSE_OSB.replace("F,0,0,1000,1", "F,0,0,2000000000,1");
+    let analysis = Storyboard::from_str(&long)?.analyze(&assets, &options);
+    assert!(analysis.load.len() <= 100_001, "{}", analysis.load.len());
+    assert_eq!(analysis.peak_load(), Some((Millis(0), 1.0)));
+    Ok(())
+}
+
+#[test]
+fn test_optimize_many_commands() -> Result<()> {
+    let sprite = || {
+        SpriteBuilder::sprite(
+            Layer::Foreground,
+            Origin::Centre,
+            "sb/dot.png",
+            Point::new(0.0, 0.0),
+        )
+    };
+
+    // a long block repeated many times becomes one loop
+    let mut builder = sprite();
+    for i in 0..2000 {
+        builder
+            .fade(Easing::Linear, i * 100, i * 100 + 50, 1.0, 0.0)
+            .move_x(Easing::Linear, i * 100 + 50, i * 100 + 100, 0.0, 10.0);
+    }
+    let start = std::time::Instant::now();
+    let event = builder.build();
+    match &event.commands().unwrap()[..] {
+        [Command::Loop(info)] => {
+            assert_eq!(info.loop_count, 2000);
+            assert_eq!(info.commands.len(), 2);
+        }
+        commands => panic!("expected a loop, got {:?}", commands),
+    }
+
+    // and lots of commands that don't repeat are left alone, without trying every block
+    let mut builder = sprite();
+    for i in 0..20_000 {
+        builder.move_x(Easing::Linear, i * 10, i * 10 + 5, i as f64, -i as f64);
+    }
+    assert_eq!(builder.build().commands().unwrap().len(), 20_000);
+    assert!(
+        start.elapsed() < std::time::Duration::from_secs(20),
+        "optimizing took {:?}",
+        start.elapsed()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_build_and_optimize() -> Result<()> {
+    let white = Color::new(255, 255, 255);
+    let mut builder = SpriteBuilder::sprite(
+        Layer::Foreground,
+        Origin::Centre,
+        "sb/dot.png",
+        Point::new(0.0, 0.0),
+    );
+    for i in 0..4 {
+        builder.fade(Easing::Linear, i * 100, i * 100 + 100, 1.0, 0.0);
+    }
+    builder
+        .color(Easing::Linear, 1000, 1000, white, white)
+        .color(Easing::QuadOut, 1500, 1500, white, white)
+        .move_to(
+            Easing::Linear,
+            1000,
+            2000,
+            Point::new(0.0, 0.0),
+            Point::new(100.0, 50.0),
+        )
+        .move_to(
+            Easing::Linear,
+            2000,
+            3000,
+            Point::new(100.0, 50.0),
+            Point::new(200.0, 100.0),
+        );
+
+    let mut commands = (0..4)
+        .map(|i| Command::Fade(Tween::new(Easing::Linear, i * 100, i * 100 + 100, 1.0, 0.0)))
+        .collect::<Vec<_>>();
+    commands.extend(vec![
+        Command::Color(Tween::new(Easing::Linear, 1000, 1000, white, white)),
+        Command::Color(Tween::new(Easing::QuadOut, 1500, 1500, white, white)),
+        Command::Move(Tween::new(
+            Easing::Linear,
+            1000,
+            2000,
+            Point::new(0.0, 0.0),
+            Point::new(100.0, 50.0),
+        )),
+        Command::Move(Tween::new(
+            Easing::Linear,
+            2000,
+            3000,
+            Point::new(100.0, 50.0),
+            Point::new(200.0, 100.0),
+        )),
+    ]);
+    let unoptimized = Event::Sprite(SpriteEvent {
+        layer: Layer::Foreground,
+        origin: Origin::Centre,
+        filepath: String::from("sb/dot.png"),
+        pos: Point::new(0.0, 0.0),
+        commands,
+    });
+
+    let event = builder.build();
+    assert_eq!(
+        event.commands().unwrap(),
+        &vec![
+            Command::Loop(LoopInfo {
+                start_time: Millis(0),
+                loop_count: 4,
+                commands: vec![Command::Fade(Tween::new(Easing::Linear, 0, 100, 1.0, 0.0))],
+            }),
+            Command::Color(Tween::constant(1000, 1500, white)),
+            Command::Move(Tween::new(
+                Easing::Linear,
+                1000,
+                3000,
+                Point::new(0.0, 0.0),
+                Point::new(200.0, 100.0),
+            )),
+        ]
+    );
+
+    // the optimized sprite looks exactly the same
+    let before = SpriteTimeline::new(&unoptimized).unwrap();
+    let after = SpriteTimeline::new(&event).unwrap();
+    for time in (-100..3100).step_by(25) {
+        match (before.state_at(time), after.state_at(time)) {
+            (Some(mut before), Some(after)) => {
+                assert!(before.pos.distance(after.pos) < 1e-6, "at {}", time);
+                before.pos = after.pos;
+                assert_eq!(before, after, "at {}", time);
+            }
+            (before, after) => assert_eq!(before, after, "at {}", time),
+        }
+    }
+
+    // and can be written into a beatmap and read back
+    let mut beatmap = Beatmap::from_str(EVENTS_OSU)?;
+    let storyboard = Storyboard {
+        variables: Vec::new(),
+        events: vec![event],
+    };
+    storyboard.write_into(&mut beatmap);
+    let beatmap = Beatmap::from_str(&beatmap.to_string())?;
+    assert_eq!(beatmap.events.len(), 3);
+    assert_eq!(beatmap.events[2], storyboard.events[0]);
+
+    Ok(())
+}
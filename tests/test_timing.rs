@@ -0,0 +1,252 @@
+//! Timing lookups should find the timing point in effect at any time
+
+use anyhow::Result;
+use libosu::prelude::*;
+
+mod common;
+
+#[test]
+fn test_points() -> Result<()> {
+    let beatmap = common::beatmap(
+        14,
+        &[(
+            "TimingPoints",
+            "1000,500,4,2,1,60,1,0
+2000,-50,4,3,2,40,0,1
+3000,250,3,1,0,80,1,0
+3000,-200,3,1,0,70,0,0",
+        )],
+    )?;
+    let timing = beatmap.timing_index();
+
+    assert!(timing.point_at(999.0).is_none());
+    assert!(timing.uninherited_at(999.0).is_none());
+    assert_eq!(beatmap.get_bpm_at_time(999), None);
+
+    // a point applies from exactly its own time
+    assert_eq!(timing.point_at(1000).unwrap().time, Time(1000.0));
+    assert_eq!(
+        beatmap.locate_timing_point(2000).unwrap().time,
+        Time(2000.0)
+    );
+    assert_eq!(
+        beatmap.locate_timing_point(1999).unwrap().time,
+        Time(1000.0)
+    );
+
+    assert_eq!(timing.bpm_at(2500), Some(120.0));
+    assert_eq!(timing.uninherited_at(2500).unwrap().time, Time(1000.0));
+    assert_eq!(timing.slider_velocity_at(1500), 1.0);
+    assert_eq!(timing.slider_velocity_at(2500), 2.0);
+    assert!(timing.kiai_at(2500));
+    assert!(!timing.kiai_at(1500));
+    assert_eq!(
+        timing.samples_at(2500),
+        Some(TimingSamples {
+            sample_set: SampleSet::Drum,
+            sample_index: 2,
+            volume: 40,
+        })
+    );
+
+    // an inherited point at the same time as an uninherited one wins
+    assert_eq!(timing.bpm_at(3000), Some(240.0));
+    assert_eq!(timing.slider_velocity_at(3000), 0.5);
+    assert_eq!(timing.samples_at(3000).unwrap().volume, 70);
+    assert_eq!(beatmap.get_slider_velocity_at_time(3500), 0.5);
+    Ok(())
+}
+
+#[test]
+fn test_beat_position() -> Result<()> {
+    let beatmap = common::beatmap(
+        14,
+        &[(
+            "TimingPoints",
+            "1000,500,4,2,1,60,1,0\n5000,250,3,2,1,60,1,0",
+        )],
+    )?;
+    let timing = beatmap.timing_index();
+
+    let pos = timing.beat_position_at(3250).unwrap();
+    assert_eq!(pos.section_start, Time(1000.0));
+    assert_eq!(pos.beats, 4.5);
+    assert_eq!(pos.measure, 1);
+    assert_eq!(pos.beat_in_measure, 0.5);
+
+    // a new section starts counting again, with its own meter
+    let pos = timing.beat_position_at(6000).unwrap();
+    assert_eq!(pos.section_start, Time(5000.0));
+    assert_eq!(pos.measure, 1);
+    assert_eq!(pos.beat_in_measure, 1.0);
+
+    // before the first section, beats count backwards from it
+    let pos = timing.beat_position_at(750).unwrap();
+    assert_eq!(pos.beats, -0.5);
+    assert_eq!(pos.measure, -1);
+    assert_eq!(pos.beat_in_measure, 3.5);
+    Ok(())
+}
+
+#[test]
+fn test_same_as_scanning() -> Result<()> {
+    let beatmap = common::real_map("774965.osu")?;
+    let timing = beatmap.timing_index();
+    for obj in beatmap.hit_objects.iter() {
+        let point = beatmap
+            .timing_points
+            .iter()
+            .rev()
+            .find(|tp| tp.time <= obj.start_time);
+        assert_eq!(
+            timing.point_at(obj.start_time).map(|tp| tp.time),
+            point.map(|tp| tp.time)
+        );
+
+        let uninherited = beatmap
+            .timing_points
+            .iter()
+            .rev()
+            .filter(|tp| matches!(tp.kind, TimingPointKind::Uninherited(_)))
+            .find(|tp| tp.time <= obj.start_time);
+        assert_eq!(
+            timing.uninherited_at(obj.start_time).map(|tp| tp.time),
+            uninherited.map(|tp| tp.time)
+        );
+    }
+
+    let pairs = beatmap.double_iter().count();
+    assert_eq!(pairs, beatmap.hit_objects.len());
+    Ok(())
+}
+
+#[test]
+fn test_musical_time() -> Result<()> {
+    // the first section ends half way through its second measure, which still counts
+    let beatmap = common::beatmap(
+        14,
+        &[(
+            "TimingPoints",
+            "1000,500,4,2,1,60,1,0\n4000,400,3,2,1,60,1,0",
+        )],
+    )?;
+    let timing = beatmap.timing_index();
+
+    let pos = timing.to_musical(Millis(3500)).unwrap();
+    assert_eq!(pos, MusicalPosition::new(1, 1));
+    let pos = timing.to_musical(Millis(4000)).unwrap();
+    assert_eq!(pos, MusicalPosition::new(2, 0));
+    let pos = timing.to_musical(Millis(5400)).unwrap();
+    assert_eq!((pos.measure, pos.beat), (3, 0));
+    assert!((pos.fraction - 0.5).abs() < 1e-9);
+    let pos = timing.to_musical(Millis(500)).unwrap();
+    assert_eq!(pos, MusicalPosition::new(-1, 3));
+
+    assert_eq!(
+        timing.from_musical(&MusicalPosition::new(1, 1)),
+        Some(Time(3500.0))
+    );
+    assert_eq!(
+        timing.from_musical(&MusicalPosition::new(3, 2)),
+        Some(Time(6000.0))
+    );
+    assert_eq!(
+        timing.from_musical(&MusicalPosition::new(-1, 0)),
+        Some(Time(-1000.0))
+    );
+
+    // no uninherited points, no musical time
+    let empty = common::beatmap(14, &[("TimingPoints", "")])?;
+    assert_eq!(empty.timing_index().to_musical(Millis(0)), None);
+    Ok(())
+}
+
+#[test]
+fn test_snapping() -> Result<()> {
+    let beatmap = common::beatmap(
+        14,
+        &[(
+            "TimingPoints",
+            "1000,500,4,2,1,60,1,0\n3900,400,4,2,1,60,1,0",
+        )],
+    )?;
+    let timing = beatmap.timing_index();
+
+    assert_eq!(timing.nearest_tick(Millis(1240), 2), Some(Time(1250.0)));
+    assert_eq!(timing.nearest_tick(Millis(1240), 4), Some(Time(1250.0)));
+    assert_eq!(timing.snap_offset(Millis(1240), 4), Some(-10.0));
+    // the start of the next section is closer than the last tick before it
+    assert_eq!(timing.nearest_tick(Millis(3880), 1), Some(Time(3900.0)));
+    assert_eq!(timing.nearest_tick(Millis(4000), 4), Some(Time(4000.0)));
+
+    assert_eq!(timing.snap_divisor(Millis(3000)), Some(1));
+    assert_eq!(timing.snap_divisor(Millis(1125)), Some(4));
+    assert_eq!(timing.snap_divisor(Millis(1167)), Some(3));
+    assert_eq!(timing.snap_divisor(Millis(3900)), Some(1));
+    assert_eq!(timing.snap_divisor(Millis(4000)), Some(4));
+    assert_eq!(timing.snap_divisor(Millis(1100)), None);
+    Ok(())
+}
+
+#[test]
+fn test_real_map_snapping() -> Result<()> {
+    let beatmap = common::real_map("774965.osu")?;
+    let timing = beatmap.timing_index();
+    for obj in beatmap.hit_objects.iter() {
+        // the map is ranked, so everything is snapped
+        assert!(
+            timing.snap_divisor(obj.start_time).is_some(),
+            "{} is unsnapped",
+            obj.start_time
+        );
+
+        let pos = timing.to_musical(obj.start_time).unwrap();
+        let time = timing.from_musical(&pos).unwrap();
+        assert!((time.0 - obj.start_time.0).abs() < 1e-6);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_lookups_scale() -> Result<()> {
+    // a green line every 10ms and a slider every 40ms: building the index (or scanning the timing
+    // points) for every object would take millions of steps per object
+    let mut timing_points = String::from("0,500,4,2,1,60,1,0\n");
+    for i in 1..100_000 {
+        timing_points += &format!("{},-{},4,2,1,60,0,0\n", i * 10, 50 + i % 100);
+    }
+    let mut hit_objects = String::new();
+    for i in 0..20_000 {
+        hit_objects += &format!(
+            "{},192,{},2,0,L|{}:192,1,100\n",
+            i % 400,
+            i * 40,
+            i % 400 + 100
+        );
+    }
+    let beatmap = common::beatmap(
+        14,
+        &[
+            ("Difficulty", "SliderMultiplier:1\nSliderTickRate:1"),
+            ("TimingPoints", &timing_points),
+            ("HitObjects", &hit_objects),
+        ],
+    )?;
+
+    let start = std::time::Instant::now();
+    let timing = beatmap.timing_index();
+    for i in 0..100_000 {
+        assert_eq!(timing.bpm_at(i * 10), Some(120.0));
+        assert!(timing.to_musical(i * 10).is_some());
+    }
+    assert_eq!(beatmap.get_bpm_at_time(999_990), Some(120.0));
+    assert!(beatmap.statistics().max_combo >= 40_000);
+    assert_eq!(beatmap.stacking(StackingAlgorithm::Stable).len(), 20_000);
+    assert!(beatmap.hitsounds().len() > 40_000);
+    assert!(
+        start.elapsed() < std::time::Duration::from_secs(20),
+        "lookups took {:?}",
+        start.elapsed()
+    );
+    Ok(())
+}
@@ -0,0 +1,311 @@
+//! Old format versions should parse into the same times osu! plays them at, and write back
+//! out in whatever version the beatmap asks for
+
+use std::str::FromStr;
+
+use anyhow::Result;
+use libosu::prelude::*;
+
+const V4: &str = "osu file format v4
+
+[General]
+AudioFilename: audio.mp3
+PreviewTime: 5000
+SampleSet: Soft
+
+[Events]
+2,1000,2000
+
+[TimingPoints]
+976,500
+1476,-50
+
+[HitObjects]
+256,192,1000,1,0
+256,192,1500,2,0,C|300:200|350:192,1,100
+";
+
+#[test]
+fn test_legacy_offset_and_defaults() -> Result<()> {
+    let beatmap = Beatmap::from_str(V4)?;
+    assert_eq!(beatmap.version, 4);
+
+    // v4 and older are played 24ms later than they're written
+    assert_eq!(beatmap.hit_objects[0].start_time, Time(1024.0));
+    assert_eq!(beatmap.timing_points[0].time, Time(1000.0));
+    assert_eq!(beatmap.preview_time, Millis(5024));
+    match &beatmap.events[0] {
+        Event::Break(evt) => {
+            assert_eq!((evt.start_time, evt.end_time), (Millis(1024), Millis(2024)))
+        }
+        event => panic!("unexpected {:?}", event),
+    }
+
+    // missing timing point fields come from [General], and a negative beat length means
+    // the point is inherited
+    let tp = &beatmap.timing_points[1];
+    assert_eq!(tp.sample_set, SampleSet::Soft);
+    assert_eq!(tp.volume, 100);
+    assert!(matches!(tp.kind, TimingPointKind::Inherited(_)));
+
+    // catmull sliders still have a path
+    let slider = &beatmap.hit_objects[1];
+    let end = slider.end_pos();
+    assert!(end.x > 256.0);
+
+    // writing it back out in the same version takes the offset off again
+    let output = beatmap.to_string();
+    assert!(output.starts_with("osu file format v4\n"));
+    assert!(output.contains("\n256,192,1000,1,0\n"));
+    assert!(output.contains("\n2,1000,2000\n"));
+    assert!(output.contains("\nPreviewTime: 5000\n"));
+
+    let doc = BeatmapDocument::from_str(V4)?;
+    assert_eq!(doc.beatmap.hit_objects[0].start_time, Time(1024.0));
+    assert_eq!(doc.to_string(), V4);
+    Ok(())
+}
+
+#[test]
+fn test_convert_version() -> Result<()> {
+    let mut beatmap = Beatmap::from_str(V4)?;
+    beatmap.version = 14;
+    let upgraded = beatmap.to_string();
+    assert!(upgraded.starts_with("osu file format v14\n"));
+    assert!(upgraded.contains("\n256,192,1024,1,0,"));
+    assert!(upgraded.contains("\nPreviewTime: 5024\n"));
+
+    // and back down again
+    let mut beatmap = Beatmap::from_str(&upgraded)?;
+    assert_eq!(beatmap.hit_objects[0].start_time, Time(1024.0));
+    beatmap.version = 4;
+    assert!(beatmap.to_string().contains("\n256,192,1000,1,0\n"));
+
+    // a missing preview time stays missing
+    beatmap.preview_time = Millis(-1);
+    assert!(beatmap.to_string().contains("\nPreviewTime: -1\n"));
+    beatmap.version = 14;
+
+    // versions before lazer's round fractional times
+    beatmap.hit_objects[0].start_time = Time(1024.4);
+    beatmap.version = FIRST_LAZER_VERSION;
+    assert!(beatmap.to_string().contains("\n256,192,1024.4,1,0,"));
+    beatmap.version = 14;
+    assert!(beatmap.to_string().contains("\n256,192,1024,1,0,"));
+    Ok(())
+}
+
+const V14: &str = "osu file format v14
+
+[General]
+AudioFilename: audio.mp3
+AudioLeadIn: 500
+PreviewTime: 5000
+Countdown: 0
+SampleSet: Soft
+StackLeniency: 0.7
+Mode: 0
+LetterboxInBreaks: 1
+WidescreenStoryboard: 1
+
+[Editor]
+Bookmarks: 1000
+DistanceSpacing: 1.2
+BeatDivisor: 4
+GridSize: 8
+TimelineZoom: 2
+
+[Metadata]
+Title:Song
+TitleUnicode:Song
+Artist:Artist
+ArtistUnicode:Artist
+Creator:Mapper
+Version:Hard
+Source:Game
+Tags:tag
+BeatmapID:1
+BeatmapSetID:2
+
+[Difficulty]
+HPDrainRate:5
+CircleSize:4
+OverallDifficulty:7
+ApproachRate:8
+SliderMultiplier:1.4
+SliderTickRate:1
+
+[Events]
+2,3000,4000
+
+[TimingPoints]
+1000,500,4,2,1,60,1,0
+2000,-50,4,2,1,60,0,1
+
+[Colours]
+Combo1 : 255,0,0
+SliderBorder : 0,0,0
+
+[HitObjects]
+256,192,1000,5,2,1:2:3:40:
+100,100,1500,2,0,B|200:100,1,100,2|0,2:0|0:0,0:0:0:0:
+256,192,5000,12,0,6000,0:0:0:0:
+";
+
+#[test]
+fn test_write_old_layouts() -> Result<()> {
+    let write = |version: u32| -> Result<String> {
+        let mut beatmap = Beatmap::from_str(V14)?;
+        beatmap.version = version;
+        Ok(beatmap.to_string())
+    };
+
+    // v3 only has the keys every version has, two timing point columns and no hit samples
+    assert_eq!(
+        write(3)?,
+        "osu file format v3
+
+[General]
+AudioFilename: audio.mp3
+PreviewTime: 4976
+SampleSet: Soft
+
+[Metadata]
+Title:Song
+Artist:Artist
+Creator:Mapper
+Version:Hard
+
+[Difficulty]
+HPDrainRate:5
+CircleSize:4
+OverallDifficulty:7
+SliderMultiplier:1.4
+SliderTickRate:1
+[Events]
+2,2976,3976
+
+[TimingPoints]
+976,500
+1976,-50
+
+[HitObjects]
+256,192,976,5,2
+100,100,1476,2,0,B|200:100,1,100,2|0
+256,192,4976,12,0,5976
+
+"
+    );
+
+    // v5 adds [Editor] and [Colours], the volume column and some [General] and [Metadata] keys
+    assert_eq!(
+        write(5)?,
+        "osu file format v5
+
+[General]
+AudioFilename: audio.mp3
+AudioLeadIn: 500
+PreviewTime: 5000
+Countdown: 0
+SampleSet: Soft
+StackLeniency: 0.7
+Mode: 0
+LetterboxInBreaks: 1
+
+[Editor]
+Bookmarks: 1000
+DistanceSpacing: 1.2
+BeatDivisor: 4
+GridSize: 8
+
+[Metadata]
+Title:Song
+Artist:Artist
+Creator:Mapper
+Version:Hard
+Source:Game
+Tags:tag
+
+[Difficulty]
+HPDrainRate:5
+CircleSize:4
+OverallDifficulty:7
+SliderMultiplier:1.4
+SliderTickRate:1
+[Events]
+2,3000,4000
+
+[TimingPoints]
+1000,500,4,2,1,60
+2000,-50,0,2,1,60
+
+[Colours]
+Combo1 : 255,0,0
+
+[HitObjects]
+256,192,1000,5,2
+100,100,1500,2,0,B|200:100,1,100,2|0
+256,192,5000,12,0,6000
+
+"
+    );
+
+    // v9 has the approach rate and every timing point column, but still no hit samples
+    assert_eq!(
+        write(9)?,
+        "osu file format v9
+
+[General]
+AudioFilename: audio.mp3
+AudioLeadIn: 500
+PreviewTime: 5000
+Countdown: 0
+SampleSet: Soft
+StackLeniency: 0.7
+Mode: 0
+LetterboxInBreaks: 1
+
+[Editor]
+Bookmarks: 1000
+DistanceSpacing: 1.2
+BeatDivisor: 4
+GridSize: 8
+
+[Metadata]
+Title:Song
+Artist:Artist
+Creator:Mapper
+Version:Hard
+Source:Game
+Tags:tag
+
+[Difficulty]
+HPDrainRate:5
+CircleSize:4
+OverallDifficulty:7
+ApproachRate:8
+SliderMultiplier:1.4
+SliderTickRate:1
+[Events]
+2,3000,4000
+
+[TimingPoints]
+1000,500,4,2,1,60,1,0
+2000,-50,0,2,1,60,0,1
+
+[Colours]
+Combo1 : 255,0,0
+
+[HitObjects]
+256,192,1000,5,2
+100,100,1500,2,0,B|200:100,1,100,2|0
+256,192,5000,12,0,6000
+
+"
+    );
+
+    // v10 brings in hit samples without the volume and filename
+    assert!(write(10)?.contains("\n256,192,1000,5,2,1:2:3\n"));
+    assert!(write(14)?.contains("\n256,192,1000,5,2,1:2:3:40:\n"));
+    Ok(())
+}